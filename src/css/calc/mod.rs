@@ -0,0 +1,465 @@
+use super::{StyleNumber, lexer::{Lexed, Token}, parser::{Value, Unit, LengthContext, ValueParser}};
+
+/// Expression tree of a `calc()`, `min()`, `max()` or `clamp()` function.
+/// Subtraction and division are stored as `Negate` and `Invert` so that sums
+/// and products can be reordered freely while simplifying.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Calc {
+  Number(StyleNumber),
+  Length(StyleNumber, Unit),
+  Percentage(StyleNumber),
+
+  Sum(Vec<Calc>),
+  Product(Vec<Calc>),
+  Negate(Box<Calc>),
+  Invert(Box<Calc>),
+
+  Min(Vec<Calc>),
+  Max(Vec<Calc>),
+  Clamp(Box<Calc>, Box<Calc>, Box<Calc>)
+}
+
+/// The type a math expression resolves to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalcType {
+  Number,
+  Length,
+  Percentage,
+  LengthPercentage
+}
+
+impl CalcType {
+  /// The type of adding a value of type `other`, if the two can be mixed.
+  fn add(self, other: CalcType) -> Option<CalcType> {
+    match (self, other) {
+      (a, b) if a == b => Some(a),
+      (CalcType::Number, _) | (_, CalcType::Number) => None,
+      _ => Some(CalcType::LengthPercentage)
+    }
+  }
+
+  /// Whether an expression of this type may be used where `expected` is.
+  pub fn fits(self, expected: CalcType) -> bool {
+    self == expected || (expected == CalcType::LengthPercentage && self != CalcType::Number)
+  }
+}
+
+const MATH_FUNCTIONS: &[&str] = &["calc", "min", "max", "clamp"];
+
+/// Parses a math function whose result is of the `expected` type, simplifying
+/// it as far as possible without knowing relative lengths or percentages.
+pub fn parse(parser: &mut ValueParser, expected: CalcType) -> Option<Value> {
+  parser.attempt(|parser| {
+    let calc = math_function(parser)?;
+    if !calc.kind()?.fits(expected) {
+      return None;
+    }
+    Some(calc.simplify().into_value())
+  })
+}
+
+fn math_function(parser: &mut ValueParser) -> Option<Calc> {
+  for name in MATH_FUNCTIONS.iter() {
+    let mut arguments = match parser.function(name) {
+      Some(arguments) => arguments,
+      None => continue
+    };
+
+    let mut values = vec![sum(&mut arguments)?];
+    if *name != "calc" {
+      while arguments.token(&Token::Comma) {
+        values.push(sum(&mut arguments)?);
+      }
+    }
+    if !arguments.is_done() {
+      return None;
+    }
+
+    return match *name {
+      "calc" => values.pop(),
+      "min" => Some(Calc::Min(values)),
+      "max" => Some(Calc::Max(values)),
+      _ if values.len() == 3 => {
+        let max = values.pop()?;
+        let value = values.pop()?;
+        let min = values.pop()?;
+        Some(Calc::Clamp(Box::new(min), Box::new(value), Box::new(max)))
+      },
+      _ => None
+    };
+  }
+
+  None
+}
+
+/// `<calc-product> [ [ '+' | '-' ] <calc-product> ]*`
+fn sum(parser: &mut ValueParser) -> Option<Calc> {
+  let mut terms = vec![product(parser)?];
+
+  loop {
+    if parser.token(&Token::Plus) {
+      terms.push(product(parser)?);
+    } else if parser.token(&Token::Minus) {
+      terms.push(Calc::Negate(Box::new(product(parser)?)));
+    } else {
+      break;
+    }
+  }
+
+  Some(if terms.len() == 1 { terms.remove(0) } else { Calc::Sum(terms) })
+}
+
+/// `<calc-value> [ [ '*' | '/' ] <calc-value> ]*`
+fn product(parser: &mut ValueParser) -> Option<Calc> {
+  let mut factors = vec![calc_value(parser)?];
+
+  loop {
+    if parser.token(&Token::Star) {
+      factors.push(calc_value(parser)?);
+    } else if parser.token(&Token::Slash) {
+      factors.push(Calc::Invert(Box::new(calc_value(parser)?)));
+    } else {
+      break;
+    }
+  }
+
+  Some(if factors.len() == 1 { factors.remove(0) } else { Calc::Product(factors) })
+}
+
+fn calc_value(parser: &mut ValueParser) -> Option<Calc> {
+  if let Some(mut contents) = parser.parenthesized() {
+    let calc = sum(&mut contents)?;
+    return if contents.is_done() { Some(calc) } else { None };
+  }
+
+  if let Some(Lexed::Function(_)) = parser.peek() {
+    return math_function(parser);
+  }
+
+  match parser.number() {
+    Some(num) => Some(Calc::Number(num)),
+    None => match parser.percentage() {
+      Some(Value::Percentage(num)) => Some(Calc::Percentage(num)),
+      _ => match parser.length() {
+        Some(Value::Length(num, unit)) => Some(Calc::Length(num, unit)),
+        _ => None
+      }
+    }
+  }
+}
+
+impl Calc {
+  /// Type checks the expression, returning `None` if it mixes incompatible
+  /// types, multiplies two dimensions or divides by a dimension.
+  pub fn kind(&self) -> Option<CalcType> {
+    match *self {
+      Calc::Number(_) => Some(CalcType::Number),
+      Calc::Length(..) => Some(CalcType::Length),
+      Calc::Percentage(_) => Some(CalcType::Percentage),
+      Calc::Negate(ref calc) => calc.kind(),
+      Calc::Invert(ref calc) => match calc.kind()? {
+        CalcType::Number => Some(CalcType::Number),
+        _ => None
+      },
+      Calc::Product(ref factors) => {
+        let mut result = CalcType::Number;
+        for factor in factors.iter() {
+          match (result, factor.kind()?) {
+            (_, CalcType::Number) => {},
+            (CalcType::Number, kind) => result = kind,
+            _ => return None
+          }
+        }
+        Some(result)
+      },
+      Calc::Sum(ref values) | Calc::Min(ref values) | Calc::Max(ref values) => {
+        let mut result = values.first()?.kind()?;
+        for value in values[1..].iter() {
+          result = result.add(value.kind()?)?;
+        }
+        Some(result)
+      },
+      Calc::Clamp(ref min, ref value, ref max) => min.kind()?.add(value.kind()?)?.add(max.kind()?)
+    }
+  }
+
+  fn leaf_number(&self) -> Option<StyleNumber> {
+    match *self {
+      Calc::Number(num) | Calc::Length(num, _) | Calc::Percentage(num) => Some(num),
+      _ => None
+    }
+  }
+
+  fn with_number(&self, num: StyleNumber) -> Calc {
+    match *self {
+      Calc::Length(_, unit) => Calc::Length(num, unit),
+      Calc::Percentage(_) => Calc::Percentage(num),
+      _ => Calc::Number(num)
+    }
+  }
+
+  /// Whether two leaves can be combined into one.
+  fn same_unit(&self, other: &Calc) -> bool {
+    match (self, other) {
+      (Calc::Number(_), Calc::Number(_)) | (Calc::Percentage(_), Calc::Percentage(_)) => true,
+      (Calc::Length(_, a), Calc::Length(_, b)) => a == b,
+      _ => false
+    }
+  }
+
+  /// Folds everything that does not depend on relative lengths or
+  /// percentages. Absolute lengths are canonicalized to px.
+  pub fn simplify(self) -> Calc {
+    match self {
+      Calc::Length(num, unit) if unit.is_absolute() => {
+        Calc::Length(ABSOLUTE.to_px(num, unit), Unit::Px)
+      },
+      Calc::Number(_) | Calc::Length(..) | Calc::Percentage(_) => self,
+      Calc::Negate(calc) => {
+        match calc.simplify() {
+          Calc::Negate(inner) => *inner,
+          calc => match calc.leaf_number() {
+            Some(num) => calc.with_number(-num),
+            None => Calc::Product(vec![Calc::Number(-1.0), calc]).simplify()
+          }
+        }
+      },
+      Calc::Invert(calc) => {
+        match calc.simplify() {
+          Calc::Number(num) => Calc::Number(1.0 / num),
+          calc => Calc::Invert(Box::new(calc))
+        }
+      },
+      Calc::Product(factors) => {
+        let mut scalar = 1.0;
+        let mut rest = Vec::new();
+
+        for factor in factors.into_iter().map(Calc::simplify) {
+          match factor {
+            Calc::Number(num) => scalar *= num,
+            Calc::Product(inner) => for factor in inner {
+              match factor {
+                Calc::Number(num) => scalar *= num,
+                factor => rest.push(factor)
+              }
+            },
+            factor => rest.push(factor)
+          }
+        }
+
+        match rest.len() {
+          0 => Calc::Number(scalar),
+          1 => {
+            let factor = rest.remove(0);
+            match factor {
+              _ if scalar == 1.0 => factor,
+              Calc::Sum(terms) => Calc::Sum(terms.into_iter()
+                .map(|term| Calc::Product(vec![Calc::Number(scalar), term]).simplify())
+                .collect()),
+              _ => match factor.leaf_number() {
+                Some(num) => factor.with_number(num * scalar),
+                None => Calc::Product(vec![Calc::Number(scalar), factor])
+              }
+            }
+          },
+          _ => {
+            rest.insert(0, Calc::Number(scalar));
+            Calc::Product(rest)
+          }
+        }
+      },
+      Calc::Sum(terms) => {
+        let mut combined: Vec<Calc> = Vec::new();
+
+        for term in terms.into_iter().map(Calc::simplify) {
+          let flattened = match term {
+            Calc::Sum(inner) => inner,
+            term => vec![term]
+          };
+
+          for term in flattened.into_iter() {
+            let existing = combined.iter_mut()
+              .find(|existing| existing.leaf_number().is_some() && existing.same_unit(&term));
+
+            match (existing, term.leaf_number()) {
+              (Some(existing), Some(num)) => {
+                let sum = existing.leaf_number().unwrap_or(0.0) + num;
+                *existing = existing.with_number(sum);
+              },
+              _ => combined.push(term)
+            }
+          }
+        }
+
+        if combined.len() == 1 { combined.remove(0) } else { Calc::Sum(combined) }
+      },
+      Calc::Min(values) => fold(values, Calc::Min, StyleNumber::min),
+      Calc::Max(values) => fold(values, Calc::Max, StyleNumber::max),
+      Calc::Clamp(min, value, max) => {
+        let (min, value, max) = (min.simplify(), value.simplify(), max.simplify());
+
+        match (min.leaf_number(), value.leaf_number(), max.leaf_number()) {
+          (Some(lower), Some(num), Some(upper)) if min.same_unit(&value) && value.same_unit(&max) => {
+            value.with_number(num.min(upper).max(lower))
+          },
+          _ => Calc::Clamp(Box::new(min), Box::new(value), Box::new(max))
+        }
+      }
+    }
+  }
+
+  /// Converts relative lengths to px and simplifies again, which leaves at
+  /// most percentages to be resolved during layout.
+  pub fn to_computed(&self, context: &LengthContext) -> Calc {
+    self.map_leaves(&|leaf| match *leaf {
+      Calc::Length(num, unit) => Calc::Length(context.to_px(num, unit), Unit::Px),
+      _ => leaf.clone()
+    }).simplify()
+  }
+
+  fn map_leaves<F: Fn(&Calc) -> Calc>(&self, f: &F) -> Calc {
+    let map = |values: &Vec<Calc>| values.iter().map(|i| i.map_leaves(f)).collect();
+
+    match *self {
+      Calc::Number(_) | Calc::Length(..) | Calc::Percentage(_) => f(self),
+      Calc::Sum(ref values) => Calc::Sum(map(values)),
+      Calc::Product(ref values) => Calc::Product(map(values)),
+      Calc::Min(ref values) => Calc::Min(map(values)),
+      Calc::Max(ref values) => Calc::Max(map(values)),
+      Calc::Negate(ref calc) => Calc::Negate(Box::new(calc.map_leaves(f))),
+      Calc::Invert(ref calc) => Calc::Invert(Box::new(calc.map_leaves(f))),
+      Calc::Clamp(ref min, ref value, ref max) => Calc::Clamp(
+        Box::new(min.map_leaves(f)),
+        Box::new(value.map_leaves(f)),
+        Box::new(max.map_leaves(f))
+      )
+    }
+  }
+
+  /// Evaluates the expression to px (or a plain number), resolving
+  /// percentages against `basis`. Returns `None` if the expression contains
+  /// percentages and no basis is known.
+  pub fn resolve(&self, context: &LengthContext, basis: Option<StyleNumber>) -> Option<StyleNumber> {
+    let all = |values: &Vec<Calc>| -> Option<Vec<StyleNumber>> {
+      values.iter().map(|i| i.resolve(context, basis)).collect()
+    };
+
+    match *self {
+      Calc::Number(num) => Some(num),
+      Calc::Length(num, unit) => Some(context.to_px(num, unit)),
+      Calc::Percentage(num) => basis.map(|basis| basis * num / 100.0),
+      Calc::Negate(ref calc) => calc.resolve(context, basis).map(|num| -num),
+      Calc::Invert(ref calc) => calc.resolve(context, basis).map(|num| 1.0 / num),
+      Calc::Sum(ref values) => all(values).map(|values| values.iter().sum()),
+      Calc::Product(ref values) => all(values).map(|values| values.iter().product()),
+      Calc::Min(ref values) => all(values)?.into_iter().reduce(StyleNumber::min),
+      Calc::Max(ref values) => all(values)?.into_iter().reduce(StyleNumber::max),
+      Calc::Clamp(ref min, ref value, ref max) => {
+        let min = min.resolve(context, basis)?;
+        let value = value.resolve(context, basis)?;
+        let max = max.resolve(context, basis)?;
+        Some(value.min(max).max(min))
+      }
+    }
+  }
+
+  /// A fully simplified leaf becomes a plain value, anything else stays a
+  /// math expression.
+  pub fn into_value(self) -> Value {
+    match self {
+      Calc::Number(num) => Value::Number(num),
+      Calc::Length(num, unit) => Value::Length(num, unit),
+      Calc::Percentage(num) => Value::Percentage(num),
+      calc => Value::Calc(Box::new(calc))
+    }
+  }
+}
+
+/// Absolute lengths do not depend on the context.
+const ABSOLUTE: LengthContext = LengthContext {
+  font_size: 0.0,
+  root_font_size: 0.0,
+  viewport_width: 0.0,
+  viewport_height: 0.0
+};
+
+/// Folds `min()`/`max()` arguments if they all share a unit.
+fn fold<C, F>(values: Vec<Calc>, constructor: C, f: F) -> Calc
+where C: Fn(Vec<Calc>) -> Calc, F: Fn(StyleNumber, StyleNumber) -> StyleNumber {
+  let values: Vec<Calc> = values.into_iter().map(Calc::simplify).collect();
+
+  let foldable = values.iter().all(|i| i.leaf_number().is_some() && i.same_unit(&values[0]));
+  if !foldable {
+    return constructor(values);
+  }
+
+  let num = values.iter().filter_map(Calc::leaf_number).reduce(f).unwrap_or(0.0);
+  values[0].with_number(num)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::lexer::lex;
+
+  fn parse_as(query: &str, expected: CalcType) -> Option<Value> {
+    let lexed = lex(query.to_string()).ok()?;
+    let mut parser = ValueParser::new(&lexed);
+    let value = parse(&mut parser, expected)?;
+    if parser.is_done() { Some(value) } else { None }
+  }
+
+  const CONTEXT: LengthContext = LengthContext {
+    font_size: 10.0,
+    root_font_size: 16.0,
+    viewport_width: 800.0,
+    viewport_height: 600.0
+  };
+
+  #[test]
+  fn simplifies_at_parse_time() {
+    assert_eq!(parse_as("calc(1px + 2px)", CalcType::Length), Some(Value::Length(3.0, Unit::Px)));
+    assert_eq!(parse_as("calc(1in - 6px * 2)", CalcType::Length), Some(Value::Length(84.0, Unit::Px)));
+    assert_eq!(parse_as("calc((1 + 2) * 3)", CalcType::Number), Some(Value::Number(9.0)));
+    assert_eq!(parse_as("max(10px, 2px, 30px)", CalcType::Length), Some(Value::Length(30.0, Unit::Px)));
+    assert_eq!(parse_as("clamp(1px, 50px, 20px)", CalcType::Length), Some(Value::Length(20.0, Unit::Px)));
+    assert_eq!(parse_as("calc(10% / 2)", CalcType::Percentage), Some(Value::Percentage(5.0)));
+    // the scalars of nested products multiply together
+    assert_eq!(parse_as("calc(2 * (3 * min(1px, 2vw)))", CalcType::Length), Some(Value::Calc(Box::new(Calc::Product(vec![
+      Calc::Number(6.0),
+      Calc::Min(vec![Calc::Length(1.0, Unit::Px), Calc::Length(2.0, Unit::Vw)])
+    ])))));
+  }
+
+  #[test]
+  fn type_checks() {
+    assert!(parse_as("calc(1px + 2)", CalcType::Length).is_none());
+    assert!(parse_as("calc(1px * 2px)", CalcType::Length).is_none());
+    assert!(parse_as("calc(1px / 2px)", CalcType::Length).is_none());
+    assert!(parse_as("calc(100% - 2px)", CalcType::Length).is_none());
+    assert!(parse_as("calc(100% - 2px)", CalcType::LengthPercentage).is_some());
+    assert!(parse_as("calc(1px -2px)", CalcType::Length).is_none());
+  }
+
+  #[test]
+  fn resolves_against_containing_block() {
+    let value = parse_as("calc(100% - 2 * (1em + 5px))", CalcType::LengthPercentage);
+    let calc = match value {
+      Some(Value::Calc(calc)) => calc,
+      other => panic!("expected an unresolved calc, got {:?}", other)
+    };
+
+    assert_eq!(calc.resolve(&CONTEXT, Some(200.0)), Some(170.0));
+    assert_eq!(calc.resolve(&CONTEXT, None), None);
+    assert_eq!(
+      calc.to_computed(&CONTEXT),
+      Calc::Sum(vec![Calc::Percentage(100.0), Calc::Length(-30.0, Unit::Px)])
+    );
+
+    let value = parse_as("min(50%, 10em)", CalcType::LengthPercentage);
+    match value {
+      Some(Value::Calc(calc)) => assert_eq!(calc.resolve(&CONTEXT, Some(300.0)), Some(100.0)),
+      other => panic!("expected an unresolved calc, got {:?}", other)
+    }
+  }
+}
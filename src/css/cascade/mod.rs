@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Where a stylesheet comes from. Declarations from later origins win over
+/// earlier ones, unless `!important` reverses the order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Origin {
+  UserAgent,
+  User,
+  Author
+}
+
+static ANONYMOUS_LAYERS: AtomicUsize = AtomicUsize::new(0);
+
+/// A unique name for an anonymous `@layer { ... }` block. The name can not
+/// collide with an identifier, so it is never addressable from a stylesheet.
+pub fn anonymous_layer() -> String {
+  format!("<anonymous-{}>", ANONYMOUS_LAYERS.fetch_add(1, Ordering::Relaxed))
+}
+
+pub fn is_anonymous_layer(name: &str) -> bool {
+  name.starts_with('<')
+}
+
+/// The order of the cascade layers of one origin. Layers are ordered by
+/// their first declaration, sublayers come before the declarations placed
+/// directly in their parent layer, and unlayered declarations come last.
+#[derive(Debug, Clone, Default)]
+pub struct LayerOrder {
+  ranks: HashMap<String, usize>,
+  unlayered: usize
+}
+
+struct LayerNode {
+  name: String,
+  children: Vec<usize>
+}
+
+impl LayerOrder {
+  /// Builds the order from the fully qualified layer names (`a.b`) of every
+  /// stylesheet of the origin, in document order.
+  pub fn new<'a, I>(declared: I) -> Self
+  where I: IntoIterator<Item = &'a String> {
+    let mut nodes = vec![LayerNode {
+      name: String::new(),
+      children: Vec::new()
+    }];
+
+    for name in declared.into_iter() {
+      let mut parent = 0;
+      let mut qualified = String::new();
+
+      for segment in name.split('.') {
+        if !qualified.is_empty() {
+          qualified.push('.');
+        }
+        qualified.push_str(segment);
+
+        let existing = nodes[parent].children.iter()
+          .cloned()
+          .find(|&child| nodes[child].name == qualified);
+
+        parent = match existing {
+          Some(child) => child,
+          None => {
+            nodes.push(LayerNode {
+              name: qualified.clone(),
+              children: Vec::new()
+            });
+            let child = nodes.len() - 1;
+            nodes[parent].children.push(child);
+            child
+          }
+        };
+      }
+    }
+
+    fn visit(node: usize, nodes: &[LayerNode], ranks: &mut HashMap<String, usize>) {
+      for &child in nodes[node].children.iter() {
+        visit(child, nodes, ranks);
+      }
+      let rank = ranks.len();
+      ranks.insert(nodes[node].name.clone(), rank);
+    }
+
+    let mut ranks = HashMap::new();
+    visit(0, &nodes, &mut ranks);
+    let unlayered = ranks.remove("").unwrap_or(0);
+
+    Self {
+      ranks,
+      unlayered
+    }
+  }
+
+  /// The rank of a layer, higher winning for normal declarations.
+  /// Undeclared layers are treated as unlayered.
+  pub fn rank(&self, layer: Option<&str>) -> usize {
+    layer.and_then(|layer| self.ranks.get(layer))
+      .cloned()
+      .unwrap_or(self.unlayered)
+  }
+}
+
+/// The precedence of a declaration from its origin, importance, whether it
+/// comes from a `style` attribute, and its layer, per CSS Cascade Level 5.
+/// Greater priorities win; ties are broken by specificity and then order of
+/// appearance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority {
+  level: u8,
+  element_attached: bool,
+  layer: isize
+}
+
+impl Priority {
+  pub fn new(origin: Origin, important: bool, layer_rank: usize) -> Self {
+    let level = match (important, origin) {
+      (false, Origin::UserAgent) => 0,
+      (false, Origin::User) => 1,
+      (false, Origin::Author) => 2,
+      (true, Origin::Author) => 3,
+      (true, Origin::User) => 4,
+      (true, Origin::UserAgent) => 5
+    };
+
+    // important declarations in earlier layers win
+    let layer = layer_rank as isize;
+
+    Self {
+      level,
+      element_attached: false,
+      layer: if important { -layer } else { layer }
+    }
+  }
+
+  /// A declaration of a `style` attribute, which wins over author rules of
+  /// the same importance in any layer.
+  pub fn style_attribute(important: bool) -> Self {
+    Self {
+      element_attached: true,
+      ..Self::new(Origin::Author, important, 0)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn orders_layers() {
+    let declared: Vec<String> = ["base", "base.reset", "theme", "base.fonts"].iter()
+      .map(|i| i.to_string())
+      .collect();
+    let order = LayerOrder::new(&declared);
+
+    let ranks: Vec<usize> = [Some("base.reset"), Some("base.fonts"), Some("base"), Some("theme"), None].iter()
+      .map(|&i| order.rank(i))
+      .collect();
+    assert!(ranks.windows(2).all(|i| i[0] < i[1]), "{:?}", ranks);
+    assert_eq!(order.rank(Some("missing")), order.rank(None));
+  }
+
+  #[test]
+  fn orders_origins_and_importance() {
+    let normal = |origin| Priority::new(origin, false, 0);
+    let important = |origin| Priority::new(origin, true, 0);
+
+    let ascending = [
+      normal(Origin::UserAgent),
+      normal(Origin::User),
+      normal(Origin::Author),
+      important(Origin::Author),
+      important(Origin::User),
+      important(Origin::UserAgent)
+    ];
+    assert!(ascending.windows(2).all(|i| i[0] < i[1]));
+
+    // later layers win normally, earlier layers win when important
+    assert!(Priority::new(Origin::Author, false, 0) < Priority::new(Origin::Author, false, 1));
+    assert!(Priority::new(Origin::Author, true, 0) > Priority::new(Origin::Author, true, 1));
+
+    // style attributes beat any layer of their origin and importance only
+    assert!(Priority::style_attribute(false) > Priority::new(Origin::Author, false, 10));
+    assert!(Priority::style_attribute(true) > Priority::new(Origin::Author, true, 0));
+    assert!(Priority::style_attribute(false) < Priority::new(Origin::Author, true, 0));
+  }
+}
@@ -0,0 +1,558 @@
+use super::{lexer::{Lexed, Token}, parser::{Value, ValueParser}};
+
+/// A color component before conversion, as written in a color function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Component {
+  Number(f64),
+  Percentage(f64),
+  Angle(f64), // degrees
+  None
+}
+
+impl Component {
+  /// The component as a number, percentages being scaled so that 100%
+  /// equals `hundred_percent`. `none` resolves to zero.
+  fn value(self, hundred_percent: f64) -> f64 {
+    match self {
+      Component::Number(num) | Component::Angle(num) => num,
+      Component::Percentage(num) => num / 100.0 * hundred_percent,
+      Component::None => 0.0
+    }
+  }
+
+  fn hue(self) -> Option<f64> {
+    match self {
+      Component::Number(deg) | Component::Angle(deg) => Some(deg),
+      Component::None => Some(0.0),
+      Component::Percentage(_) => None
+    }
+  }
+}
+
+type ColorFunction = fn(&mut ValueParser) -> Option<Value>;
+
+/// Parses any CSS Color Level 4 `<color>`: hex notation, named colors,
+/// `transparent`, `currentColor` and the color functions.
+pub fn parse_color(parser: &mut ValueParser) -> Option<Value> {
+  if let Some(Lexed::Hash(hex)) = parser.peek() {
+    parser.consume();
+    return parse_hex(hex);
+  }
+
+  if let Some(keyword) = parser.keyword(&["currentcolor"]) {
+    return Some(keyword);
+  }
+
+  let functions: &[(&str, ColorFunction)] = &[
+    ("rgb", rgb),
+    ("rgba", rgb),
+    ("hsl", hsl),
+    ("hsla", hsl),
+    ("hwb", hwb),
+    ("lab", lab),
+    ("lch", lch),
+    ("oklab", oklab),
+    ("oklch", oklch)
+  ];
+
+  for (name, f) in functions.iter() {
+    if let Some(mut arguments) = parser.function(name) {
+      let color = f(&mut arguments)?;
+      return if arguments.is_done() { Some(color) } else { None };
+    }
+  }
+
+  let name = parser.identifier()?.to_ascii_lowercase();
+  if name == "transparent" {
+    return Some(Value::Color { r: 0, g: 0, b: 0, a: 0 });
+  }
+
+  let i = NAMED_COLORS.binary_search_by(|&(color, _)| color.cmp(&name[..])).ok()?;
+  let rgb = NAMED_COLORS[i].1;
+
+  Some(Value::Color {
+    r: (rgb >> 16) as u8,
+    g: (rgb >> 8) as u8,
+    b: rgb as u8,
+    a: 255
+  })
+}
+
+/// `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+pub fn parse_hex(hex: &str) -> Option<Value> {
+  let digits = hex.chars()
+    .map(|c| c.to_digit(16).map(|d| d as u8))
+    .collect::<Option<Vec<u8>>>()?;
+
+  let channels: Vec<u8> = match digits.len() {
+    3 | 4 => digits.iter().map(|d| d * 17).collect(),
+    6 | 8 => digits.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect(),
+    _ => return None
+  };
+
+  Some(Value::Color {
+    r: channels[0],
+    g: channels[1],
+    b: channels[2],
+    a: channels.get(3).cloned().unwrap_or(255)
+  })
+}
+
+fn component(parser: &mut ValueParser) -> Option<Component> {
+  if let Some(Value::Percentage(num)) = parser.percentage() {
+    return Some(Component::Percentage(num as f64));
+  }
+  if let Some(num) = parser.number() {
+    return Some(Component::Number(num as f64));
+  }
+  if let Some(deg) = parser.angle() {
+    return Some(Component::Angle(deg as f64));
+  }
+  parser.keyword(&["none"]).map(|_| Component::None)
+}
+
+/// Three components and an optional alpha, in either the legacy comma
+/// separated syntax or the space separated syntax with `/ alpha`.
+/// Returns whether the legacy syntax was used.
+fn arguments(parser: &mut ValueParser) -> Option<([Component; 3], Component, bool)> {
+  let first = component(parser)?;
+  let legacy = parser.token(&Token::Comma);
+
+  let second = component(parser)?;
+  if legacy && !parser.token(&Token::Comma) {
+    return None;
+  }
+  let third = component(parser)?;
+
+  let alpha = if (legacy && parser.token(&Token::Comma)) || (!legacy && parser.token(&Token::Slash)) {
+    component(parser)?
+  } else {
+    Component::Number(1.0)
+  };
+
+  let components = [first, second, third];
+  if legacy && components.iter().chain(Some(&alpha)).any(|c| *c == Component::None) {
+    return None;
+  }
+
+  Some((components, alpha, legacy))
+}
+
+fn alpha(alpha: Component) -> Option<u8> {
+  match alpha {
+    Component::Angle(_) => None,
+    _ => Some(channel(alpha.value(1.0)))
+  }
+}
+
+/// Converts a channel in the range 0..1 to a byte, clipping out of gamut values.
+fn channel(value: f64) -> u8 {
+  (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn color(rgb: [f64; 3], alpha: u8) -> Value {
+  Value::Color {
+    r: channel(rgb[0]),
+    g: channel(rgb[1]),
+    b: channel(rgb[2]),
+    a: alpha
+  }
+}
+
+fn rgb(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+
+  if components.iter().any(|c| matches!(c, Component::Angle(_))) {
+    return None;
+  }
+  // the legacy syntax does not allow mixing numbers and percentages
+  if legacy {
+    let percentages = components.iter().filter(|c| matches!(c, Component::Percentage(_))).count();
+    if percentages != 0 && percentages != 3 {
+      return None;
+    }
+  }
+
+  let rgb = [
+    components[0].value(255.0) / 255.0,
+    components[1].value(255.0) / 255.0,
+    components[2].value(255.0) / 255.0
+  ];
+
+  Some(color(rgb, alpha(a)?))
+}
+
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+  let hue = hue.rem_euclid(360.0);
+  let a = saturation * lightness.min(1.0 - lightness);
+
+  let f = |n: f64| {
+    let k = (n + hue / 30.0) % 12.0;
+    lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+  };
+
+  [f(0.0), f(8.0), f(4.0)]
+}
+
+fn hsl(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+
+  if legacy && components[1..].iter().any(|c| !matches!(c, Component::Percentage(_))) {
+    return None;
+  }
+
+  let hue = components[0].hue()?;
+  let saturation = components[1].value(100.0).clamp(0.0, 100.0) / 100.0;
+  let lightness = components[2].value(100.0).clamp(0.0, 100.0) / 100.0;
+
+  Some(color(hsl_to_rgb(hue, saturation, lightness), alpha(a)?))
+}
+
+fn hwb(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+  if legacy {
+    return None;
+  }
+
+  let hue = components[0].hue()?;
+  let white = components[1].value(100.0).clamp(0.0, 100.0) / 100.0;
+  let black = components[2].value(100.0).clamp(0.0, 100.0) / 100.0;
+
+  let rgb = if white + black >= 1.0 {
+    let gray = white / (white + black);
+    [gray, gray, gray]
+  } else {
+    let pure = hsl_to_rgb(hue, 1.0, 0.5);
+    [
+      pure[0] * (1.0 - white - black) + white,
+      pure[1] * (1.0 - white - black) + white,
+      pure[2] * (1.0 - white - black) + white
+    ]
+  };
+
+  Some(color(rgb, alpha(a)?))
+}
+
+fn lab(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+  if legacy {
+    return None;
+  }
+
+  let lightness = components[0].value(100.0).max(0.0);
+  let rgb = lab_to_srgb(lightness, components[1].value(125.0), components[2].value(125.0));
+
+  Some(color(rgb, alpha(a)?))
+}
+
+fn lch(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+  if legacy {
+    return None;
+  }
+
+  let lightness = components[0].value(100.0).max(0.0);
+  let chroma = components[1].value(150.0).max(0.0);
+  let hue = components[2].hue()?.to_radians();
+  let rgb = lab_to_srgb(lightness, chroma * hue.cos(), chroma * hue.sin());
+
+  Some(color(rgb, alpha(a)?))
+}
+
+fn oklab(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+  if legacy {
+    return None;
+  }
+
+  let lightness = components[0].value(1.0).clamp(0.0, 1.0);
+  let rgb = oklab_to_srgb(lightness, components[1].value(0.4), components[2].value(0.4));
+
+  Some(color(rgb, alpha(a)?))
+}
+
+fn oklch(parser: &mut ValueParser) -> Option<Value> {
+  let (components, a, legacy) = arguments(parser)?;
+  if legacy {
+    return None;
+  }
+
+  let lightness = components[0].value(1.0).clamp(0.0, 1.0);
+  let chroma = components[1].value(0.4).max(0.0);
+  let hue = components[2].hue()?.to_radians();
+  let rgb = oklab_to_srgb(lightness, chroma * hue.cos(), chroma * hue.sin());
+
+  Some(color(rgb, alpha(a)?))
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+  let row = |r: &[f64; 3]| r[0] * vector[0] + r[1] * vector[1] + r[2] * vector[2];
+  [row(&matrix[0]), row(&matrix[1]), row(&matrix[2])]
+}
+
+/// Applies the sRGB transfer function to a linear-light channel.
+fn gamma(value: f64) -> f64 {
+  let abs = value.abs();
+  if abs <= 0.0031308 {
+    value * 12.92
+  } else {
+    value.signum() * (1.055 * abs.powf(1.0 / 2.4) - 0.055)
+  }
+}
+
+/// CIE Lab (D50 white point) to gamma encoded sRGB.
+fn lab_to_srgb(lightness: f64, a: f64, b: f64) -> [f64; 3] {
+  const EPSILON: f64 = 216.0 / 24389.0;
+  const KAPPA: f64 = 24389.0 / 27.0;
+  const D50: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+  let fy = (lightness + 16.0) / 116.0;
+  let fx = fy + a / 500.0;
+  let fz = fy - b / 200.0;
+
+  let x = if fx.powi(3) > EPSILON { fx.powi(3) } else { (116.0 * fx - 16.0) / KAPPA };
+  let y = if lightness > KAPPA * EPSILON { fy.powi(3) } else { lightness / KAPPA };
+  let z = if fz.powi(3) > EPSILON { fz.powi(3) } else { (116.0 * fz - 16.0) / KAPPA };
+
+  let xyz_d50 = [x * D50[0], y * D50[1], z * D50[2]];
+
+  // Bradford chromatic adaptation from D50 to D65
+  let xyz_d65 = multiply(&[
+    [0.955_473_452_704_218_2, -0.023_098_536_874_261_423, 0.063_259_308_661_021_7],
+    [-0.028_369_706_963_208_136, 1.009_995_458_005_822_6, 0.021_041_398_966_943_008],
+    [0.012_314_001_688_319_899, -0.020_507_696_433_477_912, 1.330_365_936_608_075_3]
+  ], xyz_d50);
+
+  let linear = multiply(&[
+    [3.240_969_941_904_522_6, -1.537_383_177_570_094, -0.498_610_760_293_003_4],
+    [-0.969_243_636_280_879_6, 1.875_967_501_507_720_2, 0.041_555_057_407_175_59],
+    [0.055_630_079_696_993_66, -0.203_976_958_888_976_52, 1.056_971_514_242_878_6]
+  ], xyz_d65);
+
+  [gamma(linear[0]), gamma(linear[1]), gamma(linear[2])]
+}
+
+/// Oklab to gamma encoded sRGB.
+fn oklab_to_srgb(lightness: f64, a: f64, b: f64) -> [f64; 3] {
+  let lms = multiply(&[
+    [1.0, 0.396_337_777_4, 0.215_803_757_3],
+    [1.0, -0.105_561_345_8, -0.063_854_172_8],
+    [1.0, -0.089_484_177_5, -1.291_485_548_0]
+  ], [lightness, a, b]);
+
+  let linear = multiply(&[
+    [4.076_741_662_1, -3.307_711_591_3, 0.230_969_929_2],
+    [-1.268_438_004_6, 2.609_757_401_1, -0.341_319_396_5],
+    [-0.004_196_086_3, -0.703_418_614_7, 1.707_614_701_0]
+  ], [lms[0].powi(3), lms[1].powi(3), lms[2].powi(3)]);
+
+  [gamma(linear[0]), gamma(linear[1]), gamma(linear[2])]
+}
+
+/// The CSS named colors, sorted by name.
+static NAMED_COLORS: &[(&str, u32)] = &[
+  ("aliceblue", 0xf0f8ff),
+  ("antiquewhite", 0xfaebd7),
+  ("aqua", 0x00ffff),
+  ("aquamarine", 0x7fffd4),
+  ("azure", 0xf0ffff),
+  ("beige", 0xf5f5dc),
+  ("bisque", 0xffe4c4),
+  ("black", 0x000000),
+  ("blanchedalmond", 0xffebcd),
+  ("blue", 0x0000ff),
+  ("blueviolet", 0x8a2be2),
+  ("brown", 0xa52a2a),
+  ("burlywood", 0xdeb887),
+  ("cadetblue", 0x5f9ea0),
+  ("chartreuse", 0x7fff00),
+  ("chocolate", 0xd2691e),
+  ("coral", 0xff7f50),
+  ("cornflowerblue", 0x6495ed),
+  ("cornsilk", 0xfff8dc),
+  ("crimson", 0xdc143c),
+  ("cyan", 0x00ffff),
+  ("darkblue", 0x00008b),
+  ("darkcyan", 0x008b8b),
+  ("darkgoldenrod", 0xb8860b),
+  ("darkgray", 0xa9a9a9),
+  ("darkgreen", 0x006400),
+  ("darkgrey", 0xa9a9a9),
+  ("darkkhaki", 0xbdb76b),
+  ("darkmagenta", 0x8b008b),
+  ("darkolivegreen", 0x556b2f),
+  ("darkorange", 0xff8c00),
+  ("darkorchid", 0x9932cc),
+  ("darkred", 0x8b0000),
+  ("darksalmon", 0xe9967a),
+  ("darkseagreen", 0x8fbc8f),
+  ("darkslateblue", 0x483d8b),
+  ("darkslategray", 0x2f4f4f),
+  ("darkslategrey", 0x2f4f4f),
+  ("darkturquoise", 0x00ced1),
+  ("darkviolet", 0x9400d3),
+  ("deeppink", 0xff1493),
+  ("deepskyblue", 0x00bfff),
+  ("dimgray", 0x696969),
+  ("dimgrey", 0x696969),
+  ("dodgerblue", 0x1e90ff),
+  ("firebrick", 0xb22222),
+  ("floralwhite", 0xfffaf0),
+  ("forestgreen", 0x228b22),
+  ("fuchsia", 0xff00ff),
+  ("gainsboro", 0xdcdcdc),
+  ("ghostwhite", 0xf8f8ff),
+  ("gold", 0xffd700),
+  ("goldenrod", 0xdaa520),
+  ("gray", 0x808080),
+  ("green", 0x008000),
+  ("greenyellow", 0xadff2f),
+  ("grey", 0x808080),
+  ("honeydew", 0xf0fff0),
+  ("hotpink", 0xff69b4),
+  ("indianred", 0xcd5c5c),
+  ("indigo", 0x4b0082),
+  ("ivory", 0xfffff0),
+  ("khaki", 0xf0e68c),
+  ("lavender", 0xe6e6fa),
+  ("lavenderblush", 0xfff0f5),
+  ("lawngreen", 0x7cfc00),
+  ("lemonchiffon", 0xfffacd),
+  ("lightblue", 0xadd8e6),
+  ("lightcoral", 0xf08080),
+  ("lightcyan", 0xe0ffff),
+  ("lightgoldenrodyellow", 0xfafad2),
+  ("lightgray", 0xd3d3d3),
+  ("lightgreen", 0x90ee90),
+  ("lightgrey", 0xd3d3d3),
+  ("lightpink", 0xffb6c1),
+  ("lightsalmon", 0xffa07a),
+  ("lightseagreen", 0x20b2aa),
+  ("lightskyblue", 0x87cefa),
+  ("lightslategray", 0x778899),
+  ("lightslategrey", 0x778899),
+  ("lightsteelblue", 0xb0c4de),
+  ("lightyellow", 0xffffe0),
+  ("lime", 0x00ff00),
+  ("limegreen", 0x32cd32),
+  ("linen", 0xfaf0e6),
+  ("magenta", 0xff00ff),
+  ("maroon", 0x800000),
+  ("mediumaquamarine", 0x66cdaa),
+  ("mediumblue", 0x0000cd),
+  ("mediumorchid", 0xba55d3),
+  ("mediumpurple", 0x9370db),
+  ("mediumseagreen", 0x3cb371),
+  ("mediumslateblue", 0x7b68ee),
+  ("mediumspringgreen", 0x00fa9a),
+  ("mediumturquoise", 0x48d1cc),
+  ("mediumvioletred", 0xc71585),
+  ("midnightblue", 0x191970),
+  ("mintcream", 0xf5fffa),
+  ("mistyrose", 0xffe4e1),
+  ("moccasin", 0xffe4b5),
+  ("navajowhite", 0xffdead),
+  ("navy", 0x000080),
+  ("oldlace", 0xfdf5e6),
+  ("olive", 0x808000),
+  ("olivedrab", 0x6b8e23),
+  ("orange", 0xffa500),
+  ("orangered", 0xff4500),
+  ("orchid", 0xda70d6),
+  ("palegoldenrod", 0xeee8aa),
+  ("palegreen", 0x98fb98),
+  ("paleturquoise", 0xafeeee),
+  ("palevioletred", 0xdb7093),
+  ("papayawhip", 0xffefd5),
+  ("peachpuff", 0xffdab9),
+  ("peru", 0xcd853f),
+  ("pink", 0xffc0cb),
+  ("plum", 0xdda0dd),
+  ("powderblue", 0xb0e0e6),
+  ("purple", 0x800080),
+  ("rebeccapurple", 0x663399),
+  ("red", 0xff0000),
+  ("rosybrown", 0xbc8f8f),
+  ("royalblue", 0x4169e1),
+  ("saddlebrown", 0x8b4513),
+  ("salmon", 0xfa8072),
+  ("sandybrown", 0xf4a460),
+  ("seagreen", 0x2e8b57),
+  ("seashell", 0xfff5ee),
+  ("sienna", 0xa0522d),
+  ("silver", 0xc0c0c0),
+  ("skyblue", 0x87ceeb),
+  ("slateblue", 0x6a5acd),
+  ("slategray", 0x708090),
+  ("slategrey", 0x708090),
+  ("snow", 0xfffafa),
+  ("springgreen", 0x00ff7f),
+  ("steelblue", 0x4682b4),
+  ("tan", 0xd2b48c),
+  ("teal", 0x008080),
+  ("thistle", 0xd8bfd8),
+  ("tomato", 0xff6347),
+  ("turquoise", 0x40e0d0),
+  ("violet", 0xee82ee),
+  ("wheat", 0xf5deb3),
+  ("white", 0xffffff),
+  ("whitesmoke", 0xf5f5f5),
+  ("yellow", 0xffff00),
+  ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::lexer::lex;
+
+  fn parse(query: &str) -> Option<Value> {
+    let lexed = lex(query.to_string()).ok()?;
+    let mut parser = ValueParser::new(&lexed);
+    let value = parse_color(&mut parser)?;
+    if parser.is_done() { Some(value) } else { None }
+  }
+
+  fn rgba(r: u8, g: u8, b: u8, a: u8) -> Option<Value> {
+    Some(Value::Color { r, g, b, a })
+  }
+
+  #[test]
+  fn named_colors_are_sorted() {
+    assert_eq!(NAMED_COLORS.len(), 148);
+    for pair in NAMED_COLORS.windows(2) {
+      assert!(pair[0].0 < pair[1].0, "{} before {}", pair[0].0, pair[1].0);
+    }
+  }
+
+  #[test]
+  fn parses_hex_and_keywords() {
+    assert_eq!(parse("#f00"), rgba(255, 0, 0, 255));
+    assert_eq!(parse("#f008"), rgba(255, 0, 0, 0x88));
+    assert_eq!(parse("#1e5A2b"), rgba(0x1e, 0x5a, 0x2b, 255));
+    assert_eq!(parse("#1e5a2b80"), rgba(0x1e, 0x5a, 0x2b, 0x80));
+    assert_eq!(parse("#12345"), None);
+    assert_eq!(parse("RebeccaPurple"), rgba(0x66, 0x33, 0x99, 255));
+    assert_eq!(parse("transparent"), rgba(0, 0, 0, 0));
+    assert_eq!(parse("currentColor"), Some(Value::Keyword("currentcolor".to_string())));
+    assert_eq!(parse("notacolor"), None);
+  }
+
+  #[test]
+  fn parses_functions() {
+    assert_eq!(parse("rgb(255, 0, 0)"), rgba(255, 0, 0, 255));
+    assert_eq!(parse("rgba(100%, 0%, 0%, 0.5)"), rgba(255, 0, 0, 128));
+    assert_eq!(parse("rgb(255 0 0 / 50%)"), rgba(255, 0, 0, 128));
+    assert_eq!(parse("rgb(255, 0%, 0)"), None);
+    assert_eq!(parse("rgb(255 none 0)"), rgba(255, 0, 0, 255));
+    assert_eq!(parse("hsl(120, 100%, 50%)"), rgba(0, 255, 0, 255));
+    assert_eq!(parse("hsl(0.5turn 100% 25%)"), rgba(0, 128, 128, 255));
+    assert_eq!(parse("hsla(240deg, 100%, 50%, 0)"), rgba(0, 0, 255, 0));
+    assert_eq!(parse("hwb(0 0% 0%)"), rgba(255, 0, 0, 255));
+    assert_eq!(parse("hwb(0 60% 60%)"), rgba(128, 128, 128, 255));
+    assert_eq!(parse("lab(100 0 0)"), rgba(255, 255, 255, 255));
+    assert_eq!(parse("lch(54.29% 106.84 40.85)"), rgba(255, 0, 0, 255));
+    assert_eq!(parse("oklab(0 0 0)"), rgba(0, 0, 0, 255));
+    assert_eq!(parse("oklch(62.8% 0.2577 29.23)"), rgba(255, 0, 0, 255));
+  }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use super::{Error, helper::{PreLexed, pre_lex}};
+use super::Error;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -19,19 +19,65 @@ pub enum Token {
   Star,
   Tilde,
   Plus,
+  Minus,
+  Slash,
+  Whitespace,
+  Bang,
+  BracketOpen,
+  BracketClose,
+  Equals,
+  Pipe,
+  Caret,
+  Dollar,
 
-  RGB,
-  Px,
   Percent
 }
 
+impl Token {
+  /// The source text of the token, whitespace being a single space.
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      Token::None => "",
+      Token::GT => ">",
+      Token::BraceOpen => "{",
+      Token::BraceClose => "}",
+      Token::Comma => ",",
+      Token::Dot => ".",
+      Token::ParOpen => "(",
+      Token::ParClose => ")",
+      Token::SemiColon => ";",
+      Token::Colon => ":",
+      Token::DoubleColon => "::",
+      Token::Hash => "#",
+      Token::Star => "*",
+      Token::Tilde => "~",
+      Token::Plus => "+",
+      Token::Minus => "-",
+      Token::Slash => "/",
+      Token::Whitespace => " ",
+      Token::Bang => "!",
+      Token::BracketOpen => "[",
+      Token::BracketClose => "]",
+      Token::Equals => "=",
+      Token::Pipe => "|",
+      Token::Caret => "^",
+      Token::Dollar => "$",
+      Token::Percent => "%"
+    }
+  }
+}
+
 pub type StyleNumber = f32;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Lexed {
   Identifier(String),
   Number(StyleNumber),
   String(String),
+  Hash(String), // #fff, #main
+  Url(String), // url(image.png)
+  Function(String), // calc( ... up to the matching Token::ParClose
+  AtKeyword(String), // @layer
   Token(Token),
 }
 
@@ -46,104 +92,236 @@ fn get_tokens() -> HashMap<&'static str, Token> {
     "#" => Token::Hash,
     "," => Token::Comma,
     "." => Token::Dot,
+    "(" => Token::ParOpen,
+    ")" => Token::ParClose,
     "*" => Token::Star,
     "~" => Token::Tilde,
     "+" => Token::Plus,
-    "px" => Token::Px,
+    "-" => Token::Minus,
+    "/" => Token::Slash,
+    "!" => Token::Bang,
+    "[" => Token::BracketOpen,
+    "]" => Token::BracketClose,
+    "=" => Token::Equals,
+    "|" => Token::Pipe,
+    "^" => Token::Caret,
+    "$" => Token::Dollar,
     "%" => Token::Percent
   }
 }
 
-fn tokenize(pre_lexed: Vec<PreLexed>) -> Result<Vec<Lexed>, Error> {
-  let mut result: Vec<Lexed> = Vec::new();
-  let token_lookup = get_tokens();
+fn is_name_start(c: char) -> bool {
+  c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
 
-  let is_identifier = |query: &str| -> Option<String> {
-    if query.len() <= 0 { return None; }
-    let mut expect_more = false; // cannot be named just '_', '-' or '0'...'9'
-    let mut first = true;
-
-    for i in query.chars() {
-      match i {
-        '0' ... '9' => {
-          if first {
-            return None;
-          } else {
-            expect_more = false;
-          }
-        }
-        'a' ... 'z' | 'A' ... 'Z' => expect_more = false,
-        '_' | '-' => expect_more = first,
-        _ => return None
+fn is_name(c: char) -> bool {
+  is_name_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+struct Scanner<'a> {
+  rest: &'a str
+}
+
+impl<'a> Scanner<'a> {
+  pub fn new(query: &'a str) -> Self {
+    Self {
+      rest: query
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.rest.chars().next()
+  }
+
+  fn peek_nth(&self, n: usize) -> Option<char> {
+    self.rest.chars().nth(n)
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.rest = &self.rest[c.len_utf8()..];
+    Some(c)
+  }
+
+  fn starts_identifier(&self) -> bool {
+    match (self.peek_nth(0), self.peek_nth(1)) {
+      (Some('-'), Some(c)) => is_name_start(c) || c == '-', // --custom-property
+      (Some(c), _) => is_name_start(c),
+      _ => false
+    }
+  }
+
+  fn starts_number(&self) -> bool {
+    let digit_at = |n: usize| self.peek_nth(n).is_some_and(|c| c.is_ascii_digit());
+
+    match self.peek_nth(0) {
+      Some('+') | Some('-') => digit_at(1) || (self.peek_nth(1) == Some('.') && digit_at(2)),
+      Some('.') => digit_at(1),
+      Some(c) => c.is_ascii_digit(),
+      None => false
+    }
+  }
+
+  fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> String {
+    let mut buf = String::new();
+    while let Some(c) = self.peek() {
+      if !predicate(c) {
+        break;
+      }
+      buf.push(c);
+      self.next();
+    }
+    buf
+  }
+
+  fn skip_comment(&mut self) -> Result<(), Error> {
+    self.next();
+    self.next();
+
+    loop {
+      match self.next() {
+        Some('*') if self.peek() == Some('/') => {
+          self.next();
+          return Ok(());
+        },
+        Some(_) => {},
+        None => return Err(Error("Unterminated comment".to_string()))
       }
-      first = false;
     }
+  }
+
+  fn string(&mut self, quote: char) -> Result<String, Error> {
+    let mut buf = String::new();
 
-    if expect_more {
-      return None;
+    loop {
+      match self.next() {
+        Some('\\') => match self.next() {
+          Some('\n') => {},
+          Some(c) => buf.push(c),
+          None => return Err(Error("Unterminated string".to_string()))
+        },
+        Some(c) if c == quote => return Ok(buf),
+        Some('\n') | None => return Err(Error("Unterminated string".to_string())),
+        Some(c) => buf.push(c)
+      }
     }
+  }
 
-    Some(String::from(query))
-  };
+  fn number(&mut self) -> Result<StyleNumber, Error> {
+    let mut buf = String::new();
+
+    if let Some(c @ '+') | Some(c @ '-') = self.peek() {
+      buf.push(c);
+      self.next();
+    }
 
-  //std::num::ParseFloatError
+    buf.push_str(&self.take_while(|c| c.is_ascii_digit()));
 
-  let is_number = |query: &str| -> Option<StyleNumber> {
-    match query.parse::<StyleNumber>() {
-      Ok(num) => Some(num),
-      _ => None
+    if self.peek() == Some('.') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+      self.next();
+      buf.push('.');
+      buf.push_str(&self.take_while(|c| c.is_ascii_digit()));
     }
-  };
 
-  let is_token = |query: &str| -> Option<Token> {
-    match token_lookup.get(query) {
-      Some(token) => Some(token.clone()),
-      None => None
+    // 1e3 is a number, 1em is a number followed by a unit
+    if let Some('e') | Some('E') = self.peek() {
+      let exponent = match (self.peek_nth(1), self.peek_nth(2)) {
+        (Some('+'), Some(c)) | (Some('-'), Some(c)) => c.is_ascii_digit(),
+        (Some(c), _) => c.is_ascii_digit(),
+        _ => false
+      };
+
+      if exponent {
+        buf.push('e');
+        self.next();
+        if let Some(c @ '+') | Some(c @ '-') = self.peek() {
+          buf.push(c);
+          self.next();
+        }
+        buf.push_str(&self.take_while(|c| c.is_ascii_digit()));
+      }
     }
+
+    buf.parse::<StyleNumber>()
+      .map_err(|_| Error(format!("Invalid number: '{}'", buf)))
+  }
+
+  fn url(&mut self) -> Result<String, Error> {
+    self.take_while(char::is_whitespace);
+
+    let url = match self.peek() {
+      Some(quote @ '"') | Some(quote @ '\'') => {
+        self.next();
+        self.string(quote)?
+      },
+      _ => self.take_while(|c| c != ')' && !c.is_whitespace())
+    };
+
+    self.take_while(char::is_whitespace);
+
+    match self.next() {
+      Some(')') => Ok(url),
+      _ => Err(Error(format!("Unterminated url: '{}'", url)))
+    }
+  }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Lexed>, Error> {
+  let mut result: Vec<Lexed> = Vec::new();
+  let token_lookup = get_tokens();
+  let mut scanner = Scanner::new(query);
+
+  let is_token = |query: &str| -> Option<Token> {
+    token_lookup.get(query).cloned()
   };
 
-  for i in pre_lexed.into_iter() {
-    match i {
-      PreLexed::String(s) => result.push(Lexed::String(s)),
-      PreLexed::Rest(rest) => {
-        let length = rest.len();
-        let mut pos = 0;
-        let mut offset = length;
-
-        while offset >= 1 {
-          if pos >= length {
-            // println!("breaking: pos: {}, offset: {}, length: {}", pos, offset, length);
-            break;
-          }
-
-          if offset - pos <= 0 {
-            return Err(Error(format!("Token not recognized: \'{}\'", &rest[pos..offset+1])))
-          }
-
-          let content = &rest[pos..offset].trim();
-          // println!("content: {:?}", content);
-
-          offset -= 1;
-
-          if let Some(token) = is_token(content) {
-            result.push(Lexed::Token(token));
-          } else if let Some(identifier) = is_identifier(content) {
-            result.push(Lexed::Identifier(identifier));
-          } else if let Some(num) = is_number(content) {
-            result.push(Lexed::Number(num));
-          } else {
-            continue;
-          }
-
-          // println!("tokenized: {:?}", tokenized);
-          pos = offset + 1;
-          offset = length;
-          // println!("{}, {}, {}", pos, offset, length);
-          // if offset == pos {
-          //   return Err(Error("Token not found".to_string()))
-          // }
-        }
+  while let Some(c) = scanner.peek() {
+    if c.is_whitespace() {
+      scanner.take_while(char::is_whitespace);
+      if result.last() != Some(&Lexed::Token(Token::Whitespace)) {
+        result.push(Lexed::Token(Token::Whitespace));
+      }
+    } else if c == '/' && scanner.peek_nth(1) == Some('*') {
+      scanner.skip_comment()?;
+    } else if c == '"' || c == '\'' {
+      scanner.next();
+      result.push(Lexed::String(scanner.string(c)?));
+    } else if scanner.starts_number() {
+      result.push(Lexed::Number(scanner.number()?));
+    } else if scanner.starts_identifier() {
+      let mut name = String::new();
+      if c == '-' {
+        scanner.next();
+        name.push('-');
       }
+      name.push_str(&scanner.take_while(is_name));
+
+      if name.eq_ignore_ascii_case("url") && scanner.peek() == Some('(') {
+        scanner.next();
+        result.push(Lexed::Url(scanner.url()?));
+      } else if scanner.peek() == Some('(') {
+        scanner.next();
+        result.push(Lexed::Function(name));
+      } else if let Some(token) = is_token(&name) {
+        result.push(Lexed::Token(token));
+      } else {
+        result.push(Lexed::Identifier(name));
+      }
+    } else if c == '#' && scanner.peek_nth(1).is_some_and(is_name) {
+      scanner.next();
+      result.push(Lexed::Hash(scanner.take_while(is_name)));
+    } else if c == '@' && scanner.peek_nth(1).is_some_and(|c| is_name_start(c) || c == '-') {
+      scanner.next();
+      result.push(Lexed::AtKeyword(scanner.take_while(is_name)));
+    } else if c == ':' && scanner.peek_nth(1) == Some(':') {
+      scanner.next();
+      scanner.next();
+      result.push(Lexed::Token(Token::DoubleColon));
+    } else if let Some(token) = is_token(&c.to_string()) {
+      scanner.next();
+      result.push(Lexed::Token(token));
+    } else {
+      return Err(Error(format!("Token not recognized: '{}'", c)));
     }
   }
 
@@ -151,9 +329,7 @@ fn tokenize(pre_lexed: Vec<PreLexed>) -> Result<Vec<Lexed>, Error> {
 }
 
 pub fn lex(query: String) -> Result<Vec<Lexed>, Error> {
-  let pre_lexed = pre_lex(query)?;
-
-  let tokenized = tokenize(pre_lexed)?;
+  let tokenized = tokenize(&query)?;
 
   Ok(tokenized)
-}
\ No newline at end of file
+}
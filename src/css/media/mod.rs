@@ -0,0 +1,236 @@
+use super::{Error, StyleNumber, lexer::{self, Lexed, Token}, parser::{Value, LengthContext, ValueParser}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaType {
+  Screen,
+  Print
+}
+
+impl MediaType {
+  pub fn name(&self) -> &'static str {
+    match *self {
+      MediaType::Screen => "screen",
+      MediaType::Print => "print"
+    }
+  }
+}
+
+/// What media queries are evaluated against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Device {
+  pub media_type: MediaType,
+  pub viewport_width: StyleNumber,
+  pub viewport_height: StyleNumber
+}
+
+impl Device {
+  /// Relative lengths in media queries use the initial font size.
+  fn length_context(&self) -> LengthContext {
+    LengthContext {
+      font_size: 16.0,
+      root_font_size: 16.0,
+      viewport_width: self.viewport_width,
+      viewport_height: self.viewport_height
+    }
+  }
+}
+
+/// A comma separated list of media queries, as in a `media` attribute.
+/// An empty list matches every device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQueryList {
+  queries: Vec<Option<MediaQuery>> // `None` for a query that failed to parse, which matches nothing
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MediaQuery {
+  negated: bool,
+  media_type: Option<String>, // `None` for `all`
+  features: Vec<MediaFeature>
+}
+
+/// `(name: value)` or the boolean `(name)`.
+#[derive(Debug, Clone, PartialEq)]
+struct MediaFeature {
+  name: String,
+  value: Option<Value>
+}
+
+impl MediaQueryList {
+  pub fn parse(query: &str) -> Result<MediaQueryList, Error> {
+    let lexed = lexer::lex(query.to_string())?;
+
+    let mut queries = Vec::new();
+    let mut start = 0;
+    for (i, item) in lexed.iter().enumerate() {
+      if *item == Lexed::Token(Token::Comma) {
+        queries.push(media_query(&lexed[start..i]));
+        start = i + 1;
+      }
+    }
+
+    if queries.is_empty() && lexed.iter().all(|i| *i == Lexed::Token(Token::Whitespace)) {
+      return Ok(MediaQueryList {
+        queries: Vec::new()
+      });
+    }
+    queries.push(media_query(&lexed[start..]));
+
+    Ok(MediaQueryList {
+      queries
+    })
+  }
+
+  pub fn matches(&self, device: &Device) -> bool {
+    self.queries.is_empty() || self.queries.iter().any(|query| match *query {
+      Some(ref query) => query.matches(device),
+      None => false
+    })
+  }
+}
+
+/// `[ not | only ]? <media-type> [ and <feature> ]*` or
+/// `<feature> [ and <feature> ]*`.
+fn media_query(lexed: &[Lexed]) -> Option<MediaQuery> {
+  let mut parser = ValueParser::new(lexed);
+  let mut query = MediaQuery {
+    negated: false,
+    media_type: None,
+    features: Vec::new()
+  };
+
+  let mut needs_and = false;
+  if let Some(Value::Keyword(prefix)) = parser.keyword(&["not", "only"]) {
+    query.negated = prefix == "not";
+    query.media_type = Some(parser.identifier()?.to_ascii_lowercase());
+    needs_and = true;
+  } else if let Some(media_type) = parser.attempt(|parser| parser.identifier()) {
+    query.media_type = Some(media_type.to_ascii_lowercase());
+    needs_and = true;
+  }
+
+  while !parser.is_done() {
+    if needs_and && parser.keyword(&["and"]).is_none() {
+      return None;
+    }
+    query.features.push(media_feature(&mut parser)?);
+    needs_and = true;
+  }
+
+  if query.media_type.is_none() && query.features.is_empty() {
+    return None;
+  }
+  if query.media_type.as_ref().is_some_and(|media_type| media_type == "all") {
+    query.media_type = None;
+  }
+
+  Some(query)
+}
+
+fn media_feature(parser: &mut ValueParser) -> Option<MediaFeature> {
+  let mut arguments = parser.parenthesized()?;
+
+  let name = arguments.identifier()?.to_ascii_lowercase();
+  let value = if arguments.token(&Token::Colon) {
+    let value = arguments.length()
+      .or_else(|| arguments.number().map(Value::Number))
+      .or_else(|| arguments.identifier().map(|i| Value::Keyword(i.to_ascii_lowercase())))?;
+    Some(value)
+  } else {
+    None
+  };
+
+  if !arguments.is_done() {
+    return None;
+  }
+
+  Some(MediaFeature {
+    name,
+    value
+  })
+}
+
+impl MediaQuery {
+  fn matches(&self, device: &Device) -> bool {
+    let media_type = self.media_type.as_ref().is_none_or(|media_type| media_type == device.media_type.name());
+    let matches = media_type && self.features.iter().all(|feature| feature.matches(device));
+
+    matches != self.negated
+  }
+}
+
+impl MediaFeature {
+  /// Unknown features and values never match.
+  fn matches(&self, device: &Device) -> bool {
+    let (prefix, name) = match self.name.find('-') {
+      Some(i) if self.name.starts_with("min-") || self.name.starts_with("max-") => (&self.name[..i], &self.name[i + 1..]),
+      _ => ("", &self.name[..])
+    };
+
+    let actual = match name {
+      "width" => device.viewport_width,
+      "height" => device.viewport_height,
+      "orientation" => {
+        let orientation = if device.viewport_height >= device.viewport_width { "portrait" } else { "landscape" };
+        return match self.value {
+          Some(Value::Keyword(ref value)) => prefix.is_empty() && value == orientation,
+          None => true,
+          _ => false
+        };
+      },
+      "color" => {
+        let bits = 8.0;
+        return match self.value {
+          Some(Value::Number(value)) if prefix == "min" => bits >= value,
+          Some(Value::Number(value)) if prefix == "max" => bits <= value,
+          Some(Value::Number(value)) => bits == value,
+          None => prefix.is_empty(),
+          _ => false
+        };
+      },
+      _ => return false
+    };
+
+    let expected = match self.value {
+      Some(ref value) => match value.to_px(&device.length_context()) {
+        Some(expected) => expected,
+        None => return false
+      },
+      None => return prefix.is_empty() && actual != 0.0
+    };
+
+    match prefix {
+      "min" => actual >= expected,
+      "max" => actual <= expected,
+      _ => actual == expected
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn evaluates_media_queries() {
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+    let matches = |query: &str| MediaQueryList::parse(query).unwrap().matches(&device);
+
+    assert!(matches(""));
+    assert!(matches("all"));
+    assert!(matches("screen"));
+    assert!(!matches("print"));
+    assert!(matches("print, screen"));
+    assert!(matches("not print"));
+    assert!(matches("only screen and (min-width: 40em) and (orientation: landscape)"));
+    assert!(!matches("screen and (max-width: 799px)"));
+    assert!(matches("(width: 800px) and (color)"));
+    assert!(!matches("(hover: hover)"));
+    assert!(!matches("screen and"));
+    assert!(matches("screen and, print, all"));
+  }
+}
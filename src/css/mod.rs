@@ -1,24 +1,33 @@
-use super::helper;
 use helper::Error;
 
-mod lexer;
-mod parser;
+pub mod lexer;
+pub mod parser;
+pub mod properties;
+pub mod shorthands;
+pub mod color;
+pub mod calc;
+pub mod variables;
+pub mod cascade;
+pub mod selectors;
+pub mod serialize;
+pub mod media;
 
-use self::lexer::{StyleNumber};
-use self::parser::{Stylesheet, Declaration};
+use self::lexer::StyleNumber;
+use self::parser::{Stylesheet, DeclarationBlock};
+use self::cascade::Origin;
 
-pub fn parse_stylesheet(query: String) -> Result<Stylesheet, Error> {
+pub fn parse_stylesheet(query: String, origin: Origin) -> Result<Stylesheet, Error> {
   println!("[init css]");
 
   let lexed = lexer::lex(query)?;
-  let stylesheet = parser::parse_stylesheet(lexed)?;
+  let stylesheet = parser::parse_stylesheet(lexed, origin)?;
 
   println!("style: {:#?}", stylesheet);
 
   Ok(stylesheet)
 }
 
-pub fn parse_declarations(query: String) -> Result<Vec<Declaration>, Error> {
+pub fn parse_declarations(query: String) -> Result<DeclarationBlock, Error> {
   let lexed = lexer::lex(query)?;
-  Ok(parser::parse_declarations(lexed)?)
+  Ok(parser::parse_declarations(lexed))
 }
\ No newline at end of file
@@ -1,379 +1,814 @@
-use super::{Error, StyleNumber, lexer::{Lexed, Token}};
+use super::{Error, StyleNumber, lexer::{Lexed, Token}, calc::{self, Calc, CalcType}, cascade::{self, Origin}, color, selectors::{self, Selector}, properties, shorthands, variables};
 
 #[derive(Debug)]
 pub struct Stylesheet {
-  rules: Vec<Rule>
+  pub origin: Origin,
+  pub rules: Vec<Rule>,
+  pub layers: Vec<String>, // fully qualified layer names in order of declaration
+  pub errors: Vec<Error>
 }
 
 #[derive(Debug)]
 pub struct Rule {
-  selectors: Vec<Selector>,
-  declarations: Vec<Declaration>
+  pub selectors: Vec<Selector>,
+  pub declarations: Vec<Declaration>,
+  pub layer: Option<String> // fully qualified, `None` if unlayered
 }
 
-#[derive(Debug)]
-pub struct Selector {
-  identifier: Option<Identifier>,
-  pseudo: Vec<Pseudo>,
-  child: Option<Box<SelectorChild>>
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+  pub name: String,
+  pub value: Value,
+  pub important: bool
 }
 
+/// The declarations of a block together with the declarations that were
+/// dropped because they did not match their property's grammar.
 #[derive(Debug)]
-pub struct SelectorChild {
-  selector: Selector,
-  child_type: SelectorChildType
+pub struct DeclarationBlock {
+  pub declarations: Vec<Declaration>,
+  pub errors: Vec<Error>
 }
 
-#[derive(Debug)]
-pub enum SelectorChildType {
-  Descendant,
-  Child,
-  GeneralSibling,
-  AdjacentSibling
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Keyword(String),
+  Length(StyleNumber, Unit),
+  Percentage(StyleNumber),
+  Number(StyleNumber),
+  Integer(i32),
+  Color {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8
+  },
+  String(String),
+  Url(String),
+  Function(String, Vec<Value>), // rect(), counter(), attr()
+  Flex(StyleNumber), // 1fr
+  Calc(Box<Calc>), // math functions that could not be simplified at parse time
+  Unparsed(Vec<Lexed>), // custom properties and values containing var()
+  Pending(String, Vec<Lexed>), // longhand of the named shorthand whose value contains var()
+  List(Vec<Value>), // space separated
+  CommaList(Vec<Value>)
 }
 
-#[derive(Debug)]
-pub enum Pseudo {
-  Match(PseudoType),
-  Not(PseudoType)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+  // absolute
+  Px,
+  Cm,
+  Mm,
+  Q,
+  In,
+  Pt,
+  Pc,
+
+  // font relative
+  Em,
+  Rem,
+  Ex,
+  Ch,
+
+  // viewport relative
+  Vw,
+  Vh,
+  Vmin,
+  Vmax
 }
 
-#[derive(Debug)]
-pub enum PseudoType {
-  FirstChild,
-  LastChild
-}
+impl Unit {
+  pub fn from_name(name: &str) -> Option<Unit> {
+    let unit = match &name.to_ascii_lowercase()[..] {
+      "px" => Unit::Px,
+      "cm" => Unit::Cm,
+      "mm" => Unit::Mm,
+      "q" => Unit::Q,
+      "in" => Unit::In,
+      "pt" => Unit::Pt,
+      "pc" => Unit::Pc,
+      "em" => Unit::Em,
+      "rem" => Unit::Rem,
+      "ex" => Unit::Ex,
+      "ch" => Unit::Ch,
+      "vw" => Unit::Vw,
+      "vh" => Unit::Vh,
+      "vmin" => Unit::Vmin,
+      "vmax" => Unit::Vmax,
+      _ => return None
+    };
 
-#[derive(Debug)]
-pub struct Identifier {
-  id: Vec<String>,
-  class: Vec<String>,
-  i_type: IdentifierType
-}
+    Some(unit)
+  }
 
-#[derive(Debug)]
-pub enum IdentifierType {
-  Simple {
-    tag_name: Option<String>
-  },
-  Everything, // *,
-  Any
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Unit::Px => "px",
+      Unit::Cm => "cm",
+      Unit::Mm => "mm",
+      Unit::Q => "q",
+      Unit::In => "in",
+      Unit::Pt => "pt",
+      Unit::Pc => "pc",
+      Unit::Em => "em",
+      Unit::Rem => "rem",
+      Unit::Ex => "ex",
+      Unit::Ch => "ch",
+      Unit::Vw => "vw",
+      Unit::Vh => "vh",
+      Unit::Vmin => "vmin",
+      Unit::Vmax => "vmax"
+    }
+  }
+
+  pub fn is_absolute(&self) -> bool {
+    matches!(*self, Unit::Px | Unit::Cm | Unit::Mm | Unit::Q | Unit::In | Unit::Pt | Unit::Pc)
+  }
 }
 
-#[derive(Debug)]
-pub struct Declaration {
-  name: String,
-  value: Value
+/// Everything relative lengths depend on. When resolving `font-size` itself,
+/// `font_size` must be the parent's font size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthContext {
+  pub font_size: StyleNumber,
+  pub root_font_size: StyleNumber,
+  pub viewport_width: StyleNumber,
+  pub viewport_height: StyleNumber
 }
 
-#[derive(Debug)]
-pub enum Value {
-  Keyword(String),
-  Length(StyleNumber, Unit),
-  Color {
-    r: u8,
-    g: u8,
-    b: u8,
-    a: u8
+impl LengthContext {
+  /// Converts a length to px. `ex` and `ch` use the common 0.5em fallback
+  /// as font metrics are not available here.
+  pub fn to_px(&self, num: StyleNumber, unit: Unit) -> StyleNumber {
+    match unit {
+      Unit::Px => num,
+      Unit::Cm => num * 96.0 / 2.54,
+      Unit::Mm => num * 96.0 / 25.4,
+      Unit::Q => num * 96.0 / 101.6,
+      Unit::In => num * 96.0,
+      Unit::Pt => num * 96.0 / 72.0,
+      Unit::Pc => num * 16.0,
+      Unit::Em => num * self.font_size,
+      Unit::Rem => num * self.root_font_size,
+      Unit::Ex | Unit::Ch => num * self.font_size * 0.5,
+      Unit::Vw => num * self.viewport_width / 100.0,
+      Unit::Vh => num * self.viewport_height / 100.0,
+      Unit::Vmin => num * self.viewport_width.min(self.viewport_height) / 100.0,
+      Unit::Vmax => num * self.viewport_width.max(self.viewport_height) / 100.0
+    }
   }
 }
 
-#[derive(Debug)]
-pub enum Unit {
-  Px
+impl Value {
+  /// The value with every length converted to px, recursing into lists
+  /// and functions. Percentages are left for layout to resolve against
+  /// their containing block.
+  pub fn to_computed(&self, context: &LengthContext) -> Value {
+    match *self {
+      Value::Length(num, unit) => Value::Length(context.to_px(num, unit), Unit::Px),
+      Value::Calc(ref calc) => calc.to_computed(context).into_value(),
+      Value::Function(ref name, ref arguments) => Value::Function(
+        name.clone(),
+        arguments.iter().map(|i| i.to_computed(context)).collect()
+      ),
+      Value::List(ref values) => Value::List(values.iter().map(|i| i.to_computed(context)).collect()),
+      Value::CommaList(ref values) => Value::CommaList(values.iter().map(|i| i.to_computed(context)).collect()),
+      _ => self.clone()
+    }
+  }
+
+  /// The length in px, if this is a length.
+  pub fn to_px(&self, context: &LengthContext) -> Option<StyleNumber> {
+    match *self {
+      Value::Length(num, unit) => Some(context.to_px(num, unit)),
+      _ => None
+    }
+  }
+
+  /// The used value in px of a length, percentage or math expression,
+  /// resolving percentages against `basis`.
+  pub fn resolve(&self, context: &LengthContext, basis: Option<StyleNumber>) -> Option<StyleNumber> {
+    match *self {
+      Value::Length(num, unit) => Some(context.to_px(num, unit)),
+      Value::Percentage(num) => basis.map(|basis| basis * num / 100.0),
+      Value::Calc(ref calc) => calc.resolve(context, basis),
+      _ => None
+    }
+  }
 }
 
-pub fn parse_stylesheet(lexed: Vec<Lexed>) -> Result<Stylesheet, Error> {
-  let length = lexed.len();
+pub fn parse_stylesheet(lexed: Vec<Lexed>, origin: Origin) -> Result<Stylesheet, Error> {
+  let mut sheet = Stylesheet {
+    origin,
+    rules: Vec::new(),
+    layers: Vec::new(),
+    errors: Vec::new()
+  };
 
-  let mut splitted: Vec<(Vec<Lexed>, Vec<Lexed>)> = Vec::new();
-  let mut buf: Vec<Lexed> = Vec::new();
+  parse_rules(&lexed, None, &mut sheet)?;
 
-  enum BufStatus {
-    Selector,
-    Declaration
+  Ok(sheet)
+}
+
+impl Stylesheet {
+  /// Declares a layer and every layer it is nested in, keeping the order
+  /// of first declaration.
+  fn declare_layer(&mut self, name: &str) {
+    let mut end = 0;
+    for segment in name.split('.') {
+      end += segment.len();
+      if !self.layers.iter().any(|i| *i == name[..end]) {
+        self.layers.push(name[..end].to_string());
+      }
+      end += 1;
+    }
   }
+}
 
-  let status = BufStatus::Selector;
+/// Parses a list of rules, placing style rules in `layer`.
+fn parse_rules(lexed: &[Lexed], layer: Option<&str>, sheet: &mut Stylesheet) -> Result<(), Error> {
+  let mut rest = trim_whitespace(lexed);
 
-  for i in lexed.into_iter() {
-    match i {
-      Lexed::Token(ref token) => match token {
-        &Token::BraceOpen => {
-          splitted.push((buf, Vec::new()));
-          buf = Vec::new();
-          let status = BufStatus::Declaration;
-          continue;
-        },
-        &Token::BraceClose => {
-          let mut last = splitted.pop().unwrap();
-          last.1 = buf;
-          splitted.push(last);
-          buf = Vec::new();
-          let status = BufStatus::Selector;
-          continue;
+  while !rest.is_empty() {
+    if rest[0] == Lexed::Token(Token::BraceClose) {
+      return Err(Error("Unexpected '}' in stylesheet".to_string()));
+    }
+
+    let (prelude, block, length) = next_rule(rest);
+    rest = trim_whitespace(&rest[length..]);
+
+    match (prelude.first(), block) {
+      (Some(Lexed::AtKeyword(name)), _) => parse_at_rule(name, trim_whitespace(&prelude[1..]), block, layer, sheet)?,
+      (_, Some(block)) => match selectors::parse_selector_list(trim_whitespace(prelude)) {
+        Ok(selectors) => {
+          let block = parse_declarations(block.to_vec());
+          sheet.errors.extend(block.errors);
+          sheet.rules.push(Rule {
+            selectors,
+            declarations: block.declarations,
+            layer: layer.map(|i| i.to_string())
+          });
         },
-        _ => {}
+        Err(err) => sheet.errors.push(err)
       },
-      _ => {}
+      (_, None) => sheet.errors.push(Error(format!("Expected '{{' after selector: {:?}", prelude)))
     }
-    buf.push(i);
   }
 
-  // println!("here: {:?}", splitted);
+  Ok(())
+}
+
+/// Splits off the next rule, returning its prelude, its block unless it
+/// ended with `;`, and the number of tokens it spans. The end of the input
+/// closes an unterminated block.
+fn next_rule(lexed: &[Lexed]) -> (&[Lexed], Option<&[Lexed]>, usize) {
+  let open = lexed.iter().position(|i| matches!(*i, Lexed::Token(Token::BraceOpen) | Lexed::Token(Token::SemiColon)));
 
-  let mut sheet = Stylesheet {
-    rules: Vec::new()
+  let open = match open {
+    Some(open) if lexed[open] == Lexed::Token(Token::BraceOpen) => open,
+    Some(end) => return (&lexed[..end], None, end + 1),
+    None => return (lexed, None, lexed.len())
   };
 
-  for i in splitted.into_iter() {
-    let selectors = parse_selectors(i.0)?;
-    let declarations = parse_declarations(i.1)?;
-
-    sheet.rules.push(Rule {
-      selectors,
-      declarations
-    });
+  let mut depth = 0;
+  for (i, item) in lexed.iter().enumerate().skip(open + 1) {
+    match *item {
+      Lexed::Token(Token::BraceOpen) => depth += 1,
+      Lexed::Token(Token::BraceClose) if depth == 0 => return (&lexed[..open], Some(&lexed[open + 1..i]), i + 1),
+      Lexed::Token(Token::BraceClose) => depth -= 1,
+      _ => {}
+    }
   }
 
-  Ok(sheet)
+  (&lexed[..open], Some(&lexed[open + 1..]), lexed.len())
 }
 
-fn parse_selectors(lexed: Vec<Lexed>) -> Result<Vec<Selector>, Error> {
-  let comma_splitted = split(lexed, &Token::Comma)?;
+fn parse_at_rule(name: &str, prelude: &[Lexed], block: Option<&[Lexed]>, layer: Option<&str>, sheet: &mut Stylesheet) -> Result<(), Error> {
+  if !name.eq_ignore_ascii_case("layer") {
+    sheet.errors.push(Error(format!("Unsupported at-rule '@{}'", name)));
+    return Ok(());
+  }
 
-  println!("comma_splitted: {:?}", comma_splitted);
+  let mut names = Vec::new();
+  if !prelude.is_empty() {
+    for i in split(prelude.to_vec(), &Token::Comma).into_iter() {
+      match layer_name(trim_whitespace(&i)) {
+        Some(name) => names.push(name),
+        None => {
+          sheet.errors.push(Error(format!("Invalid layer name: {:?}", i)));
+          return Ok(());
+        }
+      }
+    }
+  }
 
-  let mut selectors: Vec<Selector> = Vec::new();
-  let mut selector_parser = SelectorParser::new();
+  let qualify = |name: &str| match layer {
+    Some(layer) => format!("{}.{}", layer, name),
+    None => name.to_string()
+  };
 
-  for i in comma_splitted.into_iter() {
-    selector_parser.reset(i);
-    selectors.push(selector_parser.parse_selector()?);
+  match block {
+    // @layer reset, theme;
+    None if !names.is_empty() => {
+      for name in names.iter() {
+        sheet.declare_layer(&qualify(name));
+      }
+    },
+    None => sheet.errors.push(Error("Expected layer name in '@layer' statement".to_string())),
+    // @layer theme { ... } or an anonymous @layer { ... }
+    Some(block) if names.len() <= 1 => {
+      let name = qualify(&names.pop().unwrap_or_else(cascade::anonymous_layer));
+      sheet.declare_layer(&name);
+      parse_rules(block, Some(&name), sheet)?;
+    },
+    Some(_) => sheet.errors.push(Error("Expected a single layer name in '@layer' block".to_string()))
   }
 
-  println!("selectors: {:?}", selectors);
+  Ok(())
+}
+
+/// A dotted layer name like `framework.reset`.
+fn layer_name(lexed: &[Lexed]) -> Option<String> {
+  let mut name = String::new();
+
+  for (i, item) in lexed.iter().enumerate() {
+    match (i % 2, item) {
+      (0, Lexed::Identifier(identifier)) => name.push_str(identifier),
+      (1, Lexed::Token(Token::Dot)) => name.push('.'),
+      _ => return None
+    }
+  }
 
-  Ok(selectors)
+  if name.is_empty() || name.ends_with('.') {
+    return None;
+  }
+
+  Some(name)
 }
 
-fn split(lexed: Vec<Lexed>, with: &Token) -> Result<Vec<Vec<Lexed>>, Error> {
+fn split(lexed: Vec<Lexed>, with: &Token) -> Vec<Vec<Lexed>> {
   let mut splitted: Vec<Vec<Lexed>> = Vec::new();
   let mut buf: Vec<Lexed> = Vec::new();
 
   for i in lexed.into_iter() {
-    match i {
-      Lexed::Token(ref token) => if token == with {
+    if let Lexed::Token(ref token) = i {
+      if token == with {
         splitted.push(buf);
         buf = Vec::new();
         continue;
-      },
-      _ => {}
+      }
     }
     buf.push(i);
   }
 
   splitted.push(buf);
-  Ok(splitted)
+  splitted
+}
+
+pub fn parse_declarations(lexed: Vec<Lexed>) -> DeclarationBlock {
+  let mut block = DeclarationBlock {
+    declarations: Vec::new(),
+    errors: Vec::new()
+  };
+
+  for i in split(lexed, &Token::SemiColon).into_iter() {
+    let i = trim_whitespace(&i);
+    if i.is_empty() {
+      continue;
+    }
+
+    match parse_declaration(i) {
+      Ok(declarations) => block.declarations.extend(declarations),
+      Err(err) => block.errors.push(err)
+    }
+  }
+
+  block
+}
+
+/// Parses a single declaration, expanding shorthands into their longhands.
+fn parse_declaration(lexed: &[Lexed]) -> Result<Vec<Declaration>, Error> {
+  let name = match lexed.first() {
+    Some(Lexed::Identifier(identifier)) if variables::is_custom_property(identifier) => identifier.clone(),
+    Some(Lexed::Identifier(identifier)) => identifier.to_ascii_lowercase(),
+    _ => return Err(Error(format!("Expected property name in declaration: {:?}", lexed)))
+  };
+
+  let rest = trim_whitespace(&lexed[1..]);
+  let value = match rest.first() {
+    Some(Lexed::Token(Token::Colon)) => trim_whitespace(&rest[1..]),
+    _ => return Err(Error(format!("Expected ':' after property '{}'", name)))
+  };
+
+  let (value, important) = split_important(value);
+
+  // custom properties accept any tokens and are only interpreted once substituted
+  if variables::is_custom_property(&name) {
+    return Ok(vec![Declaration {
+      name,
+      value: Value::Unparsed(value.to_vec()),
+      important
+    }]);
+  }
+
+  if value.is_empty() {
+    return Err(Error(format!("Missing value for property '{}'", name)));
+  }
+
+  if let Some(shorthand) = shorthands::lookup(&name) {
+    // every longhand waits for the substituted shorthand value
+    let longhands = if variables::has_references(value) {
+      shorthand.longhands.iter()
+        .map(|&longhand| (longhand, Value::Pending(name.clone(), value.to_vec())))
+        .collect()
+    } else {
+      shorthand.expand(value)
+        .ok_or_else(|| Error(format!("Invalid value for property '{}': {:?}", name, value)))?
+    };
+
+    return Ok(longhands.into_iter()
+      .map(|(longhand, value)| Declaration {
+        name: longhand.to_string(),
+        value,
+        important
+      })
+      .collect());
+  }
+
+  let property = properties::lookup(&name)
+    .ok_or_else(|| Error(format!("Unknown property '{}'", name)))?;
+
+  // with var() references the value can only be validated at computed-value time
+  let value = if variables::has_references(value) {
+    Value::Unparsed(value.to_vec())
+  } else {
+    property.parse(value)
+      .ok_or_else(|| Error(format!("Invalid value for property '{}': {:?}", name, value)))?
+  };
+
+  Ok(vec![Declaration {
+    name,
+    value,
+    important
+  }])
 }
 
-struct SelectorParser {
+/// Strips a trailing `!important` from a declaration value.
+fn split_important(value: &[Lexed]) -> (&[Lexed], bool) {
+  if let Some(Lexed::Identifier(name)) = value.last() {
+    if name.eq_ignore_ascii_case("important") {
+      let rest = trim_whitespace(&value[..value.len() - 1]);
+      if let Some(Lexed::Token(Token::Bang)) = rest.last() {
+        return (trim_whitespace(&rest[..rest.len() - 1]), true);
+      }
+    }
+  }
+
+  (value, false)
+}
+
+fn trim_whitespace(lexed: &[Lexed]) -> &[Lexed] {
+  let is_whitespace = |i: &Lexed| *i == Lexed::Token(Token::Whitespace);
+
+  let start = lexed.iter().position(|i| !is_whitespace(i)).unwrap_or(lexed.len());
+  let end = lexed.iter().rposition(|i| !is_whitespace(i)).map_or(start, |i| i + 1);
+
+  &lexed[start..end]
+}
+
+/// Cursor over the tokens of a single declaration value. Every primitive
+/// either consumes the component it recognizes or leaves the position
+/// untouched, so grammars can try alternatives freely.
+pub struct ValueParser<'a> {
   current: usize,
-  lexed: Vec<Lexed>
+  lexed: &'a [Lexed]
 }
 
-impl SelectorParser {
-  pub fn new() -> Self {
+impl<'a> ValueParser<'a> {
+  pub fn new(lexed: &'a [Lexed]) -> Self {
     Self {
       current: 0,
-      lexed: Vec::new()
+      lexed
     }
   }
 
-  pub fn reset(&mut self, lexed: Vec<Lexed>) {
-    self.current = 0;
-    self.lexed = lexed;
+  pub fn position(&self) -> usize {
+    self.current
   }
 
-  fn parse_selector(&mut self) -> Result<Selector, Error> {
-    let mut selector = Selector {
-      child: None,
-      pseudo: Vec::new(),
-      identifier: None
-    };
+  pub fn reset(&mut self, position: usize) {
+    self.current = position;
+  }
 
-    let mut first = true;
-    let mut prev_token = &Token::None;
+  pub fn skip_whitespace(&mut self) {
+    while let Some(Lexed::Token(Token::Whitespace)) = self.lexed.get(self.current) {
+      self.current += 1;
+    }
+  }
 
-    let length = self.lexed.len();
-    let self_point: *mut Self = self;
+  pub fn is_done(&mut self) -> bool {
+    self.skip_whitespace();
+    self.current >= self.lexed.len()
+  }
 
-    loop {
-      if self.current >= length {
-        break;
-      }
+  pub fn peek(&mut self) -> Option<&'a Lexed> {
+    self.skip_whitespace();
+    self.lexed.get(self.current)
+  }
 
-      let item = &self.lexed[self.current];
-
-      match item {
-        &Lexed::String(ref s) => return Err(Error(format!("Unexpected string in selector: {:?}", s))),
-        &Lexed::Identifier(ref s) => {
-          if first {
-            selector.identifier = Some(Identifier {
-              id: Vec::new(),
-              class: Vec::new(),
-              i_type: IdentifierType::Simple {
-                tag_name: Some(s.to_string())
-              }
-            });
-          } else {
-            match prev_token {
-              &Token::Hash | &Token::Dot => {
-                // if let Some(identifier) = selector.identifier {
-                  
-                // } else {
-                //   selector.identifier = Some(Identifier::Simple {
-                //     tag_name: None,
-                //     id: Vec::new(),
-                //     class: Vec::new()
-                //   });
-                // }
-              },
-              &Token::Colon | &Token::DoubleColon => {
-
-              },
-              &Token::None | &Token::GT | &Token::Tilde | &Token::Plus => {
-                selector.child = Some(Box::new(SelectorChild {
-                  selector: unsafe {
-                    (*self_point).parse_selector()?
-                  },
-                  child_type: match prev_token {
-                    &Token::None => SelectorChildType::Descendant,
-                    &Token::GT => SelectorChildType::Child,
-                    &Token::Tilde => SelectorChildType::GeneralSibling,
-                    &Token::Plus => SelectorChildType::AdjacentSibling,
-                    _ => return Err(Error(format!("Unexpected token: {:?}", prev_token)))
-                  }
-                }))
-              },
-              _ => {
-                return Err(Error(format!("Unexpected token in selector: '{:?}'", prev_token)))
-              }
-            }
-          }
-
-          prev_token = &Token::None;
-        },
-        &Lexed::Number(ref num) => {
+  pub fn consume(&mut self) -> Option<&'a Lexed> {
+    let item = self.peek()?;
+    self.current += 1;
+    Some(item)
+  }
 
-        },
-        &Lexed::Token(ref token) => {
-          match token {
-            &Token::Star => {
-              if first {
-                selector.identifier = Some(Identifier {
-                  class: Vec::new(),
-                  id: Vec::new(),
-                  i_type: IdentifierType::Everything
-                });
-              } else {
-                return Err(Error(format!("Unexpected '*' in selector")));
-              }
-            },
-            _ => {}
-          }
-          prev_token = token;
-        }
+  /// Consumes `token`, which may be surrounded by whitespace.
+  pub fn token(&mut self, token: &Token) -> bool {
+    match self.peek() {
+      Some(Lexed::Token(t)) if t == token => {
+        self.current += 1;
+        true
+      },
+      _ => false
+    }
+  }
+
+  /// Runs `f`, restoring the position if it does not produce a value.
+  pub fn attempt<T, F: FnOnce(&mut Self) -> Option<T>>(&mut self, f: F) -> Option<T> {
+    let position = self.current;
+    let result = f(self);
+    if result.is_none() {
+      self.current = position;
+    }
+    result
+  }
+
+  pub fn identifier(&mut self) -> Option<String> {
+    match self.peek() {
+      Some(Lexed::Identifier(identifier)) => {
+        self.current += 1;
+        Some(identifier.clone())
+      },
+      _ => None
+    }
+  }
+
+  /// One of `allowed`, matched ASCII case-insensitively.
+  pub fn keyword(&mut self, allowed: &[&str]) -> Option<Value> {
+    self.attempt(|parser| {
+      let identifier = parser.identifier()?.to_ascii_lowercase();
+      if allowed.contains(&&identifier[..]) {
+        Some(Value::Keyword(identifier))
+      } else {
+        None
       }
+    })
+  }
 
-      first = false;
-      self.current += 1;
+  /// The unit directly following a number, without whitespace in between.
+  fn unit(&mut self) -> Option<&'a Lexed> {
+    let item = self.lexed.get(self.current)?;
+    match item {
+      Lexed::Identifier(_) | Lexed::Token(Token::Percent) => {
+        self.current += 1;
+        Some(item)
+      },
+      _ => None
     }
+  }
 
-    Ok(selector)
+  fn raw_number(&mut self) -> Option<StyleNumber> {
+    match self.peek() {
+      Some(Lexed::Number(num)) => {
+        self.current += 1;
+        Some(*num)
+      },
+      _ => None
+    }
   }
-}
 
-pub fn parse_declarations(lexed: Vec<Lexed>) -> Result<Vec<Declaration>, Error> {
-  let splitted = split(lexed, &Token::SemiColon)?;
+  /// A number without a unit.
+  pub fn number(&mut self) -> Option<StyleNumber> {
+    if let Some(Value::Number(num)) = calc::parse(self, CalcType::Number) {
+      return Some(num);
+    }
+
+    self.attempt(|parser| {
+      let num = parser.raw_number()?;
+      match parser.unit() {
+        Some(_) => None,
+        None => Some(num)
+      }
+    })
+  }
+
+  /// An integer, math functions being rounded to the nearest integer.
+  pub fn integer(&mut self) -> Option<i32> {
+    if let Some(Value::Number(num)) = calc::parse(self, CalcType::Number) {
+      return Some(num.round() as i32);
+    }
+
+    self.attempt(|parser| {
+      let num = parser.number()?;
+      if num.fract() == 0.0 {
+        Some(num as i32)
+      } else {
+        None
+      }
+    })
+  }
 
-  println!("decls: {:?}", splitted);
+  pub fn length(&mut self) -> Option<Value> {
+    if let Some(value) = calc::parse(self, CalcType::Length) {
+      return Some(value);
+    }
 
-  let mut declarations: Vec<Declaration> = Vec::new();
+    self.attempt(|parser| {
+      let num = parser.raw_number()?;
+      let unit = match parser.unit() {
+        Some(Lexed::Identifier(name)) => Unit::from_name(name)?,
+        Some(_) => return None,
+        None if num == 0.0 => Unit::Px,
+        None => return None
+      };
+
+      Some(Value::Length(num, unit))
+    })
+  }
 
-  for i in splitted.into_iter() {
-    let length = i.len();
-    if length <= 2 {
-      continue; // forgiving errors
+  pub fn percentage(&mut self) -> Option<Value> {
+    if let Some(value) = calc::parse(self, CalcType::Percentage) {
+      return Some(value);
     }
 
-    match &i[1] {
-      &Lexed::Token(ref token) => match token {
-        &Token::Colon => {},
-        _ => continue
+    self.attempt(|parser| {
+      let num = parser.raw_number()?;
+      match parser.unit()? {
+        Lexed::Token(Token::Percent) => Some(Value::Percentage(num)),
+        _ => None
       }
-      _ => continue
+    })
+  }
+
+  /// A length, a percentage or a math expression mixing the two.
+  pub fn length_percentage(&mut self) -> Option<Value> {
+    calc::parse(self, CalcType::LengthPercentage)
+      .or_else(|| self.length())
+      .or_else(|| self.percentage())
+  }
+
+  pub fn string(&mut self) -> Option<String> {
+    match self.peek() {
+      Some(Lexed::String(s)) => {
+        self.current += 1;
+        Some(s.clone())
+      },
+      _ => None
     }
+  }
 
-    let name = match &i[0] {
-      &Lexed::Identifier(ref identifier) => identifier.clone(),
-      _ => continue
-    };
+  pub fn url(&mut self) -> Option<String> {
+    match self.peek() {
+      Some(Lexed::Url(url)) => {
+        self.current += 1;
+        Some(url.clone())
+      },
+      _ => None
+    }
+  }
 
-    let value = match match &name as &str {
-      "width" => parse_length,
-      "display" => parse_keyword,
-      _ => continue
-    }(&i[2..]) {
-      Ok(val) => val,
-      Err(_) => continue
-    };
+  pub fn color(&mut self) -> Option<Value> {
+    self.attempt(color::parse_color)
+  }
 
-    declarations.push(Declaration {
-      name,
-      value
-    });
+  /// A non-negative flexible length like `1fr`.
+  pub fn flex(&mut self) -> Option<StyleNumber> {
+    self.attempt(|parser| {
+      let num = parser.raw_number()?;
+      match parser.unit()? {
+        Lexed::Identifier(unit) if unit.eq_ignore_ascii_case("fr") && num >= 0.0 => Some(num),
+        _ => None
+      }
+    })
   }
 
-  Ok(declarations)
-}
+  /// An angle, converted to degrees.
+  pub fn angle(&mut self) -> Option<StyleNumber> {
+    self.attempt(|parser| {
+      let num = parser.raw_number()?;
+      let unit = match parser.unit()? {
+        Lexed::Identifier(unit) => unit.to_ascii_lowercase(),
+        _ => return None
+      };
+
+      match &unit[..] {
+        "deg" => Some(num),
+        "rad" => Some(num.to_degrees()),
+        "grad" => Some(num * 0.9),
+        "turn" => Some(num * 360.0),
+        _ => None
+      }
+    })
+  }
 
-fn parse_keyword(lexed: &[Lexed]) -> Result<Value, Error> {
-  let length = lexed.len();
+  /// `name(` ... `)`, returning a parser over the arguments.
+  pub fn function(&mut self, name: &str) -> Option<ValueParser<'a>> {
+    self.attempt(|parser| {
+      match parser.peek()? {
+        Lexed::Function(function) if function.eq_ignore_ascii_case(name) => {},
+        _ => return None
+      }
+      parser.current += 1;
+      parser.until_close()
+    })
+  }
 
-  if length <= 0 {
-    return Err(Error(format!("Length too small for parse_keyword")));
+  /// `(` ... `)`, returning a parser over the contents.
+  pub fn parenthesized(&mut self) -> Option<ValueParser<'a>> {
+    self.attempt(|parser| {
+      if !parser.token(&Token::ParOpen) {
+        return None;
+      }
+      parser.until_close()
+    })
   }
 
-  let keyword = match &lexed[0] {
-    &Lexed::Identifier(ref identifier) => identifier.clone(),
-    _ => return Err(Error(format!("Not a keyword for parse_keyword")))
-  };
+  /// Consumes everything up to and including the `)` closing an already
+  /// consumed opening token.
+  fn until_close(&mut self) -> Option<ValueParser<'a>> {
+    let start = self.current;
+    let mut depth = 0;
 
-  Ok(Value::Keyword(keyword))
-}
+    loop {
+      match self.lexed.get(self.current)? {
+        Lexed::Token(Token::ParOpen) | Lexed::Function(_) => depth += 1,
+        Lexed::Token(Token::ParClose) if depth == 0 => break,
+        Lexed::Token(Token::ParClose) => depth -= 1,
+        _ => {}
+      }
+      self.current += 1;
+    }
 
-fn parse_length(lexed: &[Lexed]) -> Result<Value, Error> {
-  let length = lexed.len();
+    let contents = &self.lexed[start..self.current];
+    self.current += 1;
 
-  if length <= 1 {
-    return Err(Error(format!("Length too small for parse_length")));
+    Some(ValueParser::new(contents))
   }
+}
 
-  let number = match &lexed[0] {
-    &Lexed::Number(num) => {
-      num
-    },
-    _ => return Err(Error(format!("Length not a number for parse_length")))
-  };
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::lexer::lex;
+
+  #[test]
+  fn parses_important_and_layers() {
+    let sheet = parse_stylesheet(lex("
+      @layer reset, theme.dark;
+      a { color: red !important; --gap: 1px ! IMPORTANT; margin: 0 }
+      @layer theme { @layer dark { p { color: blue } } }
+      @layer { div { color: green } }
+      @media print { a { color: black } }
+      @layer a b { p { color: red } }
+    ".to_string()).unwrap(), Origin::User).unwrap();
+
+    assert_eq!(sheet.origin, Origin::User);
+    assert_eq!(sheet.rules.len(), 3);
+    assert_eq!(sheet.errors.len(), 2, "{:?}", sheet.errors);
+
+    let declarations = &sheet.rules[0].declarations;
+    assert_eq!(sheet.rules[0].layer, None);
+    assert!(declarations[0].important && declarations[1].important && !declarations[2].important);
+    assert_eq!(declarations[0].value, Value::Color { r: 255, g: 0, b: 0, a: 255 });
+    assert_eq!(declarations[1].value, Value::Unparsed(vec![Lexed::Number(1.0), Lexed::Identifier("px".to_string())]));
+
+    assert_eq!(sheet.rules[1].layer, Some("theme.dark".to_string()));
+    assert!(sheet.rules[2].layer.as_ref().is_some_and(|i| cascade::is_anonymous_layer(i)));
+    assert_eq!(&sheet.layers[..3], &["reset".to_string(), "theme".to_string(), "theme.dark".to_string()]);
+  }
 
-  let unit = match &lexed[1] {
-    &Lexed::Token(ref token) => match token {
-      &Token::Px => Unit::Px,
-      _ => return Err(Error(format!("Unknown unit for parse_length")))
-    },
-    _ => return Err(Error(format!("Unknown unit for parse_length")))
-  };
+  #[test]
+  fn resolves_lengths_to_px() {
+    let context = LengthContext {
+      font_size: 20.0,
+      root_font_size: 16.0,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
 
-  Ok(Value::Length(number, unit))
-}
\ No newline at end of file
+    assert_eq!(context.to_px(1.0, Unit::In), 96.0);
+    assert_eq!(context.to_px(12.0, Unit::Pt), 16.0);
+    assert_eq!(context.to_px(2.0, Unit::Em), 40.0);
+    assert_eq!(context.to_px(2.0, Unit::Rem), 32.0);
+    assert_eq!(context.to_px(10.0, Unit::Vw), 80.0);
+    assert_eq!(context.to_px(10.0, Unit::Vmin), 60.0);
+    assert_eq!(context.to_px(10.0, Unit::Vmax), 80.0);
+
+    let value = Value::List(vec![Value::Length(1.0, Unit::Em), Value::Percentage(50.0)]);
+    assert_eq!(value.to_computed(&context), Value::List(vec![
+      Value::Length(20.0, Unit::Px),
+      Value::Percentage(50.0)
+    ]));
+  }
+}
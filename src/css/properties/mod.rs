@@ -0,0 +1,628 @@
+use super::{lexer::{self, Lexed, Token}, parser::{Value, ValueParser}};
+
+/// Value grammar of a property, matched against the tokens of a declaration
+/// by `Property::parse`.
+pub enum Grammar {
+  Keyword(&'static [&'static str]),
+  Length,
+  NonNegativeLength,
+  Percentage,
+  NonNegativePercentage,
+  LengthPercentage,
+  NonNegativeLengthPercentage,
+  Number,
+  NonNegativeNumber,
+  Integer,
+  Color,
+  String,
+  Url,
+  Identifier,
+
+  Either(&'static [Grammar]), // a | b
+  AnyOrder(&'static [Grammar]), // a || b
+  Repeat(&'static Grammar, usize, usize), // a{min,max}
+  CommaList(&'static Grammar), // a#
+  Custom(fn(&mut ValueParser) -> Option<Value>)
+}
+
+/// A longhand property. Shorthands live in `css::shorthands` and are
+/// expanded into longhands when parsed.
+pub struct Property {
+  pub name: &'static str,
+  pub grammar: Grammar,
+  pub initial: &'static str, // CSS text, see `initial_value`
+  pub inherited: bool // whether an undeclared value is taken from the parent
+}
+
+/// Keywords accepted by every property, resolved by the cascade.
+pub const CSS_WIDE_KEYWORDS: &[&str] = &["inherit", "initial", "unset", "revert", "revert-layer"];
+
+impl Property {
+  /// Parses a complete declaration value, returning `None` if the tokens do
+  /// not match the property's grammar.
+  pub fn parse(&self, lexed: &[Lexed]) -> Option<Value> {
+    let mut parser = ValueParser::new(lexed);
+
+    let value = match parser.keyword(CSS_WIDE_KEYWORDS) {
+      Some(keyword) => keyword,
+      None => self.grammar.parse(&mut parser)?
+    };
+
+    if parser.is_done() {
+      Some(value)
+    } else {
+      None
+    }
+  }
+
+  /// The parsed initial value of the property.
+  pub fn initial_value(&self) -> Value {
+    lexer::lex(self.initial.to_string()).ok()
+      .and_then(|lexed| self.grammar.parse(&mut ValueParser::new(&lexed)))
+      .unwrap_or_else(|| panic!("Invalid initial value for '{}'", self.name))
+  }
+}
+
+impl Grammar {
+  pub fn parse(&self, parser: &mut ValueParser) -> Option<Value> {
+    let non_negative = |value: Value| match value {
+      Value::Length(num, _) | Value::Percentage(num) | Value::Number(num) if num < 0.0 => None,
+      _ => Some(value)
+    };
+
+    match *self {
+      Grammar::Keyword(keywords) => parser.keyword(keywords),
+      Grammar::Length => parser.length(),
+      Grammar::NonNegativeLength => parser.attempt(|parser| non_negative(parser.length()?)),
+      Grammar::Percentage => parser.percentage(),
+      Grammar::NonNegativePercentage => parser.attempt(|parser| non_negative(parser.percentage()?)),
+      Grammar::LengthPercentage => parser.length_percentage(),
+      Grammar::NonNegativeLengthPercentage => parser.attempt(|parser| non_negative(parser.length_percentage()?)),
+      Grammar::Number => parser.number().map(Value::Number),
+      Grammar::NonNegativeNumber => parser.attempt(|parser| non_negative(Value::Number(parser.number()?))),
+      Grammar::Integer => parser.integer().map(Value::Integer),
+      Grammar::Color => parser.color(),
+      Grammar::String => parser.string().map(Value::String),
+      Grammar::Url => parser.url().map(Value::Url),
+      Grammar::Identifier => parser.identifier().map(Value::Keyword),
+
+      Grammar::Either(alternatives) => alternatives.iter()
+        .filter_map(|grammar| parser.attempt(|parser| grammar.parse(parser)))
+        .next(),
+      Grammar::AnyOrder(components) => {
+        let mut found: Vec<Option<Value>> = components.iter().map(|_| None).collect();
+
+        'outer: loop {
+          for (i, grammar) in components.iter().enumerate() {
+            if found[i].is_some() {
+              continue;
+            }
+            if let Some(value) = parser.attempt(|parser| grammar.parse(parser)) {
+              found[i] = Some(value);
+              continue 'outer;
+            }
+          }
+          break;
+        }
+
+        list(found.into_iter().flatten().collect())
+      },
+      Grammar::Repeat(grammar, min, max) => parser.attempt(|parser| {
+        let mut values = Vec::new();
+        while values.len() < max {
+          match parser.attempt(|parser| grammar.parse(parser)) {
+            Some(value) => values.push(value),
+            None => break
+          }
+        }
+
+        if values.len() < min {
+          return None;
+        }
+        list(values)
+      }),
+      Grammar::CommaList(grammar) => parser.attempt(|parser| {
+        let mut values = vec![grammar.parse(parser)?];
+        while parser.token(&Token::Comma) {
+          values.push(grammar.parse(parser)?);
+        }
+        Some(Value::CommaList(values))
+      }),
+      Grammar::Custom(f) => parser.attempt(f)
+    }
+  }
+}
+
+/// A single component stands for itself, several form a space separated list.
+fn list(mut values: Vec<Value>) -> Option<Value> {
+  match values.len() {
+    0 => None,
+    1 => values.pop(),
+    _ => Some(Value::List(values))
+  }
+}
+
+const AUTO: Grammar = Grammar::Keyword(&["auto"]);
+const NONE: Grammar = Grammar::Keyword(&["none"]);
+const NORMAL: Grammar = Grammar::Keyword(&["normal"]);
+
+const LENGTH_PERCENTAGE: Grammar = Grammar::LengthPercentage;
+const LENGTH_PERCENTAGE_AUTO: Grammar = Grammar::Either(&[Grammar::LengthPercentage, AUTO]);
+const NON_NEGATIVE_LENGTH_PERCENTAGE: Grammar = Grammar::NonNegativeLengthPercentage;
+const SIZE: Grammar = Grammar::Either(&[Grammar::NonNegativeLengthPercentage, AUTO]);
+const MAX_SIZE: Grammar = Grammar::Either(&[Grammar::NonNegativeLengthPercentage, NONE]);
+
+const BORDER_STYLE: Grammar = Grammar::Keyword(&[
+  "none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset"
+]);
+const BORDER_WIDTH: Grammar = Grammar::Either(&[
+  Grammar::Keyword(&["thin", "medium", "thick"]),
+  Grammar::NonNegativeLength
+]);
+const BORDER_RADIUS: Grammar = Grammar::Repeat(&NON_NEGATIVE_LENGTH_PERCENTAGE, 1, 2);
+
+const BACKGROUND_ATTACHMENT: Grammar = Grammar::Keyword(&["scroll", "fixed"]);
+const BACKGROUND_IMAGE: Grammar = Grammar::Either(&[Grammar::Url, NONE]);
+const BACKGROUND_REPEAT: Grammar = Grammar::Keyword(&["repeat", "repeat-x", "repeat-y", "no-repeat"]);
+
+const FONT_STYLE: Grammar = Grammar::Keyword(&["normal", "italic", "oblique"]);
+const FONT_VARIANT: Grammar = Grammar::Keyword(&["normal", "small-caps"]);
+const FONT_WEIGHT: Grammar = Grammar::Either(&[
+  Grammar::Keyword(&["normal", "bold", "bolder", "lighter"]),
+  Grammar::Custom(font_weight_number)
+]);
+const FONT_SIZE: Grammar = Grammar::Either(&[
+  Grammar::Keyword(&[
+    "xx-small", "x-small", "small", "medium", "large", "x-large", "xx-large",
+    "larger", "smaller"
+  ]),
+  Grammar::NonNegativeLengthPercentage
+]);
+const LINE_HEIGHT: Grammar = Grammar::Either(&[
+  NORMAL,
+  Grammar::NonNegativeNumber,
+  Grammar::NonNegativeLengthPercentage
+]);
+
+const LIST_STYLE_TYPE: Grammar = Grammar::Keyword(&[
+  "disc", "circle", "square", "decimal", "decimal-leading-zero", "lower-roman", "upper-roman",
+  "lower-greek", "lower-latin", "upper-latin", "armenian", "georgian", "lower-alpha",
+  "upper-alpha", "none"
+]);
+const LIST_STYLE_POSITION: Grammar = Grammar::Keyword(&["inside", "outside"]);
+const LIST_STYLE_IMAGE: Grammar = Grammar::Either(&[Grammar::Url, NONE]);
+
+const OUTLINE_COLOR: Grammar = Grammar::Either(&[Grammar::Color, Grammar::Keyword(&["invert"])]);
+const OUTLINE_STYLE: Grammar = Grammar::Keyword(&[
+  "none", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset"
+]);
+
+const PAGE_BREAK: Grammar = Grammar::Keyword(&["auto", "always", "avoid", "left", "right"]);
+const SPACING: Grammar = Grammar::Either(&[NORMAL, Grammar::Length]);
+const COUNTER: Grammar = Grammar::Either(&[NONE, Grammar::Custom(counter_list)]);
+
+const TRACK_LIST: Grammar = Grammar::Either(&[
+  NONE,
+  Grammar::Repeat(&Grammar::Custom(track), 1, usize::MAX)
+]);
+
+/// Every supported property, sorted by name for `lookup`.
+static PROPERTIES: &[Property] = &[
+  Property { name: "background-attachment", grammar: BACKGROUND_ATTACHMENT, initial: "scroll", inherited: false },
+  Property { name: "background-color", grammar: Grammar::Color, initial: "transparent", inherited: false },
+  Property { name: "background-image", grammar: BACKGROUND_IMAGE, initial: "none", inherited: false },
+  Property { name: "background-position", grammar: Grammar::Custom(background_position), initial: "0% 0%", inherited: false },
+  Property { name: "background-repeat", grammar: BACKGROUND_REPEAT, initial: "repeat", inherited: false },
+  Property { name: "border-bottom-color", grammar: Grammar::Color, initial: "currentcolor", inherited: false },
+  Property { name: "border-bottom-left-radius", grammar: BORDER_RADIUS, initial: "0", inherited: false },
+  Property { name: "border-bottom-right-radius", grammar: BORDER_RADIUS, initial: "0", inherited: false },
+  Property { name: "border-bottom-style", grammar: BORDER_STYLE, initial: "none", inherited: false },
+  Property { name: "border-bottom-width", grammar: BORDER_WIDTH, initial: "medium", inherited: false },
+  Property { name: "border-collapse", grammar: Grammar::Keyword(&["collapse", "separate"]), initial: "separate", inherited: true },
+  Property { name: "border-left-color", grammar: Grammar::Color, initial: "currentcolor", inherited: false },
+  Property { name: "border-left-style", grammar: BORDER_STYLE, initial: "none", inherited: false },
+  Property { name: "border-left-width", grammar: BORDER_WIDTH, initial: "medium", inherited: false },
+  Property { name: "border-right-color", grammar: Grammar::Color, initial: "currentcolor", inherited: false },
+  Property { name: "border-right-style", grammar: BORDER_STYLE, initial: "none", inherited: false },
+  Property { name: "border-right-width", grammar: BORDER_WIDTH, initial: "medium", inherited: false },
+  Property { name: "border-spacing", grammar: Grammar::Repeat(&Grammar::NonNegativeLength, 1, 2), initial: "0", inherited: true },
+  Property { name: "border-top-color", grammar: Grammar::Color, initial: "currentcolor", inherited: false },
+  Property { name: "border-top-left-radius", grammar: BORDER_RADIUS, initial: "0", inherited: false },
+  Property { name: "border-top-right-radius", grammar: BORDER_RADIUS, initial: "0", inherited: false },
+  Property { name: "border-top-style", grammar: BORDER_STYLE, initial: "none", inherited: false },
+  Property { name: "border-top-width", grammar: BORDER_WIDTH, initial: "medium", inherited: false },
+  Property { name: "bottom", grammar: LENGTH_PERCENTAGE_AUTO, initial: "auto", inherited: false },
+  Property { name: "box-sizing", grammar: Grammar::Keyword(&["content-box", "border-box"]), initial: "content-box", inherited: false },
+  Property { name: "caption-side", grammar: Grammar::Keyword(&["top", "bottom"]), initial: "top", inherited: true },
+  Property { name: "clear", grammar: Grammar::Keyword(&["none", "left", "right", "both"]), initial: "none", inherited: false },
+  Property { name: "clip", grammar: Grammar::Either(&[AUTO, Grammar::Custom(clip_rect)]), initial: "auto", inherited: false },
+  Property { name: "color", grammar: Grammar::Color, initial: "black", inherited: true },
+  Property { name: "content", grammar: Grammar::Either(&[
+    Grammar::Keyword(&["normal", "none"]),
+    Grammar::Repeat(&Grammar::Custom(content_item), 1, usize::MAX)
+  ]), initial: "normal", inherited: false },
+  Property { name: "counter-increment", grammar: COUNTER, initial: "none", inherited: false },
+  Property { name: "counter-reset", grammar: COUNTER, initial: "none", inherited: false },
+  Property { name: "cursor", grammar: Grammar::Custom(cursor), initial: "auto", inherited: true },
+  Property { name: "direction", grammar: Grammar::Keyword(&["ltr", "rtl"]), initial: "ltr", inherited: true },
+  Property { name: "display", grammar: Grammar::Keyword(&[
+    "inline", "block", "list-item", "inline-block", "table", "inline-table",
+    "table-row-group", "table-header-group", "table-footer-group", "table-row",
+    "table-column-group", "table-column", "table-cell", "table-caption", "none",
+    "flow-root", "contents", "flex", "inline-flex", "grid", "inline-grid"
+  ]), initial: "inline", inherited: false },
+  Property { name: "empty-cells", grammar: Grammar::Keyword(&["show", "hide"]), initial: "show", inherited: true },
+  Property { name: "flex-basis", grammar: Grammar::Either(&[Grammar::Keyword(&["auto", "content"]), Grammar::NonNegativeLengthPercentage]), initial: "auto", inherited: false },
+  Property { name: "flex-grow", grammar: Grammar::NonNegativeNumber, initial: "0", inherited: false },
+  Property { name: "flex-shrink", grammar: Grammar::NonNegativeNumber, initial: "1", inherited: false },
+  Property { name: "float", grammar: Grammar::Keyword(&["left", "right", "none"]), initial: "none", inherited: false },
+  Property { name: "font-family", grammar: FONT_FAMILY, initial: "serif", inherited: true },
+  Property { name: "font-size", grammar: FONT_SIZE, initial: "medium", inherited: true },
+  Property { name: "font-style", grammar: FONT_STYLE, initial: "normal", inherited: true },
+  Property { name: "font-variant", grammar: FONT_VARIANT, initial: "normal", inherited: true },
+  Property { name: "font-weight", grammar: FONT_WEIGHT, initial: "normal", inherited: true },
+  Property { name: "grid-template-areas", grammar: Grammar::Either(&[NONE, Grammar::Repeat(&Grammar::String, 1, usize::MAX)]), initial: "none", inherited: false },
+  Property { name: "grid-template-columns", grammar: TRACK_LIST, initial: "none", inherited: false },
+  Property { name: "grid-template-rows", grammar: TRACK_LIST, initial: "none", inherited: false },
+  Property { name: "height", grammar: SIZE, initial: "auto", inherited: false },
+  Property { name: "left", grammar: LENGTH_PERCENTAGE_AUTO, initial: "auto", inherited: false },
+  Property { name: "letter-spacing", grammar: SPACING, initial: "normal", inherited: true },
+  Property { name: "line-height", grammar: LINE_HEIGHT, initial: "normal", inherited: true },
+  Property { name: "list-style-image", grammar: LIST_STYLE_IMAGE, initial: "none", inherited: true },
+  Property { name: "list-style-position", grammar: LIST_STYLE_POSITION, initial: "outside", inherited: true },
+  Property { name: "list-style-type", grammar: LIST_STYLE_TYPE, initial: "disc", inherited: true },
+  Property { name: "margin-bottom", grammar: LENGTH_PERCENTAGE_AUTO, initial: "0", inherited: false },
+  Property { name: "margin-left", grammar: LENGTH_PERCENTAGE_AUTO, initial: "0", inherited: false },
+  Property { name: "margin-right", grammar: LENGTH_PERCENTAGE_AUTO, initial: "0", inherited: false },
+  Property { name: "margin-top", grammar: LENGTH_PERCENTAGE_AUTO, initial: "0", inherited: false },
+  Property { name: "max-height", grammar: MAX_SIZE, initial: "none", inherited: false },
+  Property { name: "max-width", grammar: MAX_SIZE, initial: "none", inherited: false },
+  Property { name: "min-height", grammar: NON_NEGATIVE_LENGTH_PERCENTAGE, initial: "0", inherited: false },
+  Property { name: "min-width", grammar: NON_NEGATIVE_LENGTH_PERCENTAGE, initial: "0", inherited: false },
+  Property { name: "orphans", grammar: Grammar::Integer, initial: "2", inherited: true },
+  Property { name: "outline-color", grammar: OUTLINE_COLOR, initial: "invert", inherited: false },
+  Property { name: "outline-style", grammar: OUTLINE_STYLE, initial: "none", inherited: false },
+  Property { name: "outline-width", grammar: BORDER_WIDTH, initial: "medium", inherited: false },
+  Property { name: "overflow", grammar: Grammar::Keyword(&["visible", "hidden", "scroll", "auto"]), initial: "visible", inherited: false },
+  Property { name: "padding-bottom", grammar: NON_NEGATIVE_LENGTH_PERCENTAGE, initial: "0", inherited: false },
+  Property { name: "padding-left", grammar: NON_NEGATIVE_LENGTH_PERCENTAGE, initial: "0", inherited: false },
+  Property { name: "padding-right", grammar: NON_NEGATIVE_LENGTH_PERCENTAGE, initial: "0", inherited: false },
+  Property { name: "padding-top", grammar: NON_NEGATIVE_LENGTH_PERCENTAGE, initial: "0", inherited: false },
+  Property { name: "page-break-after", grammar: PAGE_BREAK, initial: "auto", inherited: false },
+  Property { name: "page-break-before", grammar: PAGE_BREAK, initial: "auto", inherited: false },
+  Property { name: "page-break-inside", grammar: Grammar::Keyword(&["avoid", "auto"]), initial: "auto", inherited: false },
+  Property { name: "position", grammar: Grammar::Keyword(&[
+    "static", "relative", "absolute", "fixed", "sticky"
+  ]), initial: "static", inherited: false },
+  Property { name: "quotes", grammar: Grammar::Either(&[
+    NONE,
+    Grammar::Repeat(&Grammar::Repeat(&Grammar::String, 2, 2), 1, usize::MAX)
+  ]), initial: "\"\u{201c}\" \"\u{201d}\" \"\u{2018}\" \"\u{2019}\"", inherited: true },
+  Property { name: "right", grammar: LENGTH_PERCENTAGE_AUTO, initial: "auto", inherited: false },
+  Property { name: "table-layout", grammar: Grammar::Keyword(&["auto", "fixed"]), initial: "auto", inherited: false },
+  Property { name: "text-align", grammar: Grammar::Keyword(&["left", "right", "center", "justify"]), initial: "left", inherited: true },
+  Property { name: "text-decoration", grammar: Grammar::Either(&[
+    NONE,
+    Grammar::AnyOrder(&[
+      Grammar::Keyword(&["underline"]),
+      Grammar::Keyword(&["overline"]),
+      Grammar::Keyword(&["line-through"]),
+      Grammar::Keyword(&["blink"])
+    ])
+  ]), initial: "none", inherited: false },
+  Property { name: "text-indent", grammar: LENGTH_PERCENTAGE, initial: "0", inherited: true },
+  Property { name: "text-transform", grammar: Grammar::Keyword(&[
+    "capitalize", "uppercase", "lowercase", "none"
+  ]), initial: "none", inherited: true },
+  Property { name: "top", grammar: LENGTH_PERCENTAGE_AUTO, initial: "auto", inherited: false },
+  Property { name: "unicode-bidi", grammar: Grammar::Keyword(&["normal", "embed", "bidi-override"]), initial: "normal", inherited: false },
+  Property { name: "vertical-align", grammar: Grammar::Either(&[
+    Grammar::Keyword(&[
+      "baseline", "sub", "super", "top", "text-top", "middle", "bottom", "text-bottom"
+    ]),
+    Grammar::LengthPercentage
+  ]), initial: "baseline", inherited: false },
+  Property { name: "visibility", grammar: Grammar::Keyword(&["visible", "hidden", "collapse"]), initial: "visible", inherited: true },
+  Property { name: "white-space", grammar: Grammar::Keyword(&[
+    "normal", "pre", "nowrap", "pre-wrap", "pre-line"
+  ]), initial: "normal", inherited: true },
+  Property { name: "widows", grammar: Grammar::Integer, initial: "2", inherited: true },
+  Property { name: "width", grammar: SIZE, initial: "auto", inherited: false },
+  Property { name: "word-spacing", grammar: SPACING, initial: "normal", inherited: true },
+  Property { name: "z-index", grammar: Grammar::Either(&[AUTO, Grammar::Integer]), initial: "auto", inherited: false },
+];
+
+pub fn all() -> &'static [Property] {
+  PROPERTIES
+}
+
+pub fn lookup(name: &str) -> Option<&'static Property> {
+  PROPERTIES.binary_search_by(|property| property.name.cmp(name))
+    .ok()
+    .map(|i| &PROPERTIES[i])
+}
+
+fn font_weight_number(parser: &mut ValueParser) -> Option<Value> {
+  match parser.integer()? {
+    weight @ 100..=900 if weight % 100 == 0 => Some(Value::Integer(weight)),
+    _ => None
+  }
+}
+
+/// `[ left | center | right | <length-percentage> ] [ top | center | bottom | <length-percentage> ]?`
+/// or the two keyword groups in any order.
+fn background_position(parser: &mut ValueParser) -> Option<Value> {
+  let component = |parser: &mut ValueParser| -> Option<Value> {
+    parser.keyword(&["left", "center", "right", "top", "bottom"])
+      .or_else(|| LENGTH_PERCENTAGE.parse(parser))
+  };
+  let is = |value: &Value, keywords: &[&str]| match value {
+    Value::Keyword(keyword) => keywords.contains(&&keyword[..]),
+    _ => false
+  };
+
+  let first = component(parser)?;
+  let second = match parser.attempt(component) {
+    Some(second) => second,
+    None => return Some(first)
+  };
+
+  let valid = if is(&first, &["top", "bottom"]) || is(&second, &["left", "right"]) {
+    // keywords in swapped order, only valid if neither is a length
+    matches!(first, Value::Keyword(_)) && matches!(second, Value::Keyword(_))
+      && !is(&first, &["left", "right"]) && !is(&second, &["top", "bottom"])
+  } else {
+    true
+  };
+
+  if valid {
+    Some(Value::List(vec![first, second]))
+  } else {
+    None
+  }
+}
+
+/// `rect(<top>, <right>, <bottom>, <left>)`, commas being optional.
+fn clip_rect(parser: &mut ValueParser) -> Option<Value> {
+  let mut arguments = parser.function("rect")?;
+  let mut edges = Vec::new();
+
+  for i in 0..4 {
+    if i > 0 {
+      arguments.token(&Token::Comma);
+    }
+    edges.push(Grammar::Either(&[Grammar::Length, AUTO]).parse(&mut arguments)?);
+  }
+
+  if !arguments.is_done() {
+    return None;
+  }
+  Some(Value::Function("rect".to_string(), edges))
+}
+
+/// A single item of `content`.
+fn content_item(parser: &mut ValueParser) -> Option<Value> {
+  if let Some(s) = parser.string() {
+    return Some(Value::String(s));
+  }
+  if let Some(url) = parser.url() {
+    return Some(Value::Url(url));
+  }
+  if let Some(keyword) = parser.keyword(&["open-quote", "close-quote", "no-open-quote", "no-close-quote"]) {
+    return Some(keyword);
+  }
+
+  if let Some(mut arguments) = parser.function("attr") {
+    let name = arguments.identifier()?;
+    if !arguments.is_done() {
+      return None;
+    }
+    return Some(Value::Function("attr".to_string(), vec![Value::Keyword(name)]));
+  }
+
+  if let Some(mut arguments) = parser.function("counter") {
+    let mut values = vec![Value::Keyword(arguments.identifier()?)];
+    if arguments.token(&Token::Comma) {
+      values.push(LIST_STYLE_TYPE.parse(&mut arguments)?);
+    }
+    if !arguments.is_done() {
+      return None;
+    }
+    return Some(Value::Function("counter".to_string(), values));
+  }
+
+  if let Some(mut arguments) = parser.function("counters") {
+    let mut values = vec![Value::Keyword(arguments.identifier()?)];
+    if !arguments.token(&Token::Comma) {
+      return None;
+    }
+    values.push(Value::String(arguments.string()?));
+    if arguments.token(&Token::Comma) {
+      values.push(LIST_STYLE_TYPE.parse(&mut arguments)?);
+    }
+    if !arguments.is_done() {
+      return None;
+    }
+    return Some(Value::Function("counters".to_string(), values));
+  }
+
+  None
+}
+
+/// `[ <identifier> <integer>? ]+`
+fn counter_list(parser: &mut ValueParser) -> Option<Value> {
+  let mut values = Vec::new();
+
+  while let Some(name) = parser.attempt(|parser| parser.identifier()) {
+    let lowercase = name.to_ascii_lowercase();
+    if lowercase == "none" || CSS_WIDE_KEYWORDS.contains(&&lowercase[..]) {
+      return None;
+    }
+    values.push(Value::Keyword(name));
+    if let Some(num) = parser.integer() {
+      values.push(Value::Integer(num));
+    }
+  }
+
+  list(values)
+}
+
+/// `[ <uri> , ]* <keyword>`
+fn cursor(parser: &mut ValueParser) -> Option<Value> {
+  let mut values = Vec::new();
+
+  while let Some(url) = parser.url() {
+    if !parser.token(&Token::Comma) {
+      return None;
+    }
+    values.push(Value::Url(url));
+  }
+
+  values.push(parser.keyword(&[
+    "auto", "crosshair", "default", "pointer", "move", "e-resize", "ne-resize", "nw-resize",
+    "n-resize", "se-resize", "sw-resize", "s-resize", "w-resize", "text", "wait", "help",
+    "progress"
+  ])?);
+
+  match values.len() {
+    1 => values.pop(),
+    _ => Some(Value::CommaList(values))
+  }
+}
+
+const GENERIC_FAMILIES: &[&str] = &["serif", "sans-serif", "cursive", "fantasy", "monospace"];
+
+const FONT_FAMILY: Grammar = Grammar::CommaList(&Grammar::Custom(font_family_name));
+
+/// A quoted or unquoted family name. Unquoted names made of several
+/// identifiers are joined by single spaces.
+fn font_family_name(parser: &mut ValueParser) -> Option<Value> {
+  if let Some(s) = parser.string() {
+    return Some(Value::String(s));
+  }
+
+  let mut names = vec![parser.identifier()?];
+  while let Some(name) = parser.attempt(|parser| parser.identifier()) {
+    names.push(name);
+  }
+
+  let lowercase = names[0].to_ascii_lowercase();
+  if names.len() == 1 && GENERIC_FAMILIES.contains(&&lowercase[..]) {
+    return Some(Value::Keyword(lowercase));
+  }
+  if names.iter().any(|name| CSS_WIDE_KEYWORDS.contains(&&name.to_ascii_lowercase()[..])) {
+    return None;
+  }
+  Some(Value::String(names.join(" ")))
+}
+
+/// A grid track size, `minmax()` or `repeat()` of track sizes. Line names
+/// are not supported.
+fn track(parser: &mut ValueParser) -> Option<Value> {
+  if let Some(mut arguments) = parser.function("repeat") {
+    let count = match arguments.integer() {
+      Some(count) if count > 0 => Value::Integer(count),
+      Some(_) => return None,
+      None => arguments.keyword(&["auto-fill", "auto-fit"])?
+    };
+    if !arguments.token(&Token::Comma) {
+      return None;
+    }
+
+    let mut values = vec![count];
+    while let Some(size) = arguments.attempt(track_size) {
+      values.push(size);
+    }
+    if values.len() < 2 || !arguments.is_done() {
+      return None;
+    }
+    return Some(Value::Function("repeat".to_string(), values));
+  }
+
+  track_size(parser)
+}
+
+pub fn track_size(parser: &mut ValueParser) -> Option<Value> {
+  if let Some(mut arguments) = parser.function("minmax") {
+    let min = track_breadth(&mut arguments)?;
+    if !arguments.token(&Token::Comma) {
+      return None;
+    }
+    let max = track_breadth(&mut arguments)?;
+    if !arguments.is_done() {
+      return None;
+    }
+    return Some(Value::Function("minmax".to_string(), vec![min, max]));
+  }
+
+  track_breadth(parser)
+}
+
+fn track_breadth(parser: &mut ValueParser) -> Option<Value> {
+  parser.keyword(&["auto", "min-content", "max-content"])
+    .or_else(|| parser.flex().map(Value::Flex))
+    .or_else(|| NON_NEGATIVE_LENGTH_PERCENTAGE.parse(parser))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::lexer::lex;
+  use super::super::parser::Unit;
+
+  fn parse(name: &str, value: &str) -> Option<Value> {
+    lookup(name)?.parse(&lex(value.to_string()).ok()?)
+  }
+
+  #[test]
+  fn properties_are_sorted() {
+    for pair in PROPERTIES.windows(2) {
+      assert!(pair[0].name < pair[1].name, "{} before {}", pair[0].name, pair[1].name);
+    }
+  }
+
+  #[test]
+  fn parses_typed_values() {
+    assert_eq!(parse("width", "50px"), Some(Value::Length(50.0, Unit::Px)));
+    assert_eq!(parse("width", "50%"), Some(Value::Percentage(50.0)));
+    assert_eq!(parse("width", "-5px"), None);
+    assert_eq!(parse("width", "2.5EM"), Some(Value::Length(2.5, Unit::Em)));
+    assert_eq!(parse("width", "10vmin"), Some(Value::Length(10.0, Unit::Vmin)));
+    assert_eq!(parse("width", "10 px"), None);
+    assert_eq!(parse("width", "10furlongs"), None);
+    assert_eq!(parse("width", "calc(2 * 10px)"), Some(Value::Length(20.0, Unit::Px)));
+    assert!(matches!(parse("width", "calc(100% - 2em)"), Some(Value::Calc(_))));
+    assert_eq!(parse("z-index", "calc(1.6)"), Some(Value::Integer(2)));
+    assert_eq!(parse("color", "calc(1px)"), None);
+    assert_eq!(parse("color", "#afa"), Some(Value::Color { r: 0xaa, g: 0xff, b: 0xaa, a: 255 }));
+    assert_eq!(parse("z-index", "2"), Some(Value::Integer(2)));
+    assert_eq!(parse("display", "inherit"), Some(Value::Keyword("inherit".to_string())));
+    assert_eq!(parse("display", "block inline"), None);
+  }
+
+  #[test]
+  fn initial_values_parse() {
+    for property in PROPERTIES.iter() {
+      property.initial_value();
+    }
+    assert_eq!(lookup("border-top-color").map(Property::initial_value), Some(Value::Keyword("currentcolor".to_string())));
+  }
+
+  #[test]
+  fn parses_compound_values() {
+    assert_eq!(parse("border-spacing", "1px 0"), Some(Value::List(vec![
+      Value::Length(1.0, Unit::Px),
+      Value::Length(0.0, Unit::Px)
+    ])));
+    assert!(parse("grid-template-columns", "repeat(auto-fill, minmax(100px, 1fr)) 20%").is_some());
+    assert_eq!(parse("font-family", "Times New Roman, \"Arial\", serif"), Some(Value::CommaList(vec![
+      Value::String("Times New Roman".to_string()),
+      Value::String("Arial".to_string()),
+      Value::Keyword("serif".to_string())
+    ])));
+    assert!(parse("background-position", "top left").is_some());
+    assert!(parse("background-position", "10px top").is_some());
+    assert!(parse("background-position", "top 10px").is_none());
+    assert!(parse("clip", "rect(1px, auto, 3px, 0)").is_some());
+  }
+}
@@ -0,0 +1,550 @@
+use super::{Error, lexer::{Lexed, Token}};
+
+/// A complex selector such as `div.note > p:first-child`, stored left to
+/// right. Matching starts from the last compound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+  pub compounds: Vec<Compound>,
+  pub pseudo_element: Option<String> // `::before`, which never matches an element itself
+}
+
+/// The simple selectors between two combinators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compound {
+  pub combinator: Combinator, // relation to the compound on the left, or to the anchor of a relative selector
+  pub tag_name: Option<String>, // lowercase, `None` for `*` or no type selector
+  pub id: Vec<String>,
+  pub class: Vec<String>,
+  pub attributes: Vec<AttributeSelector>,
+  pub pseudo: Vec<PseudoClass>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combinator {
+  Descendant,
+  Child,
+  GeneralSibling,
+  AdjacentSibling
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSelector {
+  pub name: String, // lowercase
+  pub operator: Option<(AttributeOperator, String)>,
+  pub case_insensitive: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeOperator {
+  Equals, // [a=b]
+  Includes, // [a~=b]
+  DashMatch, // [a|=b]
+  Prefix, // [a^=b]
+  Suffix, // [a$=b]
+  Substring // [a*=b]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PseudoClass {
+  FirstChild,
+  LastChild,
+  OnlyChild,
+  FirstOfType,
+  LastOfType,
+  OnlyOfType,
+  NthChild(i32, i32), // an+b
+  NthLastChild(i32, i32),
+  NthOfType(i32, i32),
+  NthLastOfType(i32, i32),
+  Root,
+  Empty,
+  Link,
+  Visited,
+  Hover,
+  Active,
+  Focus,
+  FocusWithin,
+  Checked,
+  Disabled,
+  Enabled,
+  Not(Vec<Selector>),
+  Is(Vec<Selector>),
+  Where(Vec<Selector>),
+  Has(Vec<Selector>) // relative selectors, the first combinator relating to the anchor
+}
+
+/// `(ids, classes, types)`, compared lexicographically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Specificity(pub u32, pub u32, pub u32);
+
+impl Specificity {
+  fn add(self, other: Specificity) -> Specificity {
+    Specificity(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+  }
+}
+
+impl Selector {
+  pub fn specificity(&self) -> Specificity {
+    let base = Specificity(0, 0, if self.pseudo_element.is_some() { 1 } else { 0 });
+    self.compounds.iter().fold(base, |sum, compound| sum.add(compound.specificity()))
+  }
+}
+
+impl Compound {
+  fn specificity(&self) -> Specificity {
+    let mut specificity = Specificity(
+      self.id.len() as u32,
+      (self.class.len() + self.attributes.len()) as u32,
+      if self.tag_name.is_some() { 1 } else { 0 }
+    );
+
+    for pseudo in self.pseudo.iter() {
+      specificity = specificity.add(match *pseudo {
+        PseudoClass::Where(_) => Specificity::default(),
+        PseudoClass::Not(ref list) | PseudoClass::Is(ref list) | PseudoClass::Has(ref list) => {
+          list.iter().map(Selector::specificity).max().unwrap_or_default()
+        },
+        _ => Specificity(0, 1, 0)
+      });
+    }
+
+    specificity
+  }
+}
+
+const PSEUDO_ELEMENTS: &[&str] = &["after", "before", "first-letter", "first-line", "marker", "placeholder", "selection"];
+
+/// Parses a comma separated selector list. An invalid selector invalidates
+/// the whole list.
+pub fn parse_selector_list(lexed: &[Lexed]) -> Result<Vec<Selector>, Error> {
+  parse_list(lexed, false)
+}
+
+fn parse_list(lexed: &[Lexed], relative: bool) -> Result<Vec<Selector>, Error> {
+  split_commas(lexed).into_iter()
+    .map(|lexed| SelectorParser::new(lexed).selector(relative))
+    .collect()
+}
+
+/// Splits at the commas outside of functions.
+fn split_commas(lexed: &[Lexed]) -> Vec<&[Lexed]> {
+  let mut parts = Vec::new();
+  let mut depth = 0;
+  let mut start = 0;
+
+  for (i, item) in lexed.iter().enumerate() {
+    match *item {
+      Lexed::Function(_) | Lexed::Token(Token::ParOpen) => depth += 1,
+      Lexed::Token(Token::ParClose) => depth -= 1,
+      Lexed::Token(Token::Comma) if depth == 0 => {
+        parts.push(&lexed[start..i]);
+        start = i + 1;
+      },
+      _ => {}
+    }
+  }
+
+  parts.push(&lexed[start..]);
+  parts
+}
+
+struct SelectorParser<'a> {
+  lexed: &'a [Lexed],
+  current: usize
+}
+
+impl<'a> SelectorParser<'a> {
+  fn new(lexed: &'a [Lexed]) -> Self {
+    Self {
+      lexed,
+      current: 0
+    }
+  }
+
+  fn peek(&self) -> Option<&'a Lexed> {
+    self.lexed.get(self.current)
+  }
+
+  fn next(&mut self) -> Option<&'a Lexed> {
+    let item = self.peek();
+    self.current += 1;
+    item
+  }
+
+  fn token(&mut self, token: &Token) -> bool {
+    if self.peek() == Some(&Lexed::Token(token.clone())) {
+      self.current += 1;
+      return true;
+    }
+    false
+  }
+
+  /// Skips whitespace, returning whether there was any.
+  fn whitespace(&mut self) -> bool {
+    let start = self.current;
+    while self.token(&Token::Whitespace) {}
+    self.current > start
+  }
+
+  fn unexpected(&self) -> Error {
+    match self.peek() {
+      Some(item) => Error(format!("Unexpected {:?} in selector", item)),
+      None => Error("Unexpected end of selector".to_string())
+    }
+  }
+
+  fn combinator(&mut self) -> Option<Combinator> {
+    let combinator = match self.peek() {
+      Some(Lexed::Token(Token::GT)) => Combinator::Child,
+      Some(Lexed::Token(Token::Plus)) => Combinator::AdjacentSibling,
+      Some(Lexed::Token(Token::Tilde)) => Combinator::GeneralSibling,
+      _ => return None
+    };
+    self.current += 1;
+    Some(combinator)
+  }
+
+  fn selector(&mut self, relative: bool) -> Result<Selector, Error> {
+    let mut selector = Selector {
+      compounds: Vec::new(),
+      pseudo_element: None
+    };
+
+    self.whitespace();
+    let mut combinator = Combinator::Descendant;
+    if relative {
+      if let Some(leading) = self.combinator() {
+        combinator = leading;
+        self.whitespace();
+      }
+    }
+
+    loop {
+      let mut compound = self.compound(&mut selector)?;
+      compound.combinator = combinator;
+      selector.compounds.push(compound);
+
+      let whitespace = self.whitespace();
+      if self.peek().is_none() {
+        break;
+      }
+      if selector.pseudo_element.is_some() {
+        return Err(Error("Pseudo-elements must end a selector".to_string()));
+      }
+
+      combinator = match self.combinator() {
+        Some(combinator) => combinator,
+        None if whitespace => Combinator::Descendant,
+        None => return Err(self.unexpected())
+      };
+      self.whitespace();
+    }
+
+    Ok(selector)
+  }
+
+  fn compound(&mut self, selector: &mut Selector) -> Result<Compound, Error> {
+    let mut compound = Compound {
+      combinator: Combinator::Descendant,
+      tag_name: None,
+      id: Vec::new(),
+      class: Vec::new(),
+      attributes: Vec::new(),
+      pseudo: Vec::new()
+    };
+
+    let start = self.current;
+    match self.peek() {
+      Some(Lexed::Identifier(name)) => {
+        compound.tag_name = Some(name.to_ascii_lowercase());
+        self.current += 1;
+      },
+      Some(Lexed::Token(Token::Star)) => self.current += 1,
+      _ => {}
+    }
+
+    loop {
+      match self.peek() {
+        Some(Lexed::Hash(id)) => {
+          compound.id.push(id.clone());
+          self.current += 1;
+        },
+        Some(Lexed::Token(Token::Dot)) => {
+          self.current += 1;
+          match self.next() {
+            Some(Lexed::Identifier(class)) => compound.class.push(class.clone()),
+            _ => return Err(Error("Expected class name after '.'".to_string()))
+          }
+        },
+        Some(Lexed::Token(Token::BracketOpen)) => {
+          self.current += 1;
+          compound.attributes.push(self.attribute()?);
+        },
+        Some(Lexed::Token(Token::Colon)) => {
+          self.current += 1;
+          match self.peek() {
+            // legacy single colon pseudo-elements
+            Some(Lexed::Identifier(name)) if ["before", "after", "first-line", "first-letter"].contains(&&name.to_ascii_lowercase()[..]) => {
+              self.current += 1;
+              selector.pseudo_element = Some(name.to_ascii_lowercase());
+              break;
+            },
+            _ => compound.pseudo.push(self.pseudo_class()?)
+          }
+        },
+        Some(Lexed::Token(Token::DoubleColon)) => {
+          self.current += 1;
+          match self.next() {
+            Some(Lexed::Identifier(name)) if PSEUDO_ELEMENTS.contains(&&name.to_ascii_lowercase()[..]) => {
+              selector.pseudo_element = Some(name.to_ascii_lowercase());
+              break;
+            },
+            item => return Err(Error(format!("Unknown pseudo-element: {:?}", item)))
+          }
+        },
+        _ => break
+      }
+    }
+
+    if self.current == start {
+      return Err(self.unexpected());
+    }
+
+    Ok(compound)
+  }
+
+  /// After `[`: `name`, `name=value` with any operator, and an optional `i`
+  /// or `s` flag.
+  fn attribute(&mut self) -> Result<AttributeSelector, Error> {
+    self.whitespace();
+    let name = match self.next() {
+      Some(Lexed::Identifier(name)) => name.to_ascii_lowercase(),
+      _ => return Err(Error("Expected attribute name".to_string()))
+    };
+    self.whitespace();
+
+    let mut attribute = AttributeSelector {
+      name,
+      operator: None,
+      case_insensitive: false
+    };
+
+    if self.token(&Token::BracketClose) {
+      return Ok(attribute);
+    }
+
+    let operator = match self.next() {
+      Some(Lexed::Token(Token::Equals)) => AttributeOperator::Equals,
+      Some(Lexed::Token(ref token)) => {
+        let operator = match *token {
+          Token::Tilde => AttributeOperator::Includes,
+          Token::Pipe => AttributeOperator::DashMatch,
+          Token::Caret => AttributeOperator::Prefix,
+          Token::Dollar => AttributeOperator::Suffix,
+          Token::Star => AttributeOperator::Substring,
+          _ => return Err(Error(format!("Unexpected {:?} in attribute selector", token)))
+        };
+        if !self.token(&Token::Equals) {
+          return Err(Error("Expected '=' in attribute selector".to_string()));
+        }
+        operator
+      },
+      item => return Err(Error(format!("Unexpected {:?} in attribute selector", item)))
+    };
+
+    self.whitespace();
+    let value = match self.next() {
+      Some(Lexed::Identifier(value)) | Some(Lexed::String(value)) => value.clone(),
+      item => return Err(Error(format!("Expected attribute value, found {:?}", item)))
+    };
+    attribute.operator = Some((operator, value));
+    self.whitespace();
+
+    if let Some(Lexed::Identifier(flag)) = self.peek() {
+      match &flag.to_ascii_lowercase()[..] {
+        "i" => attribute.case_insensitive = true,
+        "s" => {},
+        _ => return Err(Error(format!("Unknown attribute selector flag: '{}'", flag)))
+      }
+      self.current += 1;
+      self.whitespace();
+    }
+
+    if !self.token(&Token::BracketClose) {
+      return Err(Error("Expected ']' after attribute selector".to_string()));
+    }
+
+    Ok(attribute)
+  }
+
+  /// After `:`.
+  fn pseudo_class(&mut self) -> Result<PseudoClass, Error> {
+    let pseudo = match self.next() {
+      Some(Lexed::Identifier(name)) => match &name.to_ascii_lowercase()[..] {
+        "first-child" => PseudoClass::FirstChild,
+        "last-child" => PseudoClass::LastChild,
+        "only-child" => PseudoClass::OnlyChild,
+        "first-of-type" => PseudoClass::FirstOfType,
+        "last-of-type" => PseudoClass::LastOfType,
+        "only-of-type" => PseudoClass::OnlyOfType,
+        "root" => PseudoClass::Root,
+        "empty" => PseudoClass::Empty,
+        "link" | "any-link" => PseudoClass::Link,
+        "visited" => PseudoClass::Visited,
+        "hover" => PseudoClass::Hover,
+        "active" => PseudoClass::Active,
+        "focus" | "focus-visible" => PseudoClass::Focus,
+        "focus-within" => PseudoClass::FocusWithin,
+        "checked" => PseudoClass::Checked,
+        "disabled" => PseudoClass::Disabled,
+        "enabled" => PseudoClass::Enabled,
+        _ => return Err(Error(format!("Unknown pseudo-class: ':{}'", name)))
+      },
+      Some(Lexed::Function(name)) => {
+        let arguments = self.arguments()?;
+        match &name.to_ascii_lowercase()[..] {
+          "not" => PseudoClass::Not(parse_list(arguments, false)?),
+          "is" | "matches" => PseudoClass::Is(parse_list(arguments, false)?),
+          "where" => PseudoClass::Where(parse_list(arguments, false)?),
+          "has" => PseudoClass::Has(parse_list(arguments, true)?),
+          nth => {
+            let (a, b) = an_plus_b(arguments)
+              .ok_or_else(|| Error(format!("Invalid argument to ':{}()'", name)))?;
+            match nth {
+              "nth-child" => PseudoClass::NthChild(a, b),
+              "nth-last-child" => PseudoClass::NthLastChild(a, b),
+              "nth-of-type" => PseudoClass::NthOfType(a, b),
+              "nth-last-of-type" => PseudoClass::NthLastOfType(a, b),
+              _ => return Err(Error(format!("Unknown pseudo-class: ':{}()'", name)))
+            }
+          }
+        }
+      },
+      item => return Err(Error(format!("Expected pseudo-class, found {:?}", item)))
+    };
+
+    Ok(pseudo)
+  }
+
+  /// The tokens up to the `)` closing the function just consumed.
+  fn arguments(&mut self) -> Result<&'a [Lexed], Error> {
+    let start = self.current;
+    let mut depth = 0;
+
+    while let Some(item) = self.next() {
+      match *item {
+        Lexed::Function(_) | Lexed::Token(Token::ParOpen) => depth += 1,
+        Lexed::Token(Token::ParClose) if depth == 0 => return Ok(&self.lexed[start..self.current - 1]),
+        Lexed::Token(Token::ParClose) => depth -= 1,
+        _ => {}
+      }
+    }
+
+    Err(Error("Expected ')' in selector".to_string()))
+  }
+}
+
+/// The `an+b` microsyntax, as well as `odd` and `even`. The lexer splits it
+/// in inconvenient places (`2n+1` is a number, `n` and `+1`), so it is
+/// parsed from its text.
+fn an_plus_b(lexed: &[Lexed]) -> Option<(i32, i32)> {
+  let mut text = String::new();
+  for item in lexed.iter() {
+    match *item {
+      Lexed::Identifier(ref name) => text.push_str(name),
+      Lexed::Number(num) => {
+        if num.fract() != 0.0 {
+          return None;
+        }
+        // the sign of `+1` is not kept by the lexer
+        if num >= 0.0 && !text.is_empty() && !text.ends_with('+') && !text.ends_with('-') {
+          text.push('+');
+        }
+        text.push_str(&(num as i32).to_string());
+      },
+      Lexed::Token(Token::Whitespace) => {},
+      Lexed::Token(Token::Plus) => text.push('+'),
+      Lexed::Token(Token::Minus) => text.push('-'),
+      _ => return None
+    }
+  }
+
+  let text = text.to_ascii_lowercase();
+  let integer = |s: &str| match s {
+    "" | "+" => Some(1),
+    "-" => Some(-1),
+    _ => s.trim_start_matches('+').parse::<i32>().ok()
+  };
+
+  match &text[..] {
+    "odd" => Some((2, 1)),
+    "even" => Some((2, 0)),
+    _ => match text.find('n') {
+      Some(n) => {
+        let a = integer(&text[..n])?;
+        let b = match &text[n + 1..] {
+          "" => 0,
+          b if b.starts_with('+') || b.starts_with('-') => b.trim_start_matches('+').parse::<i32>().ok()?,
+          _ => return None
+        };
+        Some((a, b))
+      },
+      None => text.trim_start_matches('+').parse::<i32>().ok().map(|b| (0, b))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::lexer::lex;
+
+  fn parse(query: &str) -> Result<Vec<Selector>, Error> {
+    parse_selector_list(&lex(query.to_string()).unwrap())
+  }
+
+  #[test]
+  fn parses_complex_selectors() {
+    let selector = parse("div.note  >p#intro.a + span ~ a[href^=\"http\" i]:not(.b)").unwrap().remove(0);
+    let combinators: Vec<Combinator> = selector.compounds.iter().map(|i| i.combinator).collect();
+    assert_eq!(combinators, vec![Combinator::Descendant, Combinator::Child, Combinator::AdjacentSibling, Combinator::GeneralSibling]);
+    assert_eq!(selector.compounds[1].id, vec!["intro"]);
+    assert_eq!(selector.compounds[3].attributes[0], AttributeSelector {
+      name: "href".to_string(),
+      operator: Some((AttributeOperator::Prefix, "http".to_string())),
+      case_insensitive: true
+    });
+
+    let descendant = parse("ul li").unwrap().remove(0);
+    assert_eq!(descendant.compounds.len(), 2);
+
+    let nth = |query: &str| match parse(query).unwrap().remove(0).compounds[0].pseudo[0] {
+      PseudoClass::NthChild(a, b) => (a, b),
+      ref other => panic!("{:?}", other)
+    };
+    assert_eq!(nth(":nth-child(2n+1)"), (2, 1));
+    assert_eq!(nth(":nth-child(-n + 3)"), (-1, 3));
+    assert_eq!(nth(":nth-child(n-1)"), (1, -1));
+    assert_eq!(nth(":nth-child(even)"), (2, 0));
+    assert_eq!(nth(":nth-child(4)"), (0, 4));
+
+    assert!(parse("p::before").unwrap()[0].pseudo_element.is_some());
+    assert!(parse("a, :is(b, c)").unwrap().len() == 2);
+    assert!(parse("a,, b").is_err());
+    assert!(parse("a:unknown").is_err());
+    assert!(parse("a > > b").is_err());
+    assert!(parse("::before a").is_err());
+  }
+
+  #[test]
+  fn computes_specificity() {
+    let specificity = |query: &str| parse(query).unwrap()[0].specificity();
+
+    assert_eq!(specificity("*"), Specificity(0, 0, 0));
+    assert_eq!(specificity("li::marker"), Specificity(0, 0, 2));
+    assert_eq!(specificity("ul li.a[title]:first-child"), Specificity(0, 3, 2));
+    assert_eq!(specificity("#a #b"), Specificity(2, 0, 0));
+    assert_eq!(specificity(":is(#a, p) :where(#b) :not(.c)"), Specificity(1, 1, 0));
+    assert_eq!(specificity("a:has(> img)"), Specificity(0, 0, 2));
+  }
+}
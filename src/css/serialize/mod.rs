@@ -0,0 +1,795 @@
+use std::cmp::Reverse;
+use super::{StyleNumber, lexer::{self, Lexed}, calc::Calc, cascade, properties::CSS_WIDE_KEYWORDS, shorthands::{self, Shorthand}};
+use super::parser::{Stylesheet, Rule, Declaration, Value};
+use super::selectors::{Selector, Compound, Combinator, AttributeSelector, AttributeOperator, PseudoClass};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+  Canonical, // one declaration per line, as a formatter would print it
+  Minified
+}
+
+/// Serialization to CSS text, following CSSOM where it defines the output.
+/// Parsing the serialized text gives back an equivalent object.
+pub trait ToCss {
+  fn write_css(&self, dest: &mut String, format: Format);
+
+  fn to_css(&self) -> String {
+    let mut dest = String::new();
+    self.write_css(&mut dest, Format::Canonical);
+    dest
+  }
+
+  fn to_minified_css(&self) -> String {
+    let mut dest = String::new();
+    self.write_css(&mut dest, Format::Minified);
+    dest
+  }
+}
+
+/// `canonical` when formatting, `minified` otherwise.
+fn pick(format: Format, canonical: &'static str, minified: &'static str) -> &'static str {
+  match format {
+    Format::Canonical => canonical,
+    Format::Minified => minified
+  }
+}
+
+impl ToCss for Stylesheet {
+  /// Writes the layer order first, then the rules with consecutive rules of
+  /// the same layer grouped into `@layer` blocks.
+  fn write_css(&self, dest: &mut String, format: Format) {
+    let mut items = Vec::new();
+
+    let named: Vec<&str> = self.layers.iter()
+      .filter(|name| !name.split('.').any(cascade::is_anonymous_layer))
+      .map(|name| &name[..])
+      .collect();
+    if !named.is_empty() {
+      items.push(format!("@layer {};", named.join(pick(format, ", ", ","))));
+    }
+
+    let mut i = 0;
+    while i < self.rules.len() {
+      let layer = self.rules[i].layer.as_ref();
+      let length = self.rules[i..].iter()
+        .take_while(|rule| rule.layer.as_ref() == layer)
+        .count();
+
+      let mut item = String::new();
+      let headers = layer.map(|layer| layer_headers(layer)).unwrap_or_default();
+      write_layer(&mut item, &headers, &self.rules[i..i + length], format, 0);
+      items.push(item);
+
+      i += length;
+    }
+
+    match format {
+      Format::Canonical if !items.is_empty() => {
+        dest.push_str(&items.join("\n\n"));
+        dest.push('\n');
+      },
+      _ => dest.push_str(&items.concat())
+    }
+  }
+}
+
+/// The `@layer` blocks needed to place rules in a fully qualified layer.
+/// Named segments share a block, an anonymous segment gets an empty header.
+fn layer_headers(layer: &str) -> Vec<String> {
+  let mut headers = Vec::new();
+  let mut named: Vec<&str> = Vec::new();
+
+  for segment in layer.split('.') {
+    if cascade::is_anonymous_layer(segment) {
+      if !named.is_empty() {
+        headers.push(named.join("."));
+        named.clear();
+      }
+      headers.push(String::new());
+    } else {
+      named.push(segment);
+    }
+  }
+
+  if !named.is_empty() {
+    headers.push(named.join("."));
+  }
+  headers
+}
+
+fn indentation(format: Format, depth: usize) -> String {
+  match format {
+    Format::Canonical => "  ".repeat(depth),
+    Format::Minified => String::new()
+  }
+}
+
+fn write_layer(dest: &mut String, headers: &[String], rules: &[Rule], format: Format, depth: usize) {
+  let indent = indentation(format, depth);
+
+  let (header, rest) = match headers.split_first() {
+    Some(split) => split,
+    None => {
+      for (i, rule) in rules.iter().enumerate() {
+        if i > 0 {
+          dest.push_str(pick(format, "\n\n", ""));
+        }
+        write_rule(dest, rule, format, depth);
+      }
+      return;
+    }
+  };
+
+  dest.push_str(&indent);
+  dest.push_str("@layer");
+  if !header.is_empty() {
+    dest.push(' ');
+    dest.push_str(header);
+  }
+  dest.push_str(pick(format, " {\n", "{"));
+  write_layer(dest, rest, rules, format, depth + 1);
+  dest.push_str(pick(format, "\n", ""));
+  dest.push_str(&indent);
+  dest.push('}');
+}
+
+impl ToCss for Rule {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    write_rule(dest, self, format, 0);
+  }
+}
+
+fn write_rule(dest: &mut String, rule: &Rule, format: Format, depth: usize) {
+  let indent = indentation(format, depth);
+
+  dest.push_str(&indent);
+  for (i, selector) in rule.selectors.iter().enumerate() {
+    if i > 0 {
+      dest.push_str(pick(format, ", ", ","));
+    }
+    selector.write_css(dest, format);
+  }
+
+  let declarations = serialize_declarations(&rule.declarations, format);
+  match format {
+    Format::Canonical if declarations.is_empty() => dest.push_str(" {}"),
+    Format::Canonical => {
+      dest.push_str(" {\n");
+      for declaration in declarations.iter() {
+        dest.push_str(&indent);
+        dest.push_str("  ");
+        dest.push_str(declaration);
+        dest.push_str(";\n");
+      }
+      dest.push_str(&indent);
+      dest.push('}');
+    },
+    Format::Minified => {
+      dest.push('{');
+      dest.push_str(&declarations.join(";"));
+      dest.push('}');
+    }
+  }
+}
+
+impl ToCss for Selector {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    for (i, compound) in self.compounds.iter().enumerate() {
+      if i > 0 {
+        write_combinator(dest, compound.combinator, format);
+      } else if compound.combinator != Combinator::Descendant {
+        // the leading combinator of a relative selector in `:has()`
+        let start = dest.len();
+        write_combinator(dest, compound.combinator, format);
+        if dest[start..].starts_with(' ') {
+          dest.remove(start);
+        }
+      }
+      compound.write_css(dest, format);
+    }
+
+    if let Some(ref pseudo_element) = self.pseudo_element {
+      dest.push_str("::");
+      dest.push_str(pseudo_element);
+    }
+  }
+}
+
+fn write_combinator(dest: &mut String, combinator: Combinator, format: Format) {
+  dest.push_str(match combinator {
+    Combinator::Descendant => " ",
+    Combinator::Child => pick(format, " > ", ">"),
+    Combinator::GeneralSibling => pick(format, " ~ ", "~"),
+    Combinator::AdjacentSibling => pick(format, " + ", "+")
+  });
+}
+
+impl ToCss for Compound {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    let start = dest.len();
+
+    if let Some(ref tag_name) = self.tag_name {
+      dest.push_str(tag_name);
+    }
+    for id in self.id.iter() {
+      dest.push('#');
+      dest.push_str(id);
+    }
+    for class in self.class.iter() {
+      dest.push('.');
+      dest.push_str(class);
+    }
+    for attribute in self.attributes.iter() {
+      attribute.write_css(dest, format);
+    }
+    for pseudo in self.pseudo.iter() {
+      pseudo.write_css(dest, format);
+    }
+
+    if dest.len() == start {
+      dest.push('*');
+    }
+  }
+}
+
+impl ToCss for AttributeSelector {
+  fn write_css(&self, dest: &mut String, _format: Format) {
+    dest.push('[');
+    dest.push_str(&self.name);
+
+    if let Some((operator, ref value)) = self.operator {
+      dest.push_str(match operator {
+        AttributeOperator::Equals => "=",
+        AttributeOperator::Includes => "~=",
+        AttributeOperator::DashMatch => "|=",
+        AttributeOperator::Prefix => "^=",
+        AttributeOperator::Suffix => "$=",
+        AttributeOperator::Substring => "*="
+      });
+      write_string(dest, value);
+      if self.case_insensitive {
+        dest.push_str(" i");
+      }
+    }
+
+    dest.push(']');
+  }
+}
+
+impl ToCss for PseudoClass {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    let (name, list, nth) = match *self {
+      PseudoClass::FirstChild => ("first-child", None, None),
+      PseudoClass::LastChild => ("last-child", None, None),
+      PseudoClass::OnlyChild => ("only-child", None, None),
+      PseudoClass::FirstOfType => ("first-of-type", None, None),
+      PseudoClass::LastOfType => ("last-of-type", None, None),
+      PseudoClass::OnlyOfType => ("only-of-type", None, None),
+      PseudoClass::NthChild(a, b) => ("nth-child", None, Some((a, b))),
+      PseudoClass::NthLastChild(a, b) => ("nth-last-child", None, Some((a, b))),
+      PseudoClass::NthOfType(a, b) => ("nth-of-type", None, Some((a, b))),
+      PseudoClass::NthLastOfType(a, b) => ("nth-last-of-type", None, Some((a, b))),
+      PseudoClass::Root => ("root", None, None),
+      PseudoClass::Empty => ("empty", None, None),
+      PseudoClass::Link => ("link", None, None),
+      PseudoClass::Visited => ("visited", None, None),
+      PseudoClass::Hover => ("hover", None, None),
+      PseudoClass::Active => ("active", None, None),
+      PseudoClass::Focus => ("focus", None, None),
+      PseudoClass::FocusWithin => ("focus-within", None, None),
+      PseudoClass::Checked => ("checked", None, None),
+      PseudoClass::Disabled => ("disabled", None, None),
+      PseudoClass::Enabled => ("enabled", None, None),
+      PseudoClass::Not(ref list) => ("not", Some(list), None),
+      PseudoClass::Is(ref list) => ("is", Some(list), None),
+      PseudoClass::Where(ref list) => ("where", Some(list), None),
+      PseudoClass::Has(ref list) => ("has", Some(list), None)
+    };
+
+    dest.push(':');
+    dest.push_str(name);
+
+    if let Some(list) = list {
+      dest.push('(');
+      for (i, selector) in list.iter().enumerate() {
+        if i > 0 {
+          dest.push_str(pick(format, ", ", ","));
+        }
+        selector.write_css(dest, format);
+      }
+      dest.push(')');
+    }
+
+    if let Some((a, b)) = nth {
+      dest.push('(');
+      match (a, b) {
+        (0, b) => dest.push_str(&b.to_string()),
+        (1, _) => dest.push('n'),
+        (-1, _) => dest.push_str("-n"),
+        (a, _) => {
+          dest.push_str(&a.to_string());
+          dest.push('n');
+        }
+      }
+      if a != 0 && b != 0 {
+        dest.push(if b > 0 { '+' } else { '-' });
+        dest.push_str(&b.abs().to_string());
+      }
+      dest.push(')');
+    }
+  }
+}
+
+impl ToCss for Declaration {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    write_declaration(dest, &self.name, &self.value, self.important, format);
+  }
+}
+
+fn write_declaration(dest: &mut String, name: &str, value: &Value, important: bool, format: Format) {
+  let mut text = String::new();
+  value.write_css(&mut text, format);
+  write_declaration_text(dest, name, &text, important, format);
+}
+
+fn write_declaration_text(dest: &mut String, name: &str, value: &str, important: bool, format: Format) {
+  dest.push_str(name);
+  dest.push_str(pick(format, ": ", ":"));
+  dest.push_str(value);
+  if important {
+    dest.push_str(pick(format, " !important", "!important"));
+  }
+}
+
+/// Serializes a declaration block, turning runs of longhands that a
+/// shorthand expanded into back into the shorthand where possible.
+pub fn serialize_declarations(declarations: &[Declaration], format: Format) -> Vec<String> {
+  let mut shorthands: Vec<&Shorthand> = shorthands::all().iter().collect();
+  shorthands.sort_by_key(|shorthand| Reverse(shorthand.longhands.len()));
+
+  let mut serialized = Vec::new();
+  let mut i = 0;
+
+  while i < declarations.len() {
+    let run = &declarations[i..];
+    let found = shorthands.iter()
+      .filter_map(|shorthand| collapse(shorthand, run, format).map(|text| (text, shorthand.longhands.len())))
+      .next();
+
+    match found {
+      Some((text, length)) => {
+        serialized.push(text);
+        i += length;
+      },
+      None => {
+        let mut text = String::new();
+        run[0].write_css(&mut text, format);
+        serialized.push(text);
+        i += 1;
+      }
+    }
+  }
+
+  serialized
+}
+
+/// The shorthand declaration equivalent to the start of `run`, if `run`
+/// starts with every longhand of `shorthand` in expansion order.
+fn collapse(shorthand: &Shorthand, run: &[Declaration], format: Format) -> Option<String> {
+  if run.len() < shorthand.longhands.len() {
+    return None;
+  }
+
+  let run = &run[..shorthand.longhands.len()];
+  let important = run[0].important;
+  let matches = run.iter()
+    .zip(shorthand.longhands.iter())
+    .all(|(declaration, &name)| declaration.name == name && declaration.important == important);
+  if !matches {
+    return None;
+  }
+
+  let mut dest = String::new();
+
+  // a shorthand containing var() was never expanded
+  if let Value::Pending(ref name, ref lexed) = run[0].value {
+    if name != shorthand.name || run.iter().any(|declaration| declaration.value != run[0].value) {
+      return None;
+    }
+    write_declaration(&mut dest, name, &Value::Unparsed(lexed.clone()), important, format);
+    return Some(dest);
+  }
+
+  let values: Vec<&Value> = run.iter().map(|declaration| &declaration.value).collect();
+  let text = shorthand_value(shorthand.name, &values, format)?;
+
+  // only collapse if the shorthand expands back to the same longhands
+  let expanded = lexer::lex(text.clone()).ok()
+    .and_then(|lexed| shorthand.expand(&lexed))?;
+  if expanded.iter().zip(values.iter()).any(|((_, expanded), &value)| expanded != value) {
+    return None;
+  }
+
+  write_declaration_text(&mut dest, shorthand.name, &text, important, format);
+  Some(dest)
+}
+
+/// Candidate text of a shorthand for the values of its longhands.
+fn shorthand_value(name: &str, values: &[&Value], format: Format) -> Option<String> {
+  let text = |value: &Value| {
+    let mut dest = String::new();
+    value.write_css(&mut dest, format);
+    dest
+  };
+  let join = |values: &[&Value]| values.iter().map(|&value| text(value)).collect::<Vec<_>>().join(" ");
+
+  if let Value::Keyword(ref keyword) = *values[0] {
+    if CSS_WIDE_KEYWORDS.contains(&&keyword[..]) {
+      return Some(keyword.clone());
+    }
+  }
+
+  let sides = |values: &[&Value]| -> Option<String> {
+    let (top, right, bottom, left) = (values[0], values[1], values[2], values[3]);
+    let values = if right != left {
+      vec![top, right, bottom, left]
+    } else if top != bottom {
+      vec![top, right, bottom]
+    } else if top != right {
+      vec![top, right]
+    } else {
+      vec![top]
+    };
+    Some(join(&values))
+  };
+
+  match name {
+    "margin" | "padding" | "inset" | "border-width" | "border-style" | "border-color" => sides(values),
+    "border-radius" if !values.iter().any(|value| matches!(**value, Value::List(_))) => sides(values),
+    "border-radius" => None,
+    // only when all four sides agree
+    "border" if values.chunks(4).all(|side| side.iter().all(|value| *value == side[0])) => {
+      Some(join(&[values[0], values[4], values[8]]))
+    },
+    "border" => None,
+    "font" => Some(format!(
+      "{} {}{}{} {}",
+      join(&values[..3]),
+      text(values[3]),
+      pick(format, " / ", "/"),
+      text(values[4]),
+      text(values[5])
+    )),
+    "grid-template" if *values[2] == Value::Keyword("none".to_string()) => {
+      Some(format!("{}{}{}", text(values[0]), pick(format, " / ", "/"), text(values[1])))
+    },
+    "grid-template" => None,
+    _ => Some(join(values))
+  }
+}
+
+impl ToCss for Value {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    match *self {
+      Value::Keyword(ref keyword) => dest.push_str(keyword),
+      Value::Length(num, _) if num == 0.0 && format == Format::Minified => dest.push('0'),
+      Value::Length(num, unit) => {
+        write_number(dest, num, format);
+        dest.push_str(unit.name());
+      },
+      Value::Percentage(num) => {
+        write_number(dest, num, format);
+        dest.push('%');
+      },
+      Value::Number(num) => write_number(dest, num, format),
+      Value::Integer(num) => dest.push_str(&num.to_string()),
+      Value::Flex(num) => {
+        write_number(dest, num, format);
+        dest.push_str("fr");
+      },
+      Value::Color { r, g, b, a } => write_color(dest, [r, g, b, a], format),
+      Value::String(ref s) => write_string(dest, s),
+      Value::Url(ref url) => write_url(dest, url, format),
+      Value::Function(ref name, ref arguments) => {
+        dest.push_str(name);
+        dest.push('(');
+        for (i, argument) in arguments.iter().enumerate() {
+          // repeat(2, 1fr 10px) only separates the count by a comma
+          if i == 1 || (i > 1 && name != "repeat") {
+            dest.push_str(pick(format, ", ", ","));
+          } else if i > 1 {
+            dest.push(' ');
+          }
+          argument.write_css(dest, format);
+        }
+        dest.push(')');
+      },
+      Value::Calc(ref calc) => match **calc {
+        Calc::Min(_) | Calc::Max(_) | Calc::Clamp(..) => calc.write_css(dest, format),
+        _ => {
+          dest.push_str("calc(");
+          calc.write_css(dest, format);
+          dest.push(')');
+        }
+      },
+      Value::Unparsed(ref lexed) | Value::Pending(_, ref lexed) => {
+        for item in lexed.iter() {
+          write_lexed(dest, item, format);
+        }
+      },
+      Value::List(ref values) => {
+        for (i, value) in values.iter().enumerate() {
+          if i > 0 {
+            dest.push(' ');
+          }
+          value.write_css(dest, format);
+        }
+      },
+      Value::CommaList(ref values) => {
+        for (i, value) in values.iter().enumerate() {
+          if i > 0 {
+            dest.push_str(pick(format, ", ", ","));
+          }
+          value.write_css(dest, format);
+        }
+      }
+    }
+  }
+}
+
+/// The shortest text parsing back to the same number, minified output also
+/// dropping the leading zero.
+fn write_number(dest: &mut String, num: StyleNumber, format: Format) {
+  // avoid printing -0
+  let num = if num == 0.0 { 0.0 } else { num };
+  let text = num.to_string();
+
+  match format {
+    Format::Minified if text.starts_with("0.") => dest.push_str(&text[1..]),
+    Format::Minified if text.starts_with("-0.") => {
+      dest.push('-');
+      dest.push_str(&text[2..]);
+    },
+    _ => dest.push_str(&text)
+  }
+}
+
+/// `rgb()` or `rgba()` per CSSOM, or the shortest hex notation when minified.
+fn write_color(dest: &mut String, channels: [u8; 4], format: Format) {
+  if format == Format::Minified {
+    let channels = if channels[3] == 255 { &channels[..3] } else { &channels[..] };
+    dest.push('#');
+
+    if channels.iter().all(|c| c >> 4 == c & 0xf) {
+      for c in channels.iter() {
+        dest.push_str(&format!("{:x}", c & 0xf));
+      }
+    } else {
+      for c in channels.iter() {
+        dest.push_str(&format!("{:02x}", c));
+      }
+    }
+    return;
+  }
+
+  let [r, g, b, a] = channels;
+  if a == 255 {
+    dest.push_str(&format!("rgb({}, {}, {})", r, g, b));
+    return;
+  }
+
+  // the fewest decimals that give back the same byte
+  let alpha = (2..=3)
+    .map(|decimals| format!("{:.*}", decimals, a as f64 / 255.0))
+    .map(|alpha| alpha.trim_end_matches('0').trim_end_matches('.').to_string())
+    .find(|alpha| alpha.parse::<f64>().is_ok_and(|value| (value * 255.0).round() as u8 == a))
+    .unwrap_or_else(|| format!("{}", a as f64 / 255.0));
+
+  dest.push_str(&format!("rgba({}, {}, {}, {})", r, g, b, alpha));
+}
+
+fn write_string(dest: &mut String, s: &str) {
+  dest.push('"');
+  for c in s.chars() {
+    match c {
+      '"' | '\\' => {
+        dest.push('\\');
+        dest.push(c);
+      },
+      '\n' => dest.push_str("\\a "),
+      _ => dest.push(c)
+    }
+  }
+  dest.push('"');
+}
+
+fn write_url(dest: &mut String, url: &str, format: Format) {
+  let plain = !url.is_empty() && !url.chars().any(|c| c.is_whitespace() || "\"'()\\".contains(c));
+
+  dest.push_str("url(");
+  if format == Format::Minified && plain {
+    dest.push_str(url);
+  } else {
+    write_string(dest, url);
+  }
+  dest.push(')');
+}
+
+fn write_lexed(dest: &mut String, lexed: &Lexed, format: Format) {
+  match *lexed {
+    Lexed::Identifier(ref name) => dest.push_str(name),
+    Lexed::Number(num) => write_number(dest, num, format),
+    Lexed::String(ref s) => write_string(dest, s),
+    Lexed::Hash(ref name) => {
+      dest.push('#');
+      dest.push_str(name);
+    },
+    Lexed::Url(ref url) => write_url(dest, url, format),
+    Lexed::Function(ref name) => {
+      dest.push_str(name);
+      dest.push('(');
+    },
+    Lexed::AtKeyword(ref name) => {
+      dest.push('@');
+      dest.push_str(name);
+    },
+    Lexed::Token(ref token) => dest.push_str(token.as_str())
+  }
+}
+
+impl ToCss for Calc {
+  fn write_css(&self, dest: &mut String, format: Format) {
+    write_calc(dest, self, format, true);
+  }
+}
+
+/// Writes a math expression, parenthesizing sums nested in other operations.
+fn write_calc(dest: &mut String, calc: &Calc, format: Format, top: bool) {
+  let arguments = |dest: &mut String, name: &str, calcs: &[&Calc]| {
+    dest.push_str(name);
+    dest.push('(');
+    for (i, calc) in calcs.iter().enumerate() {
+      if i > 0 {
+        dest.push_str(pick(format, ", ", ","));
+      }
+      write_calc(dest, calc, format, true);
+    }
+    dest.push(')');
+  };
+
+  match *calc {
+    Calc::Number(num) => write_number(dest, num, format),
+    Calc::Length(num, unit) => {
+      write_number(dest, num, format);
+      dest.push_str(unit.name());
+    },
+    Calc::Percentage(num) => {
+      write_number(dest, num, format);
+      dest.push('%');
+    },
+    Calc::Sum(ref calcs) => {
+      if !top {
+        dest.push('(');
+      }
+      for (i, calc) in calcs.iter().enumerate() {
+        match *calc {
+          Calc::Negate(ref negated) if i > 0 => {
+            dest.push_str(" - ");
+            write_calc(dest, negated, format, false);
+          },
+          _ => {
+            if i > 0 {
+              dest.push_str(" + ");
+            }
+            write_calc(dest, calc, format, false);
+          }
+        }
+      }
+      if !top {
+        dest.push(')');
+      }
+    },
+    Calc::Product(ref calcs) => {
+      for (i, calc) in calcs.iter().enumerate() {
+        match *calc {
+          Calc::Invert(ref inverted) if i > 0 => {
+            dest.push_str(pick(format, " / ", "/"));
+            write_calc(dest, inverted, format, false);
+          },
+          _ => {
+            if i > 0 {
+              dest.push_str(pick(format, " * ", "*"));
+            }
+            write_calc(dest, calc, format, false);
+          }
+        }
+      }
+    },
+    Calc::Negate(ref negated) => match **negated {
+      Calc::Number(num) => write_number(dest, -num, format),
+      Calc::Length(num, unit) => write_calc(dest, &Calc::Length(-num, unit), format, top),
+      Calc::Percentage(num) => write_calc(dest, &Calc::Percentage(-num), format, top),
+      ref other => {
+        dest.push_str("(-1");
+        dest.push_str(pick(format, " * ", "*"));
+        write_calc(dest, other, format, false);
+        dest.push(')');
+      }
+    },
+    Calc::Invert(ref inverted) => {
+      dest.push_str("(1");
+      dest.push_str(pick(format, " / ", "/"));
+      write_calc(dest, inverted, format, false);
+      dest.push(')');
+    },
+    Calc::Min(ref calcs) => arguments(dest, "min", &calcs.iter().collect::<Vec<_>>()),
+    Calc::Max(ref calcs) => arguments(dest, "max", &calcs.iter().collect::<Vec<_>>()),
+    Calc::Clamp(ref min, ref value, ref max) => arguments(dest, "clamp", &[min, value, max])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{lexer::lex, parser::parse_stylesheet, cascade::Origin};
+
+  fn parse(query: &str) -> Stylesheet {
+    let sheet = parse_stylesheet(lex(query.to_string()).unwrap(), Origin::Author).unwrap();
+    assert!(sheet.errors.is_empty(), "{:?}", sheet.errors);
+    sheet
+  }
+
+  const SHEET: &str = "
+    @layer base, theme;
+    body > p, h1 { margin: 0 auto; color: rgba(255, 0, 0, .5) !important; font: bold 12px/1.5 \"Times New Roman\", serif }
+    @layer theme {
+      * { border: 1px solid #0f0; padding: 1px 2px 1px 2px; width: calc(100% - 2 * var(--gap)); --gap: 4px  8px }
+    }
+    @layer { a { background: url(\"a b.png\") no-repeat; margin: var(--gap) 0 } }
+    div { flex: 1; grid-template: 1fr 10px / repeat(2, minmax(10%, 1fr)); width: min(10px, 5vw) }
+  ";
+
+  #[test]
+  fn serializes_canonical_css() {
+    let sheet = parse(SHEET);
+    let css = sheet.to_css();
+
+    assert!(css.starts_with("@layer base, theme;\n\nbody > p, h1 {\n  margin: 0px auto;\n"), "{}", css);
+    assert!(css.contains("  color: rgba(255, 0, 0, 0.5) !important;\n"), "{}", css);
+    assert!(css.contains("  font: normal normal bold 12px / 1.5 \"Times New Roman\", serif;\n"), "{}", css);
+    assert!(css.contains("@layer theme {\n  * {\n    border: 1px solid rgb(0, 255, 0);\n    padding: 1px 2px;\n"), "{}", css);
+    assert!(css.contains("--gap: 4px 8px;"), "{}", css);
+    assert!(css.contains("@layer {\n  a {\n    background: rgba(0, 0, 0, 0) url(\"a b.png\") no-repeat"), "{}", css);
+    assert!(css.contains("    margin: var(--gap) 0;\n"), "{}", css);
+    assert!(css.contains("flex: 1 1 0%;\n  grid-template: 1fr 10px / repeat(2, minmax(10%, 1fr));\n  width: min(10px, 5vw);"), "{}", css);
+  }
+
+  #[test]
+  fn round_trips() {
+    let sheet = parse(SHEET);
+
+    for css in [sheet.to_css(), sheet.to_minified_css()].iter() {
+      let reparsed = parse(css);
+      assert_eq!(reparsed.rules.len(), sheet.rules.len());
+      assert_eq!(reparsed.to_css(), sheet.to_css(), "{}", css);
+      for (rule, reparsed) in sheet.rules.iter().zip(reparsed.rules.iter()) {
+        assert_eq!(rule.declarations, reparsed.declarations);
+      }
+    }
+  }
+
+  #[test]
+  fn serializes_selectors() {
+    let sheet = parse("ul  li.a:nth-child( 2n - 1 ) ~ [lang|=en i], a:not(:is(.b,#c)):has(> img)::before, p:nth-last-of-type(-n+3) { color: red }");
+    let selectors = "ul li.a:nth-child(2n-1) ~ [lang|=\"en\" i], a:not(:is(.b, #c)):has(> img)::before, p:nth-last-of-type(-n+3)";
+    assert!(sheet.to_css().starts_with(selectors), "{}", sheet.to_css());
+    assert_eq!(parse(&sheet.to_minified_css()).rules[0].selectors, sheet.rules[0].selectors);
+  }
+
+  #[test]
+  fn minifies() {
+    let sheet = parse("a > b, c { color: #ff0000; margin: 0.5em 0 } d { background-color: rgba(0, 0, 0, .5) }");
+    assert_eq!(sheet.to_minified_css(), "a>b,c{color:#f00;margin:.5em 0}d{background-color:#00000080}");
+  }
+}
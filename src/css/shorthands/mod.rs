@@ -0,0 +1,483 @@
+use super::{lexer::{Lexed, Token}, parser::{Value, ValueParser}, properties::{self, Grammar, Property, CSS_WIDE_KEYWORDS}};
+
+/// A shorthand property, expanded into its longhands when parsed so later
+/// stages only ever see longhands.
+pub struct Shorthand {
+  pub name: &'static str,
+  pub longhands: &'static [&'static str],
+  parse: fn(&mut ValueParser) -> Option<Vec<Option<Value>>> // a value per longhand, `None` if omitted
+}
+
+impl Shorthand {
+  /// Expands a complete declaration value into a value for every longhand,
+  /// resetting omitted longhands to their initial value. Returns `None` if
+  /// the tokens do not match the shorthand's grammar.
+  pub fn expand(&self, lexed: &[Lexed]) -> Option<Vec<(&'static str, Value)>> {
+    let mut parser = ValueParser::new(lexed);
+
+    let values = match parser.keyword(CSS_WIDE_KEYWORDS) {
+      Some(keyword) => self.longhands.iter().map(|_| Some(keyword.clone())).collect(),
+      None => (self.parse)(&mut parser)?
+    };
+
+    if !parser.is_done() {
+      return None;
+    }
+
+    Some(self.longhands.iter()
+      .zip(values)
+      .map(|(&name, value)| (name, value.unwrap_or_else(|| longhand(name).initial_value())))
+      .collect())
+  }
+}
+
+/// Every supported shorthand, sorted by name for `lookup`.
+static SHORTHANDS: &[Shorthand] = &[
+  Shorthand { name: "background", parse: background, longhands: &[
+    "background-color", "background-image", "background-repeat", "background-attachment",
+    "background-position"
+  ] },
+  Shorthand { name: "border", parse: border, longhands: &[
+    "border-top-width", "border-right-width", "border-bottom-width", "border-left-width",
+    "border-top-style", "border-right-style", "border-bottom-style", "border-left-style",
+    "border-top-color", "border-right-color", "border-bottom-color", "border-left-color"
+  ] },
+  Shorthand { name: "border-bottom", parse: border_side, longhands: &[
+    "border-bottom-width", "border-bottom-style", "border-bottom-color"
+  ] },
+  Shorthand { name: "border-color", parse: border_color, longhands: &[
+    "border-top-color", "border-right-color", "border-bottom-color", "border-left-color"
+  ] },
+  Shorthand { name: "border-left", parse: border_side, longhands: &[
+    "border-left-width", "border-left-style", "border-left-color"
+  ] },
+  Shorthand { name: "border-radius", parse: border_radius, longhands: &[
+    "border-top-left-radius", "border-top-right-radius", "border-bottom-right-radius",
+    "border-bottom-left-radius"
+  ] },
+  Shorthand { name: "border-right", parse: border_side, longhands: &[
+    "border-right-width", "border-right-style", "border-right-color"
+  ] },
+  Shorthand { name: "border-style", parse: border_style, longhands: &[
+    "border-top-style", "border-right-style", "border-bottom-style", "border-left-style"
+  ] },
+  Shorthand { name: "border-top", parse: border_side, longhands: &[
+    "border-top-width", "border-top-style", "border-top-color"
+  ] },
+  Shorthand { name: "border-width", parse: border_width, longhands: &[
+    "border-top-width", "border-right-width", "border-bottom-width", "border-left-width"
+  ] },
+  Shorthand { name: "flex", parse: flex, longhands: &["flex-grow", "flex-shrink", "flex-basis"] },
+  Shorthand { name: "font", parse: font, longhands: &[
+    "font-style", "font-variant", "font-weight", "font-size", "line-height", "font-family"
+  ] },
+  Shorthand { name: "grid-template", parse: grid_template, longhands: &[
+    "grid-template-rows", "grid-template-columns", "grid-template-areas"
+  ] },
+  Shorthand { name: "inset", parse: inset, longhands: &["top", "right", "bottom", "left"] },
+  Shorthand { name: "list-style", parse: list_style, longhands: &[
+    "list-style-type", "list-style-position", "list-style-image"
+  ] },
+  Shorthand { name: "margin", parse: margin, longhands: &[
+    "margin-top", "margin-right", "margin-bottom", "margin-left"
+  ] },
+  Shorthand { name: "outline", parse: outline, longhands: &["outline-color", "outline-style", "outline-width"] },
+  Shorthand { name: "padding", parse: padding, longhands: &[
+    "padding-top", "padding-right", "padding-bottom", "padding-left"
+  ] },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Shorthand> {
+  SHORTHANDS.binary_search_by(|shorthand| shorthand.name.cmp(name))
+    .ok()
+    .map(|i| &SHORTHANDS[i])
+}
+
+pub fn all() -> &'static [Shorthand] {
+  SHORTHANDS
+}
+
+fn longhand(name: &str) -> &'static Property {
+  properties::lookup(name).unwrap_or_else(|| panic!("Unknown longhand '{}'", name))
+}
+
+/// A single component parsed with the grammar of a longhand.
+fn component(parser: &mut ValueParser, name: &str) -> Option<Value> {
+  parser.attempt(|parser| longhand(name).grammar.parse(parser))
+}
+
+/// One to four values for top, right, bottom and left, the missing ones
+/// copied from the opposite side.
+fn sides(parser: &mut ValueParser, grammar: &Grammar) -> Option<Vec<Option<Value>>> {
+  let mut values = Vec::new();
+  while values.len() < 4 {
+    match parser.attempt(|parser| grammar.parse(parser)) {
+      Some(value) => values.push(value),
+      None => break
+    }
+  }
+
+  let indices = match values.len() {
+    1 => [0, 0, 0, 0],
+    2 => [0, 1, 0, 1],
+    3 => [0, 1, 2, 1],
+    4 => [0, 1, 2, 3],
+    _ => return None
+  };
+
+  Some(indices.iter().map(|&i| Some(values[i].clone())).collect())
+}
+
+/// The named longhands in any order, each at most once and at least one.
+fn any_order(parser: &mut ValueParser, names: &[&str]) -> Option<Vec<Option<Value>>> {
+  let mut values: Vec<Option<Value>> = names.iter().map(|_| None).collect();
+
+  'outer: loop {
+    for (i, name) in names.iter().enumerate() {
+      if values[i].is_some() {
+        continue;
+      }
+      if let Some(value) = component(parser, name) {
+        values[i] = Some(value);
+        continue 'outer;
+      }
+    }
+    break;
+  }
+
+  if values.iter().all(Option::is_none) {
+    return None;
+  }
+  Some(values)
+}
+
+fn margin(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  sides(parser, &longhand("margin-top").grammar)
+}
+
+fn padding(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  sides(parser, &longhand("padding-top").grammar)
+}
+
+fn inset(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  sides(parser, &longhand("top").grammar)
+}
+
+fn border_width(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  sides(parser, &longhand("border-top-width").grammar)
+}
+
+fn border_style(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  sides(parser, &longhand("border-top-style").grammar)
+}
+
+fn border_color(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  sides(parser, &longhand("border-top-color").grammar)
+}
+
+/// `<width> || <style> || <color>`
+fn border_side(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  any_order(parser, &["border-top-width", "border-top-style", "border-top-color"])
+}
+
+/// A single side applied to all four.
+fn border(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  let side = border_side(parser)?;
+  Some(side.into_iter()
+    .flat_map(|value| vec![value; 4])
+    .collect())
+}
+
+/// `<length-percentage>{1,4} [ / <length-percentage>{1,4} ]?`, a corner
+/// with different horizontal and vertical radii becoming a two value list.
+fn border_radius(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  let horizontal = sides(parser, &Grammar::NonNegativeLengthPercentage)?;
+  let vertical = if parser.token(&Token::Slash) {
+    sides(parser, &Grammar::NonNegativeLengthPercentage)?
+  } else {
+    horizontal.clone()
+  };
+
+  Some(horizontal.into_iter()
+    .zip(vertical)
+    .map(|(horizontal, vertical)| match (horizontal, vertical) {
+      (Some(horizontal), Some(vertical)) if horizontal != vertical => Some(Value::List(vec![horizontal, vertical])),
+      (horizontal, _) => horizontal
+    })
+    .collect())
+}
+
+fn background(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  any_order(parser, &[
+    "background-color", "background-image", "background-repeat", "background-attachment",
+    "background-position"
+  ])
+}
+
+fn outline(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  any_order(parser, &["outline-color", "outline-style", "outline-width"])
+}
+
+/// `<type> || <position> || <image>`, where `none` sets whichever of the
+/// type and image is not otherwise given.
+fn list_style(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  const NAMES: [&str; 3] = ["list-style-type", "list-style-position", "list-style-image"];
+  let mut values = vec![None, None, None];
+  let mut nones = 0;
+
+  'outer: loop {
+    if parser.keyword(&["none"]).is_some() {
+      nones += 1;
+      continue;
+    }
+    for (i, name) in NAMES.iter().enumerate() {
+      if values[i].is_none() {
+        if let Some(value) = component(parser, name) {
+          values[i] = Some(value);
+          continue 'outer;
+        }
+      }
+    }
+    break;
+  }
+
+  let none = Some(Value::Keyword("none".to_string()));
+  match nones {
+    0 if values.iter().all(Option::is_none) => return None,
+    0 => {},
+    1 if values[0].is_none() => values[0] = none,
+    1 if values[2].is_none() => values[2] = none,
+    2 if values[0].is_none() && values[2].is_none() => {
+      values[0] = none.clone();
+      values[2] = none;
+    },
+    _ => return None
+  }
+
+  Some(values)
+}
+
+/// `[ <font-style> || <font-variant> || <font-weight> ]? <font-size> [ / <line-height> ]? <font-family>`
+/// or a system font keyword. System fonts reset every longhand and keep the
+/// keyword as the family, leaving the actual font to font matching.
+fn font(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  let mut values = vec![None, None, None, None, None, None];
+
+  if let Some(keyword) = parser.keyword(&[
+    "caption", "icon", "menu", "message-box", "small-caption", "status-bar"
+  ]) {
+    values[5] = Some(keyword);
+    return Some(values);
+  }
+
+  // `normal` matches any of the three and leaves it at its initial value
+  for _ in 0..3 {
+    if parser.keyword(&["normal"]).is_some() {
+      continue;
+    }
+
+    let found = ["font-style", "font-variant", "font-weight"].iter()
+      .enumerate()
+      .filter(|&(i, _)| values[i].is_none())
+      .filter_map(|(i, name)| component(parser, name).map(|value| (i, value)))
+      .next();
+
+    match found {
+      Some((i, value)) => values[i] = Some(value),
+      None => break
+    }
+  }
+
+  values[3] = Some(component(parser, "font-size")?);
+  if parser.token(&Token::Slash) {
+    values[4] = Some(component(parser, "line-height")?);
+  }
+  values[5] = Some(component(parser, "font-family")?);
+
+  Some(values)
+}
+
+/// `none | auto | [ <grow> <shrink>? || <basis> ]`. Unlike other shorthands,
+/// omitted components default to `1 1 0%` rather than the initial values.
+fn flex(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  let auto = Value::Keyword("auto".to_string());
+
+  if parser.keyword(&["none"]).is_some() {
+    return Some(vec![Some(Value::Number(0.0)), Some(Value::Number(0.0)), Some(auto)]);
+  }
+
+  let mut grow = None;
+  let mut shrink = None;
+  let mut basis = None;
+
+  loop {
+    if grow.is_none() {
+      if let Some(value) = component(parser, "flex-grow") {
+        grow = Some(value);
+        shrink = component(parser, "flex-shrink");
+        continue;
+      }
+    }
+    if basis.is_none() {
+      if let Some(value) = component(parser, "flex-basis") {
+        basis = Some(value);
+        continue;
+      }
+    }
+    break;
+  }
+
+  // `flex: auto` is `1 1 auto`, which the basis alone already gives
+  if grow.is_none() && basis.is_none() {
+    return None;
+  }
+
+  Some(vec![
+    Some(grow.unwrap_or(Value::Number(1.0))),
+    Some(shrink.unwrap_or(Value::Number(1.0))),
+    Some(basis.unwrap_or(Value::Percentage(0.0)))
+  ])
+}
+
+/// `none | <rows> / <columns> | [ <string> <track-size>? ]+ [ / <track-list> ]?`
+fn grid_template(parser: &mut ValueParser) -> Option<Vec<Option<Value>>> {
+  let none = Value::Keyword("none".to_string());
+
+  let tracks = parser.attempt(|parser| {
+    let rows = component(parser, "grid-template-rows")?;
+    if !parser.token(&Token::Slash) {
+      return None;
+    }
+    let columns = component(parser, "grid-template-columns")?;
+    Some(vec![Some(rows), Some(columns), Some(none.clone())])
+  });
+  if tracks.is_some() {
+    return tracks;
+  }
+
+  if parser.keyword(&["none"]).is_some() {
+    return Some(vec![Some(none.clone()), Some(none.clone()), Some(none)]);
+  }
+
+  let mut areas = Vec::new();
+  let mut rows = Vec::new();
+  while let Some(area) = parser.string() {
+    areas.push(Value::String(area));
+    rows.push(parser.attempt(properties::track_size).unwrap_or_else(|| Value::Keyword("auto".to_string())));
+  }
+
+  if areas.is_empty() {
+    return None;
+  }
+
+  let columns = if parser.token(&Token::Slash) {
+    match component(parser, "grid-template-columns")? {
+      Value::Keyword(_) => return None,
+      columns => columns
+    }
+  } else {
+    none
+  };
+
+  let list = |mut values: Vec<Value>| match values.len() {
+    1 => values.pop(),
+    _ => Some(Value::List(values))
+  };
+
+  Some(vec![list(rows), Some(columns), list(areas)])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::lexer::lex;
+  use super::super::parser::Unit;
+
+  fn expand(name: &str, value: &str) -> Option<Vec<(&'static str, Value)>> {
+    lookup(name)?.expand(&lex(value.to_string()).ok()?)
+  }
+
+  fn keyword(keyword: &str) -> Value {
+    Value::Keyword(keyword.to_string())
+  }
+
+  #[test]
+  fn shorthands_are_sorted() {
+    for pair in SHORTHANDS.windows(2) {
+      assert!(pair[0].name < pair[1].name, "{} before {}", pair[0].name, pair[1].name);
+    }
+    for shorthand in SHORTHANDS.iter() {
+      for name in shorthand.longhands.iter() {
+        assert!(properties::lookup(name).is_some(), "{} of {}", name, shorthand.name);
+      }
+    }
+  }
+
+  #[test]
+  fn expands_box_shorthands() {
+    let px = |num| Value::Length(num, Unit::Px);
+
+    assert_eq!(expand("margin", "0 auto"), Some(vec![
+      ("margin-top", px(0.0)),
+      ("margin-right", keyword("auto")),
+      ("margin-bottom", px(0.0)),
+      ("margin-left", keyword("auto"))
+    ]));
+    assert_eq!(expand("padding", "1px 2px 3px").unwrap()[3], ("padding-left", px(2.0)));
+    assert_eq!(expand("padding", "-1px"), None);
+    assert_eq!(expand("inset", "1px 2px 3px 4px 5px"), None);
+
+    let radius = expand("border-radius", "1px 2px / 3px").unwrap();
+    assert_eq!(radius[0], ("border-top-left-radius", Value::List(vec![px(1.0), px(3.0)])));
+    assert_eq!(radius[1], ("border-top-right-radius", Value::List(vec![px(2.0), px(3.0)])));
+
+    let border = expand("border", "solid 1px").unwrap();
+    assert_eq!(border.len(), 12);
+    assert_eq!(border[0], ("border-top-width", px(1.0)));
+    assert_eq!(border[7], ("border-left-style", keyword("solid")));
+    assert_eq!(border[8], ("border-top-color", keyword("currentcolor")));
+  }
+
+  #[test]
+  fn resets_omitted_longhands() {
+    let font = expand("font", "bold 12px/1.5 serif").unwrap();
+    assert_eq!(font[0], ("font-style", keyword("normal")));
+    assert_eq!(font[2], ("font-weight", keyword("bold")));
+    assert_eq!(font[4], ("line-height", Value::Number(1.5)));
+    assert!(expand("font", "bold serif").is_none());
+    assert!(expand("font", "italic italic 12px serif").is_none());
+
+    assert_eq!(expand("list-style", "none"), Some(vec![
+      ("list-style-type", keyword("none")),
+      ("list-style-position", keyword("outside")),
+      ("list-style-image", keyword("none"))
+    ]));
+    assert_eq!(expand("background", "red").unwrap()[4].1, Value::List(vec![
+      Value::Percentage(0.0),
+      Value::Percentage(0.0)
+    ]));
+    assert_eq!(expand("outline", "inherit").unwrap()[1], ("outline-style", keyword("inherit")));
+
+    assert_eq!(expand("flex", "2").unwrap(), vec![
+      ("flex-grow", Value::Number(2.0)),
+      ("flex-shrink", Value::Number(1.0)),
+      ("flex-basis", Value::Percentage(0.0))
+    ]);
+    assert_eq!(expand("flex", "auto").unwrap()[0].1, Value::Number(1.0));
+    assert_eq!(expand("flex", "none").unwrap()[1].1, Value::Number(0.0));
+  }
+
+  #[test]
+  fn expands_grid_template() {
+    assert_eq!(expand("grid-template", "100px 1fr / auto").unwrap(), vec![
+      ("grid-template-rows", Value::List(vec![Value::Length(100.0, Unit::Px), Value::Flex(1.0)])),
+      ("grid-template-columns", keyword("auto")),
+      ("grid-template-areas", keyword("none"))
+    ]);
+
+    let areas = expand("grid-template", "\"a a\" 40px \"b c\" / 1fr repeat(2, 10%)").unwrap();
+    assert_eq!(areas[0].1, Value::List(vec![Value::Length(40.0, Unit::Px), keyword("auto")]));
+    assert_eq!(areas[2].1, Value::List(vec![Value::String("a a".to_string()), Value::String("b c".to_string())]));
+    assert!(expand("grid-template", "\"a\" / none").is_none());
+  }
+}
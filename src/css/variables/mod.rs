@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+use super::{lexer::{Lexed, Token}, parser::{Declaration, Value}, properties::{self, CSS_WIDE_KEYWORDS}, shorthands};
+
+pub fn is_custom_property(name: &str) -> bool {
+  name.starts_with("--")
+}
+
+fn is_var(lexed: &Lexed) -> bool {
+  match *lexed {
+    Lexed::Function(ref name) => name.eq_ignore_ascii_case("var"),
+    _ => false
+  }
+}
+
+/// The CSS-wide keyword a custom property value consists of, if any.
+pub fn css_wide_keyword(lexed: &[Lexed]) -> Option<&'static str> {
+  let mut tokens = lexed.iter().filter(|&i| *i != Lexed::Token(Token::Whitespace));
+  match (tokens.next(), tokens.next()) {
+    (Some(Lexed::Identifier(name)), None) => CSS_WIDE_KEYWORDS.iter()
+      .find(|keyword| name.eq_ignore_ascii_case(keyword))
+      .cloned(),
+    _ => None
+  }
+}
+
+/// Whether the tokens contain a `var()` reference anywhere.
+pub fn has_references(lexed: &[Lexed]) -> bool {
+  lexed.iter().any(is_var)
+}
+
+/// A `var(--name, fallback)` reference found in a token stream.
+struct Reference<'a> {
+  name: &'a str,
+  fallback: Option<&'a [Lexed]>,
+  length: usize // tokens spanned, including `var(` and `)`
+}
+
+/// Parses the `var()` whose function token is `lexed[0]`.
+fn reference(lexed: &[Lexed]) -> Option<Reference<'_>> {
+  let mut depth = 0;
+  let mut end = None;
+
+  for (i, item) in lexed.iter().enumerate().skip(1) {
+    match *item {
+      Lexed::Token(Token::ParOpen) | Lexed::Function(_) => depth += 1,
+      Lexed::Token(Token::ParClose) if depth == 0 => {
+        end = Some(i);
+        break;
+      },
+      Lexed::Token(Token::ParClose) => depth -= 1,
+      _ => {}
+    }
+  }
+
+  let end = end?;
+  let arguments = &lexed[1..end];
+  let start = arguments.iter().position(|i| *i != Lexed::Token(Token::Whitespace))?;
+
+  let name = match arguments[start] {
+    Lexed::Identifier(ref name) if is_custom_property(name) => name,
+    _ => return None
+  };
+
+  let rest = &arguments[start + 1..];
+  let fallback = match rest.iter().position(|i| *i != Lexed::Token(Token::Whitespace)) {
+    None => None,
+    Some(i) if rest[i] == Lexed::Token(Token::Comma) => Some(&rest[i + 1..]),
+    Some(_) => return None
+  };
+
+  Some(Reference {
+    name,
+    fallback,
+    length: end + 1
+  })
+}
+
+/// Every custom property name referenced by the tokens, fallbacks included.
+fn references(lexed: &[Lexed]) -> Vec<&str> {
+  let mut names = Vec::new();
+
+  for (i, item) in lexed.iter().enumerate() {
+    if is_var(item) {
+      if let Some(reference) = reference(&lexed[i..]) {
+        names.push(reference.name);
+      }
+    }
+  }
+
+  names
+}
+
+/// Replaces every `var()` in `lexed` using `lookup`, falling back to the
+/// reference's fallback when the property has no valid value. Returns `None`
+/// if a reference can not be substituted, which makes the whole value
+/// invalid at computed-value time.
+fn substitute<F>(lexed: &[Lexed], lookup: &mut F) -> Option<Vec<Lexed>>
+where F: FnMut(&str) -> Option<Vec<Lexed>> {
+  let mut result = Vec::new();
+  let mut i = 0;
+  let mut after_reference = false;
+
+  while i < lexed.len() {
+    if !is_var(&lexed[i]) {
+      if after_reference {
+        separate(&mut result);
+        after_reference = false;
+      }
+      result.push(lexed[i].clone());
+      i += 1;
+      continue;
+    }
+
+    let reference = reference(&lexed[i..])?;
+    let value = match lookup(reference.name) {
+      Some(value) => value,
+      None => substitute(reference.fallback?, lookup)?
+    };
+
+    separate(&mut result);
+    result.extend(value);
+    after_reference = true;
+    i += reference.length;
+  }
+
+  Some(result)
+}
+
+/// Keeps substituted tokens apart from those next to them, which they never
+/// join with: `var(--n)px` is a number and then an identifier, not a length.
+fn separate(result: &mut Vec<Lexed>) {
+  match result.last() {
+    None | Some(Lexed::Token(Token::Whitespace)) => (),
+    Some(_) => result.push(Lexed::Token(Token::Whitespace))
+  }
+}
+
+/// The computed custom properties of an element. Values are token streams
+/// with every `var()` already substituted, so children inherit them as is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CustomProperties {
+  values: HashMap<String, Vec<Lexed>>
+}
+
+impl CustomProperties {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn get(&self, name: &str) -> Option<&[Lexed]> {
+    self.values.get(name).map(|value| &value[..])
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  /// Computes the custom properties of an element from its parent's and the
+  /// element's own declarations in cascade order, the last one winning.
+  /// Properties taking part in a reference cycle, or referencing a missing
+  /// property without fallback, become invalid and are not inherited.
+  pub fn compute<'a, I>(parent: Option<&CustomProperties>, declarations: I) -> CustomProperties
+  where I: IntoIterator<Item = &'a Declaration> {
+    let mut specified: HashMap<&str, &[Lexed]> = HashMap::new();
+    for declaration in declarations.into_iter() {
+      if let Value::Unparsed(ref value) = declaration.value {
+        if is_custom_property(&declaration.name) {
+          specified.insert(&declaration.name, value);
+        }
+      }
+    }
+
+    let mut computed = parent.cloned().unwrap_or_default();
+    if specified.is_empty() {
+      return computed;
+    }
+
+    let cyclic = find_cycles(&specified);
+    let mut resolver = Resolver {
+      specified: &specified,
+      inherited: parent,
+      cyclic: &cyclic,
+      resolved: HashMap::new()
+    };
+
+    let names: Vec<&str> = specified.keys().cloned().collect();
+    for name in names.into_iter() {
+      match resolver.resolve(name) {
+        Some(value) => computed.values.insert(name.to_string(), value),
+        None => computed.values.remove(name)
+      };
+    }
+
+    computed
+  }
+
+  /// Substitutes every `var()` in a declaration value, `None` meaning the
+  /// value is invalid at computed-value time.
+  pub fn substitute(&self, lexed: &[Lexed]) -> Option<Vec<Lexed>> {
+    substitute(lexed, &mut |name: &str| self.values.get(name).cloned())
+  }
+
+  /// Substitutes and parses the value of a regular property that was stored
+  /// unparsed because it contains `var()`. `None` means the declaration is
+  /// invalid at computed-value time, in which case the property computes as
+  /// if it was `unset`.
+  pub fn resolve(&self, name: &str, lexed: &[Lexed]) -> Option<Value> {
+    let substituted = self.substitute(lexed)?;
+    properties::lookup(name)?.parse(&substituted)
+  }
+
+  /// Like `resolve`, for a `Value::Pending` longhand of a shorthand whose
+  /// value contains `var()`: the shorthand is substituted and expanded, and
+  /// the longhand's part of it returned.
+  pub fn resolve_pending(&self, shorthand: &str, longhand: &str, lexed: &[Lexed]) -> Option<Value> {
+    let substituted = self.substitute(lexed)?;
+    shorthands::lookup(shorthand)?.expand(&substituted)?
+      .into_iter()
+      .find(|&(name, _)| name == longhand)
+      .map(|(_, value)| value)
+  }
+}
+
+struct Resolver<'a> {
+  specified: &'a HashMap<&'a str, &'a [Lexed]>,
+  inherited: Option<&'a CustomProperties>,
+  cyclic: &'a HashSet<&'a str>,
+  resolved: HashMap<String, Option<Vec<Lexed>>>
+}
+
+impl<'a> Resolver<'a> {
+  fn resolve(&mut self, name: &str) -> Option<Vec<Lexed>> {
+    if let Some(resolved) = self.resolved.get(name) {
+      return resolved.clone();
+    }
+
+    let specified = self.specified;
+    let inherited = || self.inherited.and_then(|inherited| inherited.values.get(name).cloned());
+    let value = match specified.get(name) {
+      Some(_) if self.cyclic.contains(name) => None,
+      // `revert` is rolled back by the cascade, and otherwise acts as `unset`
+      Some(value) => match css_wide_keyword(value) {
+        Some("initial") => None,
+        Some(_) => inherited(),
+        None => substitute(value, &mut |name: &str| self.resolve(name))
+      },
+      None => inherited()
+    };
+
+    self.resolved.insert(name.to_string(), value.clone());
+    value
+  }
+}
+
+/// Finds the properties that are part of a dependency cycle, using Tarjan's
+/// strongly connected components algorithm.
+fn find_cycles<'a>(specified: &HashMap<&'a str, &'a [Lexed]>) -> HashSet<&'a str> {
+  struct State<'a> {
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    cyclic: HashSet<&'a str>
+  }
+
+  fn visit<'a>(name: &'a str, specified: &HashMap<&'a str, &'a [Lexed]>, state: &mut State<'a>) {
+    let index = state.index.len();
+    state.index.insert(name, index);
+    state.low.insert(name, index);
+    state.stack.push(name);
+    state.on_stack.insert(name);
+
+    let mut self_reference = false;
+    for dependency in references(specified[name]).into_iter() {
+      let dependency = match specified.get_key_value(dependency) {
+        Some((&key, _)) => key,
+        None => continue
+      };
+      self_reference |= dependency == name;
+
+      if !state.index.contains_key(dependency) {
+        visit(dependency, specified, state);
+        let low = state.low[name].min(state.low[dependency]);
+        state.low.insert(name, low);
+      } else if state.on_stack.contains(dependency) {
+        let low = state.low[name].min(state.index[dependency]);
+        state.low.insert(name, low);
+      }
+    }
+
+    if state.low[name] == state.index[name] {
+      let mut component = Vec::new();
+      while let Some(member) = state.stack.pop() {
+        state.on_stack.remove(member);
+        component.push(member);
+        if member == name {
+          break;
+        }
+      }
+
+      if component.len() > 1 || self_reference {
+        state.cyclic.extend(component);
+      }
+    }
+  }
+
+  let mut state = State {
+    index: HashMap::new(),
+    low: HashMap::new(),
+    stack: Vec::new(),
+    on_stack: HashSet::new(),
+    cyclic: HashSet::new()
+  };
+
+  for &name in specified.keys() {
+    if !state.index.contains_key(name) {
+      visit(name, specified, &mut state);
+    }
+  }
+
+  state.cyclic
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{lexer::lex, parser::{parse_declarations, Unit}};
+
+  fn declarations(query: &str) -> Vec<Declaration> {
+    let block = parse_declarations(lex(query.to_string()).unwrap());
+    assert!(block.errors.is_empty(), "{:?}", block.errors);
+    block.declarations
+  }
+
+  #[test]
+  fn parses_custom_properties_as_tokens() {
+    let parsed = declarations("--Brand-Color: #f00; color: var(--Brand-Color)");
+    assert_eq!(parsed[0].name, "--Brand-Color");
+    assert_eq!(parsed[0].value, Value::Unparsed(vec![Lexed::Hash("f00".to_string())]));
+    assert!(matches!(parsed[1].value, Value::Unparsed(_)));
+  }
+
+  #[test]
+  fn substitutes_and_inherits() {
+    let parent = CustomProperties::compute(None, &declarations("--gutter: 8px; --unused: 1"));
+    let child = CustomProperties::compute(Some(&parent), &declarations("--double: calc(2 * var(--gutter))"));
+
+    let width = declarations("width: calc(100% - var(--double))");
+    let value = match width[0].value {
+      Value::Unparsed(ref lexed) => child.resolve("width", lexed),
+      _ => None
+    };
+    assert!(matches!(value, Some(Value::Calc(_))));
+
+    let padding = declarations("padding: 1px var(--gutter)");
+    assert_eq!(padding.len(), 4);
+    match padding[1].value {
+      Value::Pending(ref shorthand, ref lexed) => assert_eq!(
+        child.resolve_pending(shorthand, &padding[1].name, lexed),
+        Some(Value::Length(8.0, Unit::Px))
+      ),
+      _ => panic!("expected pending value")
+    }
+
+    let margin = declarations("margin-left: var(--missing, var(--gutter))");
+    match margin[0].value {
+      Value::Unparsed(ref lexed) => assert_eq!(child.resolve("margin-left", lexed), Some(Value::Length(8.0, Unit::Px))),
+      _ => panic!("expected unparsed value")
+    }
+  }
+
+  #[test]
+  fn invalid_at_computed_value_time() {
+    let custom = CustomProperties::compute(None, &declarations(
+      "--a: var(--b); --b: var(--a, 1px); --self: var(--self); --ok: var(--a, 2px); --color: red"
+    ));
+
+    assert_eq!(custom.get("--a"), None);
+    assert_eq!(custom.get("--b"), None);
+    assert_eq!(custom.get("--self"), None);
+    assert!(custom.get("--ok").is_some());
+
+    let child = CustomProperties::compute(Some(&custom), &declarations("--color: initial; --ok: inherit; --new: unset"));
+    assert_eq!(child.get("--color"), None);
+    assert_eq!(child.get("--ok"), custom.get("--ok"));
+    assert_eq!(child.get("--new"), None);
+
+    // a substituted number does not take the unit after the reference
+    let custom = CustomProperties::compute(Some(&custom), &declarations("--n: 8"));
+    let width = declarations("width: var(--color); height: var(--nope); margin-left: var(--n)px");
+    for declaration in width.iter() {
+      match declaration.value {
+        Value::Unparsed(ref lexed) => assert_eq!(custom.resolve(&declaration.name, lexed), None),
+        _ => panic!("expected unparsed value")
+      }
+    }
+  }
+}
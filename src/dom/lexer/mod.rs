@@ -57,10 +57,7 @@ fn tokenize(pre_lexed: Vec<PreLexed>) -> Result<Vec<Lexed>, Error> {
   let token_lookup = get_tokens();
 
   let is_token = |val: &str| -> Option<Token> {
-    match token_lookup.get(val) {
-      Some(token) => Some(token.clone()),
-      None => None
-    }
+    token_lookup.get(val).cloned()
   };
 
   // let is_identifier = |val: &str| -> Option<String> {
@@ -80,9 +77,9 @@ fn tokenize(pre_lexed: Vec<PreLexed>) -> Result<Vec<Lexed>, Error> {
 
   let is_string = |val: &str| -> Option<String> {
     let len = val.len();
-    if len <= 0 { return None; }
+    if len == 0 { return None; }
 
-    for (i, v) in tokens.iter() {
+    for i in tokens.keys() {
       if val.contains(i) {
         return None;
       }
@@ -107,7 +104,7 @@ fn tokenize(pre_lexed: Vec<PreLexed>) -> Result<Vec<Lexed>, Error> {
 
   let mut tokenized: Vec<Lexed> = Vec::new();
 
-  for (i, v) in pre_lexed.into_iter().enumerate() {
+  for v in pre_lexed.into_iter() {
     match v {
       PreLexed::String(s) => {
         tokenized.push(Lexed::String(s))
@@ -120,7 +117,7 @@ fn tokenize(pre_lexed: Vec<PreLexed>) -> Result<Vec<Lexed>, Error> {
         // println!("offset: {:?}", &rest[pos..offset]);
 
         while offset >= 1 {
-          if pos >= length || offset - pos <= 0 {
+          if pos >= length || offset <= pos {
             // println!("breaking: pos: {}, offset: {}, length: {}", pos, offset, length);
             break;
           }
@@ -170,7 +167,7 @@ fn compress_tokens(tokenized: Vec<Lexed>) -> Result<Vec<Lexed>, Error> {
       let mut pos = 0;
       let mut offset = len;
 
-      loop {
+      'outer: loop {
         if pos > len || pos >= offset {
           break;
         }
@@ -180,7 +177,7 @@ fn compress_tokens(tokenized: Vec<Lexed>) -> Result<Vec<Lexed>, Error> {
         for token in current.iter() {
           for (i, v) in single_tokens.iter() {
             if *v == *token {
-              string_current.push_str(i.clone());
+              string_current.push_str(i);
             }
           } 
         }
@@ -200,7 +197,7 @@ fn compress_tokens(tokenized: Vec<Lexed>) -> Result<Vec<Lexed>, Error> {
             pos += k.len();
             offset = len;
             new_tokens.push(v.clone());
-            continue;
+            continue 'outer;
           }
         }
 
@@ -256,7 +253,7 @@ fn taginize(tokenized: Vec<Lexed>) -> Result<Vec<TagContents>, Error> {
 
   let mut pre_tag_buf: String = String::new();
 
-  for (i, v) in tokenized.into_iter().enumerate() {
+  for v in tokenized.into_iter() {
     match v {
       Lexed::String(s) => {
         if in_tag {
@@ -277,7 +274,7 @@ fn taginize(tokenized: Vec<Lexed>) -> Result<Vec<TagContents>, Error> {
       },
       Lexed::Content(s) => {
         if in_tag {
-          if let None = tag_name {
+          if tag_name.is_none() {
             let splitted = s.split(" ");
             
             let mut first = true;
@@ -305,7 +302,7 @@ fn taginize(tokenized: Vec<Lexed>) -> Result<Vec<TagContents>, Error> {
           if in_tag {
             return Err(Error("Tag error: <".to_string()));
           } else {
-            if pre_tag_buf.len() > 0 {
+            if !pre_tag_buf.is_empty() {
               tags.push(TagContents::Content(pre_tag_buf));
               pre_tag_buf = String::new();
             }
@@ -331,7 +328,7 @@ fn taginize(tokenized: Vec<Lexed>) -> Result<Vec<TagContents>, Error> {
 
             let mut attrs: AttrMap = HashMap::new();
             for i in buf_attrs.into_iter() {
-              if i.0.len() > 0 {
+              if !i.0.is_empty() {
                 attrs.insert(i.0, i.1);
               }
             }
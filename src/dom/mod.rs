@@ -3,8 +3,11 @@ use helper::Error;
 
 mod lexer;
 mod parser;
+pub mod stylesheets;
 
-pub fn parse_dom(query: String) -> Result<(), Error> {
+pub use self::parser::{Node, NodeType, ElementData};
+
+pub fn parse_dom(query: String) -> Result<Node, Error> {
   let lexed = lexer::lex(query)?;
 
   // println!("lexed: {:?}", lexed);
@@ -13,5 +16,54 @@ pub fn parse_dom(query: String) -> Result<(), Error> {
 
   println!("parsed: {:?}", parsed);
 
-  Ok(())
+  Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::parser::Value;
+
+  fn tag_names(node: &Node) -> Vec<String> {
+    node.children.iter().map(|child| match child.node_type {
+      NodeType::Element(ref element) => element.tag_name.clone(),
+      NodeType::Text(ref text) => format!("#{}", text)
+    }).collect()
+  }
+
+  #[test]
+  fn parses_void_elements() {
+    let root = parse_dom("<head><link rel=\"stylesheet\" href=\"a.css\"><title>t</title><meta charset=\"utf-8\"/><style>p {}</style></head>".to_string()).unwrap();
+    assert_eq!(tag_names(&root), vec!["link", "title", "meta", "style"]);
+    assert!(root.children.iter().all(|child| child.children.len() <= 1));
+
+    let p = parse_dom("<p>one<br>two<br/>three</p>".to_string()).unwrap();
+    assert_eq!(tag_names(&p), vec!["#one", "br", "#two", "br", "#three"]);
+  }
+
+  #[test]
+  fn parses_inline_style_lazily() {
+    let mut root = parse_dom("<p id=\"a\" style=\"color: red; width: 10px; nope: 1\">hi</p>".to_string()).unwrap();
+    let element = match root.node_type {
+      NodeType::Element(ref mut element) => element,
+      _ => panic!("expected element")
+    };
+
+    assert_eq!(element.attribute("id"), Some("a"));
+    assert_eq!(element.style().declarations.len(), 2);
+    assert_eq!(element.style().errors.len(), 1);
+
+    element.set_attribute("style", Some("display: block !important".to_string()));
+    let style = element.style();
+    assert_eq!(style.declarations.len(), 1);
+    assert!(style.declarations[0].important);
+    assert_eq!(style.declarations[0].value, Value::Keyword("block".to_string()));
+
+    element.set_attribute("STYLE", Some("color: blue".to_string()));
+    assert_eq!(element.style().declarations[0].name, "color");
+    assert_eq!(element.attribute("Style"), Some("color: blue"));
+
+    element.remove_attribute("Style");
+    assert!(element.style().declarations.is_empty());
+  }
 }
\ No newline at end of file
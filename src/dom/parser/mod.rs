@@ -1,10 +1,61 @@
+use std::mem;
+use std::str::SplitWhitespace;
+use std::sync::OnceLock;
 use AttrMap;
+use css::{self, parser::DeclarationBlock};
 use super::{Error, lexer::{TagContents, TagStatus}};
 
 #[derive(Debug)]
 pub struct Node {
-  children: Vec<Node>,
-  node_type: NodeType
+  pub children: Vec<Node>,
+  pub node_type: NodeType,
+  children_changed: bool // since the node was last styled
+}
+
+impl Node {
+  pub fn text(text: String) -> Self {
+    Self {
+      children: Vec::new(),
+      node_type: NodeType::Text(text),
+      children_changed: false
+    }
+  }
+
+  pub fn element(data: ElementData, children: Vec<Node>) -> Self {
+    Self {
+      children,
+      node_type: NodeType::Element(data),
+      children_changed: false
+    }
+  }
+
+  pub fn element_data_mut(&mut self) -> Option<&mut ElementData> {
+    match self.node_type {
+      NodeType::Element(ref mut data) => Some(data),
+      NodeType::Text(_) => None
+    }
+  }
+
+  pub fn append_child(&mut self, child: Node) {
+    let index = self.children.len();
+    self.insert_child(index, child);
+  }
+
+  pub fn insert_child(&mut self, index: usize, child: Node) {
+    self.children_changed = true;
+    self.children.insert(index, child);
+  }
+
+  pub fn remove_child(&mut self, index: usize) -> Node {
+    self.children_changed = true;
+    self.children.remove(index)
+  }
+
+  /// Whether children were added or removed through the methods above
+  /// since the last call.
+  pub fn take_children_changed(&mut self) -> bool {
+    mem::take(&mut self.children_changed)
+  }
 }
 
 #[derive(Debug)]
@@ -15,8 +66,90 @@ pub enum NodeType {
 
 #[derive(Debug)]
 pub struct ElementData {
-  tag_name: String,
-  attributes: AttrMap
+  pub tag_name: String,
+  attributes: AttrMap,
+  style: OnceLock<DeclarationBlock>, // the parsed `style` attribute, reset when it changes
+  snapshot: Option<AttrMap> // the attributes before they first changed since the element was last styled
+}
+
+impl ElementData {
+  /// Attribute names are ASCII case-insensitive, and kept lowercase.
+  pub fn new(tag_name: String, attributes: AttrMap) -> Self {
+    Self {
+      tag_name,
+      attributes: attributes.into_iter().map(|(name, value)| (name.to_ascii_lowercase(), value)).collect(),
+      style: OnceLock::new(),
+      snapshot: None
+    }
+  }
+
+  pub fn attributes(&self) -> &AttrMap {
+    &self.attributes
+  }
+
+  /// The value of an attribute, empty for attributes given without one.
+  pub fn attribute(&self, name: &str) -> Option<&str> {
+    let value = match name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+      true => self.attributes.get(&name.to_ascii_lowercase()),
+      false => self.attributes.get(name)
+    };
+    value.map(|value| value.as_ref().map_or("", |value| &value[..]))
+  }
+
+  pub fn id(&self) -> Option<&str> {
+    self.attribute("id")
+  }
+
+  pub fn classes(&self) -> SplitWhitespace<'_> {
+    self.attribute("class").unwrap_or("").split_whitespace()
+  }
+
+  pub fn set_attribute(&mut self, name: &str, value: Option<String>) {
+    self.invalidate(name);
+    self.attributes.insert(name.to_ascii_lowercase(), value);
+  }
+
+  pub fn remove_attribute(&mut self, name: &str) -> Option<Option<String>> {
+    self.invalidate(name);
+    self.attributes.remove(&name.to_ascii_lowercase())
+  }
+
+  fn invalidate(&mut self, name: &str) {
+    if self.snapshot.is_none() {
+      self.snapshot = Some(self.attributes.clone());
+    }
+    if name.eq_ignore_ascii_case("style") {
+      self.style = OnceLock::new();
+    }
+  }
+
+  /// The attributes before the first change since the last call, `None`
+  /// if they did not change.
+  pub fn take_snapshot(&mut self) -> Option<AttrMap> {
+    self.snapshot.take()
+  }
+
+  /// The declarations of the `style` attribute, parsed on first access.
+  /// They cascade as unlayered author declarations that win over any
+  /// selector.
+  pub fn style(&self) -> &DeclarationBlock {
+    self.style.get_or_init(|| {
+      let style = self.attribute("style").unwrap_or("");
+      css::parse_declarations(style.to_string()).unwrap_or_else(|err| DeclarationBlock {
+        declarations: Vec::new(),
+        errors: vec![err]
+      })
+    })
+  }
+}
+
+/// Elements that never have children, whether or not the tag is self closed.
+const VOID_ELEMENTS: &[&str] = &[
+  "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"
+];
+
+fn is_void(tag_name: &str) -> bool {
+  VOID_ELEMENTS.iter().any(|name| name.eq_ignore_ascii_case(tag_name))
 }
 
 struct Parser {
@@ -42,15 +175,12 @@ impl Parser {
 
     let mut children: Vec<Node> = Vec::new();
 
-    if my_tag.status != TagStatus::SelfClose {
+    if my_tag.status != TagStatus::SelfClose && !is_void(&my_tag.name) {
       loop {
         self.current += 1;
 
         match self.tags[self.current] {
-          TagContents::Content(ref s) => children.push(Node {
-            children: Vec::new(),
-            node_type: NodeType::Text(s.to_string())
-          }),
+          TagContents::Content(ref s) => children.push(Node::text(s.to_string())),
           TagContents::Tag(ref tag) => {
             if tag.name == my_tag.name && tag.status == TagStatus::Close {
               break;
@@ -64,13 +194,7 @@ impl Parser {
       }
     }
 
-    Ok(Node {
-      children,
-      node_type: NodeType::Element(ElementData {
-        tag_name: my_tag.name.clone(),
-        attributes: my_tag.attrs.clone()
-      })
-    })
+    Ok(Node::element(ElementData::new(my_tag.name.clone(), my_tag.attrs.clone()), children))
   }
 }
 
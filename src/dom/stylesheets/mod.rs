@@ -0,0 +1,188 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use css::{self, cascade::Origin, media::{Device, MediaQueryList}, parser::Stylesheet};
+use super::{Error, Node, NodeType, ElementData};
+
+/// Fetches the contents of external resources such as linked stylesheets.
+pub trait ResourceLoader {
+  fn load(&self, url: &str) -> Result<String, Error>;
+}
+
+/// Loads relative urls from a directory on disk, and nothing outside it.
+pub struct FileLoader {
+  pub base: PathBuf
+}
+
+impl FileLoader {
+  pub fn new<P: Into<PathBuf>>(base: P) -> Self {
+    Self {
+      base: base.into()
+    }
+  }
+}
+
+impl ResourceLoader for FileLoader {
+  fn load(&self, url: &str) -> Result<String, Error> {
+    if url.contains("://") {
+      return Err(Error(format!("Unsupported url: '{}'", url)));
+    }
+
+    let relative = Path::new(url.trim_start_matches('/'));
+    if !relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+      return Err(Error(format!("Url outside of '{}': '{}'", self.base.display(), url)));
+    }
+
+    let path = self.base.join(relative);
+    fs::read_to_string(&path)
+      .map_err(|err| Error(format!("Could not load '{}': {}", path.display(), err)))
+  }
+}
+
+/// The author stylesheets of a document that apply to a device, together
+/// with the stylesheets that could not be loaded or parsed.
+#[derive(Debug)]
+pub struct StylesheetList {
+  pub stylesheets: Vec<Stylesheet>,
+  pub errors: Vec<Error>
+}
+
+/// Collects the contents of `<style>` elements and the stylesheets of
+/// `<link rel="stylesheet">` elements in document order, skipping disabled
+/// ones and those whose `media` does not match the device.
+pub fn collect_stylesheets(root: &Node, loader: &dyn ResourceLoader, device: &Device) -> StylesheetList {
+  let mut list = StylesheetList {
+    stylesheets: Vec::new(),
+    errors: Vec::new()
+  };
+
+  collect(root, loader, device, &mut list);
+  list
+}
+
+fn collect(node: &Node, loader: &dyn ResourceLoader, device: &Device, list: &mut StylesheetList) {
+  let element = match node.node_type {
+    NodeType::Element(ref element) => element,
+    NodeType::Text(_) => return
+  };
+
+  let is_style = element.tag_name.eq_ignore_ascii_case("style");
+  let is_link = element.tag_name.eq_ignore_ascii_case("link") && is_stylesheet_link(element);
+
+  if (is_style || is_link) && applies(element, device, list) {
+    let source = if is_style {
+      Some(Ok(text_content(node)))
+    } else {
+      element.attribute("href")
+        .map(str::trim)
+        .filter(|href| !href.is_empty())
+        .map(|href| loader.load(href))
+    };
+
+    let stylesheet = source.map(|source| {
+      let lexed = css::lexer::lex(source?)?;
+      css::parser::parse_stylesheet(lexed, Origin::Author)
+    });
+
+    match stylesheet {
+      Some(Ok(stylesheet)) => list.stylesheets.push(stylesheet),
+      Some(Err(err)) => list.errors.push(err),
+      None => {}
+    }
+  }
+
+  for child in node.children.iter() {
+    collect(child, loader, device, list);
+  }
+}
+
+/// `rel` contains `stylesheet` but not `alternate`, and `type` is CSS.
+fn is_stylesheet_link(element: &ElementData) -> bool {
+  let rel = element.attribute("rel").unwrap_or("");
+  let has = |token: &str| rel.split_whitespace().any(|i| i.eq_ignore_ascii_case(token));
+
+  has("stylesheet") && !has("alternate") && is_css(element)
+}
+
+fn is_css(element: &ElementData) -> bool {
+  element.attribute("type").is_none_or(|kind| kind.is_empty() || kind.eq_ignore_ascii_case("text/css"))
+}
+
+fn applies(element: &ElementData, device: &Device, list: &mut StylesheetList) -> bool {
+  if element.attribute("disabled").is_some() || !is_css(element) {
+    return false;
+  }
+
+  match element.attribute("media").map(MediaQueryList::parse) {
+    Some(Ok(media)) => media.matches(device),
+    Some(Err(err)) => {
+      list.errors.push(err);
+      false
+    },
+    None => true
+  }
+}
+
+fn text_content(node: &Node) -> String {
+  let mut text = String::new();
+  for child in node.children.iter() {
+    match child.node_type {
+      NodeType::Text(ref s) => text.push_str(s),
+      NodeType::Element(_) => text.push_str(&text_content(child))
+    }
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+  use css::media::MediaType;
+  use dom::parse_dom;
+
+  struct MapLoader(HashMap<&'static str, &'static str>);
+
+  impl ResourceLoader for MapLoader {
+    fn load(&self, url: &str) -> Result<String, Error> {
+      self.0.get(url)
+        .map(|i| i.to_string())
+        .ok_or_else(|| Error(format!("Not found: '{}'", url)))
+    }
+  }
+
+  #[test]
+  fn collects_in_document_order() {
+    let root = parse_dom("<html><head>
+      <link rel=\"stylesheet\" href=\"a.css\" />
+      <style>b { color: red }</style>
+      <link rel=\"alternate stylesheet\" href=\"a.css\" />
+      <link rel=\"stylesheet\" href=\"a.css\" disabled />
+      <style media=\"print\">c { color: red }</style>
+      <style type=\"text/plain\">d { color: red }</style>
+      <link rel=\"stylesheet\" href=\"missing.css\" />
+    </head><body><style media=\"(min-width: 500px)\">e { color: red }</style></body></html>".to_string()).unwrap();
+
+    let loader = MapLoader(map!{ "a.css" => "a { color: red }" });
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+
+    let list = collect_stylesheets(&root, &loader, &device);
+    let first_selectors: Vec<String> = list.stylesheets.iter()
+      .map(|sheet| css::serialize::ToCss::to_css(&sheet.rules[0].selectors[0]))
+      .collect();
+
+    assert_eq!(first_selectors, vec!["a", "b", "e"]);
+    assert_eq!(list.errors.len(), 1);
+  }
+
+  #[test]
+  fn loads_files_under_base_only() {
+    let loader = FileLoader::new(env!("CARGO_MANIFEST_DIR"));
+    assert!(loader.load("/Cargo.toml").is_ok());
+    assert!(loader.load("./src/../Cargo.toml").is_err());
+    assert!(loader.load("../../etc/passwd").is_err());
+  }
+}
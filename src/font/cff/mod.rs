@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use helper::Error;
+use super::Reader;
+use super::outline::{Command, Outline, Point};
+
+/// How many subroutine calls may nest, per the Type 2 charstring limits.
+const MAX_CALL_DEPTH: usize = 10;
+const MAX_STACK: usize = 48;
+
+/// The PostScript outlines of a `CFF ` table, in Type 2 charstrings,
+/// including those of CID-keyed fonts with a dictionary per glyph group.
+#[derive(Debug, Default)]
+pub struct Cff {
+  charstrings: Index,
+  global_subrs: Index,
+  local_subrs: Vec<Index>, // per font dictionary
+  fd_select: Vec<u8> // the font dictionary of each glyph, empty for one
+}
+
+/// An INDEX: the ranges of its objects, from the start of the table.
+#[derive(Debug, Default, Clone)]
+struct Index {
+  offsets: Vec<usize>
+}
+
+impl Index {
+  /// Reads the INDEX at `offset`. Returns it and where it ends.
+  fn read(cff: Reader, offset: usize) -> Result<(Index, usize), Error> {
+    let count = cff.at(offset).u16()? as usize;
+    if count == 0 {
+      return Ok((Index::default(), offset + 2));
+    }
+    let size = cff.at(offset + 2).u8()? as usize;
+    if !(1..=4).contains(&size) {
+      return Err(Error(format!("Bad CFF INDEX offset size {}", size)));
+    }
+
+    let data = offset + 3 + (count + 1) * size - 1; // offsets start at 1
+    let mut offsets = Vec::with_capacity(count + 1);
+    for i in 0..=count {
+      let bytes = cff.bytes(offset + 3 + i * size, size)?;
+      let relative = bytes.iter().fold(0, |value, &byte| value << 8 | byte as usize);
+      offsets.push(data + relative);
+    }
+    let end = *offsets.last().unwrap();
+    cff.bytes(data, end.saturating_sub(data))?;
+    Ok((Index { offsets }, end))
+  }
+
+  fn len(&self) -> usize {
+    self.offsets.len().saturating_sub(1)
+  }
+
+  fn get<'d>(&self, cff: Reader<'d>, i: usize) -> Option<&'d [u8]> {
+    let (&start, &end) = (self.offsets.get(i)?, self.offsets.get(i + 1)?);
+    cff.bytes(start, end.checked_sub(start)?).ok()
+  }
+
+  /// The bias added to subroutine numbers in charstrings.
+  fn bias(&self) -> i32 {
+    match self.len() {
+      0..=1239 => 107,
+      1240..=33899 => 1131,
+      _ => 32768
+    }
+  }
+}
+
+/// The operands of each operator of a DICT, with two-byte operators as
+/// 1200 plus their second byte.
+fn read_dict(data: &[u8]) -> Result<HashMap<u16, Vec<f32>>, Error> {
+  let mut dict = HashMap::new();
+  let mut operands = Vec::new();
+  let mut i = 0;
+  let byte = |i: usize| data.get(i).cloned().map(i32::from).ok_or_else(|| Error("Unexpected end of CFF DICT".to_string()));
+
+  while i < data.len() {
+    let b0 = byte(i)?;
+    i += 1;
+    match b0 {
+      0..=21 => {
+        let operator = if b0 == 12 {
+          i += 1;
+          1200 + byte(i - 1)? as u16
+        } else {
+          b0 as u16
+        };
+        dict.insert(operator, std::mem::take(&mut operands));
+      },
+      28 => {
+        operands.push((byte(i)? << 8 | byte(i + 1)?) as i16 as f32);
+        i += 2;
+      },
+      29 => {
+        operands.push((byte(i)? << 24 | byte(i + 1)? << 16 | byte(i + 2)? << 8 | byte(i + 3)?) as f32);
+        i += 4;
+      },
+      30 => {
+        // a real number in nibbles, which the tables here never need
+        // beyond its place
+        while i < data.len() {
+          let nibbles = byte(i)?;
+          i += 1;
+          if nibbles & 0x0F == 0x0F || nibbles >> 4 == 0x0F {
+            break;
+          }
+        }
+        operands.push(0.0);
+      },
+      32..=246 => operands.push((b0 - 139) as f32),
+      247..=250 => {
+        operands.push(((b0 - 247) * 256 + byte(i)? + 108) as f32);
+        i += 1;
+      },
+      251..=254 => {
+        operands.push((-(b0 - 251) * 256 - byte(i)? - 108) as f32);
+        i += 1;
+      },
+      _ => return Err(Error(format!("Bad CFF DICT byte {}", b0)))
+    }
+  }
+  Ok(dict)
+}
+
+/// The offset an operator gives, or its `n`th operand.
+fn operand(dict: &HashMap<u16, Vec<f32>>, operator: u16, n: usize) -> Option<usize> {
+  dict.get(&operator).and_then(|operands| operands.get(n)).map(|&value| value as usize)
+}
+
+impl Cff {
+  pub(super) fn parse(cff: Reader, glyph_count: u16) -> Result<Cff, Error> {
+    let header_size = cff.at(2).u8()? as usize;
+    let (_names, end) = Index::read(cff, header_size)?;
+    let (top_dicts, end) = Index::read(cff, end)?;
+    let (_strings, end) = Index::read(cff, end)?;
+    let (global_subrs, _) = Index::read(cff, end)?;
+
+    let top = read_dict(top_dicts.get(cff, 0).ok_or_else(|| Error("CFF has no Top DICT".to_string()))?)?;
+    let charstrings = operand(&top, 17, 0).ok_or_else(|| Error("CFF has no CharStrings".to_string()))?;
+    let (charstrings, _) = Index::read(cff, charstrings)?;
+
+    let mut font = Cff {
+      charstrings,
+      global_subrs,
+      local_subrs: Vec::new(),
+      fd_select: Vec::new()
+    };
+
+    // a CID-keyed font has a private DICT per font DICT of its FDArray
+    let private_dicts = match (operand(&top, 1236, 0), operand(&top, 1237, 0)) {
+      (Some(fd_array), Some(fd_select)) => {
+        font.fd_select = read_fd_select(cff, fd_select, glyph_count)?;
+        let (fd_array, _) = Index::read(cff, fd_array)?;
+        (0..fd_array.len())
+          .map(|i| read_dict(fd_array.get(cff, i).unwrap_or(&[])))
+          .collect::<Result<Vec<_>, Error>>()?
+      },
+      _ => vec![top]
+    };
+    for dict in private_dicts.iter() {
+      let subrs = match (operand(dict, 18, 0), operand(dict, 18, 1)) {
+        (Some(size), Some(offset)) => {
+          let private = read_dict(cff.bytes(offset, size)?)?;
+          match operand(&private, 19, 0) {
+            Some(subrs) => Index::read(cff, offset + subrs)?.0,
+            None => Index::default()
+          }
+        },
+        _ => Index::default()
+      };
+      font.local_subrs.push(subrs);
+    }
+    Ok(font)
+  }
+
+  pub(super) fn outline(&self, cff: Reader, glyph: u16) -> Result<Outline, Error> {
+    let charstring = self.charstrings.get(cff, glyph as usize)
+      .ok_or_else(|| Error(format!("CFF has no glyph {}", glyph)))?;
+    let fd = self.fd_select.get(glyph as usize).cloned().unwrap_or(0) as usize;
+    let no_subrs = Index::default();
+
+    let mut interpreter = Interpreter {
+      cff,
+      global_subrs: &self.global_subrs,
+      local_subrs: self.local_subrs.get(fd).unwrap_or(&no_subrs),
+      stack: Vec::new(),
+      stems: 0,
+      width_parsed: false,
+      point: Point::default(),
+      open: false,
+      outline: Outline::default()
+    };
+    interpreter.run(charstring, 0)?;
+    interpreter.close();
+    Ok(interpreter.outline)
+  }
+}
+
+fn read_fd_select(cff: Reader, offset: usize, glyph_count: u16) -> Result<Vec<u8>, Error> {
+  let mut data = cff.at(offset);
+  match data.u8()? {
+    0 => Ok(cff.bytes(offset + 1, glyph_count as usize)?.to_vec()),
+    3 => {
+      let ranges = data.u16()?;
+      let mut fd_select = vec![0; glyph_count as usize];
+      let mut first = data.u16()? as usize;
+      for _ in 0..ranges {
+        let fd = data.u8()?;
+        let next = data.u16()? as usize;
+        for entry in fd_select.iter_mut().take(next).skip(first) {
+          *entry = fd;
+        }
+        first = next;
+      }
+      Ok(fd_select)
+    },
+    format => Err(Error(format!("Unknown FDSelect format {}", format)))
+  }
+}
+
+/// Runs Type 2 charstrings into an outline. Hints are skipped past.
+struct Interpreter<'c, 'd> {
+  cff: Reader<'d>,
+  global_subrs: &'c Index,
+  local_subrs: &'c Index,
+  stack: Vec<f32>,
+  stems: usize,
+  width_parsed: bool,
+  point: Point,
+  open: bool,
+  outline: Outline
+}
+
+impl<'c, 'd> Interpreter<'c, 'd> {
+  fn close(&mut self) {
+    if self.open {
+      self.outline.commands.push(Command::Close);
+      self.open = false;
+    }
+  }
+
+  fn move_to(&mut self, dx: f32, dy: f32) {
+    self.close();
+    self.point = Point::new(self.point.x + dx, self.point.y + dy);
+    self.outline.commands.push(Command::MoveTo(self.point));
+    self.open = true;
+  }
+
+  fn line_to(&mut self, dx: f32, dy: f32) {
+    self.point = Point::new(self.point.x + dx, self.point.y + dy);
+    self.outline.commands.push(Command::LineTo(self.point));
+  }
+
+  fn curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+    let c1 = Point::new(self.point.x + dx1, self.point.y + dy1);
+    let c2 = Point::new(c1.x + dx2, c1.y + dy2);
+    self.point = Point::new(c2.x + dx3, c2.y + dy3);
+    self.outline.commands.push(Command::CurveTo(c1, c2, self.point));
+  }
+
+  /// Drops the advance width that the first stack-clearing operator
+  /// takes before its arguments, which it `has` if it has one argument
+  /// more than it needs.
+  fn take_width(&mut self, s: &mut Vec<f32>, has: bool) {
+    if !self.width_parsed && has && !s.is_empty() {
+      s.remove(0);
+    }
+    self.width_parsed = true;
+  }
+
+  /// Returns whether the charstring ended the glyph.
+  fn run(&mut self, code: &[u8], depth: usize) -> Result<bool, Error> {
+    if depth > MAX_CALL_DEPTH {
+      return Err(Error("CFF subroutines nest too deep".to_string()));
+    }
+    let mut i = 0;
+    let byte = |i: usize| code.get(i).cloned().map(i32::from).ok_or_else(|| Error("Unexpected end of charstring".to_string()));
+
+    while i < code.len() {
+      let b0 = byte(i)?;
+      i += 1;
+      let number = match b0 {
+        28 => {
+          i += 2;
+          Some((byte(i - 2)? << 8 | byte(i - 1)?) as i16 as f32)
+        },
+        32..=246 => Some((b0 - 139) as f32),
+        247..=250 => {
+          i += 1;
+          Some(((b0 - 247) * 256 + byte(i - 1)? + 108) as f32)
+        },
+        251..=254 => {
+          i += 1;
+          Some((-(b0 - 251) * 256 - byte(i - 1)? - 108) as f32)
+        },
+        255 => {
+          i += 4;
+          let fixed = byte(i - 4)? << 24 | byte(i - 3)? << 16 | byte(i - 2)? << 8 | byte(i - 1)?;
+          Some(fixed as f32 / 65536.0)
+        },
+        _ => None
+      };
+      if let Some(number) = number {
+        if self.stack.len() >= MAX_STACK {
+          return Err(Error("Charstring stack overflow".to_string()));
+        }
+        self.stack.push(number);
+        continue;
+      }
+
+      // every operator but the subroutine calls clears the stack
+      let mut s = std::mem::take(&mut self.stack);
+      let n = s.len();
+      match b0 {
+        // hstem, vstem, hstemhm, vstemhm, and hintmask and cntrmask,
+        // whose arguments are vertical stems
+        1 | 3 | 18 | 23 | 19 | 20 => {
+          self.take_width(&mut s, n % 2 == 1);
+          self.stems += s.len() / 2;
+          if b0 == 19 || b0 == 20 {
+            i += self.stems.div_ceil(8);
+          }
+        },
+        21 => {
+          self.take_width(&mut s, n > 2);
+          self.move_to(arg(&s, 0)?, arg(&s, 1)?);
+        },
+        22 | 4 => {
+          self.take_width(&mut s, n > 1);
+          let d = arg(&s, 0)?;
+          if b0 == 22 {
+            self.move_to(d, 0.0);
+          } else {
+            self.move_to(0.0, d);
+          }
+        },
+        5 => {
+          for pair in s.chunks_exact(2) {
+            self.line_to(pair[0], pair[1]);
+          }
+        },
+        // hlineto and vlineto alternate
+        6 | 7 => {
+          let mut horizontal = b0 == 6;
+          for &d in s.iter() {
+            if horizontal {
+              self.line_to(d, 0.0);
+            } else {
+              self.line_to(0.0, d);
+            }
+            horizontal = !horizontal;
+          }
+        },
+        8 => {
+          for c in s.chunks_exact(6) {
+            self.curve_to(c[0], c[1], c[2], c[3], c[4], c[5]);
+          }
+        },
+        24 => {
+          let curves = n.saturating_sub(2) / 6 * 6;
+          for c in s[..curves].chunks_exact(6) {
+            self.curve_to(c[0], c[1], c[2], c[3], c[4], c[5]);
+          }
+          self.line_to(arg(&s, curves)?, arg(&s, curves + 1)?);
+        },
+        25 => {
+          let lines = n.saturating_sub(6) / 2 * 2;
+          for pair in s[..lines].chunks_exact(2) {
+            self.line_to(pair[0], pair[1]);
+          }
+          let c = &s[lines..];
+          self.curve_to(arg(c, 0)?, arg(c, 1)?, arg(c, 2)?, arg(c, 3)?, arg(c, 4)?, arg(c, 5)?);
+        },
+        // vvcurveto and hhcurveto, with an optional first offset across
+        26 | 27 => {
+          let (mut across, rest) = match n % 2 {
+            1 => (s[0], &s[1..]),
+            _ => (0.0, &s[..])
+          };
+          for c in rest.chunks_exact(4) {
+            if b0 == 26 {
+              self.curve_to(across, c[0], c[1], c[2], 0.0, c[3]);
+            } else {
+              self.curve_to(c[0], across, c[1], c[2], c[3], 0.0);
+            }
+            across = 0.0;
+          }
+        },
+        // vhcurveto and hvcurveto alternate, with an optional last offset
+        30 | 31 => {
+          let mut horizontal = b0 == 31;
+          let mut j = 0;
+          while j + 4 <= n {
+            let last = if n - j == 5 { s[j + 4] } else { 0.0 };
+            if horizontal {
+              self.curve_to(s[j], 0.0, s[j + 1], s[j + 2], last, s[j + 3]);
+            } else {
+              self.curve_to(0.0, s[j], s[j + 1], s[j + 2], s[j + 3], last);
+            }
+            horizontal = !horizontal;
+            j += 4;
+          }
+        },
+        // callsubr and callgsubr
+        10 | 29 => {
+          let subrs = if b0 == 10 { self.local_subrs } else { self.global_subrs };
+          let number = s.pop().ok_or_else(|| Error("Empty charstring stack".to_string()))?;
+          let index = number as i32 + subrs.bias();
+          let subr = usize::try_from(index).ok().and_then(|index| subrs.get(self.cff, index))
+            .ok_or_else(|| Error(format!("No charstring subroutine {}", index)))?;
+          self.stack = s;
+          if self.run(subr, depth + 1)? {
+            return Ok(true);
+          }
+        },
+        11 => {
+          self.stack = s;
+          return Ok(false);
+        },
+        14 => {
+          self.take_width(&mut s, n == 1 || n == 5);
+          self.close();
+          return Ok(true);
+        },
+        12 => {
+          let b1 = byte(i)?;
+          i += 1;
+          match b1 {
+            // flex, hflex, hflex1 and flex1, drawn as their two curves
+            35 if n >= 12 => {
+              self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+              self.curve_to(s[6], s[7], s[8], s[9], s[10], s[11]);
+            },
+            34 if n >= 7 => {
+              self.curve_to(s[0], 0.0, s[1], s[2], s[3], 0.0);
+              self.curve_to(s[4], 0.0, s[5], -s[2], s[6], 0.0);
+            },
+            36 if n >= 9 => {
+              self.curve_to(s[0], s[1], s[2], s[3], s[4], 0.0);
+              self.curve_to(s[5], 0.0, s[6], s[7], s[8], -(s[1] + s[3] + s[7]));
+            },
+            37 if n >= 11 => {
+              let dx: f32 = s[..10].iter().step_by(2).sum();
+              let dy: f32 = s[1..10].iter().step_by(2).sum();
+              let (dx6, dy6) = if dx.abs() > dy.abs() { (s[10], -dy) } else { (-dx, s[10]) };
+              self.curve_to(s[0], s[1], s[2], s[3], s[4], s[5]);
+              self.curve_to(s[6], s[7], s[8], s[9], dx6, dy6);
+            },
+            _ => ()
+          }
+        },
+        _ => ()
+      }
+    }
+    Ok(false)
+  }
+}
+
+fn arg(stack: &[f32], i: usize) -> Result<f32, Error> {
+  stack.get(i).cloned().ok_or_else(|| Error("Too few charstring arguments".to_string()))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  /// An INDEX of `objects` with one-byte offsets.
+  pub fn index(objects: &[Vec<u8>]) -> Vec<u8> {
+    if objects.is_empty() {
+      return vec![0, 0];
+    }
+    let mut data = (objects.len() as u16).to_be_bytes().to_vec();
+    data.push(1);
+    let mut offset = 1;
+    data.push(offset);
+    for object in objects.iter() {
+      offset += object.len() as u8;
+      data.push(offset);
+    }
+    data.extend(objects.concat());
+    data
+  }
+
+  /// A CFF table of .notdef and a glyph drawn by `charstring`, which can
+  /// call the global subroutine `subr` as -107.
+  pub fn cff(charstring: Vec<u8>, subr: Vec<u8>) -> Vec<u8> {
+    let long = |value: usize| {
+      let mut bytes = vec![29];
+      bytes.extend((value as u32).to_be_bytes());
+      bytes
+    };
+    let globals = index(&[subr]);
+    let charstrings = index(&[vec![14], charstring]);
+    let top = |charstrings: usize| index(&[[long(charstrings), vec![17], long(0), long(0), vec![18]].concat()]);
+    let names = index(&[b"T".to_vec()]);
+    let start = 4 + names.len() + top(0).len() + 2 + globals.len();
+
+    let mut data = vec![1, 0, 4, 1];
+    data.extend(names);
+    data.extend(top(start));
+    data.extend(index(&[]));
+    data.extend(globals);
+    data.extend(charstrings);
+    data
+  }
+
+  #[test]
+  fn draws_charstrings() {
+    // 10 0 rmoveto, 30 hlineto, 40 vlineto, then a curve and a line back
+    // in the subroutine, with a width first
+    let charstring = vec![139 + 50, 139 + 10, 139, 21, 139 + 30, 6, 139 + 40, 7, 139 - 107, 29, 14];
+    let subr = vec![139 - 10, 139, 139 - 10, 139, 139 - 10, 139, 139, 139 - 40, 24, 11];
+    let data = cff(charstring, subr);
+    let cff = Cff::parse(Reader::new(&data), 2).unwrap();
+
+    let outline = cff.outline(Reader::new(&data), 1).unwrap();
+    let p = Point::new;
+    assert_eq!(outline.commands, vec![
+      Command::MoveTo(p(10.0, 0.0)),
+      Command::LineTo(p(40.0, 0.0)),
+      Command::LineTo(p(40.0, 40.0)),
+      Command::CurveTo(p(30.0, 40.0), p(20.0, 40.0), p(10.0, 40.0)),
+      Command::LineTo(p(10.0, 0.0)),
+      Command::Close
+    ]);
+    assert!(cff.outline(Reader::new(&data), 0).unwrap().is_empty());
+    assert!(cff.outline(Reader::new(&data), 2).is_err());
+  }
+}
@@ -0,0 +1,146 @@
+use std::convert::TryFrom;
+use helper::Error;
+use super::Reader;
+
+/// The character to glyph mapping of the `cmap` table, from its Unicode
+/// subtable of format 4 (the Basic Multilingual Plane) or 12 (all planes).
+#[derive(Debug, Default)]
+pub struct Cmap {
+  segments: Vec<Segment>,
+  glyph_ids: Vec<u16>, // of format 4
+  groups: Vec<(u32, u32, u32)> // of format 12: first and last character, first glyph
+}
+
+#[derive(Debug)]
+struct Segment {
+  start: u16,
+  end: u16,
+  delta: u16,
+  range_offset: u16,
+  index: usize
+}
+
+impl Cmap {
+  pub(super) fn parse(cmap: Reader) -> Result<Cmap, Error> {
+    let count = cmap.at(2).u16()?;
+    let mut best: Option<(u8, usize)> = None;
+    for i in 0..count as usize {
+      let mut record = cmap.at(4 + i * 8);
+      let (platform, encoding, offset) = (record.u16()?, record.u16()?, record.u32()? as usize);
+      let format = cmap.at(offset).u16()?;
+      let rank = match (platform, encoding, format) {
+        (3, 10, 12) | (0, 4, 12) | (0, 6, 12) => 3,
+        (3, 1, 4) | (0, 3, 4) => 2,
+        (0, _, 4) => 1,
+        _ => continue
+      };
+      if best.is_none_or(|(best, _)| rank > best) {
+        best = Some((rank, offset));
+      }
+    }
+
+    let offset = match best {
+      Some((_, offset)) => offset,
+      None => return Err(Error("Font has no Unicode cmap".to_string()))
+    };
+    let mut subtable = cmap.at(offset);
+    let mut table = Cmap::default();
+    match subtable.u16()? {
+      4 => {
+        let length = subtable.u16()? as usize;
+        let segments = (subtable.at(offset + 6).u16()? / 2) as usize;
+        let array = |i: usize| offset + 14 + i * segments * 2 + if i > 0 { 2 } else { 0 };
+        for i in 0..segments {
+          table.segments.push(Segment {
+            end: cmap.at(array(0) + i * 2).u16()?,
+            start: cmap.at(array(1) + i * 2).u16()?,
+            delta: cmap.at(array(2) + i * 2).u16()?,
+            range_offset: cmap.at(array(3) + i * 2).u16()?,
+            index: i
+          });
+        }
+        let mut ids = cmap.at(array(4));
+        while ids.offset + 2 <= offset + length {
+          table.glyph_ids.push(ids.u16()?);
+        }
+      },
+      12 => {
+        let groups = subtable.at(offset + 12).u32()?;
+        let mut group = subtable.at(offset + 16);
+        for _ in 0..groups {
+          table.groups.push((group.u32()?, group.u32()?, group.u32()?));
+        }
+      },
+      _ => unreachable!()
+    }
+    Ok(table)
+  }
+
+  pub fn glyph_index(&self, c: char) -> Option<u16> {
+    let code = c as u32;
+    if !self.groups.is_empty() {
+      let i = self.groups.partition_point(|&(_, last, _)| last < code);
+      let &(first, _, glyph) = self.groups.get(i).filter(|&&(first, _, _)| first <= code)?;
+      return u16::try_from(glyph + (code - first)).ok();
+    }
+
+    let code = u16::try_from(code).ok()?;
+    let i = self.segments.partition_point(|segment| segment.end < code);
+    let segment = self.segments.get(i).filter(|segment| segment.start <= code)?;
+    if segment.range_offset == 0 {
+      return Some(code.wrapping_add(segment.delta));
+    }
+    // the offset is from the segment's place in the range offsets to
+    // the glyph ids that follow them
+    let index = (segment.range_offset / 2) as usize + (code - segment.start) as usize + segment.index;
+    let glyph = *self.glyph_ids.get(index.checked_sub(self.segments.len())?)?;
+    Some(glyph).filter(|&glyph| glyph != 0).map(|glyph| glyph.wrapping_add(segment.delta))
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use font::tests::be;
+
+  /// A cmap with a Windows Unicode subtable of format 4 mapping each range
+  /// of characters to consecutive glyphs, from the given one.
+  pub fn format_4(ranges: &[(i32, i32, i32)]) -> Vec<u8> {
+    let mut ranges = ranges.to_vec();
+    ranges.push((0xFFFF, 0xFFFF, 1));
+    let count = ranges.len() as i32;
+
+    let mut subtable = be(&[4, 0, 0, count * 2, 0, 0, 0]);
+    subtable.extend(be(&ranges.iter().map(|range| range.1).collect::<Vec<i32>>()));
+    subtable.extend(be(&[0]));
+    subtable.extend(be(&ranges.iter().map(|range| range.0).collect::<Vec<i32>>()));
+    subtable.extend(be(&ranges.iter().map(|range| range.2 - range.0).collect::<Vec<i32>>()));
+    subtable.extend(be(&ranges.iter().map(|_| 0).collect::<Vec<i32>>()));
+    let length = subtable.len() as u16;
+    subtable[2..4].copy_from_slice(&length.to_be_bytes());
+
+    let mut cmap = be(&[0, 1, 3, 1, 0, 12]);
+    cmap.extend(subtable);
+    cmap
+  }
+
+  #[test]
+  fn maps_characters() {
+    let cmap = Cmap::parse(Reader::new(&format_4(&[(0x41, 0x43, 10), (0x3B1, 0x3B1, 2)]))).unwrap();
+    let glyphs: Vec<Option<u16>> = "ACDα\u{1F600}".chars().map(|c| cmap.glyph_index(c)).collect();
+    assert_eq!(glyphs, vec![Some(10), Some(12), None, Some(2), None]);
+
+    // format 12 over format 4, which maps through its glyph id array
+    let mut format_12 = be(&[0, 2, 3, 1, 0, 20, 3, 10, 0, 20 + 34]);
+    format_12.extend(be(&[4, 34, 0, 4, 0, 0, 0, 0x41, 0xFFFF, 0, 0x41, 0xFFFF, 0, 1, 4, 0, 9]));
+    format_12.extend(be(&[12, 0, 0, 40, 0, 0, 0, 2, 0, 0x41, 0, 0x42, 0, 7, 1, 0xF600, 1, 0xF601, 0, 3]));
+    let cmap = Cmap::parse(Reader::new(&format_12)).unwrap();
+    let glyphs: Vec<Option<u16>> = "AB\u{1F601}C".chars().map(|c| cmap.glyph_index(c)).collect();
+    assert_eq!(glyphs, vec![Some(7), Some(8), Some(4), None]);
+
+    format_12[3] = 1;
+    let cmap = Cmap::parse(Reader::new(&format_12)).unwrap();
+    assert_eq!((cmap.glyph_index('A'), cmap.glyph_index('B')), (Some(9), None));
+    assert!(Cmap::parse(Reader::new(&be(&[0, 0]))).is_err());
+  }
+}
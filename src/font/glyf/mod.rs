@@ -0,0 +1,153 @@
+use helper::Error;
+use super::Reader;
+use super::outline::{Outline, Point};
+
+const ON_CURVE: u8 = 0x01;
+const X_SHORT: u8 = 0x02;
+const Y_SHORT: u8 = 0x04;
+const REPEAT: u8 = 0x08;
+const X_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+const ARGS_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_OFFSETS: u16 = 0x0002;
+const HAS_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const HAS_X_AND_Y_SCALE: u16 = 0x0040;
+const HAS_TWO_BY_TWO: u16 = 0x0080;
+
+/// How deep composite glyphs may nest, against fonts that loop.
+const MAX_DEPTH: usize = 8;
+
+/// The TrueType outlines of the `glyf` table, found through `loca`.
+pub(super) struct Glyf<'d> {
+  pub glyf: Reader<'d>,
+  pub loca: Reader<'d>,
+  pub long_offsets: bool // `indexToLocFormat` of `head`
+}
+
+impl<'d> Glyf<'d> {
+  pub fn outline(&self, glyph: u16) -> Result<Outline, Error> {
+    let mut outline = Outline::default();
+    self.add_glyph(glyph, &mut outline, 0)?;
+    Ok(outline)
+  }
+
+  fn location(&self, glyph: u16) -> Result<(usize, usize), Error> {
+    let i = glyph as usize;
+    Ok(if self.long_offsets {
+      (self.loca.at(i * 4).u32()? as usize, self.loca.at(i * 4 + 4).u32()? as usize)
+    } else {
+      (self.loca.at(i * 2).u16()? as usize * 2, self.loca.at(i * 2 + 2).u16()? as usize * 2)
+    })
+  }
+
+  fn add_glyph(&self, glyph: u16, outline: &mut Outline, depth: usize) -> Result<(), Error> {
+    if depth > MAX_DEPTH {
+      return Err(Error(format!("Composite glyph {} nests too deep", glyph)));
+    }
+    let (start, end) = self.location(glyph)?;
+    if end <= start {
+      return Ok(()); // no outline, like a space
+    }
+
+    let contours = self.glyf.at(start).i16()?;
+    if contours >= 0 {
+      self.add_simple(start, contours as usize, outline)
+    } else {
+      self.add_composite(start, outline, depth)
+    }
+  }
+
+  fn add_simple(&self, start: usize, contours: usize, outline: &mut Outline) -> Result<(), Error> {
+    let mut data = self.glyf.at(start + 10);
+    let mut ends = Vec::with_capacity(contours);
+    for _ in 0..contours {
+      ends.push(data.u16()? as usize);
+    }
+    let count = ends.last().map_or(0, |&last| last + 1);
+    let instructions = data.u16()? as usize;
+    data.skip(instructions);
+
+    let mut flags = Vec::with_capacity(count);
+    while flags.len() < count {
+      let flag = data.u8()?;
+      flags.push(flag);
+      if flag & REPEAT != 0 {
+        for _ in 0..data.u8()? {
+          flags.push(flag);
+        }
+      }
+    }
+    flags.truncate(count);
+
+    let mut read_coordinates = |short: u8, same_or_positive: u8| -> Result<Vec<f32>, Error> {
+      let mut value = 0i32;
+      let mut coordinates = Vec::with_capacity(count);
+      for &flag in flags.iter() {
+        value += match (flag & short != 0, flag & same_or_positive != 0) {
+          (true, true) => data.u8()? as i32,
+          (true, false) => -(data.u8()? as i32),
+          (false, true) => 0,
+          (false, false) => data.i16()? as i32
+        };
+        coordinates.push(value as f32);
+      }
+      Ok(coordinates)
+    };
+    let xs = read_coordinates(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read_coordinates(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let points: Vec<(Point, bool)> = (0..count)
+      .map(|i| (Point::new(xs[i], ys[i]), flags[i] & ON_CURVE != 0))
+      .collect();
+    let mut first = 0;
+    for end in ends {
+      if end < first || end >= count {
+        return Err(Error("Glyph contours out of order".to_string()));
+      }
+      outline.add_quadratic_contour(&points[first..=end]);
+      first = end + 1;
+    }
+    Ok(())
+  }
+
+  /// A composite glyph is made of other glyphs, each moved and possibly
+  /// scaled. Components placed by matching points are left in place.
+  fn add_composite(&self, start: usize, outline: &mut Outline, depth: usize) -> Result<(), Error> {
+    let mut data = self.glyf.at(start + 10);
+    loop {
+      let (flags, glyph) = (data.u16()?, data.u16()?);
+      let (mut dx, mut dy) = if flags & ARGS_ARE_WORDS != 0 {
+        (data.i16()? as f32, data.i16()? as f32)
+      } else {
+        (data.u8()? as i8 as f32, data.u8()? as i8 as f32)
+      };
+      if flags & ARGS_ARE_OFFSETS == 0 {
+        dx = 0.0;
+        dy = 0.0;
+      }
+
+      let mut f2dot14 = || data.i16().map(|value| value as f32 / 16384.0);
+      let (a, b, c, d) = if flags & HAS_SCALE != 0 {
+        let scale = f2dot14()?;
+        (scale, 0.0, 0.0, scale)
+      } else if flags & HAS_X_AND_Y_SCALE != 0 {
+        (f2dot14()?, 0.0, 0.0, f2dot14()?)
+      } else if flags & HAS_TWO_BY_TWO != 0 {
+        (f2dot14()?, f2dot14()?, f2dot14()?, f2dot14()?)
+      } else {
+        (1.0, 0.0, 0.0, 1.0)
+      };
+
+      let mut component = Outline::default();
+      self.add_glyph(glyph, &mut component, depth + 1)?;
+      component.transform([a, b, c, d, dx, dy]);
+      outline.append(component);
+
+      if flags & MORE_COMPONENTS == 0 {
+        return Ok(());
+      }
+    }
+  }
+}
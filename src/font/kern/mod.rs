@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use helper::Error;
+use super::Reader;
+
+const PAIR_ADJUSTMENT: u16 = 2;
+const EXTENSION: u16 = 9;
+const X_ADVANCE: u16 = 0x0004;
+
+/// Pair kerning, from the pair adjustment lookups of the `kern` feature
+/// of `GPOS`, or else the format 0 subtables of a `kern` table. The
+/// adjustments of all lookups add up; within a lookup the first subtable
+/// that has the pair applies.
+#[derive(Debug, Default)]
+pub struct Kerning {
+  lookups: Vec<Vec<PairTable>>
+}
+
+#[derive(Debug)]
+enum PairTable {
+  Pairs(HashMap<(u16, u16), i16>),
+  Classes {
+    coverage: Coverage,
+    first: ClassDef,
+    second: ClassDef,
+    second_count: usize,
+    values: Vec<i16> // by first class, then second class
+  }
+}
+
+/// The glyphs a subtable applies to, as ranges.
+#[derive(Debug, Default)]
+struct Coverage(Vec<(u16, u16)>);
+
+/// The class of each glyph, as ranges; the rest are class 0.
+#[derive(Debug, Default)]
+struct ClassDef(Vec<(u16, u16, u16)>);
+
+impl Kerning {
+  pub(super) fn parse(gpos: Option<Reader>, kern: Option<Reader>) -> Kerning {
+    let kerning = gpos.and_then(|gpos| read_gpos(gpos).ok()).unwrap_or_default();
+    if !kerning.lookups.is_empty() {
+      return kerning;
+    }
+    kern.and_then(|kern| read_kern(kern).ok()).unwrap_or_default()
+  }
+
+  /// How much closer `right` goes after `left`, in font units.
+  pub fn get(&self, left: u16, right: u16) -> i16 {
+    self.lookups.iter()
+      .filter_map(|lookup| lookup.iter().find_map(|table| table.get(left, right)))
+      .fold(0, i16::saturating_add)
+  }
+}
+
+impl PairTable {
+  fn get(&self, left: u16, right: u16) -> Option<i16> {
+    match *self {
+      PairTable::Pairs(ref pairs) => pairs.get(&(left, right)).cloned(),
+      PairTable::Classes { ref coverage, ref first, ref second, second_count, ref values } => {
+        if !coverage.contains(left) {
+          return None;
+        }
+        let i = first.class(left) as usize * second_count + second.class(right) as usize;
+        values.get(i).cloned()
+      }
+    }
+  }
+}
+
+impl Coverage {
+  fn read(table: Reader, offset: usize) -> Result<Coverage, Error> {
+    let mut data = table.at(offset);
+    let (format, count) = (data.u16()?, data.u16()?);
+    let mut ranges = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+      ranges.push(match format {
+        1 => {
+          let glyph = data.u16()?;
+          (glyph, glyph)
+        },
+        2 => {
+          let range = (data.u16()?, data.u16()?);
+          data.skip(2); // the coverage index of its start
+          range
+        },
+        _ => return Err(Error(format!("Unknown coverage format {}", format)))
+      });
+    }
+    // ordered by glyph, as the format requires
+    Ok(Coverage(ranges))
+  }
+
+  fn contains(&self, glyph: u16) -> bool {
+    let i = self.0.partition_point(|&(_, end)| end < glyph);
+    self.0.get(i).is_some_and(|&(start, _)| start <= glyph)
+  }
+}
+
+impl ClassDef {
+  fn read(table: Reader, offset: usize) -> Result<ClassDef, Error> {
+    let mut data = table.at(offset);
+    let mut ranges = Vec::new();
+    match data.u16()? {
+      1 => {
+        let (start, count) = (data.u16()?, data.u16()?);
+        for i in 0..count {
+          let glyph = start.saturating_add(i);
+          ranges.push((glyph, glyph, data.u16()?));
+        }
+      },
+      2 => {
+        for _ in 0..data.u16()? {
+          ranges.push((data.u16()?, data.u16()?, data.u16()?));
+        }
+      },
+      format => return Err(Error(format!("Unknown class definition format {}", format)))
+    }
+    Ok(ClassDef(ranges))
+  }
+
+  fn class(&self, glyph: u16) -> u16 {
+    let i = self.0.partition_point(|&(_, end, _)| end < glyph);
+    self.0.get(i).filter(|&&(start, _, _)| start <= glyph).map_or(0, |&(_, _, class)| class)
+  }
+}
+
+fn read_gpos(gpos: Reader) -> Result<Kerning, Error> {
+  let (features, lookups) = (gpos.at(6).u16()? as usize, gpos.at(8).u16()? as usize);
+
+  // the lookups of every `kern` feature, whatever the script
+  let mut indices = Vec::new();
+  for i in 0..gpos.at(features).u16()? as usize {
+    let mut record = gpos.at(features + 2 + i * 6);
+    if &record.tag()? != b"kern" {
+      continue;
+    }
+    let feature = features + record.u16()? as usize;
+    for j in 0..gpos.at(feature + 2).u16()? as usize {
+      indices.push(gpos.at(feature + 4 + j * 2).u16()?);
+    }
+  }
+  indices.sort_unstable();
+  indices.dedup();
+
+  let mut kerning = Kerning::default();
+  for index in indices {
+    let lookup = lookups + gpos.at(lookups + 2 + index as usize * 2).u16()? as usize;
+    let kind = gpos.at(lookup).u16()?;
+    let mut tables = Vec::new();
+    for i in 0..gpos.at(lookup + 4).u16()? as usize {
+      let mut subtable = lookup + gpos.at(lookup + 6 + i * 2).u16()? as usize;
+      let mut kind = kind;
+      if kind == EXTENSION {
+        kind = gpos.at(subtable + 2).u16()?;
+        subtable += gpos.at(subtable + 4).u32()? as usize;
+      }
+      if kind == PAIR_ADJUSTMENT {
+        tables.push(read_pair_adjustment(gpos, subtable)?);
+      }
+    }
+    if !tables.is_empty() {
+      kerning.lookups.push(tables);
+    }
+  }
+  Ok(kerning)
+}
+
+/// A pair adjustment subtable, of which only the advance of the first
+/// glyph matters for kerning.
+fn read_pair_adjustment(gpos: Reader, offset: usize) -> Result<PairTable, Error> {
+  let mut data = gpos.at(offset);
+  let (format, coverage) = (data.u16()?, data.u16()? as usize);
+  let (first_format, second_format) = (data.u16()?, data.u16()?);
+  let size = |format: u16| (format & 0xFF).count_ones() as usize * 2;
+  let advance = (first_format & X_ADVANCE != 0).then(|| (first_format & 0x3).count_ones() as usize * 2);
+  let record = size(first_format) + size(second_format);
+  let coverage = Coverage::read(gpos, offset + coverage)?;
+
+  match format {
+    1 => {
+      // a pair set for each covered glyph, in order
+      let firsts: Vec<u16> = coverage.0.iter().flat_map(|&(start, end)| start..=end).collect();
+      let mut pairs = HashMap::new();
+      for i in 0..data.u16()? as usize {
+        let set = offset + gpos.at(offset + 10 + i * 2).u16()? as usize;
+        let first = match firsts.get(i) {
+          Some(&glyph) => glyph,
+          None => break
+        };
+        for j in 0..gpos.at(set).u16()? as usize {
+          let pair = set + 2 + j * (2 + record);
+          let second = gpos.at(pair).u16()?;
+          let value = match advance {
+            Some(advance) => gpos.at(pair + 2 + advance).i16()?,
+            None => 0
+          };
+          pairs.entry((first, second)).or_insert(value);
+        }
+      }
+      Ok(PairTable::Pairs(pairs))
+    },
+    2 => {
+      let (first, second) = (data.u16()? as usize, data.u16()? as usize);
+      let (first_count, second_count) = (data.u16()? as usize, data.u16()? as usize);
+      let mut values = Vec::with_capacity(first_count * second_count);
+      for i in 0..first_count * second_count {
+        values.push(match advance {
+          Some(advance) => data.at(offset + 16 + i * record + advance).i16()?,
+          None => 0
+        });
+      }
+      Ok(PairTable::Classes {
+        coverage,
+        first: ClassDef::read(gpos, offset + first)?,
+        second: ClassDef::read(gpos, offset + second)?,
+        second_count,
+        values
+      })
+    },
+    format => Err(Error(format!("Unknown pair adjustment format {}", format)))
+  }
+}
+
+/// The horizontal format 0 subtables of an OpenType `kern` table.
+fn read_kern(kern: Reader) -> Result<Kerning, Error> {
+  let mut kerning = Kerning::default();
+  if kern.at(0).u16()? != 0 {
+    return Ok(kerning); // the Apple version
+  }
+
+  let mut offset = 4;
+  for _ in 0..kern.at(2).u16()? {
+    let (length, coverage) = (kern.at(offset + 2).u16()? as usize, kern.at(offset + 4).u16()?);
+    // horizontal, not minimum values or cross-stream, and format 0
+    if coverage & 0x0007 == 0x0001 && coverage >> 8 == 0 {
+      let mut pairs = HashMap::new();
+      for i in 0..kern.at(offset + 6).u16()? as usize {
+        let mut pair = kern.at(offset + 14 + i * 6);
+        pairs.insert((pair.u16()?, pair.u16()?), pair.i16()?);
+      }
+      kerning.lookups.push(vec![PairTable::Pairs(pairs)]);
+    }
+    offset += length;
+  }
+  Ok(kerning)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use font::tests::be;
+
+  /// A `kern` table of one subtable of `pairs`.
+  pub fn kern(pairs: &[(i32, i32, i32)]) -> Vec<u8> {
+    let mut table = be(&[0, 1, 0, 14 + pairs.len() as i32 * 6, 1, pairs.len() as i32, 0, 0, 0]);
+    for &(left, right, value) in pairs.iter() {
+      table.extend(be(&[left, right, value]));
+    }
+    table
+  }
+
+  #[test]
+  fn reads_kerning() {
+    let table = kern(&[(1, 2, -50), (2, 1, 20)]);
+    let kerning = Kerning::parse(None, Some(Reader::new(&table)));
+    assert_eq!((kerning.get(1, 2), kerning.get(2, 1), kerning.get(1, 1)), (-50, 20, 0));
+
+    // a `kern` feature of a class based lookup inside an extension, and
+    // a glyph based one in another lookup, which add up
+    let mut gpos = be(&[1, 0, 0, 10, 26]);
+    gpos.extend(be(&[1, 0x6B65, 0x726E, 8, 0, 2, 0, 1])); // features
+    gpos.extend(be(&[2, 6, 68])); // lookups
+    gpos.extend(be(&[9, 0, 1, 8, 1, 2, 0, 8])); // the extension
+    gpos.extend(be(&[2, 24, 4, 0, 30, 38, 2, 2, 0, 0, 0, -30])); // by class
+    gpos.extend(be(&[1, 1, 2, 1, 2, 1, 1, 1, 2, 1, 1])); // coverage and classes
+    gpos.extend(be(&[2, 0, 1, 8])); // the lookup by glyph
+    gpos.extend(be(&[1, 12, 4, 0, 1, 18, 1, 1, 2, 1, 3, -5]));
+    let kerning = Kerning::parse(Some(Reader::new(&gpos)), Some(Reader::new(&table)));
+    assert_eq!((kerning.get(2, 2), kerning.get(2, 3), kerning.get(1, 2)), (-30, -5, 0));
+  }
+}
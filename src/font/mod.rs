@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use helper::Error;
+
+pub mod cmap;
+pub mod outline;
+pub mod glyf;
+pub mod cff;
+pub mod kern;
+pub mod raster;
+
+use self::cmap::Cmap;
+use self::cff::Cff;
+use self::glyf::Glyf;
+use self::kern::Kerning;
+use self::outline::Outline;
+use self::raster::Mask;
+
+/// A TrueType or OpenType font, read from the tables of an `sfnt` file,
+/// or the first font of a collection.
+#[derive(Debug)]
+pub struct Font {
+  data: Vec<u8>,
+  tables: HashMap<[u8; 4], Range<usize>>,
+  pub family: String,
+  pub weight: u16,
+  pub italic: bool,
+  pub units_per_em: u16,
+  pub ascender: i16, // above the baseline, in font units
+  pub descender: i16, // below it, so usually negative
+  pub line_gap: i16,
+  pub x_height: Option<i16>,
+  glyph_count: u16,
+  metrics_count: u16, // the glyphs with their own advance width in `hmtx`
+  long_offsets: bool, // of `loca`
+  cmap: Cmap,
+  cff: Option<Cff>, // for PostScript outlines, rather than TrueType ones
+  kerning: Kerning
+}
+
+impl Font {
+  pub fn open(path: &Path) -> Result<Font, Error> {
+    let data = fs::read(path)
+      .map_err(|e| Error(format!("Could not read font {}: {}", path.display(), e)))?;
+    Font::parse(data)
+  }
+
+  pub fn parse(data: Vec<u8>) -> Result<Font, Error> {
+    let tables = table_directory(&data)?;
+    let table = |tag: &[u8; 4]| table(&data, &tables, tag);
+
+    let head = table(b"head")?;
+    let units_per_em = head.at(18).u16()?;
+    if units_per_em == 0 {
+      return Err(Error("Font has no units per em".to_string()));
+    }
+    let mac_style = head.at(44).u16()?;
+    let (mut weight, mut italic) = (if mac_style & 1 != 0 { 700 } else { 400 }, mac_style & 2 != 0);
+
+    let hhea = table(b"hhea")?;
+    let (mut ascender, mut descender, mut line_gap) = (hhea.at(4).i16()?, hhea.at(6).i16()?, hhea.at(8).i16()?);
+    let mut x_height = None;
+    if let Ok(os2) = table(b"OS/2") {
+      let version = os2.at(0).u16()?;
+      weight = os2.at(4).u16()?;
+      let selection = os2.at(62).u16()?;
+      italic = selection & 1 != 0;
+      // USE_TYPO_METRICS
+      if selection & 0x80 != 0 {
+        ascender = os2.at(68).i16()?;
+        descender = os2.at(70).i16()?;
+        line_gap = os2.at(72).i16()?;
+      }
+      if version >= 2 {
+        x_height = Some(os2.at(86).i16()?).filter(|&height| height > 0);
+      }
+    }
+
+    let name = table(b"name").ok();
+    let family = name.and_then(|name| read_name(name, 16).or_else(|| read_name(name, 1))).unwrap_or_default();
+    let glyph_count = table(b"maxp")?.at(4).u16()?;
+    let metrics_count = hhea.at(34).u16()?;
+    let cmap = Cmap::parse(table(b"cmap")?)?;
+    let long_offsets = head.at(50).i16()? == 1;
+    let cff = match table(b"CFF ") {
+      Ok(cff) => Some(Cff::parse(cff, glyph_count)?),
+      Err(_) => None
+    };
+    let kerning = Kerning::parse(table(b"GPOS").ok(), table(b"kern").ok());
+
+    Ok(Font {
+      data,
+      tables,
+      family,
+      weight,
+      italic,
+      units_per_em,
+      ascender,
+      descender,
+      line_gap,
+      x_height,
+      glyph_count,
+      metrics_count,
+      long_offsets,
+      cmap,
+      cff,
+      kerning
+    })
+  }
+
+  fn table(&self, tag: &[u8; 4]) -> Result<Reader<'_>, Error> {
+    table(&self.data, &self.tables, tag)
+  }
+
+  /// The glyph `c` maps to, `None` if the font does not have one.
+  pub fn glyph_index(&self, c: char) -> Option<u16> {
+    self.cmap.glyph_index(c).filter(|&glyph| glyph != 0 && glyph < self.glyph_count)
+  }
+
+  /// How far a glyph advances the pen, in font units.
+  pub fn advance_width(&self, glyph: u16) -> u16 {
+    let hmtx = match self.table(b"hmtx") {
+      Ok(hmtx) => hmtx,
+      Err(_) => return 0
+    };
+    // glyphs past the last metric share its advance
+    let index = glyph.min(self.metrics_count.saturating_sub(1)) as usize;
+    hmtx.at(index * 4).u16().unwrap_or(0)
+  }
+
+  /// The adjustment to the advance of `left` before `right`, in font
+  /// units; negative to move them closer.
+  pub fn kerning(&self, left: u16, right: u16) -> i16 {
+    self.kerning.get(left, right)
+  }
+
+  pub fn outline(&self, glyph: u16) -> Result<Outline, Error> {
+    if glyph >= self.glyph_count {
+      return Err(Error(format!("Font has no glyph {}", glyph)));
+    }
+    match self.cff {
+      Some(ref cff) => cff.outline(self.table(b"CFF ")?, glyph),
+      None => Glyf {
+        glyf: self.table(b"glyf")?,
+        loca: self.table(b"loca")?,
+        long_offsets: self.long_offsets
+      }.outline(glyph)
+    }
+  }
+
+  /// The coverage mask of a glyph at `size` pixels per em.
+  pub fn rasterize(&self, glyph: u16, size: f32) -> Result<Mask, Error> {
+    Ok(raster::rasterize(&self.outline(glyph)?, size / self.units_per_em as f32))
+  }
+}
+
+fn table<'d>(data: &'d [u8], tables: &HashMap<[u8; 4], Range<usize>>, tag: &[u8; 4]) -> Result<Reader<'d>, Error> {
+  let range = tables.get(tag)
+    .ok_or_else(|| Error(format!("Font has no {} table", String::from_utf8_lossy(tag))))?;
+  Ok(Reader::new(&data[range.clone()]))
+}
+
+/// A string of the `name` table, preferring the Windows Unicode ones.
+fn read_name(name: Reader, id: u16) -> Option<String> {
+  let (count, strings) = (name.at(2).u16().ok()?, name.at(4).u16().ok()? as usize);
+
+  let mut fallback = None;
+  for i in 0..count as usize {
+    let mut record = name.at(6 + i * 12);
+    let (platform, _encoding, _language, name_id) = (record.u16().ok()?, record.u16().ok()?, record.u16().ok()?, record.u16().ok()?);
+    let (length, offset) = (record.u16().ok()? as usize, record.u16().ok()? as usize);
+    if name_id != id {
+      continue;
+    }
+
+    let bytes = name.bytes(strings + offset, length).ok()?;
+    match platform {
+      0 | 3 => {
+        let units: Vec<u16> = bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])).collect();
+        return Some(String::from_utf16_lossy(&units));
+      },
+      1 => fallback = Some(bytes.iter().map(|&byte| byte as char).collect()),
+      _ => ()
+    }
+  }
+  fallback
+}
+
+/// Where each table is, by tag.
+fn table_directory(data: &[u8]) -> Result<HashMap<[u8; 4], Range<usize>>, Error> {
+  let mut header = Reader::new(data);
+  let start = match &header.tag()? {
+    b"ttcf" => header.at(12).u32()? as usize,
+    _ => 0
+  };
+
+  let mut directory = Reader::new(data).at(start);
+  match &directory.tag()? {
+    &[0, 1, 0, 0] | b"OTTO" | b"true" => (),
+    tag => return Err(Error(format!("Not a TrueType or OpenType font: {:?}", tag)))
+  }
+  let count = directory.u16()?;
+  directory.skip(6);
+
+  let mut tables = HashMap::new();
+  for _ in 0..count {
+    let tag = directory.tag()?;
+    directory.skip(4); // checksum
+    let (offset, length) = (directory.u32()? as usize, directory.u32()? as usize);
+    if offset.checked_add(length).is_none_or(|end| end > data.len()) {
+      return Err(Error(format!("Font table {} out of bounds", String::from_utf8_lossy(&tag))));
+    }
+    tables.insert(tag, offset..offset + length);
+  }
+  Ok(tables)
+}
+
+/// Reads big-endian values from font data, failing past the end.
+#[derive(Debug, Clone, Copy)]
+struct Reader<'d> {
+  data: &'d [u8],
+  offset: usize
+}
+
+impl<'d> Reader<'d> {
+  fn new(data: &'d [u8]) -> Self {
+    Reader {
+      data,
+      offset: 0
+    }
+  }
+
+  fn at(&self, offset: usize) -> Self {
+    Reader {
+      data: self.data,
+      offset
+    }
+  }
+
+  fn skip(&mut self, count: usize) {
+    self.offset += count;
+  }
+
+  fn bytes(&self, offset: usize, length: usize) -> Result<&'d [u8], Error> {
+    self.data.get(offset..offset + length)
+      .ok_or_else(|| Error("Unexpected end of font data".to_string()))
+  }
+
+  fn read<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+    let bytes = self.bytes(self.offset, N)?;
+    self.offset += N;
+    let mut array = [0; N];
+    array.copy_from_slice(bytes);
+    Ok(array)
+  }
+
+  fn tag(&mut self) -> Result<[u8; 4], Error> {
+    self.read()
+  }
+
+  fn u8(&mut self) -> Result<u8, Error> {
+    self.read().map(u8::from_be_bytes)
+  }
+
+  fn u16(&mut self) -> Result<u16, Error> {
+    self.read().map(u16::from_be_bytes)
+  }
+
+  fn i16(&mut self) -> Result<i16, Error> {
+    self.read().map(i16::from_be_bytes)
+  }
+
+  fn u32(&mut self) -> Result<u32, Error> {
+    self.read().map(u32::from_be_bytes)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use self::outline::Point;
+
+  /// The tables of an `sfnt` file, in tag order.
+  pub fn sfnt(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut data = vec![0, 1, 0, 0];
+    data.extend((tables.len() as u16).to_be_bytes());
+    data.extend([0; 6]);
+
+    let mut offset = 12 + tables.len() * 16;
+    let mut bodies = Vec::new();
+    for &(tag, ref table) in tables.iter() {
+      data.extend(tag);
+      data.extend([0; 4]);
+      data.extend((offset as u32).to_be_bytes());
+      data.extend((table.len() as u32).to_be_bytes());
+
+      let mut body = table.clone();
+      body.resize(table.len().next_multiple_of(4), 0);
+      offset += body.len();
+      bodies.extend(body);
+    }
+    data.extend(bodies);
+    data
+  }
+
+  pub fn be(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|&value| (value as u16).to_be_bytes()).collect()
+  }
+
+  /// A font with .notdef, `a`, `b` and a space, 600, 400 and 250 units
+  /// wide in 1000 units per em, called "Test Sans" in `name`. `a` is a
+  /// square, `b` the square halved and moved, and `a` kerns with `b`.
+  pub fn test_font() -> Vec<u8> {
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+
+    let mut hhea = be(&[1, 0, 800, -200, 100]);
+    hhea.resize(34, 0);
+    hhea.extend(be(&[4]));
+
+    let mut os2 = be(&[2, 0, 400]);
+    os2.resize(62, 0);
+    os2.extend(be(&[0x40]));
+    os2.resize(86, 0);
+    os2.extend(be(&[500]));
+    os2.resize(96, 0);
+
+    let family: Vec<i32> = "Test Sans".encode_utf16().map(|unit| unit as i32).collect();
+    let mut name = be(&[0, 1, 18, 3, 1, 0x409, 1, family.len() as i32 * 2, 0]);
+    name.extend(be(&family));
+
+    sfnt(&[
+      (b"OS/2", os2),
+      (b"cmap", cmap::tests::format_4(&[(0x20, 0x20, 3), (0x61, 0x62, 1)])),
+      (b"glyf", [
+        // one contour of four on-curve points, the flag repeated
+        be(&[1, 100, 0, 500, 500, 3, 0, 0x0903, 100, 400, 0, -400, 0, 0, 500, 0]),
+        // glyph 1 moved by words and scaled by a half
+        be(&[-1, 0, 0, 0, 0, 0x000B, 1, 50, 100, 0x2000])
+      ].concat()),
+      (b"head", head),
+      (b"hhea", hhea),
+      (b"hmtx", be(&[500, 0, 600, 0, 400, 0, 250, 0])),
+      (b"kern", kern::tests::kern(&[(1, 2, -50)])),
+      (b"loca", be(&[0, 0, 16, 26, 26])),
+      (b"maxp", be(&[0, 0x5000, 4])),
+      (b"name", name)
+    ])
+  }
+
+  #[test]
+  fn reads_metrics() {
+    let font = Font::parse(test_font()).unwrap();
+    assert_eq!(font.family, "Test Sans");
+    assert_eq!((font.weight, font.italic), (400, false));
+    assert_eq!((font.units_per_em, font.ascender, font.descender, font.line_gap), (1000, 800, -200, 100));
+    assert_eq!(font.x_height, Some(500));
+
+    let glyphs: Vec<Option<u16>> = "ab c".chars().map(|c| font.glyph_index(c)).collect();
+    assert_eq!(glyphs, vec![Some(1), Some(2), Some(3), None]);
+    assert_eq!((font.advance_width(1), font.advance_width(3)), (600, 250));
+
+    assert_eq!((font.kerning(1, 2), font.kerning(2, 1)), (-50, 0));
+
+    let outline = font.outline(1).unwrap();
+    assert_eq!(outline.bounds(), Some((Point::new(100.0, 0.0), Point::new(500.0, 500.0))));
+    let outline = font.outline(2).unwrap();
+    assert_eq!(outline.bounds(), Some((Point::new(100.0, 100.0), Point::new(300.0, 350.0))));
+    assert!(font.outline(3).unwrap().is_empty());
+    assert!(font.outline(4).is_err());
+
+    let mask = font.rasterize(1, 10.0).unwrap();
+    assert_eq!((mask.width, mask.height, mask.left, mask.top), (4, 5, 1, 5));
+    assert!(mask.coverage.iter().all(|&coverage| coverage == 255));
+
+    assert!(Font::parse(b"not a font".to_vec()).is_err());
+    assert!(Font::parse(sfnt(&[(b"head", vec![0; 54])])).is_err());
+  }
+}
@@ -0,0 +1,115 @@
+/// A point of an outline, in font units with y up.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Point {
+  pub x: f32,
+  pub y: f32
+}
+
+impl Point {
+  pub fn new(x: f32, y: f32) -> Self {
+    Point {
+      x,
+      y
+    }
+  }
+
+  fn midpoint(self, other: Point) -> Point {
+    Point::new((self.x + other.x) / 2.0, (self.y + other.y) / 2.0)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+  MoveTo(Point),
+  LineTo(Point),
+  QuadTo(Point, Point), // control point, end
+  CurveTo(Point, Point, Point), // two control points, end
+  Close
+}
+
+/// The outline of a glyph: closed contours of lines and quadratic
+/// (TrueType) or cubic (CFF) Bézier curves.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Outline {
+  pub commands: Vec<Command>
+}
+
+impl Outline {
+  pub fn is_empty(&self) -> bool {
+    self.commands.is_empty()
+  }
+
+  /// The smallest rectangle around all points, control points included,
+  /// as `(min, max)`.
+  pub fn bounds(&self) -> Option<(Point, Point)> {
+    let mut points = self.commands.iter().flat_map(|command| match *command {
+      Command::MoveTo(p) | Command::LineTo(p) => vec![p],
+      Command::QuadTo(c, p) => vec![c, p],
+      Command::CurveTo(c1, c2, p) => vec![c1, c2, p],
+      Command::Close => vec![]
+    });
+    let first = points.next()?;
+    Some(points.fold((first, first), |(min, max), p| {
+      (Point::new(min.x.min(p.x), min.y.min(p.y)), Point::new(max.x.max(p.x), max.y.max(p.y)))
+    }))
+  }
+
+  /// Applies the affine transform `[a, b, c, d, e, f]`, which maps (x, y)
+  /// to (ax + cy + e, bx + dy + f).
+  pub fn transform(&mut self, matrix: [f32; 6]) {
+    let [a, b, c, d, e, f] = matrix;
+    let map = |p: &mut Point| *p = Point::new(a * p.x + c * p.y + e, b * p.x + d * p.y + f);
+    for command in self.commands.iter_mut() {
+      match *command {
+        Command::MoveTo(ref mut p) | Command::LineTo(ref mut p) => map(p),
+        Command::QuadTo(ref mut c, ref mut p) => {
+          map(c);
+          map(p);
+        },
+        Command::CurveTo(ref mut c1, ref mut c2, ref mut p) => {
+          map(c1);
+          map(c2);
+          map(p);
+        },
+        Command::Close => ()
+      }
+    }
+  }
+
+  pub fn append(&mut self, other: Outline) {
+    self.commands.extend(other.commands);
+  }
+
+  /// Adds a TrueType contour: on-curve points and the off-curve control
+  /// points between them, where two control points in a row imply an
+  /// on-curve point halfway.
+  pub fn add_quadratic_contour(&mut self, points: &[(Point, bool)]) {
+    let (&(first, first_on), &(last, last_on)) = match (points.first(), points.last()) {
+      (Some(first), Some(last)) => (first, last),
+      _ => return
+    };
+    let (start, rest) = match (first_on, last_on) {
+      (true, _) => (first, &points[1..]),
+      (false, true) => (last, &points[..points.len() - 1]),
+      (false, false) => (first.midpoint(last), points)
+    };
+    self.commands.push(Command::MoveTo(start));
+
+    let mut control: Option<Point> = None;
+    for &(point, on_curve) in rest.iter().chain(std::iter::once(&(start, true))) {
+      match (control, on_curve) {
+        (Some(c), true) => {
+          self.commands.push(Command::QuadTo(c, point));
+          control = None;
+        },
+        (Some(c), false) => {
+          self.commands.push(Command::QuadTo(c, c.midpoint(point)));
+          control = Some(point);
+        },
+        (None, true) => self.commands.push(Command::LineTo(point)),
+        (None, false) => control = Some(point)
+      }
+    }
+    self.commands.push(Command::Close);
+  }
+}
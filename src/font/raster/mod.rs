@@ -0,0 +1,233 @@
+use super::outline::{Command, Outline, Point};
+
+/// An anti-aliased glyph image: how much of each pixel the outline
+/// covers, from 0 to 255, in rows from the top. `left` and `top` place
+/// its top left corner relative to the pen position on the baseline,
+/// with `top` up.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mask {
+  pub width: usize,
+  pub height: usize,
+  pub left: i32,
+  pub top: i32,
+  pub coverage: Vec<u8>
+}
+
+impl Mask {
+  pub fn get(&self, x: usize, y: usize) -> u8 {
+    self.coverage[y * self.width + x]
+  }
+}
+
+/// Renders `outline` at `scale` pixels per font unit. Coverage is the
+/// exact area of each pixel inside the flattened outline under the
+/// non-zero rule, for outlines that do not overlap themselves.
+pub fn rasterize(outline: &Outline, scale: f32) -> Mask {
+  let (min, max) = match outline.bounds() {
+    Some(bounds) => bounds,
+    None => return Mask::default()
+  };
+  let left = (min.x * scale).floor() as i32;
+  let top = (max.y * scale).ceil() as i32;
+  let width = ((max.x * scale).ceil() as i32 - left).max(0) as usize;
+  let height = (top - (min.y * scale).floor() as i32).max(0) as usize;
+
+  let mut raster = Raster {
+    width,
+    height,
+    areas: vec![0.0; width * height + 2]
+  };
+  // to pixels, with y down from the top of the mask
+  let to_pixels = |p: Point| Point::new(p.x * scale - left as f32, top as f32 - p.y * scale);
+
+  let (mut start, mut current) = (Point::default(), Point::default());
+  for command in outline.commands.iter() {
+    match *command {
+      Command::MoveTo(p) => {
+        raster.line(current, start);
+        start = to_pixels(p);
+        current = start;
+      },
+      Command::LineTo(p) => {
+        let p = to_pixels(p);
+        raster.line(current, p);
+        current = p;
+      },
+      Command::QuadTo(c, p) => {
+        let (c, p) = (to_pixels(c), to_pixels(p));
+        let deviation = length(current.x - 2.0 * c.x + p.x, current.y - 2.0 * c.y + p.y);
+        let segments = segments(deviation);
+        let mut last = current;
+        for i in 1..=segments {
+          let t = i as f32 / segments as f32;
+          let u = 1.0 - t;
+          let next = Point::new(
+            u * u * current.x + 2.0 * u * t * c.x + t * t * p.x,
+            u * u * current.y + 2.0 * u * t * c.y + t * t * p.y
+          );
+          raster.line(last, next);
+          last = next;
+        }
+        current = p;
+      },
+      Command::CurveTo(c1, c2, p) => {
+        let (c1, c2, p) = (to_pixels(c1), to_pixels(c2), to_pixels(p));
+        let deviation = length(current.x - 2.0 * c1.x + c2.x, current.y - 2.0 * c1.y + c2.y)
+          .max(length(c1.x - 2.0 * c2.x + p.x, c1.y - 2.0 * c2.y + p.y));
+        let segments = segments(deviation * 1.5);
+        let mut last = current;
+        for i in 1..=segments {
+          let t = i as f32 / segments as f32;
+          let u = 1.0 - t;
+          let next = Point::new(
+            u * u * u * current.x + 3.0 * u * u * t * c1.x + 3.0 * u * t * t * c2.x + t * t * t * p.x,
+            u * u * u * current.y + 3.0 * u * u * t * c1.y + 3.0 * u * t * t * c2.y + t * t * t * p.y
+          );
+          raster.line(last, next);
+          last = next;
+        }
+        current = p;
+      },
+      Command::Close => {
+        raster.line(current, start);
+        current = start;
+      }
+    }
+  }
+  raster.line(current, start);
+
+  let mut accumulated = 0.0;
+  let coverage = raster.areas.iter().take(width * height).map(|area| {
+    accumulated += area;
+    (accumulated.abs().min(1.0) * 255.0).round() as u8
+  }).collect();
+  Mask {
+    width,
+    height,
+    left,
+    top,
+    coverage
+  }
+}
+
+fn length(x: f32, y: f32) -> f32 {
+  (x * x + y * y).sqrt()
+}
+
+/// How many lines a curve whose control points stray `deviation` pixels
+/// flattens into, to stay within a twentieth of a pixel of it.
+fn segments(deviation: f32) -> usize {
+  ((deviation * 2.5).sqrt().ceil() as usize).clamp(1, 64)
+}
+
+/// Signed area changes along each row, which add up from left to right
+/// into the coverage of each pixel.
+struct Raster {
+  width: usize,
+  height: usize,
+  areas: Vec<f32>
+}
+
+impl Raster {
+  fn line(&mut self, from: Point, to: Point) {
+    if (from.y - to.y).abs() < f32::EPSILON {
+      return;
+    }
+    let (direction, top, bottom) = if from.y < to.y { (1.0, from, to) } else { (-1.0, to, from) };
+    let dxdy = (bottom.x - top.x) / (bottom.y - top.y);
+    let max_x = self.width as f32;
+
+    let first_row = top.y.max(0.0) as usize;
+    let last_row = (bottom.y.ceil().max(0.0) as usize).min(self.height);
+    let mut x = top.x + (first_row as f32 - top.y).max(0.0) * dxdy;
+    for row in first_row..last_row {
+      let start = row * self.width;
+      let dy = (row as f32 + 1.0).min(bottom.y) - (row as f32).max(top.y);
+      let next_x = x + dxdy * dy;
+      let d = dy * direction;
+
+      let (x0, x1) = if x < next_x { (x, next_x) } else { (next_x, x) };
+      let (x0, x1) = (x0.clamp(0.0, max_x), x1.clamp(0.0, max_x));
+      let x0_floor = x0.floor();
+      let (x0i, x1i) = (x0_floor as usize, x1.ceil() as usize);
+
+      if x1i <= x0i + 1 {
+        // within one pixel, which the line covers up to its midpoint
+        let middle = 0.5 * (x0 + x1) - x0_floor;
+        self.areas[start + x0i] += d * (1.0 - middle);
+        self.areas[start + x0i + 1] += d * middle;
+      } else {
+        // across pixels: a triangle in the first, a trapezoid in those
+        // between and the rest of the triangle in the last
+        let slope = (x1 - x0).recip();
+        let x0_fraction = x0 - x0_floor;
+        let first = 0.5 * slope * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+        let x1_fraction = x1 - x1.ceil() + 1.0;
+        let last = 0.5 * slope * x1_fraction * x1_fraction;
+
+        self.areas[start + x0i] += d * first;
+        if x1i == x0i + 2 {
+          self.areas[start + x0i + 1] += d * (1.0 - first - last);
+        } else {
+          let second = slope * (1.5 - x0_fraction);
+          self.areas[start + x0i + 1] += d * (second - first);
+          for xi in x0i + 2..x1i - 1 {
+            self.areas[start + xi] += d * slope;
+          }
+          let before_last = second + (x1i - x0i - 3) as f32 * slope;
+          self.areas[start + x1i - 1] += d * (1.0 - before_last - last);
+        }
+        self.areas[start + x1i] += d * last;
+      }
+      x = next_x;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rectangle(x0: f32, y0: f32, x1: f32, y1: f32) -> Outline {
+    let mut outline = Outline::default();
+    let points: Vec<(Point, bool)> = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)].iter()
+      .map(|&(x, y)| (Point::new(x, y), true))
+      .collect();
+    outline.add_quadratic_contour(&points);
+    outline
+  }
+
+  #[test]
+  fn rasterizes_outlines() {
+    let mask = rasterize(&rectangle(100.0, -100.0, 400.0, 200.0), 0.01);
+    assert_eq!((mask.width, mask.height, mask.left, mask.top), (3, 3, 1, 2));
+    assert!(mask.coverage.iter().all(|&coverage| coverage == 255));
+
+    // half a pixel in from each side horizontally
+    let mask = rasterize(&rectangle(50.0, 0.0, 250.0, 100.0), 0.01);
+    assert_eq!(mask.coverage, vec![128, 255, 128]);
+
+    // a diamond covers half of each pixel it cuts across
+    let mut diamond = Outline::default();
+    let points: Vec<(Point, bool)> = [(1.0, 0.0), (2.0, 1.0), (1.0, 2.0), (0.0, 1.0)].iter()
+      .map(|&(x, y)| (Point::new(x, y), true))
+      .collect();
+    diamond.add_quadratic_contour(&points);
+    assert_eq!(rasterize(&diamond, 1.0).coverage, vec![128, 128, 128, 128]);
+
+    // a circle of quadratic curves covers about pi r squared, less the
+    // slivers between its curves and the lines they flatten into
+    let mut circle = Outline::default();
+    let points: Vec<(Point, bool)> = (0..16).map(|i| {
+      let angle = i as f32 * std::f32::consts::PI / 8.0;
+      let radius = if i % 2 == 0 { 10.0 } else { 10.0 / (std::f32::consts::PI / 8.0).cos() };
+      (Point::new(radius * angle.cos(), radius * angle.sin()), i % 2 == 0)
+    }).collect();
+    circle.add_quadratic_contour(&points);
+    let mask = rasterize(&circle, 1.0);
+    let area: f32 = mask.coverage.iter().map(|&coverage| coverage as f32 / 255.0).sum();
+    assert!(area < 314.16 && area > 311.0, "{}", area);
+    // rounding can put control points a hair past the circle
+    assert!((20..=22).contains(&mask.width) && (20..=22).contains(&mask.height));
+  }
+}
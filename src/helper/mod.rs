@@ -1,6 +1,3 @@
-#![macro_escape]
-
-#[macro_use]
 macro_rules! map(
   { $($key:expr => $value:expr),+ } => {
     {
@@ -27,7 +24,7 @@ pub fn pre_lex(query: String) -> Result<Vec<PreLexed>, Error> {
   let mut buf: String = String::new();
   let mut is_string = false;
 
-  for (_i, c) in query.chars().enumerate() {
+  for c in query.chars() {
     match c {
       '"' => {
         if is_string {
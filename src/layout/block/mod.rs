@@ -0,0 +1,451 @@
+use style::computed::{BoxSizing, ComputedStyle, Display, Float, LengthPercentage, LengthPercentageOrAuto, Overflow, Position, Px};
+use super::{LayoutBox, BoxType, LayoutContext};
+use super::box_model::ContainingBlock;
+use super::float::Floats;
+
+/// Vertical margins that adjoin and so collapse into one margin: the
+/// largest positive one plus the most negative one, per CSS 2.1 §8.3.1.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CollapsedMargin {
+  positive: Px,
+  negative: Px
+}
+
+impl CollapsedMargin {
+  pub fn new(margin: Px) -> Self {
+    let mut collapsed = Self::default();
+    collapsed.adjoin(margin);
+    collapsed
+  }
+
+  pub fn adjoin(&mut self, margin: Px) {
+    self.positive = self.positive.max(margin);
+    self.negative = self.negative.min(margin);
+  }
+
+  pub fn resolve(&self) -> Px {
+    self.positive + self.negative
+  }
+}
+
+/// Where the next block-level box in normal flow goes: below `y`, the
+/// bottom of the border box before it, after the `margin` that adjoins
+/// its top margin.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FlowPosition {
+  pub y: Px,
+  pub margin: CollapsedMargin
+}
+
+impl FlowPosition {
+  pub fn new(y: Px) -> Self {
+    FlowPosition {
+      y,
+      margin: CollapsedMargin::default()
+    }
+  }
+}
+
+impl<'a> LayoutBox<'a> {
+  /// Lays out the root element, which establishes the initial block
+  /// formatting context, at the top of `containing_block`.
+  pub fn layout_root(&mut self, containing_block: &ContainingBlock, context: &LayoutContext) {
+    self.layout_in_flow(containing_block, &mut FlowPosition::new(containing_block.y), true, false, &mut Floats::new(), context);
+    self.position_relative(containing_block);
+  }
+
+  /// Lays out a block-level box in normal flow and its descendants at
+  /// `position`, which moves below it. Returns whether its top and bottom
+  /// margins adjoin, so that the margins around it collapse through it.
+  /// `floats` are those of the block formatting context it is in.
+  pub fn layout_block(&mut self, containing_block: &ContainingBlock, position: &mut FlowPosition, floats: &mut Floats, context: &LayoutContext) -> bool {
+    let independent = self.establishes_formatting_context();
+    if independent && !floats.is_empty() {
+      self.layout_beside_floats(containing_block, position, floats, context);
+      return false;
+    }
+    self.layout_in_flow(containing_block, position, independent, false, floats, context)
+  }
+
+  /// Lays out an atomic inline-level box with its margin box at the
+  /// origin, for the line it goes on to move it into place.
+  pub(super) fn layout_atomic(&mut self, containing_block: &ContainingBlock, context: &LayoutContext) {
+    let origin = ContainingBlock {
+      x: 0.0,
+      y: 0.0,
+      ..*containing_block
+    };
+    self.layout_in_flow(&origin, &mut FlowPosition::new(0.0), true, true, &mut Floats::new(), context);
+  }
+
+  /// The margins of a box that establishes a block formatting context,
+  /// `independent`, do not collapse with those of its children, and the
+  /// floats inside it are its own rather than among `floats`.
+  pub(super) fn layout_in_flow(&mut self, containing_block: &ContainingBlock, position: &mut FlowPosition, independent: bool, shrink_to_fit: bool, floats: &mut Floats, context: &LayoutContext) -> bool {
+    let style = self.box_style();
+    self.calculate_block_width(style, containing_block, shrink_to_fit, context);
+    self.layout_contents(containing_block, position, independent, floats, context)
+  }
+
+  /// Lays out a box whose width and horizontal edges are known: its
+  /// vertical edges, what is in it and then its height, from `position`.
+  pub(super) fn layout_contents(&mut self, containing_block: &ContainingBlock, position: &mut FlowPosition, independent: bool, floats: &mut Floats, context: &LayoutContext) -> bool {
+    let style = self.box_style();
+    self.calculate_vertical_edges(style, containing_block);
+
+    let d = self.dimensions;
+    let edges = d.padding.vertical() + d.border.vertical();
+    let height = style.and_then(|style| used_height(style, containing_block, edges));
+    let clamp = |height: Px| match style {
+      Some(style) => clamp_height(style, containing_block, height, edges),
+      None => height
+    };
+
+    // the top margin collapses with that of the first child unless a
+    // border or padding is in between, and the bottom one with that of
+    // the last child if the height also depends on the content
+    let collapse_top = !independent && d.border.top == 0.0 && d.padding.top == 0.0;
+    let collapse_bottom = !independent && d.border.bottom == 0.0 && d.padding.bottom == 0.0
+      && style.is_none_or(|style| style.height.is_auto());
+
+    let mut margin = position.margin;
+    margin.adjoin(d.margin.top);
+    let mut inner = FlowPosition::new(position.y);
+    if collapse_top {
+      inner.margin = margin;
+    } else {
+      inner.y += margin.resolve() + d.border.top + d.padding.top;
+    }
+
+    let inner_block = ContainingBlock {
+      x: d.content.x,
+      y: inner.y,
+      width: d.content.width,
+      height
+    };
+    let mut own_floats = Floats::new();
+    let floats = if independent { &mut own_floats } else { floats };
+    let first = if self.has_inline_content() {
+      self.layout_lines(&inner_block, &mut inner, floats, context)
+    } else {
+      self.layout_block_children(&inner_block, &mut inner, floats, context)
+    };
+
+    let content_top = match first {
+      _ if !collapse_top => position.y + margin.resolve() + d.border.top + d.padding.top,
+      Some(top) => top,
+      None if collapse_bottom && clamp(0.0) == 0.0 => {
+        // empty, so the margins collapse through it
+        self.dimensions.content.y = position.y + margin.resolve();
+        self.dimensions.content.height = 0.0;
+        position.margin = inner.margin;
+        position.margin.adjoin(d.margin.bottom);
+        return true;
+      },
+      None => {
+        let top = position.y + inner.margin.resolve();
+        inner = FlowPosition::new(top);
+        top
+      }
+    };
+
+    let mut content_height = if collapse_bottom {
+      inner.y - content_top
+    } else {
+      inner.y + inner.margin.resolve() - content_top
+    };
+    if independent {
+      content_height = floats.bottom().map_or(content_height, |bottom| content_height.max(bottom - content_top));
+    }
+    self.dimensions.content.y = content_top;
+    self.dimensions.content.height = height.unwrap_or_else(|| clamp(content_height));
+
+    position.y = self.dimensions.border_box().bottom();
+    position.margin = if collapse_bottom {
+      inner.margin
+    } else {
+      CollapsedMargin::default()
+    };
+    position.margin.adjoin(d.margin.bottom);
+    false
+  }
+
+  /// Whether the box establishes a new block formatting context for its
+  /// contents, per CSS 2.1 §9.4.1.
+  pub(super) fn establishes_formatting_context(&self) -> bool {
+    match self.box_style() {
+      Some(style) => style.float != Float::None
+        || matches!(style.position, Position::Absolute | Position::Fixed)
+        || style.overflow != Overflow::Visible
+        || matches!(style.display, Display::FlowRoot | Display::InlineBlock | Display::Table | Display::TableCell
+          | Display::TableCaption | Display::Flex | Display::Grid),
+      None => false
+    }
+  }
+
+  /// The style the box model of the box comes from, `None` for anonymous
+  /// boxes whose margins, borders and padding are all zero.
+  pub(super) fn box_style(&self) -> Option<&'a ComputedStyle> {
+    match self.box_type {
+      BoxType::AnonymousBlock(_) | BoxType::AnonymousInline(_) => None,
+      _ => Some(self.style())
+    }
+  }
+
+  /// The used width, horizontal edges and position, per CSS 2.1 §10.3.3,
+  /// or §10.3.9 for a `shrink_to_fit` inline-block, and the limits of
+  /// §10.4.
+  fn calculate_block_width(&mut self, style: Option<&ComputedStyle>, containing_block: &ContainingBlock, shrink_to_fit: bool, context: &LayoutContext) {
+    let style = match style {
+      Some(style) => style,
+      None => {
+        self.dimensions.content.x = containing_block.x;
+        self.dimensions.content.width = containing_block.width;
+        return;
+      }
+    };
+
+    let (min_content, max_content) = match shrink_to_fit && style.width.is_auto() {
+      true => self.intrinsic_widths(context),
+      false => (0.0, 0.0)
+    };
+
+    let basis = containing_block.width;
+    let d = &mut self.dimensions;
+    d.padding.left = style.padding.left.resolve(basis);
+    d.padding.right = style.padding.right.resolve(basis);
+    d.border.left = style.border_width.left;
+    d.border.right = style.border_width.right;
+
+    let edges = d.padding.horizontal() + d.border.horizontal();
+    let content = |length: &LengthPercentage| content_size(style.box_sizing, length.resolve(basis), edges);
+    let (margin_left, margin_right) = (style.margin.left.resolve(basis), style.margin.right.resolve(basis));
+    let solve = |width: Option<Px>| match shrink_to_fit {
+      // auto margins of an inline-block are zero
+      true => {
+        let (left, right) = (margin_left.unwrap_or(0.0), margin_right.unwrap_or(0.0));
+        let available = basis - left - right - edges;
+        (width.unwrap_or_else(|| max_content.min(available.max(min_content))), left, right)
+      },
+      false => solve_width(width, margin_left, margin_right, edges, basis)
+    };
+
+    let mut used = solve(style.width.resolve(basis).map(|width| content_size(style.box_sizing, width, edges)));
+    if let Some(max) = style.max_width.as_ref().map(&content) {
+      if used.0 > max {
+        used = solve(Some(max));
+      }
+    }
+    let min = content(&style.min_width);
+    if used.0 < min {
+      used = solve(Some(min));
+    }
+
+    let (width, margin_left, margin_right) = used;
+    d.content.width = width;
+    d.margin.left = margin_left;
+    d.margin.right = margin_right;
+    d.content.x = containing_block.x + margin_left + d.border.left + d.padding.left;
+  }
+
+  /// The vertical edges; their percentages resolve against the width of
+  /// the containing block too.
+  fn calculate_vertical_edges(&mut self, style: Option<&ComputedStyle>, containing_block: &ContainingBlock) {
+    let basis = containing_block.width;
+    let d = &mut self.dimensions;
+    if let Some(style) = style {
+      d.margin.top = style.margin.top.resolve(basis).unwrap_or(0.0);
+      d.margin.bottom = style.margin.bottom.resolve(basis).unwrap_or(0.0);
+      d.padding.top = style.padding.top.resolve(basis);
+      d.padding.bottom = style.padding.bottom.resolve(basis);
+      d.border.top = style.border_width.top;
+      d.border.bottom = style.border_width.bottom;
+    }
+  }
+
+  /// Stacks the block-level children at `position`, with the floats among
+  /// them and the static positions of absolutely positioned ones where the
+  /// next one would go. Returns the top of the border box of
+  /// the first one that the margins above do not collapse through, or
+  /// where those margins end if clearance keeps them apart from it.
+  fn layout_block_children(&mut self, containing_block: &ContainingBlock, position: &mut FlowPosition, floats: &mut Floats, context: &LayoutContext) -> Option<Px> {
+    let mut first = None;
+    for child in self.children.iter_mut() {
+      if child.is_float() {
+        child.layout_float(containing_block, position.y + position.margin.resolve(), floats, context);
+        continue;
+      }
+      if child.is_absolutely_positioned() {
+        child.at_static_position(containing_block.x, position.y + position.margin.resolve());
+        continue;
+      }
+      if !child.is_block_level() {
+        continue;
+      }
+      let top = position.y + position.margin.resolve();
+      if child.clear(containing_block, position, floats) && first.is_none() {
+        first = Some(top);
+      }
+      if !child.layout_block(containing_block, position, floats, context) && first.is_none() {
+        first = Some(child.dimensions.border_box().y);
+      }
+      child.position_relative(containing_block);
+    }
+    first
+  }
+}
+
+/// The content box size for a `width` or `height` of `size`, given the
+/// padding and borders `edges` along the same axis.
+pub(super) fn content_size(box_sizing: BoxSizing, size: Px, edges: Px) -> Px {
+  match box_sizing {
+    BoxSizing::ContentBox => size,
+    BoxSizing::BorderBox => (size - edges).max(0.0)
+  }
+}
+
+/// Solves the constraint of CSS 2.1 §10.3.3 for the used width and
+/// horizontal margins, where `None` is `auto`:
+/// margin-left + edges + width + margin-right = available.
+fn solve_width(width: Option<Px>, margin_left: Option<Px>, margin_right: Option<Px>, edges: Px, available: Px) -> (Px, Px, Px) {
+  let (mut margin_left, mut margin_right) = (margin_left, margin_right);
+
+  // auto margins of a box too wide for its containing block are zero
+  if let Some(width) = width {
+    if margin_left.unwrap_or(0.0) + edges + width + margin_right.unwrap_or(0.0) > available {
+      margin_left = margin_left.or(Some(0.0));
+      margin_right = margin_right.or(Some(0.0));
+    }
+  }
+
+  match (width, margin_left, margin_right) {
+    // over-constrained, so margin-right gives in left-to-right text
+    (Some(width), Some(left), Some(_)) | (Some(width), Some(left), None) => (width, left, available - edges - width - left),
+    (Some(width), None, Some(right)) => (width, available - edges - width - right, right),
+    (Some(width), None, None) => {
+      let margin = (available - edges - width) / 2.0;
+      (width, margin, margin)
+    },
+    (None, left, right) => {
+      let (left, right) = (left.unwrap_or(0.0), right.unwrap_or(0.0));
+      let width = available - edges - left - right;
+      if width >= 0.0 {
+        (width, left, right)
+      } else {
+        (0.0, left, available - edges - left)
+      }
+    }
+  }
+}
+
+/// `length` against the height of the containing block, `None` where a
+/// percentage cannot resolve because that height depends on the content.
+fn resolve_height(length: &LengthPercentage, containing_block: &ContainingBlock) -> Option<Px> {
+  match containing_block.height {
+    Some(height) => Some(length.resolve(height)),
+    None => length.to_px()
+  }
+}
+
+/// The used content height of a box whose `height` does not depend on its
+/// content, per CSS 2.1 §10.5 and §10.7. `edges` are its vertical padding
+/// and borders.
+fn used_height(style: &ComputedStyle, containing_block: &ContainingBlock, edges: Px) -> Option<Px> {
+  let height = match style.height {
+    LengthPercentageOrAuto::LengthPercentage(ref length) => resolve_height(length, containing_block)?,
+    LengthPercentageOrAuto::Auto => return None
+  };
+  Some(clamp_height(style, containing_block, content_size(style.box_sizing, height, edges), edges))
+}
+
+/// `height` within `min-height` and `max-height`.
+fn clamp_height(style: &ComputedStyle, containing_block: &ContainingBlock, height: Px, edges: Px) -> Px {
+  let content = |length: &LengthPercentage| resolve_height(length, containing_block)
+    .map(|size| content_size(style.box_sizing, size, edges));
+
+  let max = style.max_height.as_ref().and_then(content).unwrap_or(Px::INFINITY);
+  let min = content(&style.min_height).unwrap_or(0.0);
+  height.min(max).max(min)
+}
+
+#[cfg(test)]
+mod tests {
+  use layout::tests::lay_out;
+  use layout::box_model::Rect;
+  use style::computed::Px;
+
+  #[test]
+  fn lays_out_blocks() {
+    lay_out(concat!(
+      "<html><body>",
+        "<div id=\"a\"></div><div id=\"b\"></div><div id=\"c\"></div><div id=\"d\"></div>",
+        "<div id=\"e\"><p></p></div>",
+      "</body></html>"
+    ), "
+      body, p { margin: 0 }
+      #a { width: 400px; height: 50px; margin: 0 auto }
+      #b { width: 300px; margin: 0 10px; padding: 5px }
+      #c { width: 90%; max-width: 500px; min-width: 600px }
+      #d { box-sizing: border-box; width: 200px; height: 50%; padding: 10px; border: 5px solid }
+      #e { height: 100px }
+      #e p { height: 50%; margin-left: auto }
+    ", (0.0, 0.0), |html| {
+      let body = &html.children[0];
+      let content: Vec<Rect> = body.children.iter().map(|i| i.dimensions.content).collect();
+      let rect = |x, y, width, height| Rect { x, y, width, height };
+      assert_eq!(content, vec![
+        rect(200.0, 0.0, 400.0, 50.0), // centered
+        rect(15.0, 55.0, 300.0, 0.0),
+        rect(0.0, 60.0, 600.0, 0.0), // min-width wins over max-width
+        rect(15.0, 75.0, 170.0, 0.0), // the percentage height is auto in an auto height body
+        rect(0.0, 90.0, 800.0, 100.0)
+      ]);
+      assert_eq!(body.children[1].dimensions.margin.right, 800.0 - 10.0 - 310.0);
+      assert_eq!(body.children[2].dimensions.margin.right, 200.0);
+      assert_eq!(body.children[3].dimensions.border_box().width, 200.0);
+      assert_eq!(body.children[4].children[0].dimensions.content, rect(0.0, 90.0, 800.0, 50.0));
+      assert_eq!(body.dimensions.content.height, 190.0);
+      assert_eq!(html.dimensions.content.height, 190.0);
+    });
+  }
+
+  #[test]
+  fn collapses_margins() {
+    lay_out(concat!(
+      "<html><body>",
+        "<div id=\"a\"></div><div id=\"b\"></div>",
+        "<div id=\"c\"><p></p></div>",
+        "<div id=\"d\"></div>",
+        "<div id=\"e\"><p></p></div>",
+        "<div id=\"f\"><p></p></div>",
+      "</body></html>"
+    ), "
+      body { margin: 0 }
+      p { height: 10px; margin: 15px 0 10px }
+      #a { height: 10px; margin-bottom: 20px }
+      #b { height: 10px; margin: 30px 0 -10px }
+      #c { margin-top: 5px }
+      #d { margin: 20px 0 -30px }
+      #e { overflow: hidden }
+      #f { margin-top: 10px; border-top: 1px solid }
+    ", (0.0, 0.0), |html| {
+      let body = &html.children[0];
+      let border_boxes: Vec<(Px, Px)> = body.children.iter()
+        .map(|i| (i.dimensions.border_box().y, i.dimensions.border_box().height))
+        .collect();
+      assert_eq!(border_boxes, vec![
+        (0.0, 10.0),
+        (40.0, 10.0), // the larger of the sibling margins
+        (55.0, 10.0), // 15px - 10px, through the top and past the bottom of the parent
+        (85.0, 0.0), // empty, so the margins around it collapse
+        (55.0, 35.0), // 20px - 30px, but not with the children of a new formatting context
+        (100.0, 26.0)
+      ]);
+
+      let first_child = |i: usize| body.children[i].children[0].dimensions.border_box().y;
+      assert_eq!((first_child(2), first_child(4), first_child(5)), (55.0, 70.0, 116.0));
+      assert_eq!(body.dimensions.content, Rect { x: 0.0, y: 0.0, width: 800.0, height: 126.0 });
+      assert_eq!(html.dimensions.content.height, 136.0); // the root keeps the last margin inside
+    });
+  }
+}
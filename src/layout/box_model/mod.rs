@@ -0,0 +1,101 @@
+use css::media::Device;
+use style::computed::Px;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Rect {
+  pub x: Px,
+  pub y: Px,
+  pub width: Px,
+  pub height: Px
+}
+
+impl Rect {
+  pub fn expanded_by(self, edge: EdgeSizes) -> Rect {
+    Rect {
+      x: self.x - edge.left,
+      y: self.y - edge.top,
+      width: self.width + edge.left + edge.right,
+      height: self.height + edge.top + edge.bottom
+    }
+  }
+
+  pub fn translated(self, dx: Px, dy: Px) -> Rect {
+    Rect {
+      x: self.x + dx,
+      y: self.y + dy,
+      ..self
+    }
+  }
+
+  pub fn right(&self) -> Px {
+    self.x + self.width
+  }
+
+  pub fn bottom(&self) -> Px {
+    self.y + self.height
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EdgeSizes {
+  pub top: Px,
+  pub right: Px,
+  pub bottom: Px,
+  pub left: Px
+}
+
+impl EdgeSizes {
+  pub fn horizontal(&self) -> Px {
+    self.left + self.right
+  }
+
+  pub fn vertical(&self) -> Px {
+    self.top + self.bottom
+  }
+}
+
+/// The position and size of a box: its content box in absolute
+/// coordinates and the edges around it, per CSS 2.1 §8.1.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Dimensions {
+  pub content: Rect,
+  pub padding: EdgeSizes,
+  pub border: EdgeSizes,
+  pub margin: EdgeSizes
+}
+
+impl Dimensions {
+  pub fn padding_box(&self) -> Rect {
+    self.content.expanded_by(self.padding)
+  }
+
+  pub fn border_box(&self) -> Rect {
+    self.padding_box().expanded_by(self.border)
+  }
+
+  pub fn margin_box(&self) -> Rect {
+    self.border_box().expanded_by(self.margin)
+  }
+}
+
+/// The content box a box is laid out in. Its `height` is known only when it
+/// does not depend on what is laid out in it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ContainingBlock {
+  pub x: Px,
+  pub y: Px,
+  pub width: Px,
+  pub height: Option<Px>
+}
+
+impl ContainingBlock {
+  /// The viewport, which the root element is laid out in.
+  pub fn initial(device: &Device) -> Self {
+    ContainingBlock {
+      x: 0.0,
+      y: 0.0,
+      width: device.viewport_width,
+      height: Some(device.viewport_height)
+    }
+  }
+}
@@ -0,0 +1,254 @@
+use style::computed::{Clear, Float, Px};
+use super::{LayoutBox, BoxType, LayoutContext};
+use super::block::FlowPosition;
+use super::box_model::{ContainingBlock, Rect};
+
+/// The floats placed so far in a block formatting context, by their margin
+/// boxes, per CSS 2.1 §9.5.
+#[derive(Debug, Default, Clone)]
+pub struct Floats {
+  boxes: Vec<(Float, Rect)>
+}
+
+impl Floats {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.boxes.is_empty()
+  }
+
+  /// The left and right edges of the space in `containing_block` that the
+  /// floats leave free from `y` down `height` px.
+  pub fn available(&self, y: Px, height: Px, containing_block: &ContainingBlock) -> (Px, Px) {
+    let edges = (containing_block.x, containing_block.x + containing_block.width);
+    self.boxes.iter()
+      .filter(|&&(_, rect)| overlaps(rect, y, height))
+      .fold(edges, |(left, right), &(side, rect)| match side {
+        Float::Left => (left.max(rect.right()), right),
+        Float::Right => (left, right.min(rect.x)),
+        Float::None => (left, right)
+      })
+  }
+
+  /// The bottom of the first float below `y` that ends there, where the
+  /// space next to the floats next changes.
+  pub fn next_bottom(&self, y: Px) -> Option<Px> {
+    self.boxes.iter()
+      .map(|&(_, rect)| rect.bottom())
+      .filter(|&bottom| bottom > y)
+      .fold(None, |next: Option<Px>, bottom| Some(next.map_or(bottom, |next| next.min(bottom))))
+  }
+
+  /// Below the floats that `clear` keeps a box clear of, `None` if there
+  /// are none.
+  pub fn clearance(&self, clear: Clear) -> Option<Px> {
+    self.boxes.iter()
+      .filter(|&&(side, _)| match clear {
+        Clear::None => false,
+        Clear::Left => side == Float::Left,
+        Clear::Right => side == Float::Right,
+        Clear::Both => true
+      })
+      .map(|&(_, rect)| rect.bottom())
+      .fold(None, |bottom: Option<Px>, next| Some(bottom.map_or(next, |bottom| bottom.max(next))))
+  }
+
+  /// The bottom of the lowest float, which a formatting context root of
+  /// auto height grows to contain, per CSS 2.1 §10.6.7.
+  pub fn bottom(&self) -> Option<Px> {
+    self.clearance(Clear::Both)
+  }
+
+  /// Places a float with a margin box `width` by `height` as high as the
+  /// rules of CSS 2.1 §9.5.1 let it go but no higher than `y`, and then as
+  /// far to its side as it goes. Returns the top left of its margin box.
+  pub fn place(&mut self, side: Float, clear: Clear, width: Px, height: Px, y: Px, containing_block: &ContainingBlock) -> (Px, Px) {
+    // no higher than earlier floats or those it clears
+    let mut y = self.boxes.iter().map(|&(_, rect)| rect.y).fold(y, Px::max);
+    if let Some(clearance) = self.clearance(clear) {
+      y = y.max(clearance);
+    }
+
+    let (left, right) = loop {
+      let (left, right) = self.available(y, height, containing_block);
+      let beside_none = left <= containing_block.x && right >= containing_block.x + containing_block.width;
+      if right - left >= width || beside_none {
+        break (left, right);
+      }
+      match self.next_bottom(y) {
+        Some(bottom) => y = bottom,
+        None => break (left, right)
+      }
+    };
+
+    let x = match side {
+      Float::Right => right - width,
+      Float::Left | Float::None => left
+    };
+    self.boxes.push((side, Rect { x, y, width, height }));
+    (x, y)
+  }
+}
+
+/// Whether a float is next to the band from `y` down `height` px; a band
+/// without height is next to the floats it touches the top of.
+fn overlaps(rect: Rect, y: Px, height: Px) -> bool {
+  rect.bottom() > y && (rect.y < y + height || rect.y <= y)
+}
+
+impl<'a> LayoutBox<'a> {
+  /// Whether the box is floated, which takes it out of normal flow.
+  pub fn is_float(&self) -> bool {
+    matches!(self.box_type, BoxType::Block(node) if node.style.float != Float::None)
+  }
+
+  /// Lays out a float, shrunk to fit per CSS 2.1 §10.3.5, and places it
+  /// among `floats` no higher than `y`.
+  pub(super) fn layout_float(&mut self, containing_block: &ContainingBlock, y: Px, floats: &mut Floats, context: &LayoutContext) {
+    self.layout_atomic(containing_block, context);
+    let margin_box = self.dimensions.margin_box();
+    let style = self.style();
+    let (x, y) = floats.place(style.float, style.clear, margin_box.width, margin_box.height, y, containing_block);
+    self.translate(x - margin_box.x, y - margin_box.y);
+    self.position_relative(containing_block);
+  }
+
+  /// Moves `position` below the floats that the box clears, per CSS 2.1
+  /// §9.5.2: its top border edge goes to their bottom, and the margins
+  /// above it no longer collapse with its own. Returns whether it did.
+  pub(super) fn clear(&self, containing_block: &ContainingBlock, position: &mut FlowPosition, floats: &Floats) -> bool {
+    let style = match self.box_style() {
+      Some(style) => style,
+      None => return false
+    };
+    let clearance = match floats.clearance(style.clear) {
+      Some(clearance) => clearance,
+      None => return false
+    };
+
+    let margin_top = style.margin.top.resolve(containing_block.width).unwrap_or(0.0);
+    let mut margin = position.margin;
+    margin.adjoin(margin_top);
+    if position.y + margin.resolve() >= clearance {
+      return false;
+    }
+    *position = FlowPosition::new(clearance - margin_top);
+    true
+  }
+
+  /// Lays out a box that establishes a block formatting context next to
+  /// the floats of the one it is in rather than over them, per CSS 2.1
+  /// §9.5: in the space between them where it fits, or else further down.
+  pub(super) fn layout_beside_floats(&mut self, containing_block: &ContainingBlock, position: &mut FlowPosition, floats: &mut Floats, context: &LayoutContext) {
+    let margin_top = self.box_style().map_or(0.0, |style| style.margin.top.resolve(containing_block.width).unwrap_or(0.0));
+    let mut margin = position.margin;
+    margin.adjoin(margin_top);
+    let start = position.y + margin.resolve();
+
+    let (mut top, mut height) = (start, 0.0);
+    loop {
+      let (left, right) = floats.available(top, height, containing_block);
+      let space = ContainingBlock {
+        x: left,
+        width: right - left,
+        ..*containing_block
+      };
+      let mut attempt = if top == start { *position } else { FlowPosition::new(top - margin_top) };
+      self.layout_in_flow(&space, &mut attempt, true, false, floats, context);
+
+      let border_box = self.dimensions.border_box();
+      let beside_none = left <= containing_block.x && right >= containing_block.x + containing_block.width;
+      let fits = border_box.x >= left && border_box.right() <= right;
+      if beside_none || fits && floats.available(top, border_box.height, containing_block) == (left, right) {
+        *position = attempt;
+        return;
+      }
+      if fits && border_box.height > height {
+        // taller than the space it was laid out in, which narrows below
+        height = border_box.height;
+        continue;
+      }
+      match floats.next_bottom(top) {
+        Some(bottom) => {
+          top = bottom;
+          height = 0.0;
+        },
+        None => {
+          *position = attempt;
+          return;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use layout::LayoutBox;
+  use layout::tests::lay_out;
+  use layout::box_model::Rect;
+  use style::computed::Px;
+
+  #[test]
+  fn places_floats() {
+    lay_out(concat!(
+      "<html><body>",
+        "<div id=\"a\"></div><div id=\"b\"></div><div id=\"c\"></div>",
+        "<p>aaaa bbbb cccc dddd eeee<br>ffff</p>",
+        "<div id=\"d\"></div>",
+        "<div id=\"h\"></div><div id=\"e\"></div>",
+        "<div id=\"f\"><div id=\"g\"></div></div>",
+        "<p id=\"q\">aaaa <i id=\"l\"></i>bbbb <i id=\"r\"></i>cccc</p>",
+      "</body></html>"
+    ), "
+      body, p { margin: 0 }
+      p { font-size: 10px; line-height: 10px }
+      #a { float: left; width: 100px; height: 30px; margin-right: 10px }
+      #b { float: right; width: 640px; height: 20px }
+      #c { float: left; width: 700px; height: 20px }
+      #d { clear: left; height: 5px; margin-top: 5px }
+      #h { float: right; width: 100px; height: 10px }
+      #e { overflow: hidden; height: 10px }
+      #f { display: flow-root }
+      #g { float: left; width: 10px; height: 25px }
+      #q { width: 60px }
+      #l { float: left; width: 20px; height: 10px }
+      #r { float: right; width: 30px; height: 10px }
+    ", (0.0, 0.0), |html| {
+      let body = &html.children[0];
+      let border_box = |i: usize| body.children[i].dimensions.border_box();
+      let rect = |x, y, width, height| Rect { x, y, width, height };
+      // side by side until the third is too wide, which goes below the
+      // floats next to it
+      assert_eq!(
+        (border_box(0), border_box(1), border_box(2)),
+        (rect(0.0, 0.0, 100.0, 30.0), rect(160.0, 0.0, 640.0, 20.0), rect(0.0, 30.0, 700.0, 20.0))
+      );
+
+      // the paragraph starts at the top, with its lines shortened around the
+      // floats next to them
+      let p: &LayoutBox = &body.children[3];
+      let lines: Vec<(Px, Px, Px)> = p.lines.iter().map(|line| (line.rect.x, line.rect.y, line.rect.width)).collect();
+      assert_eq!(lines, vec![(110.0, 0.0, 50.0), (110.0, 10.0, 50.0), (110.0, 20.0, 690.0), (700.0, 30.0, 100.0)]);
+      assert_eq!(p.lines[3].fragments[0].rect.x, 700.0);
+      assert_eq!(p.dimensions.content, rect(0.0, 0.0, 800.0, 40.0));
+
+      // cleared past the left floats, and a formatting context root next to
+      // the float beside it
+      assert_eq!(border_box(4), rect(0.0, 50.0, 800.0, 5.0));
+      assert_eq!((border_box(5), border_box(6)), (rect(700.0, 55.0, 100.0, 10.0), rect(0.0, 55.0, 700.0, 10.0)));
+      assert_eq!(body.children[7].dimensions.content.height, 25.0); // contains its float
+
+      // floats in a line go at its top when they fit next to the content
+      // before them, and the line breaks around them
+      let q = &body.children[8];
+      let lines: Vec<(Px, Px, Px)> = q.lines.iter().map(|line| (line.rect.x, line.rect.y, line.rect.width)).collect();
+      assert_eq!(lines, vec![(20.0, 90.0, 40.0), (0.0, 100.0, 30.0), (0.0, 110.0, 60.0)]);
+      let (l, r) = (q.children[1].dimensions.margin_box(), q.children[3].dimensions.margin_box());
+      assert_eq!((l, r), (rect(0.0, 90.0, 20.0, 10.0), rect(30.0, 100.0, 30.0, 10.0)));
+      assert_eq!(body.children.len(), 9);
+    });
+  }
+}
@@ -0,0 +1,1033 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use dom::NodeType;
+use style::StyledNode;
+use style::computed::{BoxSizing, ComputedStyle, LineHeight, LengthPercentage, LengthPercentageOrAuto, Overflow, Px, TextAlign, VerticalAlign, WhiteSpace};
+use super::{LayoutBox, BoxType, LayoutContext};
+use super::block::FlowPosition;
+use super::box_model::{ContainingBlock, Rect};
+use super::float::Floats;
+use super::positioned::relative_offset;
+use super::line_break::{line_breaks, Break};
+use super::text::FontMetrics;
+
+/// A line box, per CSS 2.1 §9.4.2.
+#[derive(Debug, Clone)]
+pub struct LineBox<'a> {
+  pub rect: Rect,
+  pub baseline: Px,
+  pub fragments: Vec<Fragment<'a>>
+}
+
+/// What a line box holds of an inline-level box.
+#[derive(Debug, Clone)]
+pub struct Fragment<'a> {
+  pub node: &'a StyledNode<'a>,
+  pub rect: Rect, // the content area, or the margin box of an atomic inline
+  pub kind: FragmentKind
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FragmentKind {
+  Text(String),
+  /// The part of an inline box on the line. A box split across lines has
+  /// its left edges on its first part only, and its right ones on its last.
+  Inline { first: bool, last: bool },
+  /// An atomic inline-level box, by its index among those of the
+  /// formatting context in tree order.
+  Atomic(usize)
+}
+
+impl<'a> LayoutBox<'a> {
+  /// Whether the box is a block container of inline-level boxes, which
+  /// establishes an inline formatting context.
+  pub(super) fn has_inline_content(&self) -> bool {
+    !self.children.is_empty() && !self.children.iter().any(LayoutBox::is_block_level)
+  }
+
+  /// Breaks the inline content into line boxes stacked at `position`,
+  /// shortened next to `floats`, and places the floats among the content.
+  /// Returns the top of the first line, `None` if there are no lines and
+  /// so nothing to keep margins apart.
+  pub(super) fn layout_lines(&mut self, containing_block: &ContainingBlock, position: &mut FlowPosition, floats: &mut Floats, context: &LayoutContext) -> Option<Px> {
+    let mut sizes = Vec::new();
+    let mut atomics = Vec::new();
+    atomic_boxes_mut(&mut self.children, &mut atomics);
+    for atomic in atomics {
+      atomic.layout_atomic(containing_block, context);
+      let margin_box = atomic.dimensions.margin_box();
+      sizes.push(AtomicSize {
+        width: margin_box.width,
+        height: margin_box.height,
+        baseline: atomic.baseline().map_or(margin_box.height, |baseline| baseline - margin_box.y)
+      });
+    }
+    let mut float_sizes = Vec::new();
+    let mut inner_floats = Vec::new();
+    out_of_flow_boxes_mut(&mut self.children, LayoutBox::is_float, &mut inner_floats);
+    for float in inner_floats {
+      float.layout_atomic(containing_block, context);
+      let margin_box = float.dimensions.margin_box();
+      float_sizes.push((float.style(), margin_box.width, margin_box.height));
+    }
+
+    let style = self.style();
+    let basis = containing_block.width;
+    let content = InlineContent::new(&self.children, style, basis, &sizes, context.fonts);
+    let items = content.items(&sizes.iter().map(|size| size.width).collect::<Vec<Px>>(), context.fonts);
+
+    let indent = match self.box_type {
+      BoxType::AnonymousBlock(_) => 0.0,
+      _ => style.text_indent.resolve(basis)
+    };
+    let top = position.y + position.margin.resolve();
+    let mut placement = LinePlacement {
+      content: &content,
+      items: &items,
+      style,
+      fonts: context.fonts,
+      y: top,
+      open: Vec::new(),
+      atomics: vec![(0.0, 0.0); sizes.len()],
+      absolutes: vec![(containing_block.x, top); content.absolute_count()]
+    };
+
+    // a float goes at the top of the line it is on where it fits next to
+    // the content before it, and otherwise below the line, per CSS 2.1
+    // §9.5.1
+    let strut = line_height(style, context.fonts);
+    let mut float_offsets: Vec<Option<(Px, Px)>> = vec![None; float_sizes.len()];
+    let place_float = |index: usize, y: Px, floats: &mut Floats, offsets: &mut Vec<Option<(Px, Px)>>| {
+      let (style, width, height) = float_sizes[index];
+      offsets[index] = Some(floats.place(style.float, style.clear, width, height, y, containing_block));
+    };
+    let (mut lines, mut start) = (Vec::new(), 0);
+    while start < items.len() {
+      let indent = if start == 0 { indent } else { 0.0 };
+      let (left, right) = floats.available(placement.y, strut, containing_block);
+      let available = right - left - indent;
+      let (end, justify) = break_line(&items, start, available);
+
+      let unplaced: Vec<(usize, usize)> = items[start..end].iter().enumerate()
+        .filter_map(|(i, item)| match item.piece {
+          Piece::Float(index) if float_offsets[index].is_none() => Some((start + i, index)),
+          _ => None
+        })
+        .collect();
+      if let Some(&(i, index)) = unplaced.first() {
+        let before = line_width(&items[start..i]);
+        if before == 0.0 || before + float_sizes[index].1 <= available {
+          place_float(index, placement.y, floats, &mut float_offsets);
+          continue; // for the content to break around
+        }
+      }
+      // content too wide for the space next to the floats goes below them
+      if line_width(&items[start..end]) > available && right - left < containing_block.width {
+        if let Some(bottom) = floats.next_bottom(placement.y) {
+          placement.y = bottom;
+          continue;
+        }
+      }
+
+      lines.extend(placement.place(&(start..end, justify), indent, (left, right)));
+      for &(_, index) in unplaced.iter() {
+        place_float(index, placement.y, floats, &mut float_offsets);
+      }
+      start = end;
+    }
+    self.lines = lines;
+    let (bottom, offsets, static_positions) = (placement.y, placement.atomics, placement.absolutes);
+
+    let mut atomics = Vec::new();
+    atomic_boxes_mut(&mut self.children, &mut atomics);
+    for (atomic, (dx, dy)) in atomics.into_iter().zip(offsets) {
+      atomic.translate(dx, dy);
+    }
+    let mut inner_floats = Vec::new();
+    out_of_flow_boxes_mut(&mut self.children, LayoutBox::is_float, &mut inner_floats);
+    for (float, offset) in inner_floats.into_iter().zip(float_offsets) {
+      let (x, y) = offset.unwrap_or((0.0, bottom));
+      let margin_box = float.dimensions.margin_box();
+      float.translate(x - margin_box.x, y - margin_box.y);
+    }
+    let mut absolutes = Vec::new();
+    out_of_flow_boxes_mut(&mut self.children, LayoutBox::is_absolutely_positioned, &mut absolutes);
+    for (absolute, (x, y)) in absolutes.into_iter().zip(static_positions) {
+      absolute.at_static_position(x, y);
+    }
+
+    if self.lines.is_empty() {
+      return None;
+    }
+    *position = FlowPosition::new(bottom);
+    Some(top)
+  }
+
+  /// The baseline of the box as an inline-block: that of its last line
+  /// box in normal flow, per CSS 2.1 §10.8.1.
+  fn baseline(&self) -> Option<Px> {
+    if self.style().overflow != Overflow::Visible {
+      return None;
+    }
+    self.last_line_baseline()
+  }
+
+  fn last_line_baseline(&self) -> Option<Px> {
+    match self.lines.last() {
+      Some(line) => Some(line.baseline),
+      None => self.children.iter().rev()
+        .filter(|child| child.is_block_level())
+        .find_map(LayoutBox::last_line_baseline)
+    }
+  }
+
+  /// The min-content and max-content widths of the content box, for
+  /// shrink-to-fit widths per CSS 2.1 §10.3.5. Floats in inline content
+  /// can all go on one line with it.
+  pub(super) fn intrinsic_widths(&self, context: &LayoutContext) -> (Px, Px) {
+    if !self.has_inline_content() {
+      return self.children.iter()
+        .filter(|child| child.is_block_level() || child.is_float())
+        .map(|child| child.outer_intrinsic_widths(context))
+        .fold((0.0, 0.0), |(min, max), (child_min, child_max)| (min.max(child_min), max.max(child_max)));
+    }
+
+    let mut atomics = Vec::new();
+    atomic_boxes(&self.children, &mut atomics);
+    let (mins, maxes): (Vec<Px>, Vec<Px>) = atomics.iter().map(|atomic| atomic.outer_intrinsic_widths(context)).unzip();
+
+    let sizes: Vec<AtomicSize> = atomics.iter().map(|_| AtomicSize::default()).collect();
+    let content = InlineContent::new(&self.children, self.style(), 0.0, &sizes, context.fonts);
+    let widest = |items: Vec<Item>, mandatory_only: bool| {
+      let (mut widest, mut width, mut hang) = (0.0 as Px, 0.0, 0.0);
+      for item in items.iter() {
+        let breaks = match item.break_before {
+          Some(Break::Mandatory) => true,
+          Some(Break::Allowed) => !mandatory_only,
+          None => false
+        };
+        if breaks {
+          widest = widest.max(width - hang);
+          width = 0.0;
+          hang = 0.0;
+        }
+        width += item.width;
+        hang = item.hang_after(hang);
+      }
+      widest.max(width - hang)
+    };
+    let (min, max) = (widest(content.items(&mins, context.fonts), false), widest(content.items(&maxes, context.fonts), true));
+
+    let mut floats = Vec::new();
+    out_of_flow_boxes(&self.children, LayoutBox::is_float, &mut floats);
+    floats.iter().map(|float| float.outer_intrinsic_widths(context))
+      .fold((min, max), |(min, max), (float_min, float_max)| (min.max(float_min), max + float_max))
+  }
+
+  /// The intrinsic widths of the margin box; percentages count as zero.
+  fn outer_intrinsic_widths(&self, context: &LayoutContext) -> (Px, Px) {
+    let style = match self.box_type {
+      BoxType::AnonymousBlock(_) | BoxType::AnonymousInline(_) => return self.intrinsic_widths(context),
+      _ => self.style()
+    };
+    let edges = style.padding.left.resolve(0.0) + style.padding.right.resolve(0.0)
+      + style.border_width.left + style.border_width.right;
+    let margins = style.margin.left.resolve(0.0).unwrap_or(0.0) + style.margin.right.resolve(0.0).unwrap_or(0.0);
+
+    match style.width {
+      LengthPercentageOrAuto::LengthPercentage(LengthPercentage::Length(width)) => {
+        let outer = width + margins + match style.box_sizing {
+          BoxSizing::ContentBox => edges,
+          BoxSizing::BorderBox => 0.0
+        };
+        (outer, outer)
+      },
+      _ => {
+        let (min, max) = self.intrinsic_widths(context);
+        (min + edges + margins, max + edges + margins)
+      }
+    }
+  }
+}
+
+fn atomic_boxes<'b, 'a>(boxes: &'b [LayoutBox<'a>], atomics: &mut Vec<&'b LayoutBox<'a>>) {
+  for child in boxes.iter() {
+    match child.box_type {
+      BoxType::InlineBlock(_) => atomics.push(child),
+      BoxType::Inline(_) => atomic_boxes(&child.children, atomics),
+      _ => ()
+    }
+  }
+}
+
+fn atomic_boxes_mut<'b, 'a>(boxes: &'b mut [LayoutBox<'a>], atomics: &mut Vec<&'b mut LayoutBox<'a>>) {
+  for child in boxes.iter_mut() {
+    match child.box_type {
+      BoxType::InlineBlock(_) => atomics.push(child),
+      BoxType::Inline(_) => atomic_boxes_mut(&mut child.children, atomics),
+      _ => ()
+    }
+  }
+}
+
+/// The boxes out of flow among inline content that are `which` kind.
+fn out_of_flow_boxes<'b, 'a>(boxes: &'b [LayoutBox<'a>], which: fn(&LayoutBox<'a>) -> bool, found: &mut Vec<&'b LayoutBox<'a>>) {
+  for child in boxes.iter() {
+    match child.box_type {
+      BoxType::Block(_) if which(child) => found.push(child),
+      BoxType::Inline(_) => out_of_flow_boxes(&child.children, which, found),
+      _ => ()
+    }
+  }
+}
+
+fn out_of_flow_boxes_mut<'b, 'a>(boxes: &'b mut [LayoutBox<'a>], which: fn(&LayoutBox<'a>) -> bool, found: &mut Vec<&'b mut LayoutBox<'a>>) {
+  for child in boxes.iter_mut() {
+    match child.box_type {
+      BoxType::Block(_) if which(child) => found.push(child),
+      BoxType::Inline(_) => out_of_flow_boxes_mut(&mut child.children, which, found),
+      _ => ()
+    }
+  }
+}
+
+/// The margin box of a laid out atomic inline and its baseline below the
+/// top of it.
+#[derive(Debug, Default, Clone, Copy)]
+struct AtomicSize {
+  width: Px,
+  height: Px,
+  baseline: Px
+}
+
+/// An inline box or atomic inline of a formatting context, with what it
+/// takes to align it: its ascent and descent around its baseline, which
+/// sits `shift` below that of its parent, per CSS 2.1 §10.8.
+struct InlineBox<'a> {
+  node: &'a StyledNode<'a>,
+  parent: Option<usize>,
+  atomic: Option<usize>,
+  left: Px, // margin, border and padding
+  right: Px,
+  ascent: Px,
+  descent: Px,
+  shift: Px,
+  edge: Option<LineEdge>, // for `vertical-align: top` and `bottom`
+  relative: (Px, Px) // how far `position: relative` moves it
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineEdge {
+  Top,
+  Bottom
+}
+
+enum Run<'a> {
+  Text(&'a StyledNode<'a>, Option<usize>),
+  Start(usize),
+  End(usize),
+  Atomic(usize),
+  Float(usize), // by its index among the floats of the formatting context
+  Absolute(usize, Option<usize>) // and among the absolutely positioned boxes
+}
+
+/// The inline-level boxes of a formatting context, flattened into runs
+/// over the text of the whole context, in which an atomic inline is an
+/// object replacement character and a float takes no text at all.
+struct InlineContent<'a> {
+  text: String,
+  runs: Vec<(Run<'a>, Range<usize>)>,
+  boxes: Vec<InlineBox<'a>>
+}
+
+impl<'a> InlineContent<'a> {
+  fn new(children: &[LayoutBox<'a>], style: &ComputedStyle, basis: Px, atomics: &[AtomicSize], fonts: &dyn FontMetrics) -> Self {
+    let mut content = InlineContent {
+      text: String::new(),
+      runs: Vec::new(),
+      boxes: Vec::new()
+    };
+    content.collect(children, None, &mut true, basis, atomics, fonts);
+
+    for i in 0..content.boxes.len() {
+      let parent_style = match content.boxes[i].parent {
+        Some(parent) => &content.boxes[parent].node.style,
+        None => style
+      };
+      let inline_box = &content.boxes[i];
+      let line_height = match inline_box.atomic {
+        Some(_) => inline_box.ascent + inline_box.descent,
+        None => line_height(&inline_box.node.style, fonts)
+      };
+      let (shift, edge) = baseline_shift(&inline_box.node.style.vertical_align, parent_style, inline_box.ascent, inline_box.descent, line_height, fonts);
+      content.boxes[i].shift = shift;
+      content.boxes[i].edge = edge;
+    }
+    content
+  }
+
+  /// `after_space` is whether the text so far ends in a collapsible space.
+  fn collect(&mut self, children: &[LayoutBox<'a>], parent: Option<usize>, after_space: &mut bool, basis: Px, atomics: &[AtomicSize], fonts: &dyn FontMetrics) {
+    for child in children.iter() {
+      let start = self.text.len();
+      match child.box_type {
+        BoxType::AnonymousInline(node) => {
+          if let NodeType::Text(ref text) = node.node.node_type {
+            self.text.push_str(&process_white_space(text, node.style.white_space, after_space));
+            self.runs.push((Run::Text(node, parent), start..self.text.len()));
+          }
+        },
+        BoxType::Inline(node) if is_line_break(node) => {
+          self.text.push('\n');
+          self.runs.push((Run::Text(node, parent), start..self.text.len()));
+          *after_space = true;
+        },
+        BoxType::Inline(node) => {
+          let style = &node.style;
+          let (ascent, descent) = half_leading(style, fonts);
+          let index = self.boxes.len();
+          self.boxes.push(InlineBox {
+            node,
+            parent,
+            atomic: None,
+            left: style.margin.left.resolve(basis).unwrap_or(0.0) + style.border_width.left + style.padding.left.resolve(basis),
+            right: style.margin.right.resolve(basis).unwrap_or(0.0) + style.border_width.right + style.padding.right.resolve(basis),
+            ascent,
+            descent,
+            shift: 0.0,
+            edge: None,
+            relative: relative_offset(style, basis, None)
+          });
+
+          self.runs.push((Run::Start(index), start..start));
+          self.collect(&child.children, Some(index), after_space, basis, atomics, fonts);
+          let end = self.text.len();
+          self.runs.push((Run::End(index), end..end));
+        },
+        BoxType::InlineBlock(node) => {
+          let atomic = self.boxes.iter().filter(|inline_box| inline_box.atomic.is_some()).count();
+          let size = atomics[atomic];
+          self.boxes.push(InlineBox {
+            node,
+            parent,
+            atomic: Some(atomic),
+            left: 0.0,
+            right: 0.0,
+            ascent: size.baseline,
+            descent: size.height - size.baseline,
+            shift: 0.0,
+            edge: None,
+            relative: relative_offset(&node.style, basis, None)
+          });
+
+          self.text.push('\u{fffc}');
+          self.runs.push((Run::Atomic(self.boxes.len() - 1), start..self.text.len()));
+          *after_space = false;
+        },
+        BoxType::Block(_) if child.is_float() => {
+          let index = self.runs.iter().filter(|&(run, _)| matches!(run, Run::Float(_))).count();
+          self.runs.push((Run::Float(index), start..start));
+        },
+        BoxType::Block(_) if child.is_absolutely_positioned() => {
+          let index = self.absolute_count();
+          self.runs.push((Run::Absolute(index, parent), start..start));
+        },
+        BoxType::Block(_) | BoxType::AnonymousBlock(_) => ()
+      }
+    }
+  }
+
+  /// The content split at its line break opportunities, with atomic
+  /// inlines `widths` wide. A break at the start of an inline box goes
+  /// before it, and one at its end after it.
+  fn items(&self, widths: &[Px], fonts: &dyn FontMetrics) -> Vec<Item<'a>> {
+    let wraps_before = |position: usize| self.runs.iter()
+      .find(|&(_, range)| range.start < position && position <= range.end)
+      .is_some_and(|(run, _)| match *run {
+        Run::Text(node, _) => wraps(node.style.white_space),
+        Run::Atomic(index) => wraps(self.boxes[index].node.style.white_space),
+        Run::Start(_) | Run::End(_) | Run::Float(_) | Run::Absolute(..) => false
+      });
+    let breaks: HashMap<usize, Break> = line_breaks(&self.text).into_iter()
+      .filter(|&(position, opportunity)| opportunity == Break::Mandatory || wraps_before(position))
+      .collect();
+
+    let mut claimed = None;
+    let mut take = |position: usize| match breaks.get(&position) {
+      Some(&opportunity) if claimed != Some(position) => {
+        claimed = Some(position);
+        Some(opportunity)
+      },
+      _ => None
+    };
+
+    let mut items = Vec::new();
+    for (run, range) in self.runs.iter() {
+      match *run {
+        Run::Text(node, parent) => {
+          let mut starts: Vec<usize> = breaks.keys().cloned().filter(|&i| range.start < i && i < range.end).collect();
+          starts.sort_unstable();
+          starts.insert(0, range.start);
+          for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).cloned().unwrap_or(range.end);
+            let text = &self.text[start..end];
+            items.push(Item {
+              piece: Piece::Text(node, parent, text.to_string()),
+              width: text_width(text, &node.style, fonts),
+              hang: trailing_space_width(text, &node.style, fonts),
+              break_before: take(start)
+            });
+          }
+        },
+        Run::Start(index) => items.push(Item {
+          piece: Piece::Start(index),
+          width: self.boxes[index].left,
+          hang: 0.0,
+          break_before: take(range.start)
+        }),
+        Run::End(index) => items.push(Item {
+          piece: Piece::End(index),
+          width: self.boxes[index].right,
+          hang: 0.0,
+          break_before: None
+        }),
+        Run::Atomic(index) => items.push(Item {
+          piece: Piece::Atomic(index),
+          width: self.boxes[index].atomic.map_or(0.0, |atomic| widths[atomic]),
+          hang: 0.0,
+          break_before: take(range.start)
+        }),
+        Run::Float(index) => items.push(Item {
+          piece: Piece::Float(index),
+          width: 0.0,
+          hang: 0.0,
+          break_before: None
+        }),
+        Run::Absolute(index, parent) => items.push(Item {
+          piece: Piece::Absolute(index, parent),
+          width: 0.0,
+          hang: 0.0,
+          break_before: None
+        })
+      }
+    }
+    items
+  }
+
+  /// The offset of the baseline of a box below that of the line, given
+  /// how far the boxes aligned to the line edges moved on it.
+  fn offset(&self, index: Option<usize>, moved: &[Px]) -> Px {
+    match index {
+      Some(index) => {
+        let inline_box = &self.boxes[index];
+        self.offset(inline_box.parent, moved) + inline_box.shift + moved[index]
+      },
+      None => 0.0
+    }
+  }
+
+  /// How far `position: relative` moves a box and its ancestors.
+  fn relative(&self, index: Option<usize>) -> (Px, Px) {
+    match index {
+      Some(index) => {
+        let inline_box = &self.boxes[index];
+        let (x, y) = self.relative(inline_box.parent);
+        (x + inline_box.relative.0, y + inline_box.relative.1)
+      },
+      None => (0.0, 0.0)
+    }
+  }
+
+  fn absolute_count(&self) -> usize {
+    self.runs.iter().filter(|&(run, _)| matches!(run, Run::Absolute(..))).count()
+  }
+
+  /// Whether a box or one of its ancestors is aligned to a line edge.
+  fn edge_aligned(&self, index: usize) -> bool {
+    let inline_box = &self.boxes[index];
+    inline_box.edge.is_some() || inline_box.parent.is_some_and(|parent| self.edge_aligned(parent))
+  }
+}
+
+enum Piece<'a> {
+  Text(&'a StyledNode<'a>, Option<usize>, String), // with its inline box
+  Start(usize),
+  End(usize),
+  Atomic(usize),
+  Float(usize),
+  Absolute(usize, Option<usize>) // with its inline box
+}
+
+/// A piece of inline content that no line breaks inside of. `hang` is the
+/// width of spaces at its end that do not count at the end of a line.
+struct Item<'a> {
+  piece: Piece<'a>,
+  width: Px,
+  hang: Px,
+  break_before: Option<Break>
+}
+
+impl<'a> Item<'a> {
+  /// The width that hangs at the end of a line ending in the item, after
+  /// `hang` before it.
+  fn hang_after(&self, hang: Px) -> Px {
+    match self.piece {
+      Piece::Text(..) if self.hang == self.width => hang + self.width,
+      Piece::Text(..) => self.hang,
+      Piece::End(_) | Piece::Float(_) | Piece::Absolute(..) => hang,
+      Piece::Start(_) | Piece::Atomic(_) => 0.0
+    }
+  }
+}
+
+/// The end of the line of items from `start`, filled greedily into
+/// `available`, and whether it can be justified: whether it does not end
+/// in a forced break.
+fn break_line(items: &[Item], start: usize, available: Px) -> (usize, bool) {
+  let (mut width, mut hang) = (0.0, 0.0);
+  let mut last_break = None;
+
+  for (i, item) in items.iter().enumerate().skip(start) {
+    if let Some(opportunity) = item.break_before.filter(|_| i > start) {
+      if let Some(end) = last_break.filter(|_| width - hang > available) {
+        return (end, true);
+      }
+      if opportunity == Break::Mandatory {
+        return (i, false);
+      }
+      last_break = Some(i);
+    }
+    width += item.width;
+    hang = item.hang_after(hang);
+  }
+
+  match last_break {
+    Some(end) if width - hang > available => (end, true),
+    _ => (items.len(), false)
+  }
+}
+
+/// The width of a line of `items`, without what hangs at its end.
+fn line_width(items: &[Item]) -> Px {
+  let (width, hang) = items.iter().fold((0.0, 0.0), |(width, hang), item| (width + item.width, item.hang_after(hang)));
+  width - hang
+}
+
+/// Places lines one below the other.
+struct LinePlacement<'c, 'a: 'c> {
+  content: &'c InlineContent<'a>,
+  items: &'c [Item<'a>],
+  style: &'c ComputedStyle, // of the block container
+  fonts: &'c dyn FontMetrics,
+  y: Px,
+  open: Vec<usize>, // the inline boxes open at the start of the next line
+  atomics: Vec<(Px, Px)>, // where each atomic inline goes
+  absolutes: Vec<(Px, Px)> // the static position of each absolutely positioned box
+}
+
+impl<'c, 'a: 'c> LinePlacement<'c, 'a> {
+  /// The line box of the items in `line` between the left and right edges
+  /// of `space`, starting `indent` in; `None` for a line with nothing to
+  /// show, which takes no space.
+  fn place(&mut self, line: &(Range<usize>, bool), indent: Px, space: (Px, Px)) -> Option<LineBox<'a>> {
+    let (ref range, justify) = *line;
+    let items = &self.items[range.clone()];
+    let content = self.content;
+    let texts = trimmed_texts(items, self.fonts);
+
+    let open_before = self.open.clone();
+    let has_content = items.iter().zip(texts.iter()).any(|(item, text)| match item.piece {
+      Piece::Text(..) => text.as_ref().is_some_and(|(text, _)| !text.is_empty()),
+      Piece::Start(_) | Piece::End(_) => item.width != 0.0,
+      Piece::Atomic(_) => true,
+      Piece::Float(_) | Piece::Absolute(..) => false
+    });
+
+    // horizontally
+    let width: Px = items.iter().zip(texts.iter()).map(|(item, text)| text.as_ref().map_or(item.width, |&(_, width)| width)).sum();
+    let hang = match self.style.white_space {
+      WhiteSpace::PreWrap => items.iter().fold(0.0, |hang, item| item.hang_after(hang)),
+      _ => 0.0
+    };
+    let (space_left, space_right) = space;
+    let free = space_right - space_left - indent - (width - hang);
+    let spaces: usize = texts.iter().flatten().map(|(text, _)| text.matches(' ').count()).sum();
+    let (offset, stretch) = match self.style.text_align {
+      TextAlign::Justify if justify && spaces > 0 && free > 0.0 => (0.0, free / spaces as Px),
+      TextAlign::Left | TextAlign::Justify => (0.0, 0.0),
+      TextAlign::Right => (free, 0.0),
+      TextAlign::Center => (free / 2.0, 0.0)
+    };
+
+    let left = space_left + indent + offset;
+    let mut x = left;
+    let mut starts: HashMap<usize, Px> = open_before.iter().map(|&i| (i, left)).collect();
+    let mut spans = Vec::new(); // inline boxes as (index, start, end, first, last)
+    let mut texts_at = Vec::new(); // (node, parent, text, x, width)
+    let mut atomics_at = Vec::new(); // (index, x, width)
+
+    for (item, text) in items.iter().zip(texts) {
+      match item.piece {
+        Piece::Text(node, parent, _) => {
+          let (text, width) = text.unwrap();
+          let width = width + text.matches(' ').count() as Px * stretch;
+          if !text.is_empty() {
+            texts_at.push((node, parent, text, x, width));
+          }
+          x += width;
+        },
+        Piece::Start(index) => {
+          x += item.width;
+          starts.insert(index, x);
+          self.open.push(index);
+        },
+        Piece::End(index) => {
+          let start = starts.remove(&index).unwrap_or(left);
+          spans.push((index, start, x, !open_before.contains(&index), true));
+          x += item.width;
+          self.open.retain(|&open| open != index);
+        },
+        Piece::Atomic(index) => {
+          atomics_at.push((index, x, item.width));
+          x += item.width;
+        },
+        Piece::Absolute(index, parent) => {
+          let (dx, dy) = content.relative(parent);
+          self.absolutes[index] = (x + dx, self.y + dy);
+        },
+        Piece::Float(_) => ()
+      }
+    }
+    for &index in self.open.iter() {
+      let start = starts[&index];
+      spans.push((index, start, x, !open_before.contains(&index), false));
+    }
+    if !has_content {
+      return None;
+    }
+
+    // vertically, first without the boxes aligned to the line edges
+    let on_line: Vec<usize> = spans.iter().map(|span| span.0)
+      .chain(atomics_at.iter().map(|atomic| atomic.0))
+      .collect();
+    let mut moved = vec![0.0; content.boxes.len()];
+    let (root_ascent, root_descent) = half_leading(self.style, self.fonts);
+    let (mut top, mut bottom) = (-root_ascent, root_descent);
+    for &index in on_line.iter().filter(|&&index| !content.edge_aligned(index)) {
+      let (inline_box, offset) = (&content.boxes[index], content.offset(Some(index), &moved));
+      top = top.min(offset - inline_box.ascent);
+      bottom = bottom.max(offset + inline_box.descent);
+    }
+    for &index in on_line.iter() {
+      let inline_box = &content.boxes[index];
+      let height = inline_box.ascent + inline_box.descent;
+      match inline_box.edge {
+        Some(LineEdge::Top) => bottom = bottom.max(top + height),
+        Some(LineEdge::Bottom) => top = top.min(bottom - height),
+        None => continue
+      }
+    }
+    for &index in on_line.iter() {
+      let inline_box = &content.boxes[index];
+      let offset = content.offset(Some(index), &moved);
+      moved[index] = match inline_box.edge {
+        Some(LineEdge::Top) => top + inline_box.ascent - offset,
+        Some(LineEdge::Bottom) => bottom - inline_box.descent - offset,
+        None => 0.0
+      };
+    }
+
+    let baseline = self.y - top;
+    let fonts = self.fonts;
+    let content_area = |style: &ComputedStyle, offset: Px, x: Px, width: Px| {
+      let metrics = fonts.line_metrics(style);
+      Rect {
+        x,
+        y: baseline + offset - metrics.ascent,
+        width,
+        height: metrics.ascent + metrics.descent
+      }
+    };
+
+    let mut fragments = Vec::new();
+    for (index, start, end, first, last) in spans {
+      let node = content.boxes[index].node;
+      let (dx, dy) = content.relative(Some(index));
+      fragments.push(Fragment {
+        node,
+        rect: content_area(&node.style, content.offset(Some(index), &moved), start, end - start).translated(dx, dy),
+        kind: FragmentKind::Inline { first, last }
+      });
+    }
+    for (node, parent, text, x, width) in texts_at {
+      let (dx, dy) = content.relative(parent);
+      fragments.push(Fragment {
+        node,
+        rect: content_area(&node.style, content.offset(parent, &moved), x, width).translated(dx, dy),
+        kind: FragmentKind::Text(text)
+      });
+    }
+    for (index, x, width) in atomics_at {
+      let inline_box = &content.boxes[index];
+      let atomic = inline_box.atomic.unwrap();
+      let (dx, dy) = content.relative(Some(index));
+      let (x, y) = (x + dx, baseline + content.offset(Some(index), &moved) - inline_box.ascent + dy);
+      self.atomics[atomic] = (x, y);
+      fragments.push(Fragment {
+        node: inline_box.node,
+        rect: Rect {
+          x,
+          y,
+          width,
+          height: inline_box.ascent + inline_box.descent
+        },
+        kind: FragmentKind::Atomic(atomic)
+      });
+    }
+
+    let line = LineBox {
+      rect: Rect {
+        x: space_left,
+        y: self.y,
+        width: space_right - space_left,
+        height: bottom - top
+      },
+      baseline,
+      fragments
+    };
+    self.y += bottom - top;
+    Some(line)
+  }
+}
+
+/// The text of each text item on a line and its width, without forced
+/// breaks and the spaces that collapse away at the end of the line.
+fn trimmed_texts(items: &[Item], fonts: &dyn FontMetrics) -> Vec<Option<(String, Px)>> {
+  let mut texts: Vec<Option<(String, Px)>> = items.iter().map(|item| match item.piece {
+    Piece::Text(_, _, ref text) => Some((text.replace('\n', ""), item.width)),
+    _ => None
+  }).collect();
+
+  for (item, text) in items.iter().zip(texts.iter_mut()).rev() {
+    match (&item.piece, text) {
+      (&Piece::Text(node, _, _), &mut Some((ref mut text, ref mut width))) => {
+        if !matches!(node.style.white_space, WhiteSpace::Normal | WhiteSpace::Nowrap | WhiteSpace::PreLine) {
+          break;
+        }
+        let trimmed = text.trim_end_matches(' ').len();
+        *width -= text_width(text, &node.style, fonts) - text_width(&text[..trimmed], &node.style, fonts);
+        text.truncate(trimmed);
+        if !text.is_empty() {
+          break;
+        }
+      },
+      (&Piece::End(_), _) | (&Piece::Float(_), _) | (&Piece::Absolute(..), _) => continue,
+      _ => break
+    }
+  }
+  texts
+}
+
+fn is_line_break(node: &StyledNode) -> bool {
+  match node.node.node_type {
+    NodeType::Element(ref data) => data.tag_name.eq_ignore_ascii_case("br"),
+    NodeType::Text(_) => false
+  }
+}
+
+fn wraps(white_space: WhiteSpace) -> bool {
+  matches!(white_space, WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+}
+
+/// Collapses and transforms white space per CSS Text §4.1.1. Spaces at the
+/// start of a line that follow a collapsible space, including the start of
+/// the formatting context, go as well.
+fn process_white_space(text: &str, white_space: WhiteSpace, after_space: &mut bool) -> String {
+  let (collapse, keep_newlines) = match white_space {
+    WhiteSpace::Normal | WhiteSpace::Nowrap => (true, false),
+    WhiteSpace::PreLine => (true, true),
+    WhiteSpace::Pre | WhiteSpace::PreWrap => (false, true)
+  };
+
+  let mut processed = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '\r' => (),
+      '\n' if keep_newlines => {
+        if collapse {
+          let trimmed = processed.trim_end_matches(' ').len();
+          processed.truncate(trimmed);
+        }
+        processed.push('\n');
+        *after_space = collapse;
+      },
+      ' ' | '\t' | '\n' if collapse => {
+        if !*after_space {
+          processed.push(' ');
+          *after_space = true;
+        }
+      },
+      '\t' => {
+        processed.push('\t');
+        *after_space = false;
+      },
+      c => {
+        processed.push(c);
+        *after_space = false;
+      }
+    }
+  }
+  processed
+}
+
+/// The advance of `c` with letter and word spacing; a tab stops eight
+/// spaces on.
+fn advance(c: char, style: &ComputedStyle, fonts: &dyn FontMetrics) -> Px {
+  match c {
+    '\n' => 0.0,
+    '\t' => 8.0 * fonts.advance(' ', style),
+    ' ' | '\u{a0}' => fonts.advance(c, style) + style.letter_spacing + style.word_spacing,
+    _ => fonts.advance(c, style) + style.letter_spacing
+  }
+}
+
+/// The advances of the characters of `text`, kerned in pairs.
+fn text_width(text: &str, style: &ComputedStyle, fonts: &dyn FontMetrics) -> Px {
+  let mut previous = None;
+  text.chars().map(|c| {
+    let kerning = previous.map_or(0.0, |left| fonts.kerning(left, c, style));
+    previous = Some(c);
+    advance(c, style, fonts) + kerning
+  }).sum()
+}
+
+/// The width of the spaces at the end of `text` that hang or collapse
+/// away at the end of a line.
+fn trailing_space_width(text: &str, style: &ComputedStyle, fonts: &dyn FontMetrics) -> Px {
+  match style.white_space {
+    WhiteSpace::Pre => 0.0,
+    _ => text_width(text, style, fonts) - text_width(text.trim_end_matches(' '), style, fonts)
+  }
+}
+
+fn line_height(style: &ComputedStyle, fonts: &dyn FontMetrics) -> Px {
+  match style.line_height {
+    LineHeight::Normal => {
+      let metrics = fonts.line_metrics(style);
+      metrics.ascent + metrics.descent + metrics.line_gap
+    },
+    LineHeight::Number(number) => style.font_size * number,
+    LineHeight::Length(length) => length
+  }
+}
+
+/// The ascent and descent of an inline box, which are those of its font
+/// plus half the leading each, per CSS 2.1 §10.8.1.
+fn half_leading(style: &ComputedStyle, fonts: &dyn FontMetrics) -> (Px, Px) {
+  let metrics = fonts.line_metrics(style);
+  let (ascent, descent) = (metrics.ascent, metrics.descent);
+  let leading = line_height(style, fonts) - (ascent + descent);
+  (ascent + leading / 2.0, descent + leading / 2.0)
+}
+
+/// How far below the baseline of its parent `vertical-align` puts the
+/// baseline of a box, or which line edge it aligns it to instead.
+fn baseline_shift(align: &VerticalAlign, parent: &ComputedStyle, ascent: Px, descent: Px, line_height: Px, fonts: &dyn FontMetrics) -> (Px, Option<LineEdge>) {
+  let parent_metrics = fonts.line_metrics(parent);
+  let shift = match *align {
+    VerticalAlign::Keyword("sub") => parent.font_size * 0.2,
+    VerticalAlign::Keyword("super") => -parent.font_size * 0.35,
+    VerticalAlign::Keyword("text-top") => ascent - parent_metrics.ascent,
+    VerticalAlign::Keyword("text-bottom") => parent_metrics.descent - descent,
+    VerticalAlign::Keyword("middle") => -parent_metrics.x_height / 2.0 + (ascent - descent) / 2.0,
+    VerticalAlign::Keyword("top") => return (0.0, Some(LineEdge::Top)),
+    VerticalAlign::Keyword("bottom") => return (0.0, Some(LineEdge::Bottom)),
+    VerticalAlign::Keyword(_) => 0.0,
+    VerticalAlign::LengthPercentage(ref length) => -length.resolve(line_height)
+  };
+  (shift, None)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use layout::tests::lay_out;
+
+  fn texts(line: &LineBox) -> Vec<(String, Px)> {
+    line.fragments.iter().filter_map(|fragment| match fragment.kind {
+      FragmentKind::Text(ref text) => Some((text.clone(), fragment.rect.x)),
+      _ => None
+    }).collect()
+  }
+
+  fn text(line: &LineBox) -> String {
+    texts(line).into_iter().map(|(text, _)| text).collect()
+  }
+
+  #[test]
+  fn lays_out_lines() {
+    lay_out(concat!(
+      "<html><body>",
+        "<p>Hello  = there <b>bold words</b> and more</p>",
+        "<p class=\"center\">ab cd</p>",
+        "<p class=\"justify\">aaaa bbbb cccc dddd eeee</p>",
+        "<p class=\"pre\">a  b\nc  </p>",
+        "<p class=\"nowrap\">a<br>bbbb bbbb bbbb bbbb bbbb</p>",
+        "<p>x<span class=\"big\">y</span><span class=\"super\">z</span></p>",
+        "<p>a<em class=\"box\"></em>b <em class=\"fit\">hi there</em></p>",
+      "</body></html>"
+    ), "
+      body { margin: 0 }
+      p { margin: 0; width: 100px; font-size: 10px; line-height: 20px }
+      .center { text-align: center }
+      .justify { text-align: justify }
+      .pre { white-space: pre }
+      .nowrap { white-space: nowrap }
+      .big { font-size: 20px }
+      .super { vertical-align: super }
+      .box { display: inline-block; width: 30px; height: 40px; margin-left: 5px }
+      .fit { display: inline-block }
+    ", (0.0, 0.0), |html| {
+      let p: Vec<&LayoutBox> = html.children[0].children.iter().collect();
+
+      // broken at spaces, with the bold box split across the lines
+      let lines = &p[0].lines;
+      assert_eq!(lines.iter().map(text).collect::<Vec<String>>(), vec!["Hello = there bold", "words and more"]);
+      let bold: Vec<(FragmentKind, Px, Px)> = lines.iter()
+        .flat_map(|line| line.fragments.iter())
+        .filter(|fragment| matches!(fragment.kind, FragmentKind::Inline { .. }))
+        .map(|fragment| (fragment.kind.clone(), fragment.rect.x, fragment.rect.width))
+        .collect();
+      assert_eq!(bold, vec![
+        (FragmentKind::Inline { first: true, last: false }, 70.0, 20.0), // without the collapsed space
+        (FragmentKind::Inline { first: false, last: true }, 0.0, 25.0)
+      ]);
+      // half the leading above and below the content area
+      assert_eq!((lines[1].rect.y, lines[1].baseline), (20.0, 33.0));
+      assert_eq!(lines[1].fragments[1].rect, Rect { x: 0.0, y: 25.0, width: 25.0, height: 10.0 });
+      assert_eq!(p[0].dimensions.content.height, 40.0);
+
+      assert_eq!(texts(&p[1].lines[0]), vec![("ab ".to_string(), 37.5), ("cd".to_string(), 52.5)]);
+      let justified = p[2].lines[0].fragments.last().unwrap().rect;
+      assert_eq!((text(&p[2].lines[0]), justified.right()), ("aaaa bbbb cccc dddd".to_string(), 100.0));
+      assert_eq!(texts(&p[2].lines[1]), vec![("eeee".to_string(), 0.0)]);
+
+      assert_eq!(p[3].lines.iter().map(text).collect::<Vec<String>>(), vec!["a  b", "c  "]);
+      assert_eq!(p[4].lines.iter().map(text).collect::<Vec<String>>(), vec!["a", "bbbb bbbb bbbb bbbb bbbb"]);
+
+      // the larger font and the raised text make the line taller
+      let line = &p[5].lines[0];
+      assert_eq!((line.rect.height, line.baseline - line.rect.y), (23.5, 16.5));
+
+      // an inline-block without lines sits on the baseline with its bottom
+      // margin edge, and one with text shrinks to fit it
+      let line = &p[6].lines[0];
+      let (empty, fit) = (&p[6].children[1], &p[6].children[3]);
+      assert_eq!(empty.dimensions.content, Rect { x: 10.0, y: line.rect.y, width: 30.0, height: 40.0 });
+      assert_eq!(line.baseline, line.rect.y + 40.0);
+      assert_eq!(fit.dimensions.content.width, 40.0);
+      assert_eq!(fit.lines[0].baseline, line.baseline);
+      assert_eq!(p.len(), 7);
+    });
+  }
+}
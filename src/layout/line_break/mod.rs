@@ -0,0 +1,227 @@
+/// What kind of line break an opportunity is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Break {
+  Mandatory, // after a hard line break
+  Allowed
+}
+
+/// The line breaking classes of UAX #14 that the rules below tell apart.
+/// Characters of the complex-context class SA are treated as AL, and the
+/// ambiguous ones as in non-East-Asian text.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+  BK, CR, LF, NL, SP, ZW, ZWJ, CM, WJ, GL,
+  OP, CL, CP, QU, EX, IS, SY, NU, PR, PO,
+  AL, HL, ID, IN, HY, BA, BB, B2, NS, CB,
+  JL, JV, JT, H2, H3, RI, EB, EM
+}
+
+use self::Class::*;
+
+fn class(c: char) -> Class {
+  match c as u32 {
+    0x0A => LF,
+    0x0D => CR,
+    0x0B | 0x0C | 0x2028 | 0x2029 => BK,
+    0x85 => NL,
+    0x09 => BA,
+    0x00..=0x1F | 0x7F..=0x9F => CM,
+    0x20 => SP,
+    0x21 | 0x3F => EX,
+    0x22 | 0x27 => QU,
+    0x24 | 0x2B | 0x5C | 0xA3..=0xA5 | 0xB1 => PR,
+    0x25 | 0xA2 | 0xB0 => PO,
+    0x28 | 0x5B | 0x7B | 0xA1 | 0xBF => OP,
+    0x29 | 0x5D => CP,
+    0x7D => CL,
+    0x2C | 0x2E | 0x3A | 0x3B | 0x2044 => IS,
+    0x2D => HY,
+    0x2F => SY,
+    0x30..=0x39 => NU,
+    0x7C | 0xAD => BA,
+    0xA0 | 0x2007 | 0x2011 | 0x202F => GL,
+    0xAB | 0xBB | 0x2018 | 0x2019 | 0x201B..=0x201D | 0x201F | 0x2039 | 0x203A => QU,
+    0xB4 => BB,
+    0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200C | 0x20D0..=0x20FF
+      | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F => CM,
+    0x05D0..=0x05EA => HL,
+    0x1100..=0x115F => JL,
+    0x1160..=0x11A7 => JV,
+    0x11A8..=0x11FF => JT,
+    0x2000..=0x2006 | 0x2008..=0x200A | 0x2010 | 0x2012 | 0x2013 | 0x2027 | 0x3000 => BA,
+    0x200B => ZW,
+    0x200D => ZWJ,
+    0x2014 => B2,
+    0x201A | 0x201E => OP,
+    0x2024..=0x2026 => IN,
+    0x2030..=0x2037 => PO,
+    0x203C | 0x203D | 0x3005 | 0x30FB | 0x30FC | 0xFF1A | 0xFF1B => NS,
+    0x2060 | 0xFEFF => WJ,
+    0x20A0..=0x20CF => PR,
+    0x3001 | 0x3002 | 0x3009 | 0x300B | 0x300D | 0x300F | 0x3011 | 0xFF09 | 0xFF0C | 0xFF0E => CL,
+    0x3008 | 0x300A | 0x300C | 0x300E | 0x3010 | 0xFF08 => OP,
+    0xFF01 | 0xFF1F => EX,
+    0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0xFF01..=0xFF60
+      | 0x20000..=0x3FFFD => ID,
+    0xAC00..=0xD7A3 if (c as u32 - 0xAC00).is_multiple_of(28) => H2,
+    0xAC00..=0xD7A3 => H3,
+    0xFFFC => CB,
+    0x1F1E6..=0x1F1FF => RI,
+    0x1F3FB..=0x1F3FF => EM,
+    0x1F466..=0x1F469 | 0x1F44A..=0x1F450 | 0x1F6B4..=0x1F6B6 | 0x1F918..=0x1F91F => EB,
+    0x1F000..=0x1FAFF => ID,
+    _ => AL
+  }
+}
+
+/// The line break opportunities in `text` per the rules of UAX #14: the
+/// byte offsets a line may or must break before. The start of the text is
+/// never one and the end always is, so neither is listed.
+pub fn line_breaks(text: &str) -> Vec<(usize, Break)> {
+  let mut breaks = Vec::new();
+  let mut chars = text.char_indices();
+  let mut last_raw = match chars.next() {
+    Some((_, c)) => class(c),
+    None => return breaks
+  };
+
+  let mut before = resolve(last_raw);
+  let mut before_spaces = before; // the last class that is not SP
+  let mut before_that: Option<Class> = None; // the class ahead of `before`
+  let mut regional_indicators = if before == RI { 1 } else { 0 };
+
+  for (i, c) in chars {
+    let raw = class(c);
+
+    // LB9: combining marks take the class of the character they follow
+    if matches!(raw, CM | ZWJ) && !matches!(before, BK | CR | LF | NL | SP | ZW) {
+      last_raw = raw;
+      continue;
+    }
+
+    let after = resolve(raw);
+    if let Some(opportunity) = pair(before, before_spaces, before_that, after, last_raw == ZWJ, regional_indicators) {
+      breaks.push((i, opportunity));
+    }
+
+    regional_indicators = if after == RI { regional_indicators + 1 } else { 0 };
+    before_that = Some(before);
+    before = after;
+    if after != SP {
+      before_spaces = after;
+    }
+    last_raw = raw;
+  }
+
+  breaks
+}
+
+/// LB10: combining marks with nothing to attach to are alphabetic.
+fn resolve(class: Class) -> Class {
+  match class {
+    CM | ZWJ => AL,
+    class => class
+  }
+}
+
+/// Whether a line can break between a character of class `before` and
+/// one of class `after`, by rules LB4 to LB31. `spaced` is the class
+/// ahead of any spaces before `after`.
+fn pair(before: Class, spaced: Class, before_that: Option<Class>, after: Class, after_zwj: bool, regional_indicators: usize) -> Option<Break> {
+  let spaced = if before == SP { spaced } else { before };
+  let is = |class: Class, classes: &[Class]| classes.contains(&class);
+
+  // LB4 to LB8a: hard breaks, spaces and zero width joiners
+  if is(before, &[BK, LF, NL]) || (before == CR && after != LF) {
+    return Some(Break::Mandatory);
+  }
+  if is(after, &[BK, CR, LF, NL, SP, ZW]) {
+    return None;
+  }
+  if spaced == ZW {
+    return Some(Break::Allowed);
+  }
+  if after_zwj {
+    return None;
+  }
+
+  let allowed = match (before, after) {
+    // LB11 to LB13: glue and closing punctuation
+    (WJ, _) | (_, WJ) | (GL, _) => false,
+    (b, GL) if !is(b, &[SP, BA, HY]) => false,
+    (_, CL) | (_, CP) | (_, EX) | (_, IS) | (_, SY) => false,
+
+    // LB14 to LB17: across spaces
+    _ if spaced == OP => false,
+    (_, OP) if spaced == QU => false,
+    (_, NS) if is(spaced, &[CL, CP]) => false,
+    (_, B2) if spaced == B2 => false,
+    (SP, _) => true,
+
+    // LB19 to LB22
+    (QU, _) | (_, QU) => false,
+    (CB, _) | (_, CB) => true,
+    (_, BA) | (_, HY) | (_, NS) | (BB, _) => false,
+    (HY, _) | (BA, _) if before_that == Some(HL) => false,
+    (SY, HL) | (_, IN) => false,
+
+    // LB23 to LB25: letters, numbers and their prefixes and postfixes
+    (AL, NU) | (HL, NU) | (NU, AL) | (NU, HL) => false,
+    (PR, ID) | (PR, EB) | (PR, EM) | (ID, PO) | (EB, PO) | (EM, PO) => false,
+    (PR, AL) | (PR, HL) | (PO, AL) | (PO, HL) | (AL, PR) | (AL, PO) | (HL, PR) | (HL, PO) => false,
+    (CL, PO) | (CP, PO) | (CL, PR) | (CP, PR) | (NU, PO) | (NU, PR) | (PO, OP) | (PO, NU)
+      | (PR, OP) | (PR, NU) | (HY, NU) | (IS, NU) | (NU, NU) | (SY, NU) => false,
+
+    // LB26 and LB27: Korean syllable blocks
+    (JL, JL) | (JL, JV) | (JL, H2) | (JL, H3) | (JV, JV) | (JV, JT) | (H2, JV) | (H2, JT)
+      | (JT, JT) | (H3, JT) => false,
+    (JL, PO) | (JV, PO) | (JT, PO) | (H2, PO) | (H3, PO) => false,
+    (PR, JL) | (PR, JV) | (PR, JT) | (PR, H2) | (PR, H3) => false,
+
+    // LB28 to LB30b
+    (AL, AL) | (AL, HL) | (HL, AL) | (HL, HL) | (IS, AL) | (IS, HL) => false,
+    (AL, OP) | (HL, OP) | (NU, OP) | (CP, AL) | (CP, HL) | (CP, NU) => false,
+    (RI, RI) => regional_indicators.is_multiple_of(2),
+    (EB, EM) => false,
+
+    _ => true
+  };
+  if allowed {
+    Some(Break::Allowed)
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The text split at its break opportunities, with `|` before the
+  /// mandatory ones.
+  fn split(text: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, opportunity) in line_breaks(text) {
+      pieces.push(text[start..i].to_string());
+      if opportunity == Break::Mandatory {
+        pieces.push("|".to_string());
+      }
+      start = i;
+    }
+    pieces.push(text[start..].to_string());
+    pieces
+  }
+
+  #[test]
+  fn finds_break_opportunities() {
+    assert_eq!(split("Hello = there"), vec!["Hello ", "= ", "there"]);
+    assert_eq!(split("one-two (three), 3.5%\u{a0}more"), vec!["one-", "two ", "(three), ", "3.5%\u{a0}more"]);
+    assert_eq!(split("a\nb\r\nc  d"), vec!["a\n", "|", "b\r\n", "|", "c  ", "d"]);
+    assert_eq!(split("$10 \"quoted\" a/b e\u{301}t\u{e9}"), vec!["$10 ", "\"quoted\" ", "a/", "b ", "e\u{301}t\u{e9}"]);
+    assert_eq!(split("日本語「テキスト」です。"), vec!["日", "本", "語", "「テ", "キ", "ス", "ト」", "で", "す。"]);
+    assert_eq!(split("a\u{200b}b\u{2060}c x\u{fffc}y"), vec!["a\u{200b}", "b\u{2060}c ", "x", "\u{fffc}", "y"]);
+    assert!(line_breaks("").is_empty());
+  }
+}
@@ -0,0 +1,293 @@
+use dom::NodeType;
+use style::StyledNode;
+use style::computed::{ComputedStyle, Display, Px, WhiteSpace};
+
+pub mod box_model;
+pub mod block;
+pub mod float;
+pub mod inline;
+pub mod line_break;
+pub mod positioned;
+pub mod text;
+
+use self::box_model::{ContainingBlock, Dimensions};
+use self::inline::LineBox;
+use self::text::FontMetrics;
+
+/// A box of the box tree, generated from a styled node per CSS 2.1 §9.2.
+#[derive(Debug)]
+pub struct LayoutBox<'a> {
+  pub box_type: BoxType<'a>,
+  pub dimensions: Dimensions, // once laid out
+  pub lines: Vec<LineBox<'a>>, // of a block container of inline-level boxes
+  pub children: Vec<LayoutBox<'a>>
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BoxType<'a> {
+  Block(&'a StyledNode<'a>),
+  Inline(&'a StyledNode<'a>), // one of the pieces when split around blocks
+  InlineBlock(&'a StyledNode<'a>), // atomic inline-level: `inline-block`, `inline-table`, ...
+  /// Wraps inline-level boxes that sit next to block-level ones. Holds the
+  /// element it is generated inside of, which it inherits from; its other
+  /// properties are initial.
+  AnonymousBlock(&'a StyledNode<'a>),
+  /// A run of text, styled by its text node.
+  AnonymousInline(&'a StyledNode<'a>)
+}
+
+impl<'a> LayoutBox<'a> {
+  fn new(box_type: BoxType<'a>, children: Vec<LayoutBox<'a>>) -> Self {
+    LayoutBox {
+      box_type,
+      dimensions: Dimensions::default(),
+      lines: Vec::new(),
+      children
+    }
+  }
+
+  pub fn node(&self) -> &'a StyledNode<'a> {
+    match self.box_type {
+      BoxType::Block(node) | BoxType::Inline(node) | BoxType::InlineBlock(node)
+        | BoxType::AnonymousBlock(node) | BoxType::AnonymousInline(node) => node
+    }
+  }
+
+  /// The style of the box; for an anonymous block only its inherited
+  /// properties apply.
+  pub fn style(&self) -> &'a ComputedStyle {
+    &self.node().style
+  }
+
+  /// Whether the box is block-level and in normal flow; floats and
+  /// absolutely positioned boxes are neither block-level nor inline-level
+  /// to the boxes around them.
+  pub fn is_block_level(&self) -> bool {
+    matches!(self.box_type, BoxType::Block(_) | BoxType::AnonymousBlock(_)) && !self.is_out_of_flow()
+  }
+
+  /// Whether the box is floated or absolutely positioned, and so laid out
+  /// apart from the boxes around it.
+  pub fn is_out_of_flow(&self) -> bool {
+    self.is_float() || self.is_absolutely_positioned()
+  }
+
+  /// Moves the box and everything laid out in it.
+  pub fn translate(&mut self, dx: Px, dy: Px) {
+    self.dimensions.content = self.dimensions.content.translated(dx, dy);
+    for line in self.lines.iter_mut() {
+      line.rect = line.rect.translated(dx, dy);
+      line.baseline += dy;
+      for fragment in line.fragments.iter_mut() {
+        fragment.rect = fragment.rect.translated(dx, dy);
+      }
+    }
+    for child in self.children.iter_mut() {
+      child.translate(dx, dy);
+    }
+  }
+
+  /// Text that white space processing removes entirely.
+  fn is_collapsible_whitespace(&self) -> bool {
+    match self.box_type {
+      BoxType::AnonymousInline(node) => {
+        let collapses = matches!(node.style.white_space, WhiteSpace::Normal | WhiteSpace::Nowrap);
+        collapses && match node.node.node_type {
+          NodeType::Text(ref text) => text.chars().all(|c| c.is_ascii_whitespace()),
+          NodeType::Element(_) => false
+        }
+      },
+      _ => false
+    }
+  }
+}
+
+/// The box tree of a styled tree, `None` if the root generates no box. The
+/// root element is always block-level once styled. Tables and their parts
+/// lay out as blocks.
+pub fn layout_tree<'a>(root: &'a StyledNode<'a>) -> Option<LayoutBox<'a>> {
+  let mut boxes = Vec::new();
+  generate_boxes(root, &mut boxes);
+  match boxes.len() {
+    1 => boxes.pop(),
+    _ => None
+  }
+}
+
+/// What laying out a tree depends on besides the boxes themselves.
+pub struct LayoutContext<'f> {
+  pub fonts: &'f dyn FontMetrics,
+  pub scroll: (Px, Px) // how far the viewport is scrolled, for fixed and sticky boxes
+}
+
+/// Lays out the box tree of the root element in `containing_block`,
+/// usually the initial one: normal flow first, and then the boxes
+/// positioned against what it laid out.
+pub fn layout(root: &mut LayoutBox, containing_block: &ContainingBlock, context: &LayoutContext) {
+  root.layout_root(containing_block, context);
+  root.layout_positioned(containing_block, context);
+}
+
+/// Appends the boxes `node` generates, which are those of its children for
+/// `display: contents` and none for `display: none`.
+fn generate_boxes<'a>(node: &'a StyledNode<'a>, boxes: &mut Vec<LayoutBox<'a>>) {
+  if let NodeType::Text(ref text) = node.node.node_type {
+    if !text.is_empty() {
+      boxes.push(LayoutBox::new(BoxType::AnonymousInline(node), Vec::new()));
+    }
+    return;
+  }
+
+  match node.style.display {
+    Display::None => (),
+    Display::Contents => {
+      for child in node.children.iter() {
+        generate_boxes(child, boxes);
+      }
+    },
+    Display::Inline => inline_boxes(node, boxes),
+    display if display.is_inline_level() => {
+      boxes.push(LayoutBox::new(BoxType::InlineBlock(node), block_container_children(node)));
+    },
+    _ => boxes.push(LayoutBox::new(BoxType::Block(node), block_container_children(node)))
+  }
+}
+
+/// An inline box, split around the block-level boxes inside it per CSS 2.1
+/// §9.2.1.1. The blocks end up next to the pieces, among the boxes of the
+/// parent, which wraps the pieces in anonymous blocks.
+fn inline_boxes<'a>(node: &'a StyledNode<'a>, boxes: &mut Vec<LayoutBox<'a>>) {
+  let mut children = Vec::new();
+  for child in node.children.iter() {
+    generate_boxes(child, &mut children);
+  }
+
+  let mut piece = Vec::new();
+  for child in children {
+    if child.is_block_level() {
+      boxes.push(LayoutBox::new(BoxType::Inline(node), piece));
+      boxes.push(child);
+      piece = Vec::new();
+    } else {
+      piece.push(child);
+    }
+  }
+  boxes.push(LayoutBox::new(BoxType::Inline(node), piece));
+}
+
+/// The children of a block container: either all block-level or all
+/// inline-level. Inline-level runs among blocks are wrapped in anonymous
+/// blocks, unless they are only white space that collapses away.
+fn block_container_children<'a>(node: &'a StyledNode<'a>) -> Vec<LayoutBox<'a>> {
+  let mut children = Vec::new();
+  for child in node.children.iter() {
+    generate_boxes(child, &mut children);
+  }
+  if !children.iter().any(LayoutBox::is_block_level) {
+    return children;
+  }
+
+  let mut wrapped = Vec::new();
+  let mut run = Vec::new();
+  for child in children {
+    if child.is_block_level() {
+      wrap_run(node, &mut run, &mut wrapped);
+      wrapped.push(child);
+    } else {
+      run.push(child);
+    }
+  }
+  wrap_run(node, &mut run, &mut wrapped);
+  wrapped
+}
+
+/// Boxes out of flow between blocks stay out of anonymous blocks.
+fn wrap_run<'a>(node: &'a StyledNode<'a>, run: &mut Vec<LayoutBox<'a>>, wrapped: &mut Vec<LayoutBox<'a>>) {
+  if run.iter().all(|child| child.is_collapsible_whitespace() || child.is_out_of_flow()) {
+    wrapped.extend(run.drain(..).filter(LayoutBox::is_out_of_flow));
+  } else {
+    wrapped.push(LayoutBox::new(BoxType::AnonymousBlock(node), run.split_off(0)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::{self, cascade::Origin, media::{Device, MediaType}};
+  use dom::parse_dom;
+  use style::{style_tree, StyleOptions};
+  use self::text::FixedMetrics;
+
+  /// Lays out `html` with the author stylesheet `css` in an 800 by 600 px
+  /// viewport scrolled by `scroll`, measuring text with `FixedMetrics`, and
+  /// hands the box of the root element to `check`.
+  pub fn lay_out<F: FnOnce(&LayoutBox)>(html: &str, css: &str, scroll: (Px, Px), check: F) {
+    let root = parse_dom(html.to_string()).unwrap();
+    let author = css::parse_stylesheet(css.to_string(), Origin::Author).unwrap();
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+
+    let styled = style_tree(&root, &[&author], &device, &StyleOptions::default());
+    let mut layout_box = layout_tree(&styled).unwrap();
+    layout(&mut layout_box, &ContainingBlock::initial(&device), &LayoutContext { fonts: &FixedMetrics::default(), scroll });
+    check(&layout_box);
+  }
+
+  /// The tree as `tag[children]`, with `anon` for anonymous blocks and
+  /// `"text"` for text.
+  fn shape(layout_box: &LayoutBox) -> String {
+    let name = match layout_box.box_type {
+      BoxType::AnonymousBlock(_) => "anon".to_string(),
+      BoxType::AnonymousInline(node) => match node.node.node_type {
+        NodeType::Text(ref text) => format!("{:?}", text.trim()),
+        NodeType::Element(_) => unreachable!()
+      },
+      _ => match layout_box.node().node.node_type {
+        NodeType::Element(ref data) => data.tag_name.clone(),
+        NodeType::Text(_) => unreachable!()
+      }
+    };
+
+    if layout_box.children.is_empty() {
+      return name;
+    }
+    let children: Vec<String> = layout_box.children.iter().map(shape).collect();
+    format!("{}[{}]", name, children.join(" "))
+  }
+
+  #[test]
+  fn generates_boxes() {
+    let root = parse_dom("<html>
+      <head><title>a</title></head>
+      <body>
+        <p>a <b>b</b></p>
+        c <i>d</i>
+        <div style=\"display: contents\"><p>e</p></div>
+        <span>f<div>g</div>h</span>
+        <em style=\"display: inline-block\"><p>i</p>j</em>
+        <p style=\"display: none\">k</p>
+        <b style=\"float: left\">l</b>
+        <span>m<i style=\"float: right\">n</i></span>
+      </body>
+    </html>".to_string()).unwrap();
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+
+    let styled = style_tree(&root, &[], &device, &StyleOptions::default());
+    let layout = layout_tree(&styled).unwrap();
+    assert_eq!(shape(&layout), concat!(
+      "html[body[",
+        "p[\"a\" b[\"b\"]] ",
+        "anon[\"c\" i[\"d\"] \"\"] ",
+        "p[\"e\"] ",
+        "anon[\"\" span[\"f\"]] div[\"g\"] anon[span[\"h\"] \"\" em[p[\"i\"] anon[\"j\"]] \"\" \"\" b[\"l\"] \"\" span[\"m\" i[\"n\"]] \"\"]",
+      "]]"
+    ));
+  }
+}
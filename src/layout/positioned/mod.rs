@@ -0,0 +1,494 @@
+use std::ptr;
+use style::computed::{ComputedStyle, LengthPercentage, LengthPercentageOrAuto, Position, Px};
+use super::{LayoutBox, BoxType, LayoutContext};
+use super::block::{content_size, FlowPosition};
+use super::box_model::{ContainingBlock, Dimensions, Rect};
+use super::float::Floats;
+use super::inline::{FragmentKind, LineBox};
+
+/// How far `position: relative` moves a box from where it is laid out in a
+/// containing block `width` by `height`, per CSS 2.1 §9.4.3: by `left` or
+/// else back by `right`, and by `top` or else back by `bottom`.
+/// Percentages of a height that depends on the content count as `auto`.
+pub fn relative_offset(style: &ComputedStyle, width: Px, height: Option<Px>) -> (Px, Px) {
+  if style.position != Position::Relative {
+    return (0.0, 0.0);
+  }
+  let vertical = |length: &LengthPercentageOrAuto| match (length, height) {
+    (LengthPercentageOrAuto::LengthPercentage(length), Some(height)) => Some(length.resolve(height)),
+    (LengthPercentageOrAuto::LengthPercentage(length), None) => length.to_px(),
+    (LengthPercentageOrAuto::Auto, _) => None
+  };
+  let along = |start: Option<Px>, end: Option<Px>| match (start, end) {
+    (Some(start), _) => start,
+    (None, Some(end)) => -end,
+    (None, None) => 0.0
+  };
+  let inset = &style.inset;
+  (
+    along(inset.left.resolve(width), inset.right.resolve(width)),
+    along(vertical(&inset.top), vertical(&inset.bottom))
+  )
+}
+
+/// The containing blocks of the boxes positioned after normal flow.
+#[derive(Clone, Copy)]
+struct Containers<'l, 'a: 'l> {
+  absolute: ContainingBlock, // the padding box of the nearest positioned ancestor
+  fixed: ContainingBlock, // the viewport, where it is scrolled to
+  block: Rect, // the content box of the nearest block container, for sticky boxes
+  lines: &'l [LineBox<'a>] // the lines of that block container, for positioned inline boxes
+}
+
+impl<'a> LayoutBox<'a> {
+  /// Whether the box is absolutely positioned, which takes it out of
+  /// normal flow; fixed boxes are too.
+  pub fn is_absolutely_positioned(&self) -> bool {
+    matches!(self.box_type, BoxType::Block(node) if matches!(node.style.position, Position::Absolute | Position::Fixed))
+  }
+
+  /// Whether the box is positioned, and so the containing block of the
+  /// absolutely positioned boxes inside it.
+  pub fn is_positioned(&self) -> bool {
+    self.box_style().is_some_and(|style| style.position != Position::Static)
+  }
+
+  /// Moves a relatively positioned box by its offsets, after laying it out
+  /// in `containing_block`.
+  pub(super) fn position_relative(&mut self, containing_block: &ContainingBlock) {
+    if let Some(style) = self.box_style() {
+      let (dx, dy) = relative_offset(style, containing_block.width, containing_block.height);
+      if dx != 0.0 || dy != 0.0 {
+        self.translate(dx, dy);
+      }
+    }
+  }
+
+  /// Leaves an absolutely positioned box where it would be in normal flow
+  /// until it is positioned: at its static position, the top left corner
+  /// of its margin box, per CSS 2.1 §10.3.7.
+  pub(super) fn at_static_position(&mut self, x: Px, y: Px) {
+    self.dimensions = Dimensions {
+      content: Rect {
+        x,
+        y,
+        ..Rect::default()
+      },
+      ..Dimensions::default()
+    };
+  }
+
+  /// Positions the absolutely positioned, fixed and sticky boxes of a tree
+  /// laid out in normal flow in `initial`, the initial containing block,
+  /// with the viewport scrolled as far as `context` says.
+  pub(super) fn layout_positioned(&mut self, initial: &ContainingBlock, context: &LayoutContext) {
+    let (scroll_x, scroll_y) = context.scroll;
+    let containers = Containers {
+      absolute: *initial,
+      fixed: ContainingBlock {
+        x: initial.x + scroll_x,
+        y: initial.y + scroll_y,
+        ..*initial
+      },
+      block: self.dimensions.content,
+      lines: &[]
+    };
+    self.position_descendants(&containers, context);
+  }
+
+  fn position_descendants(&mut self, containers: &Containers<'_, 'a>, context: &LayoutContext) {
+    let mut inner = *containers;
+    if self.is_positioned() {
+      inner.absolute = self.containing_block_for_descendants(containers);
+    }
+    if !matches!(self.box_type, BoxType::Inline(_) | BoxType::AnonymousInline(_)) {
+      inner.block = self.dimensions.content;
+      inner.lines = &self.lines;
+    }
+
+    for child in self.children.iter_mut() {
+      match child.box_style().map(|style| style.position) {
+        Some(Position::Absolute) => child.layout_absolute(&inner.absolute, context),
+        Some(Position::Fixed) => child.layout_absolute(&inner.fixed, context),
+        Some(Position::Sticky) if !matches!(child.box_type, BoxType::Inline(_)) => {
+          let fixed = inner.fixed;
+          let scrollport = Rect {
+            x: fixed.x,
+            y: fixed.y,
+            width: fixed.width,
+            height: fixed.height.unwrap_or(0.0)
+          };
+          child.stick(inner.block, scrollport);
+        },
+        _ => ()
+      }
+      child.position_descendants(&inner, context);
+    }
+  }
+
+  /// The padding box of a positioned box. That of an inline box runs from
+  /// the start of its first fragment to the end of its last, per CSS 2.1
+  /// §10.1.
+  fn containing_block_for_descendants(&self, containers: &Containers) -> ContainingBlock {
+    let rect = match self.box_type {
+      BoxType::Inline(node) => {
+        let mut fragments = containers.lines.iter()
+          .flat_map(|line| line.fragments.iter())
+          .filter(|fragment| ptr::eq(fragment.node, node) && matches!(fragment.kind, FragmentKind::Inline { .. }));
+        let first = match fragments.next() {
+          Some(first) => first.rect,
+          None => return containers.absolute
+        };
+        let last = fragments.next_back().map_or(first, |last| last.rect);
+        let padding = &node.style.padding;
+        let basis = containers.block.width;
+        let (x, y) = (first.x - padding.left.resolve(basis), first.y - padding.top.resolve(basis));
+        Rect {
+          x,
+          y,
+          width: (last.right() + padding.right.resolve(basis) - x).max(0.0),
+          height: (last.bottom() + padding.bottom.resolve(basis) - y).max(0.0)
+        }
+      },
+      _ => self.dimensions.padding_box()
+    };
+    ContainingBlock {
+      x: rect.x,
+      y: rect.y,
+      width: rect.width,
+      height: Some(rect.height)
+    }
+  }
+
+  /// Lays out an absolutely positioned box against `containing_block`, per
+  /// CSS 2.1 §10.3.7 and §10.6.4, from its static position. Its width
+  /// shrinks to fit where it depends on the content.
+  fn layout_absolute(&mut self, containing_block: &ContainingBlock, context: &LayoutContext) {
+    let style = self.style();
+    let static_x = self.dimensions.content.x - containing_block.x;
+    let static_y = self.dimensions.content.y - containing_block.y;
+    let (width, height) = (containing_block.width, containing_block.height.unwrap_or(0.0));
+
+    let (min_content, max_content) = match style.width.is_auto() {
+      true => self.intrinsic_widths(context),
+      false => (0.0, 0.0)
+    };
+    let d = &mut self.dimensions;
+    d.padding.left = style.padding.left.resolve(width);
+    d.padding.right = style.padding.right.resolve(width);
+    d.border.left = style.border_width.left;
+    d.border.right = style.border_width.right;
+
+    let edges = d.padding.horizontal() + d.border.horizontal();
+    let horizontal = Axis {
+      start: style.inset.left.resolve(width),
+      end: style.inset.right.resolve(width),
+      margin_start: style.margin.left.resolve(width),
+      margin_end: style.margin.right.resolve(width),
+      edges,
+      available: width,
+      static_start: static_x,
+      centers_negative: false
+    };
+    let shrink_to_fit = |available: Px| max_content.min(available.max(min_content));
+    let content = |length: &LengthPercentage| content_size(style.box_sizing, length.resolve(width), edges);
+    let solve = |size: Option<Px>| horizontal.solve(size, &shrink_to_fit);
+    let mut used = solve(style.width.resolve(width).map(|size| content_size(style.box_sizing, size, edges)));
+    if let Some(max) = style.max_width.as_ref().map(&content) {
+      if used.size > max {
+        used = solve(Some(max));
+      }
+    }
+    let min = content(&style.min_width);
+    if used.size < min {
+      used = solve(Some(min));
+    }
+    d.content.width = used.size;
+    d.margin.left = used.margin_start;
+    d.margin.right = used.margin_end;
+    d.content.x = containing_block.x + used.start + used.margin_start + d.border.left + d.padding.left;
+
+    // what is in it goes from the top of the containing block, to move
+    // down with it once its height is known
+    self.layout_contents(containing_block, &mut FlowPosition::new(containing_block.y), true, &mut Floats::new(), context);
+
+    let d = self.dimensions;
+    let edges = d.padding.vertical() + d.border.vertical();
+    let vertical = Axis {
+      start: style.inset.top.resolve(height),
+      end: style.inset.bottom.resolve(height),
+      margin_start: style.margin.top.resolve(width),
+      margin_end: style.margin.bottom.resolve(width),
+      edges,
+      available: height,
+      static_start: static_y,
+      centers_negative: true
+    };
+    let content_height = d.content.height;
+    let content = |length: &LengthPercentage| content_size(style.box_sizing, length.resolve(height), edges);
+    let solve = |size: Option<Px>| vertical.solve(size, &|_| content_height);
+    let mut used = solve(style.height.resolve(height).map(|size| content_size(style.box_sizing, size, edges)));
+    if let Some(max) = style.max_height.as_ref().map(&content) {
+      if used.size > max {
+        used = solve(Some(max));
+      }
+    }
+    let min = content(&style.min_height);
+    if used.size < min {
+      used = solve(Some(min));
+    }
+
+    let y = containing_block.y + used.start + used.margin_start + d.border.top + d.padding.top;
+    self.translate(0.0, y - d.content.y);
+    self.dimensions.content.height = used.size;
+    self.dimensions.margin.top = used.margin_start;
+    self.dimensions.margin.bottom = used.margin_end;
+  }
+
+  /// Moves a sticky box as far as it takes to stay inset in `scrollport`
+  /// by its `top`, `right`, `bottom` and `left`, but no further than it
+  /// stays inside `container`, the content box of its block container,
+  /// per CSS Positioned Layout §3.4.
+  fn stick(&mut self, container: Rect, scrollport: Rect) {
+    let style = self.style();
+    let (border_box, margin_box) = (self.dimensions.border_box(), self.dimensions.margin_box());
+    let inset = &style.inset;
+    let dx = stick_along(
+      (inset.left.resolve(scrollport.width), inset.right.resolve(scrollport.width)),
+      (border_box.x, border_box.right()),
+      (margin_box.x, margin_box.right()),
+      (scrollport.x, scrollport.right()),
+      (container.x, container.right())
+    );
+    let dy = stick_along(
+      (inset.top.resolve(scrollport.height), inset.bottom.resolve(scrollport.height)),
+      (border_box.y, border_box.bottom()),
+      (margin_box.y, margin_box.bottom()),
+      (scrollport.y, scrollport.bottom()),
+      (container.y, container.bottom())
+    );
+    if dx != 0.0 || dy != 0.0 {
+      self.translate(dx, dy);
+    }
+  }
+}
+
+/// How far a sticky box moves along one axis, given its `insets` and where
+/// its border box, margin box, the scrollport and its container start and
+/// end: forward to stay its start inset into the scrollport, or else back
+/// to stay its end inset.
+fn stick_along(insets: (Option<Px>, Option<Px>), border: (Px, Px), margin: (Px, Px), scrollport: (Px, Px), container: (Px, Px)) -> Px {
+  if let Some(start) = insets.0 {
+    let limit = scrollport.0 + start;
+    if border.0 < limit {
+      return (limit - border.0).min(container.1 - margin.1).max(0.0);
+    }
+  }
+  if let Some(end) = insets.1 {
+    let limit = scrollport.1 - end;
+    if border.1 > limit {
+      return (limit - border.1).max(container.0 - margin.0).min(0.0);
+    }
+  }
+  0.0
+}
+
+/// What an absolutely positioned box and its containing block measure
+/// along one axis, where `None` is `auto`.
+struct Axis {
+  start: Option<Px>, // `left` or `top`
+  end: Option<Px>,
+  margin_start: Option<Px>,
+  margin_end: Option<Px>,
+  edges: Px, // padding and borders
+  available: Px, // the size of the containing block
+  static_start: Px, // where the margin box would start in normal flow
+  centers_negative: bool // whether margins that center it may be negative
+}
+
+/// The used values of an `Axis`, with `start` from the containing block.
+struct Used {
+  start: Px,
+  size: Px,
+  margin_start: Px,
+  margin_end: Px
+}
+
+impl Axis {
+  /// Solves the constraint of CSS 2.1 §10.3.7 or §10.6.4:
+  /// start + margin-start + edges + size + margin-end + end = available,
+  /// where `fit` is the size the content takes given the space for it.
+  fn solve(&self, size: Option<Px>, fit: &dyn Fn(Px) -> Px) -> Used {
+    let used = |start: Px, size: Px, margin_start: Px, margin_end: Px| Used {
+      start,
+      size,
+      margin_start,
+      margin_end
+    };
+    let available = self.available;
+    let (margin_start, margin_end) = (self.margin_start.unwrap_or(0.0), self.margin_end.unwrap_or(0.0));
+    let rest = self.edges + margin_start + margin_end;
+
+    match (self.start, size, self.end) {
+      (Some(start), Some(size), Some(end)) => {
+        let free = available - start - self.edges - size - end;
+        match (self.margin_start, self.margin_end) {
+          // centered, unless that takes a negative margin left to right
+          (None, None) if free < 0.0 && !self.centers_negative => used(start, size, 0.0, free),
+          (None, None) => used(start, size, free / 2.0, free / 2.0),
+          (None, Some(margin_end)) => used(start, size, free - margin_end, margin_end),
+          (Some(margin_start), None) => used(start, size, margin_start, free - margin_start),
+          // over-constrained, so the end inset is ignored
+          (Some(margin_start), Some(margin_end)) => used(start, size, margin_start, margin_end)
+        }
+      },
+      (None, None, None) => {
+        let start = self.static_start;
+        used(start, fit(available - start - rest), margin_start, margin_end)
+      },
+      (None, None, Some(end)) => {
+        let size = fit(available - end - rest);
+        used(available - end - rest - size, size, margin_start, margin_end)
+      },
+      (None, Some(size), None) => used(self.static_start, size, margin_start, margin_end),
+      (Some(start), None, None) => used(start, fit(available - start - rest), margin_start, margin_end),
+      (None, Some(size), Some(end)) => used(available - end - rest - size, size, margin_start, margin_end),
+      (Some(start), None, Some(end)) => used(start, (available - start - end - rest).max(0.0), margin_start, margin_end),
+      (Some(start), Some(size), None) => used(start, size, margin_start, margin_end)
+    }
+  }
+}
+
+/// A stacking context, per CSS 2.1 §9.9.1 and Appendix E: a layer that a
+/// box and what is in it paint as, at its `z_index` in the layer it is in.
+#[derive(Debug)]
+pub struct StackingContext<'b, 'a: 'b> {
+  pub layout_box: &'b LayoutBox<'a>,
+  pub z_index: i32,
+  /// Whether the positioned boxes inside it stack in it. A positioned box
+  /// of `z-index: auto` paints as a layer at level 0, but those inside it
+  /// stack in the stacking context it is in.
+  pub isolated: bool,
+  /// The layers in it in painting order: by z-index, and of the same one
+  /// in tree order.
+  pub children: Vec<StackingContext<'b, 'a>>
+}
+
+/// The stacking contexts of a laid out tree, that of the root element
+/// first. Positioned boxes with an integer `z-index` establish them, and
+/// fixed and sticky ones do whatever it is, per CSS Positioned Layout.
+pub fn stacking_context<'b, 'a>(root: &'b LayoutBox<'a>) -> StackingContext<'b, 'a> {
+  let mut children = Vec::new();
+  collect_layers(&root.children, &mut children);
+  // stable, so tree order within a z-index
+  children.sort_by_key(|layer| layer.z_index);
+  StackingContext {
+    layout_box: root,
+    z_index: 0,
+    isolated: true,
+    children
+  }
+}
+
+fn collect_layers<'b, 'a>(boxes: &'b [LayoutBox<'a>], layers: &mut Vec<StackingContext<'b, 'a>>) {
+  for child in boxes.iter() {
+    let style = match child.box_style() {
+      Some(style) if style.position != Position::Static => style,
+      _ => {
+        collect_layers(&child.children, layers);
+        continue;
+      }
+    };
+
+    let isolated = style.z_index.is_some() || matches!(style.position, Position::Fixed | Position::Sticky);
+    let mut layer = StackingContext {
+      layout_box: child,
+      z_index: style.z_index.unwrap_or(0),
+      isolated,
+      children: Vec::new()
+    };
+    if isolated {
+      collect_layers(&child.children, &mut layer.children);
+      layer.children.sort_by_key(|layer| layer.z_index);
+      layers.push(layer);
+    } else {
+      layers.push(layer);
+      collect_layers(&child.children, layers);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use layout::tests::lay_out;
+  use layout::box_model::Rect;
+  use layout::positioned::stacking_context;
+
+  #[test]
+  fn positions_boxes() {
+    lay_out(concat!(
+      "<html><body>",
+        "<div id=\"a\"></div>",
+        "<div id=\"p\">",
+          "<div id=\"b\"></div><div id=\"c\"></div><div id=\"d\"></div><div id=\"e\">aa</div><div id=\"o\"></div>",
+        "</div>",
+        "<div id=\"f\"></div>",
+        "<div id=\"s\"><div id=\"g\"></div></div>",
+        "<p>aa <i id=\"h\"></i>bb <b id=\"r\">cc</b></p>",
+      "</body></html>"
+    ), "
+      body, p { margin: 0 }
+      p { font-size: 10px; line-height: 10px }
+      #a { position: relative; left: 10px; bottom: 5px; height: 20px; z-index: 1 }
+      #p { position: relative; margin: 0 100px; padding: 10px; height: 80px; z-index: -1 }
+      #b { position: absolute; top: 5px; right: 5px; width: 50px; height: 10px }
+      #c { position: absolute; left: 0; right: 0; top: 0; bottom: 0; width: 100px; height: 20px; margin: auto }
+      #d { position: absolute; left: 20px; right: 20px; height: 10px }
+      #e { position: absolute; bottom: 0; font-size: 10px; line-height: 10px; z-index: 2 }
+      #o { position: absolute; left: 0; right: 0; width: 100px; height: 10px; margin: 0 }
+      #f { position: fixed; top: 10px; left: 10px; width: 10px; height: 10px }
+      #s { height: 200px }
+      #g { position: sticky; top: 0; height: 10px }
+      #h { position: absolute; width: 5px; height: 5px }
+      #r { position: relative; top: 2px }
+    ", (0.0, 150.0), |html| {
+      let body = &html.children[0];
+      let rect = |x, y, width, height| Rect { x, y, width, height };
+      // moved by its offsets, without moving what comes after it
+      assert_eq!(body.children[0].dimensions.border_box(), rect(10.0, -5.0, 800.0, 20.0));
+      let p = &body.children[1];
+      assert_eq!(p.dimensions.padding_box(), rect(100.0, 20.0, 600.0, 100.0));
+
+      // against the padding box of the positioned parent: from an inset,
+      // centered by auto margins, stretched between insets and shrunk to fit
+      let border_box = |i: usize| p.children[i].dimensions.border_box();
+      assert_eq!(border_box(0), rect(645.0, 25.0, 50.0, 10.0));
+      assert_eq!(border_box(1), rect(350.0, 60.0, 100.0, 20.0));
+      assert_eq!(p.children[1].dimensions.margin.left, 250.0);
+      assert_eq!(border_box(2), rect(120.0, 30.0, 560.0, 10.0));
+      assert_eq!(border_box(3), rect(110.0, 110.0, 10.0, 10.0));
+      // over-constrained, so `right` does not count
+      let o = &p.children[4].dimensions;
+      assert_eq!((o.margin_box(), o.margin.right), (rect(100.0, 30.0, 100.0, 10.0), 0.0));
+
+      // against the viewport where it is scrolled to, and stuck to its top
+      // as long as its container is in view
+      assert_eq!(body.children[2].dimensions.border_box(), rect(10.0, 160.0, 10.0, 10.0));
+      assert_eq!(body.children[3].children[0].dimensions.border_box(), rect(0.0, 150.0, 800.0, 10.0));
+
+      // at its static position in the line, and a relatively positioned
+      // inline box moved with what is in it
+      let line = &body.children[4].lines[0];
+      assert_eq!(body.children[4].children[1].dimensions.border_box(), rect(15.0, 320.0, 5.0, 5.0));
+      let rects: Vec<Rect> = line.fragments.iter().map(|fragment| fragment.rect).collect();
+      assert_eq!(rects, vec![rect(30.0, 322.0, 10.0, 10.0), rect(0.0, 320.0, 15.0, 10.0), rect(15.0, 320.0, 15.0, 10.0), rect(30.0, 322.0, 10.0, 10.0)]);
+
+      // in z-index order, with the relatively positioned parent below the
+      // root and its children stacked inside it
+      let stacking = stacking_context(html);
+      let z_indices: Vec<i32> = stacking.children.iter().map(|layer| layer.z_index).collect();
+      assert_eq!(z_indices, vec![-1, 0, 0, 0, 0, 1]);
+      assert_eq!(stacking.children[0].children.len(), 5);
+    });
+  }
+}
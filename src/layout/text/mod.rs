@@ -0,0 +1,264 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use font::Font;
+use helper::Error;
+use style::computed::{ComputedStyle, FontStyle, Px};
+
+/// The vertical metrics of a font at the size of a style, in px: its
+/// ascent above the baseline and descent below it, the gap it asks for
+/// between lines and the height of its lowercase letters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+  pub ascent: Px,
+  pub descent: Px,
+  pub line_gap: Px,
+  pub x_height: Px
+}
+
+/// Measures text for layout, in the first available font of a style per
+/// CSS 2.1 §10.8.1, or whichever font has the glyph for an advance.
+pub trait FontMetrics {
+  fn line_metrics(&self, style: &ComputedStyle) -> LineMetrics;
+
+  /// How far `c` advances the pen, before letter and word spacing.
+  fn advance(&self, c: char, style: &ComputedStyle) -> Px;
+
+  /// How much the advance of `left` changes when `right` follows it.
+  fn kerning(&self, _left: char, _right: char, _style: &ComputedStyle) -> Px {
+    0.0
+  }
+}
+
+/// The same metrics for every font and character, as fractions of the
+/// font size: monospace text that lays out the same on every machine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedMetrics {
+  pub ascent: Px,
+  pub descent: Px,
+  pub line_gap: Px,
+  pub advance: Px,
+  pub x_height: Px
+}
+
+impl Default for FixedMetrics {
+  fn default() -> Self {
+    FixedMetrics {
+      ascent: 0.8,
+      descent: 0.2,
+      line_gap: 0.2,
+      advance: 0.5,
+      x_height: 0.5
+    }
+  }
+}
+
+impl FontMetrics for FixedMetrics {
+  fn line_metrics(&self, style: &ComputedStyle) -> LineMetrics {
+    LineMetrics {
+      ascent: style.font_size * self.ascent,
+      descent: style.font_size * self.descent,
+      line_gap: style.font_size * self.line_gap,
+      x_height: style.font_size * self.x_height
+    }
+  }
+
+  fn advance(&self, c: char, style: &ComputedStyle) -> Px {
+    match c {
+      '\n' | '\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{feff}' | '\u{300}'..='\u{36f}' => 0.0,
+      _ => style.font_size * self.advance
+    }
+  }
+}
+
+/// Metrics of fonts read from files, matched to a style by its
+/// `font-family` list and then by weight and style, per CSS Fonts §5.
+/// Without any fonts it falls back to `FixedMetrics`.
+#[derive(Debug, Default)]
+pub struct FontFiles {
+  fonts: Vec<Font>,
+  fallback: FixedMetrics,
+  selected: RefCell<Vec<Selection>> // what `select` found for each family list, weight and style
+}
+
+/// A font family list, weight and whether italic, and the font they select.
+type Selection = (Vec<String>, u16, bool, Option<usize>);
+
+impl FontFiles {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add(&mut self, font: Font) {
+    self.fonts.push(font);
+    self.selected.get_mut().clear();
+  }
+
+  /// Adds the fonts under `dir`, skipping files that do not parse.
+  /// Symbolic links are followed to files but not to directories, which
+  /// could lead back up the tree. Returns how many were added.
+  pub fn load_dir(&mut self, dir: &Path) -> Result<usize, Error> {
+    let entries = fs::read_dir(dir)
+      .map_err(|e| Error(format!("Could not read font directory {}: {}", dir.display(), e)))?;
+
+    let mut paths: Vec<_> = entries
+      .filter_map(|entry| entry.ok().and_then(|entry| Some((entry.path(), entry.file_type().ok()?))))
+      .collect();
+    paths.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut count = 0;
+    for (path, file_type) in paths {
+      if file_type.is_dir() {
+        count += self.load_dir(&path).unwrap_or(0);
+        continue;
+      }
+      if file_type.is_symlink() && !fs::metadata(&path).is_ok_and(|metadata| metadata.is_file()) {
+        continue;
+      }
+      let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+      if matches!(extension.as_deref(), Some("ttf") | Some("otf") | Some("ttc")) {
+        if let Ok(font) = Font::open(&path) {
+          self.add(font);
+          count += 1;
+        }
+      }
+    }
+    Ok(count)
+  }
+
+  /// The first family of the list that there are fonts of, or else any
+  /// font, and of those the closest in style and then weight. Text is
+  /// measured a character at a time, so each selection is kept.
+  fn select(&self, style: &ComputedStyle) -> Option<&Font> {
+    let italic = style.font_style != FontStyle::Normal;
+    let mut selected = self.selected.borrow_mut();
+    let cached = selected.iter()
+      .find(|&&(ref families, weight, cached_italic, _)| weight == style.font_weight && cached_italic == italic && *families == style.font_family)
+      .map(|&(.., index)| index);
+    let index = match cached {
+      Some(index) => index,
+      None => {
+        let index = self.find(style, italic);
+        selected.push((style.font_family.clone(), style.font_weight, italic, index));
+        index
+      }
+    };
+    index.map(|index| &self.fonts[index])
+  }
+
+  fn find(&self, style: &ComputedStyle, italic: bool) -> Option<usize> {
+    let in_family = |family: &String| -> Vec<usize> {
+      (0..self.fonts.len()).filter(|&i| matches_family(&self.fonts[i].family, family)).collect()
+    };
+    let family = style.font_family.iter()
+      .map(in_family)
+      .find(|fonts| !fonts.is_empty())
+      .unwrap_or_else(|| (0..self.fonts.len()).collect());
+
+    family.into_iter().min_by_key(|&i| {
+      let font = &self.fonts[i];
+      ((font.italic != italic) as u32, (font.weight as i32 - style.font_weight as i32).unsigned_abs())
+    })
+  }
+}
+
+/// Whether a font of `name` is of the `font-family` `family`; the generic
+/// families go by what the names of their usual fonts contain.
+fn matches_family(name: &str, family: &str) -> bool {
+  let name = name.to_lowercase();
+  match family.to_lowercase().as_str() {
+    "monospace" => name.contains("mono") || name.contains("courier"),
+    "serif" => name.contains("serif") && !name.contains("sans") || name.contains("times"),
+    "sans-serif" => name.contains("sans") || name.contains("arial") || name.contains("helvetica"),
+    family => name == family
+  }
+}
+
+impl FontMetrics for FontFiles {
+  fn line_metrics(&self, style: &ComputedStyle) -> LineMetrics {
+    let font = match self.select(style) {
+      Some(font) => font,
+      None => return self.fallback.line_metrics(style)
+    };
+    let scale = style.font_size / font.units_per_em as Px;
+    LineMetrics {
+      ascent: font.ascender as Px * scale,
+      descent: -font.descender as Px * scale,
+      line_gap: font.line_gap as Px * scale,
+      x_height: font.x_height.map_or(style.font_size * self.fallback.x_height, |height| height as Px * scale)
+    }
+  }
+
+  fn advance(&self, c: char, style: &ComputedStyle) -> Px {
+    let selected = match self.select(style) {
+      Some(font) => font,
+      None => return self.fallback.advance(c, style)
+    };
+    // characters the font has no glyph for come from the first that has
+    // one, and are its missing glyph where none does
+    let (font, glyph) = selected.glyph_index(c).map(|glyph| (selected, glyph))
+      .or_else(|| self.fonts.iter().find_map(|font| font.glyph_index(c).map(|glyph| (font, glyph))))
+      .unwrap_or((selected, 0));
+    font.advance_width(glyph) as Px * style.font_size / font.units_per_em as Px
+  }
+
+  /// Pairs only kern when the selected font has both glyphs.
+  fn kerning(&self, left: char, right: char, style: &ComputedStyle) -> Px {
+    let font = match self.select(style) {
+      Some(font) => font,
+      None => return 0.0
+    };
+    match (font.glyph_index(left), font.glyph_index(right)) {
+      (Some(left), Some(right)) => font.kerning(left, right) as Px * style.font_size / font.units_per_em as Px,
+      _ => 0.0
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use font::tests::test_font;
+  use style::PropertyMap;
+
+  #[test]
+  fn measures_fonts() {
+    let mut style = ComputedStyle::new(&PropertyMap::new());
+    style.font_size = 20.0;
+    style.font_family = vec!["Missing".to_string(), "test sans".to_string()];
+
+    let fixed = FixedMetrics::default();
+    assert_eq!(fixed.advance('x', &style), 10.0);
+    assert_eq!(FontFiles::new().line_metrics(&style), fixed.line_metrics(&style));
+
+    let mut fonts = FontFiles::new();
+    fonts.add(Font::parse(test_font()).unwrap());
+    assert_eq!(fonts.line_metrics(&style), LineMetrics { ascent: 16.0, descent: 4.0, line_gap: 2.0, x_height: 10.0 });
+    let advances: Vec<Px> = "ab z".chars().map(|c| fonts.advance(c, &style)).collect();
+    assert_eq!(advances, vec![12.0, 8.0, 5.0, 10.0]); // z is the missing glyph
+    assert_eq!((fonts.kerning('a', 'b', &style), fonts.kerning('b', 'a', &style)), (-1.0, 0.0));
+    assert_eq!(fonts.kerning('a', 'z', &style), 0.0);
+
+    assert!(matches_family("DejaVu Sans Mono", "monospace"));
+    assert!(!matches_family("DejaVu Sans", "serif"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn loads_font_directories() {
+    use std::os::unix::fs::symlink;
+
+    let dir = std::env::temp_dir().join(format!("browser-fonts-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("nested/test.ttf"), test_font()).unwrap();
+    fs::write(dir.join("broken.ttf"), b"not a font").unwrap();
+    symlink(&dir, dir.join("nested/loop")).unwrap();
+    symlink(dir.join("nested/test.ttf"), dir.join("linked.ttf")).unwrap();
+
+    // the loop back up is not followed, the link to a file is
+    let mut fonts = FontFiles::new();
+    let count = fonts.load_dir(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(count.unwrap(), 2);
+  }
+}
@@ -2,10 +2,14 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read};
 
+#[macro_use]
 mod helper;
 
-mod dom;
-mod css;
+pub mod dom;
+pub mod css;
+pub mod style;
+pub mod layout;
+pub mod font;
 
 type AttrMap = HashMap<String, Option<String>>;
 
@@ -19,16 +23,36 @@ pub fn init() -> Result<(), helper::Error> {
   f.read_to_string(&mut contents)
     .expect("something went wrong reading the file");
 
-  dom::parse_dom(contents.to_string())?;
+  let document = dom::parse_dom(contents.to_string())?;
 
-  let mut f = File::open("example/index.css")
-    .expect("file not found");
+  let device = css::media::Device {
+    media_type: css::media::MediaType::Screen,
+    viewport_width: 800.0,
+    viewport_height: 600.0
+  };
+  let loader = dom::stylesheets::FileLoader::new("example");
+  let stylesheets = dom::stylesheets::collect_stylesheets(&document, &loader, &device);
 
-  let mut contents = String::new();
-  f.read_to_string(&mut contents)
-    .expect("something went wrong reading the file");
+  println!("[init stylesheets] {} loaded, {} errors", stylesheets.stylesheets.len(), stylesheets.errors.len());
+
+  let sheets: Vec<&css::parser::Stylesheet> = stylesheets.stylesheets.iter().collect();
+  let styled = style::style_tree(&document, &sheets, &device, &style::StyleOptions::default());
 
-  css::parse_stylesheet(contents.to_string())?;
+  fn count(node: &style::StyledNode) -> usize {
+    1 + node.children.iter().map(count).sum::<usize>()
+  }
+  println!("[init style] {} styled nodes", count(&styled));
+
+  // measured the same on every machine, without system fonts
+  let context = layout::LayoutContext {
+    fonts: &layout::text::FixedMetrics::default(),
+    scroll: (0.0, 0.0)
+  };
+
+  if let Some(mut root) = layout::layout_tree(&styled) {
+    layout::layout(&mut root, &layout::box_model::ContainingBlock::initial(&device), &context);
+    println!("[init layout] root {:?}", root.dimensions.margin_box());
+  }
 
   Ok(())
 }
@@ -0,0 +1,120 @@
+use style::matching::Element;
+
+const KEY_BITS: u32 = 12;
+const KEY_MASK: u32 = (1 << KEY_BITS) - 1;
+
+/// A counting Bloom filter over the tags, ids and classes of the ancestors
+/// of the element being styled. A descendant selector whose ancestor
+/// compounds name something the filter has never seen cannot match, so it
+/// is rejected without walking up the tree.
+///
+/// Every hash sets two counters, one from its low and one from its high
+/// bits. Counters stick once they saturate, so removing stays exact for
+/// everything else.
+pub struct BloomFilter {
+  counters: Box<[u8; 1 << KEY_BITS]>
+}
+
+impl BloomFilter {
+  pub fn new() -> Self {
+    BloomFilter {
+      counters: Box::new([0; 1 << KEY_BITS])
+    }
+  }
+
+  pub fn insert(&mut self, hash: u32) {
+    for key in keys(hash).iter() {
+      let counter = &mut self.counters[*key];
+      *counter = counter.saturating_add(1);
+    }
+  }
+
+  pub fn remove(&mut self, hash: u32) {
+    for key in keys(hash).iter() {
+      let counter = &mut self.counters[*key];
+      if *counter != u8::MAX {
+        *counter -= 1;
+      }
+    }
+  }
+
+  /// `false` only if `hash` was certainly never inserted.
+  pub fn might_contain(&self, hash: u32) -> bool {
+    keys(hash).iter().all(|key| self.counters[*key] != 0)
+  }
+
+  pub fn insert_element(&mut self, element: &Element) {
+    for hash in element_hashes(element) {
+      self.insert(hash);
+    }
+  }
+
+  pub fn remove_element(&mut self, element: &Element) {
+    for hash in element_hashes(element) {
+      self.remove(hash);
+    }
+  }
+}
+
+impl Default for BloomFilter {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn keys(hash: u32) -> [usize; 2] {
+  [(hash & KEY_MASK) as usize, ((hash >> KEY_BITS) & KEY_MASK) as usize]
+}
+
+/// FNV-1a, salted with a prefix so a tag, an id and a class with the same
+/// name hash apart.
+fn hash(prefix: u8, name: &str) -> u32 {
+  let mut hash: u32 = 0x811c_9dc5;
+  for byte in Some(prefix).into_iter().chain(name.bytes()) {
+    hash ^= byte as u32;
+    hash = hash.wrapping_mul(0x0100_0193);
+  }
+  hash
+}
+
+pub fn tag_hash(tag_name: &str) -> u32 {
+  hash(b'<', &tag_name.to_ascii_lowercase())
+}
+
+pub fn id_hash(id: &str) -> u32 {
+  hash(b'#', id)
+}
+
+pub fn class_hash(class: &str) -> u32 {
+  hash(b'.', class)
+}
+
+fn element_hashes<'e>(element: &Element<'e>) -> impl Iterator<Item = u32> + 'e {
+  let data = element.data;
+  Some(tag_hash(&data.tag_name)).into_iter()
+    .chain(data.id().map(id_hash))
+    .chain(data.classes().map(class_hash))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_insertions() {
+    let mut filter = BloomFilter::new();
+    let (div, note) = (tag_hash("DIV"), class_hash("note"));
+
+    filter.insert(div);
+    filter.insert(div);
+    filter.insert(note);
+    assert!(filter.might_contain(tag_hash("div")));
+    assert!(!filter.might_contain(id_hash("div")));
+
+    filter.remove(div);
+    assert!(filter.might_contain(div));
+    filter.remove(div);
+    assert!(!filter.might_contain(div));
+    assert!(filter.might_contain(note));
+  }
+}
@@ -0,0 +1,730 @@
+use css::{calc::Calc, lexer::StyleNumber, media::Device, parser::{LengthContext, Unit, Value}};
+use super::PropertyMap;
+
+pub type Px = StyleNumber;
+
+/// The font size of `medium` and of the root's parent.
+pub const MEDIUM_FONT_SIZE: Px = 16.0;
+
+/// Computed lengths are in px, so resolving them needs no context.
+const ABSOLUTE: LengthContext = LengthContext {
+  font_size: 0.0,
+  root_font_size: 0.0,
+  viewport_width: 0.0,
+  viewport_height: 0.0
+};
+
+/// Converts specified values to computed values: lengths to px, `font-size`
+/// and `font-weight` to absolute values, `currentcolor` to the `color` and
+/// `display` blockified for floats, absolutely positioned boxes and the
+/// root. `parent` holds the parent's computed values, `None` for the root,
+/// and `root_font_size` the root's computed font size once known.
+pub fn compute_values(specified: &PropertyMap, parent: Option<&PropertyMap>, root_font_size: Option<Px>, device: &Device) -> PropertyMap {
+  let inherited = |name: &str| parent.and_then(|parent| parent.get(name));
+
+  let parent_font_size = match inherited("font-size") {
+    Some(&Value::Length(size, _)) => size,
+    _ => MEDIUM_FONT_SIZE
+  };
+  let mut context = LengthContext {
+    font_size: parent_font_size,
+    root_font_size: root_font_size.unwrap_or(MEDIUM_FONT_SIZE),
+    viewport_width: device.viewport_width,
+    viewport_height: device.viewport_height
+  };
+  let font_size = font_size(&specified["font-size"], &context);
+  context.font_size = font_size;
+  context.root_font_size = root_font_size.unwrap_or(font_size);
+
+  // `currentcolor` in `color` itself means the inherited color
+  let color = match specified["color"] {
+    Value::Keyword(_) => inherited("color").cloned().unwrap_or(Value::Color { r: 0, g: 0, b: 0, a: 255 }),
+    ref color => color.clone()
+  };
+
+  let mut computed: PropertyMap = specified.iter()
+    .map(|(name, value)| {
+      let value = match &name[..] {
+        "font-size" => Value::Length(font_size, Unit::Px),
+        "color" => color.clone(),
+        "font-weight" => Value::Integer(font_weight(value, inherited("font-weight"))),
+        "line-height" => match *value {
+          Value::Percentage(num) => Value::Length(font_size * num / 100.0, Unit::Px),
+          Value::Calc(ref calc) => match calc.resolve(&context, Some(font_size)) {
+            Some(num) => Value::Length(num, Unit::Px),
+            None => value.to_computed(&context)
+          },
+          _ => value.to_computed(&context)
+        },
+        "outline-width" => line_width(value, specified.get("outline-style"), &context),
+        name if name.starts_with("border-") && name.ends_with("-width") => {
+          let style = name.replace("-width", "-style");
+          line_width(value, specified.get(&style), &context)
+        },
+        _ => match *value {
+          Value::Keyword(ref keyword) if keyword == "currentcolor" => color.clone(),
+          _ => value.to_computed(&context)
+        }
+      };
+      (name.clone(), value)
+    })
+    .collect();
+
+  // CSS 2.1 §9.7
+  let keyword = |computed: &PropertyMap, name: &str| match computed.get(name) {
+    Some(Value::Keyword(keyword)) => keyword.clone(),
+    _ => String::new()
+  };
+  let positioned = matches!(&keyword(&computed, "position")[..], "absolute" | "fixed");
+  if positioned {
+    computed.insert("float".to_string(), Value::Keyword("none".to_string()));
+  }
+  if positioned || keyword(&computed, "float") != "none" || parent.is_none() {
+    let display = match Display::from_keyword(&keyword(&computed, "display")).blockified() {
+      // `contents` generates no box to float or position, but the root's box is a block
+      Display::Contents if parent.is_none() => Display::Block,
+      display => display
+    };
+    computed.insert("display".to_string(), Value::Keyword(display.keyword().to_string()));
+  }
+
+  computed
+}
+
+/// `context.font_size` is the parent's font size.
+fn font_size(value: &Value, context: &LengthContext) -> Px {
+  let parent = context.font_size;
+  let scale = |factor: StyleNumber| MEDIUM_FONT_SIZE * factor;
+
+  match *value {
+    Value::Keyword(ref keyword) => match &keyword[..] {
+      "xx-small" => scale(3.0 / 5.0),
+      "x-small" => scale(3.0 / 4.0),
+      "small" => scale(8.0 / 9.0),
+      "large" => scale(6.0 / 5.0),
+      "x-large" => scale(3.0 / 2.0),
+      "xx-large" => scale(2.0),
+      "larger" => parent * 1.2,
+      "smaller" => parent / 1.2,
+      _ => MEDIUM_FONT_SIZE
+    },
+    _ => value.resolve(context, Some(parent)).unwrap_or(parent)
+  }
+}
+
+/// `bolder` and `lighter` follow the table of CSS Fonts Level 4.
+fn font_weight(value: &Value, parent: Option<&Value>) -> i32 {
+  let parent = match parent {
+    Some(&Value::Integer(weight)) => weight,
+    _ => 400
+  };
+
+  match *value {
+    Value::Integer(weight) => weight,
+    Value::Keyword(ref keyword) => match &keyword[..] {
+      "bold" => 700,
+      "bolder" => match parent {
+        0..=349 => 400,
+        350..=549 => 700,
+        550..=899 => 900,
+        _ => parent
+      },
+      "lighter" => match parent {
+        0..=99 => parent,
+        100..=549 => 100,
+        550..=749 => 400,
+        _ => 700
+      },
+      _ => 400
+    },
+    _ => 400
+  }
+}
+
+/// Border and outline widths are zero without a visible style.
+fn line_width(value: &Value, style: Option<&Value>, context: &LengthContext) -> Value {
+  let width = match (style, value) {
+    (Some(Value::Keyword(style)), _) if style == "none" || style == "hidden" => 0.0,
+    (_, Value::Keyword(keyword)) => match &keyword[..] {
+      "thin" => 1.0,
+      "thick" => 5.0,
+      _ => 3.0
+    },
+    _ => value.resolve(context, None).unwrap_or(0.0).max(0.0)
+  };
+
+  Value::Length(width, Unit::Px)
+}
+
+/// A keyword enum, parsed from computed values. Unknown keywords fall back
+/// to the first variant, which is the initial value.
+macro_rules! keywords {
+  ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident = $keyword:expr),+ }) => {
+    $(#[$meta])*
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum $name {
+      $($variant),+
+    }
+
+    impl $name {
+      pub fn from_keyword(keyword: &str) -> Self {
+        $(
+          if keyword == $keyword {
+            return $name::$variant;
+          }
+        )+
+        [$($name::$variant),+][0]
+      }
+
+      pub fn keyword(&self) -> &'static str {
+        match *self {
+          $($name::$variant => $keyword),+
+        }
+      }
+    }
+  };
+}
+
+keywords! {
+  pub enum Display {
+    Inline = "inline",
+    Block = "block",
+    ListItem = "list-item",
+    InlineBlock = "inline-block",
+    Table = "table",
+    InlineTable = "inline-table",
+    TableRowGroup = "table-row-group",
+    TableHeaderGroup = "table-header-group",
+    TableFooterGroup = "table-footer-group",
+    TableRow = "table-row",
+    TableColumnGroup = "table-column-group",
+    TableColumn = "table-column",
+    TableCell = "table-cell",
+    TableCaption = "table-caption",
+    None = "none",
+    FlowRoot = "flow-root",
+    Contents = "contents",
+    Flex = "flex",
+    InlineFlex = "inline-flex",
+    Grid = "grid",
+    InlineGrid = "inline-grid"
+  }
+}
+
+impl Display {
+  /// The block-level equivalent of an inline-level or internal table
+  /// display, per CSS 2.1 §9.7 and CSS Display.
+  pub fn blockified(self) -> Display {
+    match self {
+      Display::InlineTable => Display::Table,
+      Display::InlineFlex => Display::Flex,
+      Display::InlineGrid => Display::Grid,
+      Display::Inline | Display::InlineBlock | Display::TableRowGroup | Display::TableHeaderGroup
+        | Display::TableFooterGroup | Display::TableRow | Display::TableColumnGroup
+        | Display::TableColumn | Display::TableCell | Display::TableCaption => Display::Block,
+      display => display
+    }
+  }
+
+  pub fn is_inline_level(self) -> bool {
+    matches!(self, Display::Inline | Display::InlineBlock | Display::InlineTable | Display::InlineFlex | Display::InlineGrid)
+  }
+}
+
+keywords! {
+  pub enum Position {
+    Static = "static",
+    Relative = "relative",
+    Absolute = "absolute",
+    Fixed = "fixed",
+    Sticky = "sticky"
+  }
+}
+
+keywords! {
+  pub enum Float {
+    None = "none",
+    Left = "left",
+    Right = "right"
+  }
+}
+
+keywords! {
+  pub enum Clear {
+    None = "none",
+    Left = "left",
+    Right = "right",
+    Both = "both"
+  }
+}
+
+keywords! {
+  pub enum BoxSizing {
+    ContentBox = "content-box",
+    BorderBox = "border-box"
+  }
+}
+
+keywords! {
+  pub enum Overflow {
+    Visible = "visible",
+    Hidden = "hidden",
+    Scroll = "scroll",
+    Auto = "auto"
+  }
+}
+
+keywords! {
+  pub enum Visibility {
+    Visible = "visible",
+    Hidden = "hidden",
+    Collapse = "collapse"
+  }
+}
+
+keywords! {
+  pub enum BorderStyle {
+    None = "none",
+    Hidden = "hidden",
+    Dotted = "dotted",
+    Dashed = "dashed",
+    Solid = "solid",
+    Double = "double",
+    Groove = "groove",
+    Ridge = "ridge",
+    Inset = "inset",
+    Outset = "outset"
+  }
+}
+
+keywords! {
+  pub enum TextAlign {
+    Left = "left",
+    Right = "right",
+    Center = "center",
+    Justify = "justify"
+  }
+}
+
+keywords! {
+  pub enum TextTransform {
+    None = "none",
+    Capitalize = "capitalize",
+    Uppercase = "uppercase",
+    Lowercase = "lowercase"
+  }
+}
+
+keywords! {
+  pub enum WhiteSpace {
+    Normal = "normal",
+    Pre = "pre",
+    Nowrap = "nowrap",
+    PreWrap = "pre-wrap",
+    PreLine = "pre-line"
+  }
+}
+
+keywords! {
+  pub enum FontStyle {
+    Normal = "normal",
+    Italic = "italic",
+    Oblique = "oblique"
+  }
+}
+
+keywords! {
+  pub enum ListStylePosition {
+    Outside = "outside",
+    Inside = "inside"
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+  pub a: u8
+}
+
+impl Color {
+  pub const TRANSPARENT: Color = Color { r: 0, g: 0, b: 0, a: 0 };
+  pub const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+
+  fn from_value(value: Option<&Value>) -> Option<Color> {
+    match value {
+      Some(&Value::Color { r, g, b, a }) => Some(Color { r, g, b, a }),
+      _ => None
+    }
+  }
+}
+
+/// A length in px or a percentage, resolved against a basis during layout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LengthPercentage {
+  Length(Px),
+  Percentage(StyleNumber),
+  Calc(Box<Calc>) // mixing lengths and percentages
+}
+
+impl LengthPercentage {
+  pub fn zero() -> Self {
+    LengthPercentage::Length(0.0)
+  }
+
+  fn from_value(value: Option<&Value>) -> Option<Self> {
+    match value? {
+      Value::Length(num, _) => Some(LengthPercentage::Length(*num)),
+      Value::Percentage(num) => Some(LengthPercentage::Percentage(*num)),
+      Value::Number(num) if *num == 0.0 => Some(LengthPercentage::zero()),
+      Value::Calc(calc) => Some(LengthPercentage::Calc(calc.clone())),
+      _ => None
+    }
+  }
+
+  pub fn resolve(&self, basis: Px) -> Px {
+    match *self {
+      LengthPercentage::Length(px) => px,
+      LengthPercentage::Percentage(num) => basis * num / 100.0,
+      LengthPercentage::Calc(ref calc) => calc.resolve(&ABSOLUTE, Some(basis)).unwrap_or(0.0)
+    }
+  }
+
+  /// The length in px if it does not depend on a basis.
+  pub fn to_px(&self) -> Option<Px> {
+    match *self {
+      LengthPercentage::Length(px) => Some(px),
+      LengthPercentage::Percentage(_) => None,
+      LengthPercentage::Calc(ref calc) => calc.resolve(&ABSOLUTE, None)
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LengthPercentageOrAuto {
+  Auto,
+  LengthPercentage(LengthPercentage)
+}
+
+impl LengthPercentageOrAuto {
+  fn from_value(value: Option<&Value>) -> Self {
+    LengthPercentage::from_value(value)
+      .map_or(LengthPercentageOrAuto::Auto, LengthPercentageOrAuto::LengthPercentage)
+  }
+
+  /// `None` for `auto`.
+  pub fn resolve(&self, basis: Px) -> Option<Px> {
+    match *self {
+      LengthPercentageOrAuto::Auto => None,
+      LengthPercentageOrAuto::LengthPercentage(ref length) => Some(length.resolve(basis))
+    }
+  }
+
+  pub fn is_auto(&self) -> bool {
+    *self == LengthPercentageOrAuto::Auto
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineHeight {
+  Normal,
+  Number(StyleNumber), // multiplied by each element's own font size
+  Length(Px)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerticalAlign {
+  Keyword(&'static str), // baseline, sub, super, top, text-top, middle, bottom, text-bottom
+  LengthPercentage(LengthPercentage) // percentages refer to the line height
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlexBasis {
+  Auto,
+  Content,
+  LengthPercentage(LengthPercentage)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextDecoration {
+  pub underline: bool,
+  pub overline: bool,
+  pub line_through: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sides<T> {
+  pub top: T,
+  pub right: T,
+  pub bottom: T,
+  pub left: T
+}
+
+impl<T> Sides<T> {
+  fn from_fn<F: Fn(&str) -> T>(f: F) -> Self {
+    Sides {
+      top: f("top"),
+      right: f("right"),
+      bottom: f("bottom"),
+      left: f("left")
+    }
+  }
+}
+
+/// Horizontal and vertical radii of each corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Corners {
+  pub top_left: (LengthPercentage, LengthPercentage),
+  pub top_right: (LengthPercentage, LengthPercentage),
+  pub bottom_right: (LengthPercentage, LengthPercentage),
+  pub bottom_left: (LengthPercentage, LengthPercentage)
+}
+
+/// The computed values layout and painting use, typed. Properties without
+/// a field here are only available as computed `Value`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedStyle {
+  pub display: Display,
+  pub position: Position,
+  pub float: Float,
+  pub clear: Clear,
+  pub inset: Sides<LengthPercentageOrAuto>, // top, right, bottom and left
+  pub z_index: Option<i32>, // `None` for `auto`
+
+  pub width: LengthPercentageOrAuto,
+  pub height: LengthPercentageOrAuto,
+  pub min_width: LengthPercentage,
+  pub min_height: LengthPercentage,
+  pub max_width: Option<LengthPercentage>, // `None` for `none`
+  pub max_height: Option<LengthPercentage>,
+  pub box_sizing: BoxSizing, // which box `width`, `height` and their limits size
+
+  pub margin: Sides<LengthPercentageOrAuto>,
+  pub padding: Sides<LengthPercentage>,
+  pub border_width: Sides<Px>,
+  pub border_style: Sides<BorderStyle>,
+  pub border_color: Sides<Color>,
+  pub border_radius: Corners,
+  pub outline_width: Px,
+  pub outline_style: BorderStyle,
+  pub outline_color: Option<Color>, // `None` for `invert`
+
+  pub overflow: Overflow,
+  pub visibility: Visibility,
+  pub color: Color,
+  pub background_color: Color,
+  pub background_image: Option<String>,
+
+  pub font_family: Vec<String>,
+  pub font_size: Px,
+  pub font_style: FontStyle,
+  pub font_small_caps: bool,
+  pub font_weight: u16,
+  pub line_height: LineHeight,
+
+  pub letter_spacing: Px, // `normal` is zero
+  pub word_spacing: Px,
+  pub text_align: TextAlign,
+  pub text_indent: LengthPercentage,
+  pub text_transform: TextTransform,
+  pub text_decoration: TextDecoration,
+  pub white_space: WhiteSpace,
+  pub vertical_align: VerticalAlign,
+
+  pub list_style_type: String,
+  pub list_style_position: ListStylePosition,
+  pub list_style_image: Option<String>,
+
+  pub flex_grow: StyleNumber,
+  pub flex_shrink: StyleNumber,
+  pub flex_basis: FlexBasis
+}
+
+const VERTICAL_ALIGN: &[&str] = &["baseline", "sub", "super", "top", "text-top", "middle", "bottom", "text-bottom"];
+
+impl ComputedStyle {
+  /// Types the values returned by `compute_values`.
+  pub fn new(values: &PropertyMap) -> Self {
+    let value = |name: &str| values.get(name);
+    let keyword = |name: &str| match value(name) {
+      Some(Value::Keyword(keyword)) => &keyword[..],
+      _ => ""
+    };
+    let px = |name: &str| match value(name) {
+      Some(&Value::Length(px, _)) => px,
+      _ => 0.0
+    };
+    let number = |name: &str| match value(name) {
+      Some(&Value::Number(num)) => num,
+      _ => 0.0
+    };
+    let length = |name: &str| LengthPercentage::from_value(value(name)).unwrap_or_else(LengthPercentage::zero);
+    let url = |name: &str| match value(name) {
+      Some(Value::Url(url)) => Some(url.clone()),
+      _ => None
+    };
+    let color = Color::from_value(value("color")).unwrap_or(Color::BLACK);
+    let radius = |name: &str| match value(name) {
+      Some(Value::List(values)) => (
+        LengthPercentage::from_value(values.first()).unwrap_or_else(LengthPercentage::zero),
+        LengthPercentage::from_value(values.get(1)).unwrap_or_else(LengthPercentage::zero)
+      ),
+      value => {
+        let radius = LengthPercentage::from_value(value).unwrap_or_else(LengthPercentage::zero);
+        (radius.clone(), radius)
+      }
+    };
+    let decorations = |decoration: &str| match value("text-decoration") {
+      Some(Value::List(values)) => values.contains(&Value::Keyword(decoration.to_string())),
+      Some(Value::Keyword(keyword)) => keyword == decoration,
+      _ => false
+    };
+
+    ComputedStyle {
+      display: Display::from_keyword(keyword("display")),
+      position: Position::from_keyword(keyword("position")),
+      float: Float::from_keyword(keyword("float")),
+      clear: Clear::from_keyword(keyword("clear")),
+      inset: Sides::from_fn(|side| LengthPercentageOrAuto::from_value(value(side))),
+      z_index: match value("z-index") {
+        Some(&Value::Integer(z_index)) => Some(z_index),
+        _ => None
+      },
+
+      width: LengthPercentageOrAuto::from_value(value("width")),
+      height: LengthPercentageOrAuto::from_value(value("height")),
+      min_width: length("min-width"),
+      min_height: length("min-height"),
+      max_width: LengthPercentage::from_value(value("max-width")),
+      max_height: LengthPercentage::from_value(value("max-height")),
+      box_sizing: BoxSizing::from_keyword(keyword("box-sizing")),
+
+      margin: Sides::from_fn(|side| LengthPercentageOrAuto::from_value(value(&format!("margin-{}", side)))),
+      padding: Sides::from_fn(|side| length(&format!("padding-{}", side))),
+      border_width: Sides::from_fn(|side| px(&format!("border-{}-width", side))),
+      border_style: Sides::from_fn(|side| BorderStyle::from_keyword(keyword(&format!("border-{}-style", side)))),
+      border_color: Sides::from_fn(|side| Color::from_value(value(&format!("border-{}-color", side))).unwrap_or(color)),
+      border_radius: Corners {
+        top_left: radius("border-top-left-radius"),
+        top_right: radius("border-top-right-radius"),
+        bottom_right: radius("border-bottom-right-radius"),
+        bottom_left: radius("border-bottom-left-radius")
+      },
+      outline_width: px("outline-width"),
+      outline_style: BorderStyle::from_keyword(keyword("outline-style")),
+      outline_color: Color::from_value(value("outline-color")),
+
+      overflow: Overflow::from_keyword(keyword("overflow")),
+      visibility: Visibility::from_keyword(keyword("visibility")),
+      color,
+      background_color: Color::from_value(value("background-color")).unwrap_or(Color::TRANSPARENT),
+      background_image: url("background-image"),
+
+      font_family: match value("font-family") {
+        Some(Value::CommaList(families)) => families.iter()
+          .filter_map(|family| match *family {
+            Value::String(ref name) | Value::Keyword(ref name) => Some(name.clone()),
+            _ => None
+          })
+          .collect(),
+        _ => Vec::new()
+      },
+      font_size: px("font-size"),
+      font_style: FontStyle::from_keyword(keyword("font-style")),
+      font_small_caps: keyword("font-variant") == "small-caps",
+      font_weight: match value("font-weight") {
+        Some(&Value::Integer(weight)) => weight as u16,
+        _ => 400
+      },
+      line_height: match value("line-height") {
+        Some(&Value::Number(num)) => LineHeight::Number(num),
+        Some(&Value::Length(px, _)) => LineHeight::Length(px),
+        _ => LineHeight::Normal
+      },
+
+      letter_spacing: px("letter-spacing"),
+      word_spacing: px("word-spacing"),
+      text_align: TextAlign::from_keyword(keyword("text-align")),
+      text_indent: length("text-indent"),
+      text_transform: TextTransform::from_keyword(keyword("text-transform")),
+      text_decoration: TextDecoration {
+        underline: decorations("underline"),
+        overline: decorations("overline"),
+        line_through: decorations("line-through")
+      },
+      white_space: WhiteSpace::from_keyword(keyword("white-space")),
+      vertical_align: match VERTICAL_ALIGN.iter().find(|&&i| i == keyword("vertical-align")) {
+        Some(keyword) => VerticalAlign::Keyword(keyword),
+        None => LengthPercentage::from_value(value("vertical-align"))
+          .map_or(VerticalAlign::Keyword("baseline"), VerticalAlign::LengthPercentage)
+      },
+
+      list_style_type: keyword("list-style-type").to_string(),
+      list_style_position: ListStylePosition::from_keyword(keyword("list-style-position")),
+      list_style_image: url("list-style-image"),
+
+      flex_grow: number("flex-grow"),
+      flex_shrink: number("flex-shrink"),
+      flex_basis: match keyword("flex-basis") {
+        "content" => FlexBasis::Content,
+        "auto" => FlexBasis::Auto,
+        _ => FlexBasis::LengthPercentage(length("flex-basis"))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::media::MediaType;
+  use dom::parse_dom;
+  use style::{style_tree, StyleOptions};
+
+  #[test]
+  fn computes_absolute_values() {
+    let root = parse_dom("<div style=\"font-size: 20px; color: green; line-height: 1.5\">
+      <p style=\"font-size: 2em; border-top: thin solid; line-height: 150%; font-weight: bolder; margin-left: 1rem; width: 10vw; border-left: max(1px, 0.5em) solid; border-right: calc(1px + 1em) solid; outline: calc(2px + .5em) solid\">
+        <span style=\"float: left; font-weight: lighter; font-size: larger; border-bottom-width: 4px; padding-left: 10%\">text</span>
+      </p>
+    </div>".to_string()).unwrap();
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+
+    let div = style_tree(&root, &[], &device, &StyleOptions {
+      user_agent_stylesheet: false,
+      ..StyleOptions::default()
+    });
+    let p = div.children.iter().find(|i| i.style.font_size != 20.0).unwrap();
+    let span = p.children.iter().find(|i| i.style.float != Float::None).unwrap();
+    let text = &span.children[0];
+
+    assert_eq!(div.style.display, Display::Block);
+    assert_eq!(div.style.line_height, LineHeight::Number(1.5));
+
+    assert_eq!(p.style.font_size, 40.0);
+    assert_eq!(p.style.line_height, LineHeight::Length(60.0));
+    assert_eq!(p.style.font_weight, 700);
+    assert_eq!(p.style.margin.left, LengthPercentageOrAuto::LengthPercentage(LengthPercentage::Length(20.0)));
+    assert_eq!(p.style.width.resolve(0.0), Some(80.0));
+    assert_eq!(p.style.border_width.top, 1.0);
+    assert_eq!(p.style.border_color.top, Color { r: 0, g: 128, b: 0, a: 255 });
+    assert_eq!(p.style.border_width.bottom, 0.0);
+    assert_eq!((p.style.border_width.left, p.style.border_width.right, p.style.outline_width), (20.0, 41.0, 22.0));
+
+    assert_eq!(span.style.display, Display::Block);
+    assert_eq!(span.style.font_weight, 400);
+    assert_eq!(span.style.font_size, 48.0);
+    assert_eq!(span.style.border_width.bottom, 0.0);
+    assert_eq!(span.style.padding.left.resolve(300.0), 30.0);
+
+    // computed values inherit, so relative lengths are not resolved again
+    assert_eq!(text.style.font_size, 48.0);
+    assert_eq!(text.style.line_height, LineHeight::Length(60.0));
+    assert_eq!(text.style.display, Display::Inline);
+  }
+}
@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet};
+use AttrMap;
+use css::selectors::{Selector, Compound, Combinator, PseudoClass};
+use dom::{Node, NodeType};
+use style::matching::Element;
+use style::rule_hash::RuleHash;
+use style::sharing::SharedStyle;
+
+/// Which elements a change to an element can restyle, relative to it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Invalidation {
+  pub element: bool,
+  pub descendants: bool,
+  pub siblings: bool, // the following siblings and their descendants
+  pub has: bool, // the ancestors and earlier siblings a `:has()` can be anchored at
+  pub document: bool
+}
+
+impl Invalidation {
+  fn element() -> Self {
+    Invalidation {
+      element: true,
+      ..Self::default()
+    }
+  }
+
+  pub fn union(&mut self, other: Invalidation) {
+    self.element |= other.element;
+    self.descendants |= other.descendants;
+    self.siblings |= other.siblings;
+    self.has |= other.has;
+    self.document |= other.document;
+  }
+
+  pub fn is_empty(&self) -> bool {
+    *self == Self::default()
+  }
+}
+
+/// The invalidation sets of a set of selectors: what changing a class, an
+/// id or an attribute of an element, or its children, can restyle.
+#[derive(Debug, Default)]
+pub struct InvalidationMap {
+  classes: HashMap<String, Invalidation>,
+  ids: HashMap<String, Invalidation>,
+  attributes: HashMap<String, Invalidation>, // by lowercase name
+  has: bool // whether any selector uses `:has()`
+}
+
+impl InvalidationMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add_selector(&mut self, selector: &Selector) {
+    self.add_compounds(&selector.compounds, Invalidation::element());
+  }
+
+  /// `subject` is what the rightmost compound invalidates.
+  fn add_compounds(&mut self, compounds: &[Compound], subject: Invalidation) {
+    for (i, compound) in compounds.iter().enumerate() {
+      let position = match compounds.get(i + 1) {
+        None => subject,
+        Some(next) => {
+          let mut position = match next.combinator {
+            Combinator::Child | Combinator::Descendant => Invalidation {
+              descendants: true,
+              ..Invalidation::default()
+            },
+            Combinator::AdjacentSibling | Combinator::GeneralSibling => Invalidation {
+              siblings: true,
+              ..Invalidation::default()
+            }
+          };
+          position.union(Invalidation {
+            element: false,
+            ..subject
+          });
+          position
+        }
+      };
+      self.add_compound(compound, position);
+    }
+  }
+
+  fn add_compound(&mut self, compound: &Compound, position: Invalidation) {
+    for id in compound.id.iter() {
+      self.ids.entry(id.clone()).or_default().union(position);
+    }
+    for class in compound.class.iter() {
+      self.classes.entry(class.clone()).or_default().union(position);
+    }
+    for attribute in compound.attributes.iter() {
+      self.add_attribute(&attribute.name, position);
+    }
+
+    for pseudo in compound.pseudo.iter() {
+      match *pseudo {
+        PseudoClass::Link => self.add_attribute("href", position),
+        PseudoClass::Checked => {
+          self.add_attribute("checked", position);
+          self.add_attribute("selected", position);
+        },
+        PseudoClass::Disabled | PseudoClass::Enabled => self.add_attribute("disabled", position),
+        PseudoClass::Not(ref list) | PseudoClass::Is(ref list) | PseudoClass::Where(ref list) => {
+          for selector in list.iter() {
+            self.add_compounds(&selector.compounds, position);
+          }
+        },
+        PseudoClass::Has(ref list) => {
+          self.has = true;
+          // a change anywhere in the argument can flip the anchor, and
+          // what depends on an anchor that is not the subject is not
+          // tracked
+          let anchor = if position == Invalidation::element() {
+            Invalidation {
+              has: true,
+              ..Invalidation::default()
+            }
+          } else {
+            Invalidation {
+              document: true,
+              ..Invalidation::default()
+            }
+          };
+          for compound in list.iter().flat_map(|selector| selector.compounds.iter()) {
+            self.add_compound(compound, anchor);
+          }
+        },
+        _ => ()
+      }
+    }
+  }
+
+  fn add_attribute(&mut self, name: &str, position: Invalidation) {
+    self.attributes.entry(name.to_ascii_lowercase()).or_default().union(position);
+  }
+
+  /// What changing the attributes of an element from `old` to `new`
+  /// invalidates.
+  pub fn attributes_changed(&self, old: &AttrMap, new: &AttrMap) -> Invalidation {
+    let mut invalidation = Invalidation::default();
+    let names: HashSet<&String> = old.keys().chain(new.keys()).collect();
+
+    for name in names.into_iter().filter(|&name| old.get(name) != new.get(name)) {
+      let name = name.to_ascii_lowercase();
+      if let Some(&by_attribute) = self.attributes.get(&name) {
+        invalidation.union(by_attribute);
+      }
+
+      let value = |attributes: &AttrMap| attributes.iter()
+        .find(|&(key, _)| key.eq_ignore_ascii_case(&name))
+        .and_then(|(_, value)| value.clone())
+        .unwrap_or_default();
+
+      match &name[..] {
+        "style" => invalidation.union(Invalidation::element()),
+        "id" => {
+          for id in [value(old), value(new)].iter() {
+            if let Some(&by_id) = self.ids.get(id) {
+              invalidation.union(by_id);
+            }
+          }
+        },
+        "class" => {
+          let (old, new) = (value(old), value(new));
+          let (old, new): (HashSet<&str>, HashSet<&str>) = (old.split_whitespace().collect(), new.split_whitespace().collect());
+          for class in old.symmetric_difference(&new) {
+            if let Some(&by_class) = self.classes.get(*class) {
+              invalidation.union(by_class);
+            }
+          }
+        },
+        _ => ()
+      }
+    }
+
+    invalidation
+  }
+
+  /// What adding or removing a child of an element invalidates. Its
+  /// children all move, and the element itself can start or stop matching
+  /// `:empty` or `:has()`.
+  pub fn children_changed(&self) -> Invalidation {
+    Invalidation {
+      element: true,
+      descendants: true,
+      has: self.has,
+      ..Invalidation::default()
+    }
+  }
+}
+
+/// Which parts of a cached style need recomputing.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RestyleHint {
+  pub element: bool,
+  pub descendants: bool
+}
+
+/// The styles of a styled tree without its nodes, so the nodes can change
+/// and be restyled from it.
+#[derive(Debug)]
+pub struct CachedStyles {
+  pub style: SharedStyle,
+  pub hint: RestyleHint,
+  pub children: Vec<CachedStyles>
+}
+
+impl CachedStyles {
+  fn at_mut(&mut self, path: &[usize]) -> Option<&mut CachedStyles> {
+    path.iter().try_fold(self, |styles, &i| styles.children.get_mut(i))
+  }
+}
+
+/// Turns what changed under `root` since `styles` was computed into
+/// restyle hints: the attribute snapshots and changed children recorded
+/// on the nodes, which are cleared, and the elements `changed_rules`
+/// could apply to.
+pub fn invalidate(root: &mut Node, styles: &mut CachedStyles, map: &InvalidationMap, changed_rules: &RuleHash) {
+  let mut changes = Vec::new();
+  collect_changes(root, &mut Vec::new(), map, changed_rules, &mut changes);
+
+  for (path, invalidation) in changes {
+    apply(styles, &path, invalidation);
+  }
+}
+
+fn collect_changes(node: &mut Node, path: &mut Vec<usize>, map: &InvalidationMap, changed_rules: &RuleHash, changes: &mut Vec<(Vec<usize>, Invalidation)>) {
+  let mut invalidation = Invalidation::default();
+
+  if node.take_children_changed() {
+    invalidation.union(map.children_changed());
+  }
+  if let NodeType::Element(ref mut data) = node.node_type {
+    if let Some(old) = data.take_snapshot() {
+      invalidation.union(map.attributes_changed(&old, data.attributes()));
+    }
+  }
+  if let Some(element) = Element::root(node) {
+    if !changed_rules.candidates(&element).is_empty() {
+      invalidation.union(Invalidation::element());
+    }
+  }
+
+  if !invalidation.is_empty() {
+    changes.push((path.clone(), invalidation));
+  }
+
+  for (i, child) in node.children.iter_mut().enumerate() {
+    path.push(i);
+    collect_changes(child, path, map, changed_rules, changes);
+    path.pop();
+  }
+}
+
+fn apply(root: &mut CachedStyles, path: &[usize], invalidation: Invalidation) {
+  if invalidation.document {
+    hint(root, &[], true, true);
+  }
+  hint(root, path, invalidation.element, invalidation.descendants);
+
+  if invalidation.siblings {
+    if let Some((&index, parent)) = path.split_last() {
+      let count = root.at_mut(parent).map_or(0, |parent| parent.children.len());
+      for next in index + 1..count {
+        hint(root, &[parent, &[next]].concat(), true, true);
+      }
+    }
+  }
+
+  if invalidation.has {
+    for depth in 0..path.len() {
+      hint(root, &path[..depth], true, false);
+    }
+    // the earlier siblings of the element and of each of its ancestors
+    for depth in 1..=path.len() {
+      let (parent, index) = (&path[..depth - 1], path[depth - 1]);
+      for previous in 0..index {
+        hint(root, &[parent, &[previous]].concat(), true, false);
+      }
+    }
+  }
+}
+
+/// Paths through children that changed can miss, but those children are
+/// restyled regardless.
+fn hint(root: &mut CachedStyles, path: &[usize], element: bool, descendants: bool) {
+  if let Some(styles) = root.at_mut(path) {
+    styles.hint.element |= element;
+    styles.hint.descendants |= descendants;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::{lexer::lex, selectors::parse_selector_list};
+  use css::media::{Device, MediaType};
+  use dom::parse_dom;
+  use style::{style_tree, StyleOptions};
+
+  fn map(selectors: &str) -> InvalidationMap {
+    let mut map = InvalidationMap::new();
+    for selector in parse_selector_list(&lex(selectors.to_string()).unwrap()).unwrap().iter() {
+      map.add_selector(selector);
+    }
+    map
+  }
+
+  fn attributes(pairs: &[(&str, &str)]) -> AttrMap {
+    pairs.iter().map(|&(name, value)| (name.to_string(), Some(value.to_string()))).collect()
+  }
+
+  #[test]
+  fn derives_invalidation_sets() {
+    let map = map(".a p, .b + li, #c, [data-x], .d:has(> .e), :is(.f .g), .h:has(.i) p");
+    let class_change = |class: &str| map.attributes_changed(&attributes(&[]), &attributes(&[("class", class)]));
+    let only = |set: fn(&mut Invalidation)| {
+      let mut invalidation = Invalidation::default();
+      set(&mut invalidation);
+      invalidation
+    };
+
+    assert_eq!(class_change("a"), only(|i| i.descendants = true));
+    assert_eq!(class_change("b"), only(|i| i.siblings = true));
+    assert_eq!(class_change("d g"), only(|i| i.element = true));
+    assert_eq!(class_change("e"), only(|i| i.has = true));
+    assert_eq!(class_change("f"), only(|i| i.descendants = true));
+    assert_eq!(class_change("i"), only(|i| i.document = true));
+    assert!(class_change("unused").is_empty());
+
+    let old = attributes(&[("id", "c"), ("data-x", "1"), ("class", "a b")]);
+    assert_eq!(map.attributes_changed(&old, &attributes(&[("id", "c"), ("class", "b a")])), only(|i| i.element = true));
+    assert!(map.attributes_changed(&old, &old).is_empty());
+  }
+
+  #[test]
+  fn hints_affected_elements() {
+    let mut root = parse_dom("<ul><li>a</li><li>b</li><li>c</li><li>d</li></ul>".to_string()).unwrap();
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+    let mut styles = style_tree(&root, &[], &device, &StyleOptions::default()).detach();
+
+    root.children[1].element_data_mut().unwrap().set_attribute("class", Some("b".to_string()));
+    invalidate(&mut root, &mut styles, &map(".b ~ li, ul:has(.b)"), &RuleHash::new());
+
+    let hints: Vec<RestyleHint> = styles.children.iter().map(|i| i.hint).collect();
+    let (clean, sibling) = (RestyleHint::default(), RestyleHint {
+      element: true,
+      descendants: true
+    });
+    assert_eq!(hints, vec![RestyleHint { element: true, descendants: false }, clean, sibling, sibling]);
+    assert_eq!(styles.hint, RestyleHint { element: true, descendants: false });
+    assert!(root.children[1].element_data_mut().unwrap().take_snapshot().is_none());
+  }
+}
@@ -0,0 +1,292 @@
+use std::ptr;
+use css::selectors::{Selector, Compound, Combinator, AttributeSelector, AttributeOperator, PseudoClass};
+use dom::{Node, NodeType, ElementData};
+
+/// An element together with its position in the tree. The DOM has no
+/// parent pointers, so the chain of ancestors lives on the stack of the
+/// traversal that reached the element.
+#[derive(Debug, Clone, Copy)]
+pub struct Element<'a> {
+  pub node: &'a Node,
+  pub data: &'a ElementData,
+  index: usize, // among the parent's children, text included
+  parent: Option<&'a Element<'a>>
+}
+
+impl<'a> Element<'a> {
+  /// The root of a tree, `None` for text.
+  pub fn root(node: &'a Node) -> Option<Element<'a>> {
+    Element::new(node, 0, None)
+  }
+
+  fn new(node: &'a Node, index: usize, parent: Option<&'a Element<'a>>) -> Option<Element<'a>> {
+    match node.node_type {
+      NodeType::Element(ref data) => Some(Element {
+        node,
+        data,
+        index,
+        parent
+      }),
+      NodeType::Text(_) => None
+    }
+  }
+
+  pub fn parent(&self) -> Option<Element<'a>> {
+    self.parent.cloned()
+  }
+
+  pub fn ancestors(&self) -> Ancestors<'a> {
+    Ancestors {
+      next: self.parent
+    }
+  }
+
+  /// The child elements, each linked back to this one.
+  pub fn children<'b>(&'b self) -> impl Iterator<Item = Element<'b>> {
+    self.node.children.iter()
+      .enumerate()
+      .filter_map(move |(i, child)| self.child(child, i))
+  }
+
+  /// `node` as the child at `index`, `None` for text.
+  pub fn child<'b>(&'b self, node: &'b Node, index: usize) -> Option<Element<'b>> {
+    Element::new(node, index, Some(self))
+  }
+
+  /// The element siblings before this one, closest first.
+  pub fn previous_siblings(&self) -> impl Iterator<Item = Element<'a>> {
+    let (siblings, parent) = self.siblings();
+    siblings[..self.index.min(siblings.len())].iter()
+      .enumerate()
+      .rev()
+      .filter_map(move |(i, sibling)| Element::new(sibling, i, parent))
+  }
+
+  /// The element siblings after this one, closest first.
+  pub fn next_siblings(&self) -> impl Iterator<Item = Element<'a>> {
+    let (siblings, parent) = self.siblings();
+    let start = (self.index + 1).min(siblings.len());
+    siblings[start..].iter()
+      .enumerate()
+      .filter_map(move |(i, sibling)| Element::new(sibling, start + i, parent))
+  }
+
+  fn siblings(&self) -> (&'a [Node], Option<&'a Element<'a>>) {
+    match self.parent {
+      Some(parent) => (&parent.node.children, self.parent),
+      None => (&[], None)
+    }
+  }
+
+  fn is(&self, other: &Element) -> bool {
+    ptr::eq(self.node, other.node)
+  }
+
+  fn has_tag(&self, names: &[&str]) -> bool {
+    names.iter().any(|name| self.data.tag_name.eq_ignore_ascii_case(name))
+  }
+
+  /// Attribute names are case insensitive in HTML.
+  fn attribute(&self, name: &str) -> Option<&'a str> {
+    self.data.attributes().iter()
+      .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_ref().map_or("", |value| &value[..]))
+  }
+}
+
+pub struct Ancestors<'a> {
+  next: Option<&'a Element<'a>>
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+  type Item = Element<'a>;
+
+  fn next(&mut self) -> Option<Element<'a>> {
+    let element = self.next?;
+    self.next = element.parent;
+    Some(*element)
+  }
+}
+
+/// Whether the selector matches the element, starting from the rightmost
+/// compound. Selectors with a pseudo-element match no element.
+pub fn matches(selector: &Selector, element: &Element) -> bool {
+  selector.pseudo_element.is_none() && matches_compounds(&selector.compounds, element, None)
+}
+
+/// `anchor` is the element a relative selector of `:has()` is relative to.
+fn matches_compounds(compounds: &[Compound], element: &Element, anchor: Option<&Element>) -> bool {
+  let (last, rest) = match compounds.split_last() {
+    Some(split) => split,
+    None => return false
+  };
+
+  if !matches_compound(last, element) {
+    return false;
+  }
+  if rest.is_empty() && anchor.is_none() {
+    return true;
+  }
+
+  let mut related: Box<dyn Iterator<Item = Element>> = match last.combinator {
+    Combinator::Child => Box::new(element.parent().into_iter()),
+    Combinator::Descendant => Box::new(element.ancestors()),
+    Combinator::AdjacentSibling => Box::new(element.previous_siblings().take(1)),
+    Combinator::GeneralSibling => Box::new(element.previous_siblings())
+  };
+
+  match anchor {
+    Some(anchor) if rest.is_empty() => related.any(|related| related.is(anchor)),
+    _ => related.any(|related| matches_compounds(rest, &related, anchor))
+  }
+}
+
+fn matches_compound(compound: &Compound, element: &Element) -> bool {
+  let data = element.data;
+
+  compound.tag_name.as_ref().is_none_or(|tag_name| data.tag_name.eq_ignore_ascii_case(tag_name))
+    && compound.id.iter().all(|id| data.id() == Some(&id[..]))
+    && compound.class.iter().all(|class| data.classes().any(|i| i == class))
+    && compound.attributes.iter().all(|attribute| matches_attribute(attribute, element))
+    && compound.pseudo.iter().all(|pseudo| matches_pseudo_class(pseudo, element))
+}
+
+fn matches_attribute(selector: &AttributeSelector, element: &Element) -> bool {
+  let actual = match element.attribute(&selector.name) {
+    Some(actual) => actual,
+    None => return false
+  };
+  let (operator, expected) = match selector.operator {
+    Some((operator, ref expected)) => (operator, expected),
+    None => return true
+  };
+
+  let (actual, expected) = if selector.case_insensitive {
+    (actual.to_lowercase(), expected.to_lowercase())
+  } else {
+    (actual.to_string(), expected.clone())
+  };
+
+  match operator {
+    AttributeOperator::Equals => actual == expected,
+    AttributeOperator::Includes => actual.split_whitespace().any(|i| i == expected),
+    AttributeOperator::DashMatch => actual == expected || actual.starts_with(&format!("{}-", expected)),
+    AttributeOperator::Prefix => !expected.is_empty() && actual.starts_with(&expected[..]),
+    AttributeOperator::Suffix => !expected.is_empty() && actual.ends_with(&expected[..]),
+    AttributeOperator::Substring => !expected.is_empty() && actual.contains(&expected[..])
+  }
+}
+
+/// Whether the 1-based `position` is `an+b` for some non-negative `n`.
+fn nth(a: i32, b: i32, position: usize) -> bool {
+  let offset = position as i32 - b;
+  match a {
+    0 => offset == 0,
+    a => offset % a == 0 && offset / a >= 0
+  }
+}
+
+const FORM_ELEMENTS: &[&str] = &["button", "fieldset", "input", "optgroup", "option", "select", "textarea"];
+
+/// Dynamic pseudo-classes never match as nothing is hovered, focused or
+/// visited yet.
+fn matches_pseudo_class(pseudo: &PseudoClass, element: &Element) -> bool {
+  let same_type = |sibling: &Element| sibling.data.tag_name.eq_ignore_ascii_case(&element.data.tag_name);
+  let before = || element.previous_siblings().count() + 1;
+  let after = || element.next_siblings().count() + 1;
+  let before_of_type = || element.previous_siblings().filter(same_type).count() + 1;
+  let after_of_type = || element.next_siblings().filter(same_type).count() + 1;
+
+  match *pseudo {
+    PseudoClass::FirstChild => before() == 1,
+    PseudoClass::LastChild => after() == 1,
+    PseudoClass::OnlyChild => before() == 1 && after() == 1,
+    PseudoClass::FirstOfType => before_of_type() == 1,
+    PseudoClass::LastOfType => after_of_type() == 1,
+    PseudoClass::OnlyOfType => before_of_type() == 1 && after_of_type() == 1,
+    PseudoClass::NthChild(a, b) => nth(a, b, before()),
+    PseudoClass::NthLastChild(a, b) => nth(a, b, after()),
+    PseudoClass::NthOfType(a, b) => nth(a, b, before_of_type()),
+    PseudoClass::NthLastOfType(a, b) => nth(a, b, after_of_type()),
+    PseudoClass::Root => element.parent().is_none(),
+    PseudoClass::Empty => element.node.children.iter().all(|child| match child.node_type {
+      NodeType::Text(ref text) => text.trim().is_empty(),
+      NodeType::Element(_) => false
+    }),
+    PseudoClass::Link => element.has_tag(&["a", "area", "link"]) && element.attribute("href").is_some(),
+    PseudoClass::Visited | PseudoClass::Hover | PseudoClass::Active | PseudoClass::Focus | PseudoClass::FocusWithin => false,
+    PseudoClass::Checked => {
+      (element.has_tag(&["input"]) && element.attribute("checked").is_some())
+        || (element.has_tag(&["option"]) && element.attribute("selected").is_some())
+    },
+    PseudoClass::Disabled => element.has_tag(FORM_ELEMENTS) && element.attribute("disabled").is_some(),
+    PseudoClass::Enabled => element.has_tag(FORM_ELEMENTS) && element.attribute("disabled").is_none(),
+    PseudoClass::Not(ref list) => !list.iter().any(|selector| matches(selector, element)),
+    PseudoClass::Is(ref list) | PseudoClass::Where(ref list) => list.iter().any(|selector| matches(selector, element)),
+    PseudoClass::Has(ref list) => list.iter().any(|selector| matches_relative(selector, element))
+  }
+}
+
+/// Searches the elements a relative selector can reach from its anchor:
+/// descendants, or for a leading sibling combinator the following siblings
+/// and their descendants.
+fn matches_relative(selector: &Selector, anchor: &Element) -> bool {
+  let matches = |element: &Element| matches_compounds(&selector.compounds, element, Some(anchor));
+
+  match selector.compounds.first().map(|i| i.combinator) {
+    Some(Combinator::Descendant) | Some(Combinator::Child) => any_descendant(anchor, &matches),
+    Some(Combinator::AdjacentSibling) | Some(Combinator::GeneralSibling) => {
+      anchor.next_siblings().any(|sibling| matches(&sibling) || any_descendant(&sibling, &matches))
+    },
+    None => false
+  }
+}
+
+fn any_descendant<F: Fn(&Element) -> bool>(element: &Element, predicate: &F) -> bool {
+  element.children().any(|child| predicate(&child) || any_descendant(&child, predicate))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::{lexer::lex, selectors::parse_selector_list};
+  use dom::parse_dom;
+
+  /// The `id`s of the elements matching `query`, in document order.
+  fn select(root: &Node, query: &str) -> Vec<String> {
+    let selectors = parse_selector_list(&lex(query.to_string()).unwrap()).unwrap();
+    let mut found = Vec::new();
+
+    fn visit(element: &Element, selectors: &[Selector], found: &mut Vec<String>) {
+      if selectors.iter().any(|selector| matches(selector, element)) {
+        found.push(element.data.id().unwrap_or("").to_string());
+      }
+      for child in element.children() {
+        visit(&child, selectors, found);
+      }
+    }
+
+    visit(&Element::root(root).unwrap(), &selectors, &mut found);
+    found
+  }
+
+  #[test]
+  fn matches_right_to_left() {
+    let root = parse_dom("<div id=\"a\" class=\"x y\">
+      <p id=\"b\" lang=\"en-US\">text</p>
+      <span id=\"c\"><p id=\"d\"></p></span>
+      <p id=\"e\" class=\"y\"><img id=\"f\" /></p>
+    </div>".to_string()).unwrap();
+
+    assert_eq!(select(&root, "div p"), vec!["b", "d", "e"]);
+    assert_eq!(select(&root, "div > p"), vec!["b", "e"]);
+    assert_eq!(select(&root, ".x.y > span + p"), vec!["e"]);
+    assert_eq!(select(&root, "#b ~ *"), vec!["c", "e"]);
+    assert_eq!(select(&root, "[lang|=en], [class~=x]"), vec!["a", "b"]);
+    assert_eq!(select(&root, "p:first-child, p:last-of-type"), vec!["b", "d", "e"]);
+    assert_eq!(select(&root, ":root > :nth-child(2n+1)"), vec!["b", "e"]);
+    assert_eq!(select(&root, "p:not(.y):empty"), vec!["d"]);
+    assert_eq!(select(&root, "p:has(> img), span:has(+ .y), div:has(span p)"), vec!["a", "c", "e"]);
+    assert_eq!(select(&root, "p::before, p:hover"), Vec::<String>::new());
+  }
+}
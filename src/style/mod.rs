@@ -0,0 +1,704 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use css::{cascade::{LayerOrder, Origin, Priority}, parser::{Declaration, Stylesheet, Value}, selectors::{Selector, Specificity}};
+use css::{media::Device, properties::{self, CSS_WIDE_KEYWORDS}, variables::{self, CustomProperties}};
+use css::parser::Rule;
+use dom::Node;
+
+pub mod matching;
+pub mod computed;
+pub mod user_agent;
+pub mod bloom;
+pub mod rule_hash;
+pub mod sharing;
+pub mod invalidation;
+pub mod parallel;
+
+use self::matching::Element;
+use self::computed::{ComputedStyle, Px};
+use self::bloom::BloomFilter;
+use self::rule_hash::RuleHash;
+use self::sharing::{SharedStyle, SharingCache};
+use self::invalidation::{CachedStyles, InvalidationMap, RestyleHint};
+
+pub use self::user_agent::user_agent_stylesheet;
+
+pub type PropertyMap = HashMap<String, Value>;
+
+/// A node with the specified and computed value of every property. Text
+/// nodes inherit the values of their parent element.
+#[derive(Debug)]
+pub struct StyledNode<'a> {
+  pub node: &'a Node,
+  pub specified_values: PropertyMap,
+  pub computed_values: PropertyMap,
+  pub style: ComputedStyle, // the computed values, typed
+  pub custom_properties: CustomProperties,
+  pub children: Vec<StyledNode<'a>>
+}
+
+impl<'a> StyledNode<'a> {
+  fn new(node: &'a Node, style: SharedStyle) -> Self {
+    StyledNode {
+      node,
+      specified_values: style.specified_values,
+      computed_values: style.computed_values,
+      style: style.style,
+      custom_properties: style.custom_properties,
+      children: Vec::new()
+    }
+  }
+
+  fn shared_style(&self) -> SharedStyle {
+    SharedStyle {
+      specified_values: self.specified_values.clone(),
+      computed_values: self.computed_values.clone(),
+      style: self.style.clone(),
+      custom_properties: self.custom_properties.clone()
+    }
+  }
+
+  /// The styles of the tree, releasing its nodes so they can change. See
+  /// `restyle`.
+  pub fn detach(self) -> CachedStyles {
+    CachedStyles {
+      hint: RestyleHint::default(),
+      children: self.children.into_iter().map(StyledNode::detach).collect(),
+      style: SharedStyle {
+        specified_values: self.specified_values,
+        computed_values: self.computed_values,
+        style: self.style,
+        custom_properties: self.custom_properties
+      }
+    }
+  }
+}
+
+/// The style rules of a set of stylesheets in order of appearance, with
+/// the layer rank of each rule within its origin. Their selectors are
+/// looked up through a rule hash.
+pub struct RuleSet<'s> {
+  rules: Vec<IndexedRule<'s>>,
+  hash: RuleHash<'s>,
+  revalidation: Vec<&'s Selector>, // see `sharing::needs_revalidation`
+  invalidation: InvalidationMap
+}
+
+struct IndexedRule<'s> {
+  declarations: &'s [Declaration],
+  origin: Origin,
+  layer_rank: usize
+}
+
+/// A declaration that applies to an element, with what decides whether it
+/// wins the cascade.
+struct Candidate<'s> {
+  origin: Origin,
+  priority: Priority,
+  specificity: Specificity,
+  order: usize,
+  declaration: &'s Declaration
+}
+
+impl<'s> RuleSet<'s> {
+  pub fn new(stylesheets: &[&'s Stylesheet]) -> Self {
+    let mut layers: HashMap<Origin, LayerOrder> = HashMap::new();
+    for &origin in [Origin::UserAgent, Origin::User, Origin::Author].iter() {
+      let declared = stylesheets.iter()
+        .filter(|sheet| sheet.origin == origin)
+        .flat_map(|sheet| sheet.layers.iter());
+      layers.insert(origin, LayerOrder::new(declared));
+    }
+
+    let mut rules = Vec::new();
+    let mut hash = RuleHash::new();
+    let mut revalidation = Vec::new();
+    let mut invalidation = InvalidationMap::new();
+
+    for sheet in stylesheets.iter() {
+      for rule in sheet.rules.iter() {
+        for selector in rule.selectors.iter() {
+          hash.insert(rules.len(), selector);
+          if sharing::needs_revalidation(selector) {
+            revalidation.push(selector);
+          }
+          invalidation.add_selector(selector);
+        }
+        rules.push(IndexedRule {
+          declarations: &rule.declarations,
+          origin: sheet.origin,
+          layer_rank: layers[&sheet.origin].rank(rule.layer.as_ref().map(|i| &i[..]))
+        });
+      }
+    }
+
+    Self {
+      rules,
+      hash,
+      revalidation,
+      invalidation
+    }
+  }
+
+  /// The declarations of matching rules and of the `style` attribute.
+  /// `ancestors` holds the ancestors of the element, when known.
+  fn candidates<'c>(&'c self, element: &Element<'c>, ancestors: Option<&BloomFilter>) -> Vec<Candidate<'c>> {
+    let mut candidates = Vec::new();
+
+    let selectors = self.hash.candidates(element);
+    for selectors in selectors.chunk_by(|a, b| a.rule == b.rule) {
+      // a rule applies with its most specific matching selector
+      let specificity = selectors.iter()
+        .filter(|selector| ancestors.is_none_or(|filter| selector.might_match(filter)))
+        .filter(|selector| matching::matches(selector.selector, element))
+        .map(|selector| selector.selector.specificity())
+        .max();
+
+      if let Some(specificity) = specificity {
+        let rule = &self.rules[selectors[0].rule];
+        for declaration in rule.declarations.iter() {
+          candidates.push(Candidate {
+            origin: rule.origin,
+            priority: Priority::new(rule.origin, declaration.important, rule.layer_rank),
+            specificity,
+            order: candidates.len(),
+            declaration
+          });
+        }
+      }
+    }
+
+    for declaration in element.data.style().declarations.iter() {
+      candidates.push(Candidate {
+        origin: Origin::Author,
+        priority: Priority::style_attribute(declaration.important),
+        specificity: Specificity::default(),
+        order: candidates.len(),
+        declaration
+      });
+    }
+
+    candidates
+  }
+}
+
+/// The winning declaration of every property declared for the element.
+/// `revert` rolls back to the declarations of earlier origins and
+/// `revert-layer` to those of earlier layers; a property reverted past
+/// every declaration is left out, as if it was `unset`.
+pub fn cascaded_values<'c>(element: &Element<'c>, rules: &'c RuleSet, ancestors: Option<&BloomFilter>) -> HashMap<&'c str, &'c Declaration> {
+  let mut candidates = rules.candidates(element, ancestors);
+  candidates.sort_by_key(|i| (i.priority, i.specificity, i.order));
+
+  let mut by_name: HashMap<&str, Vec<&Candidate>> = HashMap::new();
+  for candidate in candidates.iter() {
+    by_name.entry(&candidate.declaration.name[..]).or_default().push(candidate);
+  }
+
+  by_name.into_iter()
+    .filter_map(|(name, candidates)| {
+      let mut i = candidates.len() - 1;
+      loop {
+        let winner = candidates[i];
+        let reverted = match css_wide_keyword(&winner.declaration.value) {
+          Some("revert") => candidates[..i].iter().rposition(|i| i.origin < winner.origin),
+          Some("revert-layer") => candidates[..i].iter().rposition(|i| i.priority != winner.priority),
+          _ => return Some((name, winner.declaration))
+        };
+        i = reverted?;
+      }
+    })
+    .collect()
+}
+
+fn css_wide_keyword(value: &Value) -> Option<&str> {
+  match *value {
+    Value::Keyword(ref keyword) if CSS_WIDE_KEYWORDS.contains(&&keyword[..]) => Some(keyword),
+    Value::Unparsed(ref lexed) => variables::css_wide_keyword(lexed),
+    _ => None
+  }
+}
+
+/// The initial value of every property, parsed once.
+fn initial_values() -> &'static PropertyMap {
+  static INITIAL: OnceLock<PropertyMap> = OnceLock::new();
+  INITIAL.get_or_init(|| {
+    properties::all().iter()
+      .map(|property| (property.name.to_string(), property.initial_value()))
+      .collect()
+  })
+}
+
+/// The values of a node without declarations of its own: inherited
+/// properties take the parent's computed value, the others their initial
+/// value.
+fn default_values(parent: Option<&PropertyMap>) -> PropertyMap {
+  let mut values = initial_values().clone();
+
+  if let Some(parent) = parent {
+    for property in properties::all().iter().filter(|property| property.inherited) {
+      if let Some(value) = parent.get(property.name) {
+        values.insert(property.name.to_string(), value.clone());
+      }
+    }
+  }
+
+  values
+}
+
+/// Applies the cascaded values over the defaults. `var()` references are
+/// substituted first, an invalid substitution making the property `unset`.
+fn specified_values(cascaded: &HashMap<&str, &Declaration>, parent: Option<&StyledNode>) -> (PropertyMap, CustomProperties) {
+  let custom_properties = CustomProperties::compute(
+    parent.map(|parent| &parent.custom_properties),
+    cascaded.iter()
+      .filter(|&(name, _)| variables::is_custom_property(name))
+      .map(|(_, &declaration)| declaration)
+  );
+
+  let parent_values = parent.map(|parent| &parent.computed_values);
+  let mut values = default_values(parent_values);
+
+  for (&name, &declaration) in cascaded.iter() {
+    let property = match properties::lookup(name) {
+      Some(property) => property,
+      None => continue
+    };
+
+    let value = match declaration.value {
+      Value::Unparsed(ref lexed) => custom_properties.resolve(name, lexed),
+      Value::Pending(ref shorthand, ref lexed) => custom_properties.resolve_pending(shorthand, name, lexed),
+      ref value => Some(value.clone())
+    };
+
+    let value = match value {
+      Some(value) => value,
+      None => continue
+    };
+
+    let inherit = match css_wide_keyword(&value) {
+      Some("inherit") => true,
+      Some("initial") => false,
+      Some(_) => property.inherited,
+      None => {
+        values.insert(name.to_string(), value);
+        continue;
+      }
+    };
+
+    let value = match parent_values.and_then(|parent| parent.get(name)) {
+      Some(parent) if inherit => parent.clone(),
+      _ => initial_values()[name].clone()
+    };
+    values.insert(name.to_string(), value);
+  }
+
+  (values, custom_properties)
+}
+
+/// What styling a tree depends on besides the nodes themselves.
+struct StyleContext<'r> {
+  rules: &'r RuleSet<'r>,
+  device: &'r Device,
+  root_font_size: Option<Px> // known once the root is styled
+}
+
+/// How a tree is styled.
+#[derive(Debug, Clone)]
+pub struct StyleOptions {
+  /// Whether the default rendering of HTML applies below `stylesheets`.
+  pub user_agent_stylesheet: bool,
+  pub traversal: TraversalMode
+}
+
+impl Default for StyleOptions {
+  fn default() -> Self {
+    StyleOptions {
+      user_agent_stylesheet: true,
+      traversal: TraversalMode::Sequential
+    }
+  }
+}
+
+/// Whether `style_tree` styles subtrees on one thread or several. Both
+/// give the same styles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraversalMode {
+  Sequential,
+  Parallel(usize) // threads
+}
+
+/// Styles the tree under `root` with the rules of `stylesheets`, for
+/// `device`.
+pub fn style_tree<'a>(root: &'a Node, stylesheets: &[&Stylesheet], device: &Device, options: &StyleOptions) -> StyledNode<'a> {
+  let sheets = with_user_agent(stylesheets, options);
+  let rules = RuleSet::new(&sheets);
+  let context = StyleContext {
+    rules: &rules,
+    device,
+    root_font_size: None
+  };
+
+  match (Element::root(root), options.traversal) {
+    (Some(element), TraversalMode::Parallel(threads)) if threads > 1 => {
+      parallel::style_tree(root, &element, &context, threads)
+    },
+    (Some(element), _) => style_element(root, &element, &context, &mut Traversal::new(), None),
+    (None, _) => style_text(root, &context, None)
+  }
+}
+
+/// Restyles the tree under `root` from the styles it had, `previous`,
+/// after its nodes changed or `changed_rules` were added to or removed
+/// from `stylesheets`. Only elements the changes can affect are matched
+/// again; the rest keep their styles unless their parent's changed.
+/// Restyling is always sequential.
+/// Nodes must change through the methods of `Node` and `ElementData` for
+/// their changes to be seen.
+pub fn restyle<'a>(root: &'a mut Node, previous: CachedStyles, changed_rules: &[&Rule], stylesheets: &[&Stylesheet], device: &Device, options: &StyleOptions) -> StyledNode<'a> {
+  let sheets = with_user_agent(stylesheets, options);
+  let rules = RuleSet::new(&sheets);
+
+  let mut changed = RuleHash::new();
+  for (i, rule) in changed_rules.iter().enumerate() {
+    for selector in rule.selectors.iter() {
+      changed.insert(i, selector);
+    }
+  }
+  let mut previous = previous;
+  invalidation::invalidate(root, &mut previous, &rules.invalidation, &changed);
+
+  let root: &'a Node = root;
+  let context = StyleContext {
+    rules: &rules,
+    device,
+    root_font_size: None
+  };
+  let mut traversal = Traversal::new();
+
+  match Element::root(root) {
+    Some(element) => restyle_element(root, &element, &context, &mut traversal, None, previous, Restyle::Hinted),
+    None => style_text(root, &context, None)
+  }
+}
+
+fn with_user_agent<'s>(stylesheets: &[&'s Stylesheet], options: &StyleOptions) -> Vec<&'s Stylesheet> {
+  let mut sheets = Vec::with_capacity(stylesheets.len() + 1);
+  if options.user_agent_stylesheet {
+    sheets.push(user_agent_stylesheet());
+  }
+  sheets.extend_from_slice(stylesheets);
+  sheets
+}
+
+/// What a traversal carries from one element to the next.
+struct Traversal<'a> {
+  ancestors: BloomFilter, // of the element being styled
+  sharing: SharingCache<'a>
+}
+
+impl<'a> Traversal<'a> {
+  fn new() -> Self {
+    Traversal {
+      ancestors: BloomFilter::new(),
+      sharing: SharingCache::new()
+    }
+  }
+}
+
+/// `element` is `node` linked to its ancestors, which only live as long as
+/// the traversal.
+fn style_element<'a>(node: &'a Node, element: &Element, context: &StyleContext, traversal: &mut Traversal<'a>, parent: Option<&StyledNode<'a>>) -> StyledNode<'a> {
+  let mut styled = element_style(node, element, context, traversal, parent);
+
+  let context = StyleContext {
+    root_font_size: context.root_font_size.or(Some(styled.style.font_size)),
+    ..*context
+  };
+  traversal.ancestors.insert_element(element);
+  let mut children = Vec::with_capacity(node.children.len());
+  for (i, child) in node.children.iter().enumerate() {
+    children.push(match element.child(child, i) {
+      Some(child_element) => style_element(child, &child_element, &context, traversal, Some(&styled)),
+      None => style_text(child, &context, Some(&styled))
+    });
+  }
+  traversal.ancestors.remove_element(element);
+
+  styled.children = children;
+  styled
+}
+
+/// The style of the element itself, shared with an earlier sibling when no
+/// selector tells them apart.
+fn element_style<'a>(node: &'a Node, element: &Element, context: &StyleContext, traversal: &mut Traversal<'a>, parent: Option<&StyledNode<'a>>) -> StyledNode<'a> {
+  let cascade = |ancestors: &BloomFilter| {
+    let (specified_values, custom_properties) = specified_values(&cascaded_values(element, context.rules, Some(ancestors)), parent);
+    computed_node(node, specified_values, custom_properties, context, parent)
+  };
+
+  let parent_node = match parent {
+    Some(parent) => parent.node,
+    None => return cascade(&traversal.ancestors)
+  };
+
+  let revalidation = sharing::revalidate(&context.rules.revalidation, element);
+  if let Some(shared) = traversal.sharing.lookup(node, parent_node, &revalidation) {
+    return StyledNode::new(node, shared.clone());
+  }
+
+  let styled = cascade(&traversal.ancestors);
+  traversal.sharing.insert(node, parent_node, revalidation, styled.shared_style());
+  styled
+}
+
+/// How much of a cached subtree is out of date, besides its hints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Restyle {
+  Hinted,
+  Element, // the parent changed, so the inherited values may have
+  Subtree
+}
+
+fn restyle_element<'a>(node: &'a Node, element: &Element, context: &StyleContext, traversal: &mut Traversal<'a>, parent: Option<&StyledNode<'a>>, previous: CachedStyles, restyle: Restyle) -> StyledNode<'a> {
+  if restyle == Restyle::Subtree {
+    return style_element(node, element, context, traversal, parent);
+  }
+
+  let CachedStyles { style: previous_style, hint, children: previous_children } = previous;
+  let (mut styled, changed, font_size_changed) = if restyle == Restyle::Element || hint.element {
+    let styled = element_style(node, element, context, traversal, parent);
+    let changed = styled.computed_values != previous_style.computed_values
+      || styled.custom_properties != previous_style.custom_properties;
+    let font_size_changed = styled.style.font_size != previous_style.style.font_size;
+    (styled, changed, font_size_changed)
+  } else {
+    (StyledNode::new(node, previous_style), false, false)
+  };
+
+  // every `rem` depends on the font size of the root
+  let children_restyle = if hint.descendants || (font_size_changed && parent.is_none()) || previous_children.len() != node.children.len() {
+    Restyle::Subtree
+  } else if changed {
+    Restyle::Element
+  } else {
+    Restyle::Hinted
+  };
+
+  let context = StyleContext {
+    root_font_size: context.root_font_size.or(Some(styled.style.font_size)),
+    ..*context
+  };
+  traversal.ancestors.insert_element(element);
+  let mut children = Vec::with_capacity(node.children.len());
+  let mut previous_children = previous_children.into_iter();
+  for (i, child) in node.children.iter().enumerate() {
+    let previous = previous_children.next();
+    children.push(match (element.child(child, i), previous) {
+      (Some(child_element), Some(previous)) => {
+        restyle_element(child, &child_element, &context, traversal, Some(&styled), previous, children_restyle)
+      },
+      (Some(child_element), None) => style_element(child, &child_element, &context, traversal, Some(&styled)),
+      (None, Some(previous)) if children_restyle == Restyle::Hinted => StyledNode::new(child, previous.style),
+      (None, _) => style_text(child, &context, Some(&styled))
+    });
+  }
+  traversal.ancestors.remove_element(element);
+
+  styled.children = children;
+  styled
+}
+
+fn style_text<'a>(node: &'a Node, context: &StyleContext, parent: Option<&StyledNode>) -> StyledNode<'a> {
+  let specified_values = default_values(parent.map(|parent| &parent.computed_values));
+  let custom_properties = parent.map(|parent| parent.custom_properties.clone()).unwrap_or_default();
+  computed_node(node, specified_values, custom_properties, context, parent)
+}
+
+fn computed_node<'a>(node: &'a Node, specified_values: PropertyMap, custom_properties: CustomProperties, context: &StyleContext, parent: Option<&StyledNode>) -> StyledNode<'a> {
+  let computed_values = computed::compute_values(
+    &specified_values,
+    parent.map(|parent| &parent.computed_values),
+    context.root_font_size,
+    context.device
+  );
+
+  StyledNode {
+    node,
+    style: ComputedStyle::new(&computed_values),
+    specified_values,
+    computed_values,
+    custom_properties,
+    children: Vec::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::{self, media::MediaType, serialize::ToCss};
+  use dom::parse_dom;
+
+  const DEVICE: Device = Device {
+    media_type: MediaType::Screen,
+    viewport_width: 800.0,
+    viewport_height: 600.0
+  };
+
+  const NO_USER_AGENT: StyleOptions = StyleOptions {
+    user_agent_stylesheet: false,
+    traversal: TraversalMode::Sequential
+  };
+
+  #[test]
+  fn cascades_declarations() {
+    let root = parse_dom("<div>
+      <p id=\"intro\" class=\"note\" style=\"margin-left: 3px\">text</p>
+      <p class=\"note\" style=\"color: green; display: inline\">text</p>
+    </div>".to_string()).unwrap();
+
+    let user_agent = css::parse_stylesheet("p { display: block !important; color: black }".to_string(), Origin::UserAgent).unwrap();
+    let author = css::parse_stylesheet("
+      @layer base { p { margin-left: 9px !important } }
+      #intro { color: blue }
+      div p.note { color: red }
+      p { display: none; margin-left: 1px !important }
+    ".to_string(), Origin::Author).unwrap();
+
+    let styled = style_tree(&root, &[&user_agent, &author], &DEVICE, &NO_USER_AGENT);
+    let paragraphs = elements(&styled);
+    assert_eq!(paragraphs.len(), 2);
+
+    let value = |i: usize, name: &str| paragraphs[i].specified_values[name].to_css();
+    // the id outweighs the classes, important user agent declarations beat
+    // everything and the style attribute beats normal author rules only
+    assert_eq!(value(0, "color"), "rgb(0, 0, 255)");
+    assert_eq!(value(1, "color"), "rgb(0, 128, 0)");
+    assert_eq!(value(0, "display"), "block");
+    assert_eq!(value(1, "display"), "block");
+    // earlier layers win among important declarations
+    assert_eq!(value(0, "margin-left"), "9px");
+
+    assert_eq!(styled.specified_values["display"].to_css(), "inline");
+  }
+
+  fn elements<'b, 'a>(node: &'b StyledNode<'a>) -> Vec<&'b StyledNode<'a>> {
+    node.children.iter()
+      .filter(|child| Element::root(child.node).is_some())
+      .collect()
+  }
+
+  #[test]
+  fn inherits_and_defaults() {
+    let root = parse_dom("<div style=\"--gap: 4px\"><section><p>text</p><span></span></section></div>".to_string()).unwrap();
+
+    let user_agent = css::parse_stylesheet("p { display: block }".to_string(), Origin::UserAgent).unwrap();
+    let author = css::parse_stylesheet("
+      @layer base { p { margin-right: 5px } }
+      div { color: red; width: 10px; font-size: 20px }
+      section { width: inherit; color: initial; font-size: unset }
+      p { color: inherit; display: revert; margin-right: revert-layer; margin-left: revert }
+      span { margin-left: var(--gap); padding: var(--missing); display: unset }
+    ".to_string(), Origin::Author).unwrap();
+
+    let styled = style_tree(&root, &[&user_agent, &author], &DEVICE, &NO_USER_AGENT);
+    let section = elements(&styled)[0];
+    let (p, span) = (elements(section)[0], elements(section)[1]);
+    let value = |node: &StyledNode, name: &str| node.specified_values[name].to_css();
+
+    assert_eq!(value(section, "width"), "10px");
+    assert_eq!(value(section, "color"), "rgb(0, 0, 0)");
+    assert_eq!(value(section, "font-size"), "20px");
+
+    assert_eq!(value(p, "color"), "rgb(0, 0, 0)");
+    assert_eq!(value(p, "display"), "block");
+    assert_eq!(value(p, "margin-right"), "5px");
+    assert_eq!(value(p, "margin-left"), "0px");
+    assert_eq!(value(p, "width"), "auto");
+
+    // text inherits inherited properties only
+    let text = &p.children[0];
+    assert_eq!(value(text, "font-size"), "20px");
+    assert_eq!(value(text, "display"), "inline");
+
+    assert_eq!(value(span, "margin-left"), "4px");
+    assert_eq!(value(span, "padding-left"), "0px");
+    assert_eq!(value(span, "display"), "inline");
+    assert!(span.custom_properties.get("--gap").is_some());
+  }
+
+  #[test]
+  fn shares_styles_between_siblings() {
+    let root = parse_dom("<ul>
+      <li class=\"a\">1</li><li class=\"a\">2</li><li class=\"a\">3</li><li class=\"b\">4</li>
+    </ul>".to_string()).unwrap();
+
+    let author = css::parse_stylesheet("
+      li { color: blue }
+      li:first-child { color: red }
+      .a + .b { width: 3px }
+      ul li.b { margin-left: 2px }
+      section li { margin-left: 9px }
+    ".to_string(), Origin::Author).unwrap();
+
+    let styled = style_tree(&root, &[&author], &DEVICE, &NO_USER_AGENT);
+    let items = elements(&styled);
+    let value = |i: usize, name: &str| items[i].specified_values[name].to_css();
+
+    // siblings only differing in position match apart
+    assert_eq!(value(0, "color"), "rgb(255, 0, 0)");
+    assert_eq!(value(1, "color"), "rgb(0, 0, 255)");
+    assert_eq!(items[1].computed_values, items[2].computed_values);
+    assert_eq!(value(3, "width"), "3px");
+    assert_eq!(value(3, "margin-left"), "2px");
+    assert_eq!(value(2, "margin-left"), "0px");
+  }
+
+  fn computed_values<'s>(styled: &'s StyledNode, values: &mut Vec<&'s PropertyMap>) {
+    values.push(&styled.computed_values);
+    for child in styled.children.iter() {
+      computed_values(child, values);
+    }
+  }
+
+  #[test]
+  fn restyles_after_changes() {
+    let mut root = parse_dom("<div><section><p class=\"x\">a</p><p>b</p></section><span><em>c</em></span><b>d</b></div>".to_string()).unwrap();
+    let sheet = |extra: &str| css::parse_stylesheet(format!("
+      .on p {{ color: red }}
+      p.x + p {{ width: 5px }}
+      .big {{ font-size: 30px }}
+      b:empty {{ display: none }}
+      {}
+    ", extra), Origin::Author).unwrap();
+    let (before, after) = (sheet(""), sheet("em { color: blue }"));
+
+    let previous = style_tree(&root, &[&before], &DEVICE, &StyleOptions::default()).detach();
+
+    let section = &mut root.children[0];
+    section.element_data_mut().unwrap().set_attribute("class", Some("on".to_string()));
+    section.children[0].element_data_mut().unwrap().remove_attribute("class");
+    section.children[1].element_data_mut().unwrap().set_attribute("class", Some("big".to_string()));
+    root.children[2].remove_child(0);
+
+    // only the rule added to `after` is new
+    let changed = vec![after.rules.last().unwrap()];
+    let restyled = restyle(&mut root, previous, &changed, &[&after], &DEVICE, &StyleOptions::default());
+    let mut incremental = Vec::new();
+    computed_values(&restyled, &mut incremental);
+
+    let styled = style_tree(restyled.node, &[&after], &DEVICE, &StyleOptions::default());
+    let mut full = Vec::new();
+    computed_values(&styled, &mut full);
+    assert_eq!(incremental, full);
+
+    let (section, span, b) = (elements(&restyled)[0], elements(&restyled)[1], elements(&restyled)[2]);
+    let paragraphs = elements(section);
+    assert_eq!(paragraphs[0].computed_values["color"].to_css(), "rgb(255, 0, 0)");
+    assert_eq!(paragraphs[1].computed_values["width"].to_css(), "auto");
+    assert_eq!(paragraphs[1].style.font_size, 30.0);
+    assert_eq!(elements(span)[0].computed_values["color"].to_css(), "rgb(0, 0, 255)");
+    assert_eq!(b.computed_values["display"].to_css(), "none");
+  }
+
+}
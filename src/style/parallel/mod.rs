@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::thread;
+use dom::Node;
+use super::{StyledNode, StyleContext, Traversal, style_element, element_style, style_text};
+use super::matching::Element;
+
+/// Subtrees handed out per thread, so that stealing can even out subtrees
+/// of different sizes.
+const TASKS_PER_THREAD: usize = 4;
+
+/// How deep the tree is styled sequentially at most before it is split.
+const MAX_SPLIT_DEPTH: usize = 8;
+
+/// Runs `f` on every task on `threads` threads. Tasks are dealt out to the
+/// threads in turn; a thread works from the back of its own queue and,
+/// once it runs dry, steals from the front of the others'. The results
+/// are in the order of `tasks`.
+pub fn run<T: Send, R: Send, F: Fn(T) -> R + Sync>(tasks: Vec<T>, threads: usize, f: F) -> Vec<R> {
+  let threads = threads.max(1).min(tasks.len().max(1));
+  let count = tasks.len();
+
+  let mut queues: Vec<VecDeque<(usize, T)>> = (0..threads).map(|_| VecDeque::new()).collect();
+  for (i, task) in tasks.into_iter().enumerate() {
+    queues[i % threads].push_back((i, task));
+  }
+  let queues: Vec<Mutex<VecDeque<(usize, T)>>> = queues.into_iter().map(Mutex::new).collect();
+
+  let take = |worker: usize| -> Option<(usize, T)> {
+    if let Some(task) = queues[worker].lock().unwrap().pop_back() {
+      return Some(task);
+    }
+    (1..threads)
+      .map(|offset| (worker + offset) % threads)
+      .find_map(|victim| queues[victim].lock().unwrap().pop_front())
+  };
+
+  let mut results: Vec<Option<R>> = (0..count).map(|_| None).collect();
+  thread::scope(|scope| {
+    let workers: Vec<_> = (0..threads)
+      .map(|worker| {
+        let (take, f) = (&take, &f);
+        scope.spawn(move || {
+          let mut done = Vec::new();
+          // no task adds tasks, so once every queue is empty all are taken
+          while let Some((i, task)) = take(worker) {
+            done.push((i, f(task)));
+          }
+          done
+        })
+      })
+      .collect();
+
+    for worker in workers {
+      for (i, result) in worker.join().unwrap() {
+        results[i] = Some(result);
+      }
+    }
+  });
+
+  results.into_iter().map(|result| result.unwrap()).collect()
+}
+
+/// A child left for a thread to style along with its descendants.
+struct Task<'a> {
+  ancestors: Vec<(&'a Node, usize)>, // below the root, down to the parent, with their indices
+  node: &'a Node,
+  index: usize
+}
+
+/// Styles the tree under `root` like `style_element`, but styles the
+/// subtrees below a certain depth on `threads` threads. Every subtree is
+/// styled from its parent's style and its ancestors alone, so the result
+/// is the one of the sequential traversal.
+pub(super) fn style_tree<'a>(root: &'a Node, element: &Element, context: &StyleContext, threads: usize) -> StyledNode<'a> {
+  let split = match split_depth(root, threads) {
+    Some(split) => split,
+    None => return style_element(root, element, context, &mut Traversal::new(), None)
+  };
+
+  let mut tasks = Vec::new();
+  let mut styled = style_top(root, element, context, &mut Traversal::new(), None, split, &mut Vec::new(), &mut tasks);
+
+  let context = StyleContext {
+    root_font_size: Some(styled.style.font_size),
+    ..*context
+  };
+  let results = {
+    let styled = &styled;
+    let tasks: Vec<(&Task, &StyledNode)> = tasks.iter()
+      .map(|task| (task, styled_at(styled, &task.ancestors)))
+      .collect();
+
+    run(tasks, threads, |(task, parent)| {
+      let mut traversal = Traversal::new();
+      let root = Element::root(root).unwrap();
+      in_ancestors(&root, &task.ancestors, &mut traversal, &mut |parent_element, traversal| {
+        match parent_element.child(task.node, task.index) {
+          Some(child) => style_element(task.node, &child, &context, traversal, Some(parent)),
+          None => style_text(task.node, &context, Some(parent))
+        }
+      })
+    })
+  };
+
+  for (task, result) in tasks.iter().zip(results) {
+    styled_at_mut(&mut styled, &task.ancestors).children.push(result);
+  }
+  styled
+}
+
+/// The shallowest depth with enough nodes to keep every thread busy, or
+/// failing that the one with the most; `None` if no depth has two.
+fn split_depth(root: &Node, threads: usize) -> Option<usize> {
+  let mut counts = Vec::new();
+  let mut level = vec![root];
+  while !level.is_empty() && counts.len() <= MAX_SPLIT_DEPTH {
+    counts.push(level.len());
+    level = level.iter().flat_map(|node| node.children.iter()).collect();
+  }
+
+  let enough = threads * TASKS_PER_THREAD;
+  let (depth, &count) = counts.iter().enumerate()
+    .find(|&(_, &count)| count >= enough)
+    .or_else(|| counts.iter().enumerate().rev().max_by_key(|&(_, &count)| count))?;
+  if count < 2 {
+    None
+  } else {
+    Some(depth)
+  }
+}
+
+/// `style_element` down to the parents of the nodes at depth `split`,
+/// whose children are left as tasks.
+#[allow(clippy::too_many_arguments)]
+fn style_top<'a>(node: &'a Node, element: &Element, context: &StyleContext, traversal: &mut Traversal<'a>, parent: Option<&StyledNode<'a>>, split: usize, ancestors: &mut Vec<(&'a Node, usize)>, tasks: &mut Vec<Task<'a>>) -> StyledNode<'a> {
+  let mut styled = element_style(node, element, context, traversal, parent);
+
+  if ancestors.len() + 1 == split {
+    tasks.extend(node.children.iter().enumerate().map(|(index, child)| Task {
+      ancestors: ancestors.clone(),
+      node: child,
+      index
+    }));
+    return styled;
+  }
+
+  let context = StyleContext {
+    root_font_size: context.root_font_size.or(Some(styled.style.font_size)),
+    ..*context
+  };
+  traversal.ancestors.insert_element(element);
+  let mut children = Vec::with_capacity(node.children.len());
+  for (i, child) in node.children.iter().enumerate() {
+    children.push(match element.child(child, i) {
+      Some(child_element) => {
+        ancestors.push((child, i));
+        let styled = style_top(child, &child_element, &context, traversal, Some(&styled), split, ancestors, tasks);
+        ancestors.pop();
+        styled
+      },
+      None => style_text(child, &context, Some(&styled))
+    });
+  }
+  traversal.ancestors.remove_element(element);
+
+  styled.children = children;
+  styled
+}
+
+/// Links the ancestors of a task back up to the root, adding each to the
+/// Bloom filter, and calls `f` with the parent.
+fn in_ancestors<'a, R>(element: &Element, ancestors: &[(&Node, usize)], traversal: &mut Traversal<'a>, f: &mut dyn FnMut(&Element, &mut Traversal<'a>) -> R) -> R {
+  traversal.ancestors.insert_element(element);
+  match ancestors.split_first() {
+    Some((&(node, index), rest)) => {
+      let child = element.child(node, index).expect("ancestors are elements");
+      in_ancestors(&child, rest, traversal, f)
+    },
+    None => f(element, traversal)
+  }
+}
+
+fn styled_at<'s, 'a>(styled: &'s StyledNode<'a>, ancestors: &[(&Node, usize)]) -> &'s StyledNode<'a> {
+  ancestors.iter().fold(styled, |styled, &(_, i)| &styled.children[i])
+}
+
+fn styled_at_mut<'s, 'a>(styled: &'s mut StyledNode<'a>, ancestors: &[(&Node, usize)]) -> &'s mut StyledNode<'a> {
+  ancestors.iter().fold(styled, |styled, &(_, i)| &mut styled.children[i])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::{self, cascade::Origin, media::{Device, MediaType}};
+  use dom::parse_dom;
+  use style::{style_tree, PropertyMap, StyleOptions, TraversalMode};
+
+  #[test]
+  fn steals_in_order() {
+    let tasks: Vec<u64> = (0..100).collect();
+    let results = run(tasks, 4, |i| {
+      if i % 7 == 0 {
+        thread::sleep(std::time::Duration::from_millis(1));
+      }
+      i * i
+    });
+    assert_eq!(results, (0..100).map(|i| i * i).collect::<Vec<u64>>());
+  }
+
+  fn computed_values<'s>(styled: &'s StyledNode, values: &mut Vec<&'s PropertyMap>) {
+    values.push(&styled.computed_values);
+    for child in styled.children.iter() {
+      computed_values(child, values);
+    }
+  }
+
+  #[test]
+  fn matches_sequential() {
+    let items: String = (0..40)
+      .map(|i| format!("<section class=\"s{}\"><ul><li>a</li><li class=\"x\">b<em>c</em></li><li>d</li></ul><p>e</p></section>", i % 3))
+      .collect();
+    let root = parse_dom(format!("<html><body style=\"font-size: 20px\">{}</body></html>", items)).unwrap();
+    let author = css::parse_stylesheet("
+      .s1 li:nth-child(odd) { color: red }
+      section:has(.x) p { margin-left: 2em }
+      .s2 .x + li { width: 5rem }
+      li.x em { font-size: larger }
+    ".to_string(), Origin::Author).unwrap();
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+
+    let sequential = style_tree(&root, &[&author], &device, &StyleOptions::default());
+    for &threads in [1, 2, 3, 8].iter() {
+      let parallel = style_tree(&root, &[&author], &device, &StyleOptions {
+        traversal: TraversalMode::Parallel(threads),
+        ..StyleOptions::default()
+      });
+
+      let (mut expected, mut actual) = (Vec::new(), Vec::new());
+      computed_values(&sequential, &mut expected);
+      computed_values(&parallel, &mut actual);
+      assert_eq!(expected, actual);
+    }
+  }
+}
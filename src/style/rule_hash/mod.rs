@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use css::selectors::{Selector, Combinator};
+use style::bloom::{self, BloomFilter};
+use style::matching::Element;
+
+/// The most ancestor hashes kept per selector; the leftmost compounds
+/// rarely reject anything the nearer ones do not.
+const MAX_ANCESTOR_HASHES: usize = 4;
+
+/// A selector of a rule, with the hashes of what its ancestor compounds
+/// require.
+pub struct IndexedSelector<'s> {
+  pub rule: usize,
+  pub selector: &'s Selector,
+  order: usize,
+  ancestor_hashes: Vec<u32>
+}
+
+impl<'s> IndexedSelector<'s> {
+  /// `false` if the ancestors in `filter` cannot satisfy the selector.
+  pub fn might_match(&self, filter: &BloomFilter) -> bool {
+    self.ancestor_hashes.iter().all(|&hash| filter.might_contain(hash))
+  }
+}
+
+/// Selectors bucketed by the id, class or tag their rightmost compound
+/// requires, so an element is only matched against selectors that could
+/// apply to it. Selectors requiring none of them are universal.
+#[derive(Default)]
+pub struct RuleHash<'s> {
+  id: HashMap<String, Vec<IndexedSelector<'s>>>,
+  class: HashMap<String, Vec<IndexedSelector<'s>>>,
+  tag: HashMap<String, Vec<IndexedSelector<'s>>>,
+  universal: Vec<IndexedSelector<'s>>,
+  len: usize
+}
+
+impl<'s> RuleHash<'s> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert(&mut self, rule: usize, selector: &'s Selector) {
+    let indexed = IndexedSelector {
+      rule,
+      selector,
+      order: self.len,
+      ancestor_hashes: ancestor_hashes(selector)
+    };
+    self.len += 1;
+
+    let compound = match selector.compounds.last() {
+      Some(compound) => compound,
+      None => return
+    };
+
+    let bucket = if let Some(id) = compound.id.first() {
+      self.id.entry(id.clone()).or_default()
+    } else if let Some(class) = compound.class.first() {
+      self.class.entry(class.clone()).or_default()
+    } else if let Some(ref tag_name) = compound.tag_name {
+      self.tag.entry(tag_name.clone()).or_default()
+    } else {
+      &mut self.universal
+    };
+    bucket.push(indexed);
+  }
+
+  /// The selectors that could match `element`, in order of insertion.
+  pub fn candidates(&self, element: &Element) -> Vec<&IndexedSelector<'s>> {
+    let data = element.data;
+    let mut candidates: Vec<&IndexedSelector> = self.universal.iter().collect();
+
+    let buckets = data.id().and_then(|id| self.id.get(id)).into_iter()
+      .chain(data.classes().filter_map(|class| self.class.get(class)))
+      .chain(self.tag.get(&data.tag_name.to_ascii_lowercase()));
+    for bucket in buckets {
+      candidates.extend(bucket.iter());
+    }
+
+    // a class listed twice on the element yields its bucket twice
+    candidates.sort_by_key(|i| i.order);
+    candidates.dedup_by_key(|i| i.order);
+    candidates
+  }
+}
+
+/// The hashes of the tags, ids and classes of the compounds that must
+/// match ancestors: those left of a child or descendant combinator. The
+/// compound left of a sibling combinator matches a sibling instead.
+fn ancestor_hashes(selector: &Selector) -> Vec<u32> {
+  let compounds = &selector.compounds;
+  let mut hashes = Vec::new();
+
+  for i in (1..compounds.len()).rev() {
+    match compounds[i].combinator {
+      Combinator::Child | Combinator::Descendant => (),
+      Combinator::AdjacentSibling | Combinator::GeneralSibling => continue
+    }
+
+    let ancestor = &compounds[i - 1];
+    hashes.extend(ancestor.id.iter().map(|id| bloom::id_hash(id)));
+    hashes.extend(ancestor.class.iter().map(|class| bloom::class_hash(class)));
+    hashes.extend(ancestor.tag_name.iter().map(|tag_name| bloom::tag_hash(tag_name)));
+  }
+
+  hashes.truncate(MAX_ANCESTOR_HASHES);
+  hashes
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::{lexer::lex, selectors::parse_selector_list};
+  use dom::parse_dom;
+
+  #[test]
+  fn buckets_by_rightmost_compound() {
+    let selectors = parse_selector_list(&lex("#a, p.note, .x > span, p, *, :is(p), ul + li p".to_string()).unwrap()).unwrap();
+    let mut hash = RuleHash::new();
+    for (i, selector) in selectors.iter().enumerate() {
+      hash.insert(i, selector);
+    }
+
+    let root = parse_dom("<p id=\"a\" class=\"note note\"></p>".to_string()).unwrap();
+    let element = Element::root(&root).unwrap();
+    let rules: Vec<usize> = hash.candidates(&element).iter().map(|i| i.rule).collect();
+    assert_eq!(rules, vec![0, 1, 3, 4, 5, 6]);
+
+    let mut filter = BloomFilter::new();
+    let last = hash.candidates(&element).into_iter().last().unwrap();
+    assert!(!last.might_match(&filter));
+    filter.insert(bloom::tag_hash("li"));
+    assert!(last.might_match(&filter));
+  }
+}
@@ -0,0 +1,101 @@
+use std::ptr;
+use css::selectors::{Selector, Combinator, PseudoClass};
+use css::variables::CustomProperties;
+use dom::{Node, NodeType};
+use style::PropertyMap;
+use style::computed::ComputedStyle;
+use style::matching::Element;
+
+/// How many recently styled elements are kept as candidates.
+const CAPACITY: usize = 8;
+
+/// The style of a node apart from the node, handed to siblings and kept
+/// for restyling.
+#[derive(Debug, Clone)]
+pub struct SharedStyle {
+  pub specified_values: PropertyMap,
+  pub computed_values: PropertyMap,
+  pub style: ComputedStyle,
+  pub custom_properties: CustomProperties
+}
+
+/// The styles of recently styled elements, reused for a later sibling with
+/// the same tag and attributes. Siblings inherit from the same parent and
+/// match the same rules, except for selectors that look at the position of
+/// an element or at its contents; those are matched against both elements
+/// and must agree.
+#[derive(Default)]
+pub struct SharingCache<'a> {
+  entries: Vec<Entry<'a>> // most recent first
+}
+
+struct Entry<'a> {
+  parent: &'a Node,
+  node: &'a Node,
+  revalidation: Vec<bool>,
+  style: SharedStyle
+}
+
+impl<'a> SharingCache<'a> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The style of an earlier sibling of `node` no selector tells apart,
+  /// given the child of `parent` and what the revalidation selectors match.
+  pub fn lookup(&self, node: &Node, parent: &Node, revalidation: &[bool]) -> Option<&SharedStyle> {
+    self.entries.iter()
+      .find(|entry| {
+        ptr::eq(entry.parent, parent)
+          && entry.revalidation == revalidation
+          && same_element(entry.node, node)
+      })
+      .map(|entry| &entry.style)
+  }
+
+  pub fn insert(&mut self, node: &'a Node, parent: &'a Node, revalidation: Vec<bool>, style: SharedStyle) {
+    self.entries.truncate(CAPACITY - 1);
+    self.entries.insert(0, Entry {
+      parent,
+      node,
+      revalidation,
+      style
+    });
+  }
+}
+
+fn same_element(a: &Node, b: &Node) -> bool {
+  match (&a.node_type, &b.node_type) {
+    (NodeType::Element(a), NodeType::Element(b)) => {
+      a.tag_name.eq_ignore_ascii_case(&b.tag_name) && a.attributes() == b.attributes()
+    },
+    _ => false
+  }
+}
+
+/// Whether the selector can match one of two siblings with the same tag
+/// and attributes but not the other: it involves siblings, the position
+/// among them or the contents of the element.
+pub fn needs_revalidation(selector: &Selector) -> bool {
+  selector.compounds.iter().any(|compound| {
+    matches!(compound.combinator, Combinator::AdjacentSibling | Combinator::GeneralSibling)
+      || compound.pseudo.iter().any(|pseudo| match *pseudo {
+        PseudoClass::FirstChild | PseudoClass::LastChild | PseudoClass::OnlyChild
+          | PseudoClass::FirstOfType | PseudoClass::LastOfType | PseudoClass::OnlyOfType
+          | PseudoClass::NthChild(..) | PseudoClass::NthLastChild(..)
+          | PseudoClass::NthOfType(..) | PseudoClass::NthLastOfType(..)
+          | PseudoClass::Empty | PseudoClass::Has(_) => true,
+        PseudoClass::Not(ref list) | PseudoClass::Is(ref list) | PseudoClass::Where(ref list) => {
+          list.iter().any(needs_revalidation)
+        },
+        _ => false
+      })
+  })
+}
+
+/// What each revalidation selector matches for `element`.
+pub fn revalidate(selectors: &[&Selector], element: &Element) -> Vec<bool> {
+  selectors.iter()
+    .map(|selector| super::matching::matches(selector, element))
+    .collect()
+}
@@ -0,0 +1,60 @@
+use std::sync::OnceLock;
+use css::{cascade::Origin, lexer, parser::{self, Stylesheet}};
+
+const HTML: &str = include_str!("html.css");
+
+/// The default rendering of HTML, parsed on first use and applied at the
+/// user-agent origin.
+pub fn user_agent_stylesheet() -> &'static Stylesheet {
+  static STYLESHEET: OnceLock<Stylesheet> = OnceLock::new();
+  STYLESHEET.get_or_init(|| {
+    lexer::lex(HTML.to_string())
+      .and_then(|lexed| parser::parse_stylesheet(lexed, Origin::UserAgent))
+      .unwrap_or_else(|err| panic!("Invalid user-agent stylesheet: {:?}", err))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use css::media::{Device, MediaType};
+  use dom::parse_dom;
+  use style::{style_tree, StyleOptions, computed::Display};
+
+  #[test]
+  fn parses_without_errors() {
+    let stylesheet = user_agent_stylesheet();
+    assert!(stylesheet.errors.is_empty(), "{:?}", stylesheet.errors);
+    assert_eq!(stylesheet.origin, Origin::UserAgent);
+  }
+
+  #[test]
+  fn renders_html_defaults() {
+    let root = parse_dom("<html><head><title>a</title></head><body><h1>a</h1><p><b>a</b></p></body></html>".to_string()).unwrap();
+    let device = Device {
+      media_type: MediaType::Screen,
+      viewport_width: 800.0,
+      viewport_height: 600.0
+    };
+
+    let styled = style_tree(&root, &[], &device, &StyleOptions::default());
+    let element = |path: &[usize]| path.iter().fold(&styled, |node, &i| &node.children[i]);
+
+    let (head, body) = (element(&[0]), element(&[1]));
+    assert_eq!(head.style.display, Display::None);
+    assert_eq!(body.style.margin.top.resolve(0.0), Some(8.0));
+
+    let (h1, p, b) = (element(&[1, 0]), element(&[1, 1]), element(&[1, 1, 0]));
+    assert_eq!(h1.style.font_size, 32.0);
+    assert_eq!(h1.style.font_weight, 700);
+    assert_eq!(p.style.display, Display::Block);
+    assert_eq!(p.style.margin.bottom.resolve(0.0), Some(16.0));
+    assert_eq!(b.style.font_weight, 700);
+
+    let plain = style_tree(&root, &[], &device, &StyleOptions {
+      user_agent_stylesheet: false,
+      ..StyleOptions::default()
+    });
+    assert_eq!(plain.children[0].style.display, Display::Inline);
+  }
+}